@@ -0,0 +1,90 @@
+//! Generic syntax trees for tools which don't need bespoke types for each grammar.
+//!
+//! The ast method builds an AstNode for each named production which matched (see the named
+//! method) so a grammar only has to name the productions which are interesting:
+//!
+//! ```
+//! let number = match1(is_digit).s0().named("number");
+//! let expr = number.list("+".s0()).named("expr");
+//! let nodes = ast(expr).parse(@~"test", "1 + 2");
+//! // node_to_str(&nodes[0]) == ~"(expr (number \"1 \") (number \"2\"))"
+//! ```
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// A named production which matched. Kind is the production's name and text is the
+/// text it matched. Children are the named productions which matched within it.
+pub struct AstNode {kind: @~str, span: Span, children: @~[AstNode], text: @~str}
+
+/// Returns a parser which matches the same text as parser but whose value is the nodes
+/// for the outermost named productions within it (parser itself may be named in
+/// which case there will be a single node).
+pub fn ast<T: Copy Durable>(parser: Parser<T>) -> Parser<@~[AstNode]>
+{
+	|input: State|
+	{
+		// This works like parse_derivation except that the derivations are only recorded
+		// while parser runs.
+		let deriving = input.ctx.deriving.enabled;
+		input.ctx.deriving.enabled = true;
+		let mut siblings = ~[];
+		siblings <-> input.ctx.deriving.derived;
+		let status = parser(input);
+		
+		let mut derived = ~[];
+		derived <-> input.ctx.deriving.derived;
+		input.ctx.deriving.derived <-> siblings;
+		input.ctx.deriving.enabled = deriving;
+		
+		do result::chain(status)
+		|pass|
+		{
+			let end = pass.new_state.index;
+			let derived = vec::filter(derived, |d| d.end <= end);
+			if deriving
+			{
+				for vec::each(derived) |d|
+				{
+					add_derivation(input.ctx, *d);
+				}
+			}
+			
+			let starts = input.line_starts();
+			result::Ok(Succeeded {new_state: pass.new_state, value: @to_nodes(derived, input, *starts)})
+		}
+	}
+}
+
+/// Returns a Lisp like representation of node, e.g. `(expr (number "1") (number "2"))`.
+/// Nodes without children include their text.
+pub fn node_to_str(node: &AstNode) -> ~str
+{
+	let mut s = ~"(" + *node.kind;
+	if vec::is_empty(*node.children)
+	{
+		str::push_char(&mut s, ' ');
+		str::push_str(&mut s, json_str(*node.text));
+	}
+	for vec::each(*node.children) |child|
+	{
+		str::push_char(&mut s, ' ');
+		str::push_str(&mut s, node_to_str(child));
+	}
+	str::push_char(&mut s, ')');
+	s
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn to_nodes(derivations: &[Derivation], input: State, starts: &[uint]) -> ~[AstNode]
+{
+	do vec::map(derivations)
+	|d|
+	{
+		let (start_line, _) = line_col(starts, d.start);
+		let (end_line, _) = line_col(starts, d.end);
+		let span = Span {file: input.file, start: d.start, end: d.end, start_offset: d.start_offset, end_offset: d.end_offset, start_line: start_line as int, end_line: end_line as int};
+		let text = str::from_chars(vec::slice(input.text, d.start, d.end));
+		AstNode {kind: d.name, span: span, children: @to_nodes(*d.children, input, starts), text: @text}
+	}
+}
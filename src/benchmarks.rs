@@ -0,0 +1,130 @@
+//! Timings for representative grammars.
+//!
+//! These are used to check that changes to the combinators don't make parsing slower
+//! (and to see whether optimizations actually help). Run them with `make bench`.
+use c99_parsers::*;
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Size is the number of characters parsed per iteration. Seconds is the time taken
+/// by all of the iterations.
+pub struct Benchmark {name: ~str, size: uint, iterations: uint, seconds: float}
+
+/// Runs each of the benchmarks. Larger scales parse larger texts.
+pub fn run_benchmarks(scale: uint) -> ~[Benchmark]
+{
+	~[
+		time_parser("expr", expr_grammar(), expr_text(100u*scale), 10u),
+		time_parser("json", json_grammar(), json_text(20u*scale), 10u),
+		time_parser("backtracking", backtracking_grammar(), backtracking_text(10u*scale), 10u),
+	]
+}
+
+/// Parses text with parser iterations times and returns how long that took. Fails
+/// if the text cannot be parsed.
+pub fn time_parser<T: Copy Durable>(name: &str, parser: Parser<T>, text: &str, iterations: uint) -> Benchmark
+{
+	let start = std::time::precise_time_s();
+	for uint::range(0u, iterations) |_i|
+	{
+		match parser.parse(@name.to_owned(), text)
+		{
+			result::Ok(_) => (),
+			result::Err(ref failure) => fail format_failure(failure, text),
+		}
+	}
+	Benchmark {name: name.to_owned(), size: str::char_len(text), iterations: iterations, seconds: std::time::precise_time_s() - start}
+}
+
+/// Returns a table with the size, iterations, time per iteration (in ms), and name of
+/// each benchmark.
+pub fn benchmarks_to_str(benchmarks: &[Benchmark]) -> ~str
+{
+	let mut s = ~"size\titerations\tms\tname\n";
+	for vec::each(benchmarks) |b|
+	{
+		let ms = 1000.0*b.seconds/(b.iterations as float);
+		str::push_str(&mut s, fmt!("%u\t%u\t%.3f\t%s\n", b.size, b.iterations, ms, b.name));
+	}
+	return s;
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Arithmetic on integers: uses chainl1, or, and forward references.
+fn expr_grammar() -> Parser<int>
+{
+	let expr_ptr = @mut ret(0i);
+	let expr_ref = forward_ref(expr_ptr);
+	
+	// factor := number | '(' expr ')'
+	let factor = decimal_number().s0().or(seq3_ret1("(".s0(), expr_ref, ")".s0()));
+	
+	// term := factor ([*/] factor)*
+	let term = do factor.chainl1("*".s0().or("/".s0()))
+		|lhs, op, rhs| {if op == @~"*" {lhs*rhs} else {lhs/rhs}};
+	
+	// expr := term ([+-] term)*
+	let expr = do term.chainl1("+".s0().or("-".s0()))
+		|lhs, op, rhs| {if op == @~"+" {lhs + rhs} else {lhs - rhs}};
+	*expr_ptr = expr;
+	
+	expr.everything_ws()
+}
+
+fn expr_text(count: uint) -> ~str
+{
+	str::connect(vec::from_elem(count, ~"(12 + 3*4) / (7 - 5)"), " - ")
+}
+
+// JSON without escapes: uses list, or_v, and lots of little literals.
+fn json_grammar() -> Parser<()>
+{
+	let value_ptr = @mut ret(());
+	let value_ref = forward_ref(value_ptr);
+	
+	let string = seq3_ret1("\"".lit(), match0(|ch| ch != '"' && ch != EOT), "\"".s0());
+	let number = seq2_ret_str("-".lit().optional(), float_number().or(decimal_number().map(|v| v as f64))).s0();
+	
+	// object := '{' (string ':' value (',' string ':' value)*)? '}'
+	let member = seq3_ret2(string, ":".s0(), value_ref);
+	let object = seq3_ret1("{".s0(), member.list(",".s0()).optional(), "}".s0());
+	
+	// array := '[' (value (',' value)*)? ']'
+	let array = seq3_ret1("[".s0(), value_ref.list(",".s0()).optional(), "]".s0());
+	
+	let value = or_v(@~[
+		string.map(|_v| ()),
+		number.map(|_v| ()),
+		object.map(|_v| ()),
+		array.map(|_v| ()),
+		"true".s0().map(|_v| ()),
+		"false".s0().map(|_v| ()),
+		"null".s0().map(|_v| ())]);
+	*value_ptr = value;
+	
+	value.everything_ws()
+}
+
+fn json_text(count: uint) -> ~str
+{
+	let record = ~"{\"name\": \"widget\", \"price\": 12.5, \"count\": 3, \"tags\": [\"a\", \"b\", null], \"ok\": true}";
+	~"[" + str::connect(vec::from_elem(count, record), ",\n") + ~"]"
+}
+
+// Alternatives with long common prefixes so each one re-parses most of the text.
+fn backtracking_grammar() -> Parser<()>
+{
+	let word = identifier().s0();
+	let words = word.r1();
+	let p = or_v(@~[
+		seq2_ret0(words, ";".s0()),
+		seq2_ret0(words, ",".s0()),
+		seq2_ret0(words, ".".s0())]);
+	p.r1().map(|_v| ()).everything_ws()
+}
+
+fn backtracking_text(count: uint) -> ~str
+{
+	str::connect(vec::from_elem(count, ~"the quick brown fox jumps over the lazy dog ."), "\n")
+}
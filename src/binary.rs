@@ -0,0 +1,444 @@
+//! Parsers for binary data, e.g. file headers and network protocols.
+//!
+//! Binary input is parsed with parse_binary which maps each byte to the char with the
+//! same value (so 0x47 is 'G'). This means that the usual combinators (e.g. lit, seq3,
+//! and list) can be mixed with the integer parsers in this module:
+//!
+//! ```
+//! let header = seq3_ret1("GIF89a".lit(), le_u16(), le_u16());
+//! ```
+//!
+//! Note that text parsers which stop at EOT will also stop at a 0x03 byte so binary
+//! parsers check the length of the input instead (e.g. with State's at_end method).
+//!
+//! Going the other way base64 and hex_blob decode bytes which have been encoded as
+//! text (e.g. in PEM files, data URLs, and hex dumps).
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Returns an array with one char for each byte followed by an EOT (see parse_binary).
+pub fn bytes_to_chars(bytes: &[u8]) -> @[char]
+{
+	do at_vec::build_sized(vec::len(bytes) + 1u)
+	|push|
+	{
+		for vec::each(bytes) |b|
+		{
+			push(*b as char);
+		}
+		push(EOT);
+	}
+}
+
+/// Uses parser to parse bytes. Errors have line 1, offset set to the offset of the bad
+/// byte, and col set to the offset plus one.
+pub fn parse_binary<T: Copy Durable>(parser: Parser<T>, file: @~str, bytes: &[u8]) -> ParseStatus<T>
+{
+	let input = State {file: file, text: bytes_to_chars(bytes), index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	let status = parser(input);
+	let status = match input.ctx.fatal
+	{
+		option::Some(ref failure) => result::Err(*failure),
+		option::None => status,
+	};
+	
+	match status
+	{
+		result::Ok(ref pass) =>
+		{
+			result::Ok(pass.value)
+		}
+		result::Err(ref failure) =>
+		{
+			let index = failure.err_state.index;
+			result::Err(ParseFailed {file: file, line: 1u, col: index + 1u, offset: index, mesg: failure.mesg, includes: @~[], incomplete: failure.err_state.at_end()})
+		}
+	}
+}
+
+/// Matches a single byte.
+pub fn byte() -> Parser<u8>
+{
+	unsigned(1u, true).map(|n| n as u8)
+}
+
+/// Matches a byte and returns it as a signed integer.
+pub fn signed_byte() -> Parser<i8>
+{
+	unsigned(1u, true).map(|n| n as u8 as i8)
+}
+
+/// Matches a signed LEB128 integer (as used by DWARF and WebAssembly). Note that
+/// protobuf's sint types use zigzag encoding instead: decode them with varint and then
+/// compute (n >> 1) ^ -(n & 1).
+pub fn signed_varint() -> Parser<i64>
+{
+	leb128(true).map(|n| n as i64)
+}
+
+/// Matches a two byte big-endian (most significant byte first) integer.
+pub fn be_u16() -> Parser<u16>
+{
+	unsigned(2u, true).map(|n| n as u16)
+}
+
+/// Matches a four byte big-endian integer.
+pub fn be_u32() -> Parser<u32>
+{
+	unsigned(4u, true).map(|n| n as u32)
+}
+
+/// Matches an eight byte big-endian integer.
+pub fn be_u64() -> Parser<u64>
+{
+	unsigned(8u, true)
+}
+
+/// Matches a two byte big-endian two's complement integer.
+pub fn be_i16() -> Parser<i16>
+{
+	unsigned(2u, true).map(|n| n as u16 as i16)
+}
+
+/// Matches a four byte big-endian two's complement integer.
+pub fn be_i32() -> Parser<i32>
+{
+	unsigned(4u, true).map(|n| n as u32 as i32)
+}
+
+/// Matches an eight byte big-endian two's complement integer.
+pub fn be_i64() -> Parser<i64>
+{
+	unsigned(8u, true).map(|n| n as i64)
+}
+
+/// Matches a two byte little-endian (least significant byte first) integer.
+pub fn le_u16() -> Parser<u16>
+{
+	unsigned(2u, false).map(|n| n as u16)
+}
+
+/// Matches a four byte little-endian integer.
+pub fn le_u32() -> Parser<u32>
+{
+	unsigned(4u, false).map(|n| n as u32)
+}
+
+/// Matches an eight byte little-endian integer.
+pub fn le_u64() -> Parser<u64>
+{
+	unsigned(8u, false)
+}
+
+/// Matches a two byte little-endian two's complement integer.
+pub fn le_i16() -> Parser<i16>
+{
+	unsigned(2u, false).map(|n| n as u16 as i16)
+}
+
+/// Matches a four byte little-endian two's complement integer.
+pub fn le_i32() -> Parser<i32>
+{
+	unsigned(4u, false).map(|n| n as u32 as i32)
+}
+
+/// Matches an eight byte little-endian two's complement integer.
+pub fn le_i64() -> Parser<i64>
+{
+	unsigned(8u, false).map(|n| n as i64)
+}
+
+/// Matches bytes (e.g. the signature at the start of a PNG file). Errors are reported
+/// at the first byte which doesn't match.
+pub fn magic(bytes: &[u8]) -> Parser<@~[u8]>
+{
+	let bytes = @vec::map(bytes, |b| *b);
+	let mesg = @fmt!("magic number %s", str::connect(vec::map(*bytes, |b| fmt!("%02X", *b as uint)), " "));
+	
+	|input: State|
+	{
+		let mut i = 0u;
+		while i < vec::len(*bytes)
+		{
+			if input.index + i + 1u >= vec::len(input.text) || input.text[input.index + i] != bytes[i] as char
+			{
+				return result::Err(Failed {old_state: input, err_state: input.advance(i), mesg: mesg});
+			}
+			i += 1u;
+		}
+		result::Ok(Succeeded {new_state: input.advance(i), value: bytes})
+	}
+}
+
+/// Matches count bytes and returns them.
+pub fn take_bytes(count: uint) -> Parser<@~[u8]>
+{
+	let mesg = @fmt!("%u bytes", count);
+	let text_mesg = @~"binary input (see parse_binary)";
+	
+	|input: State|
+	{
+		if input.index + count >= vec::len(input.text)
+		{
+			return result::Err(Failed {old_state: input, err_state: input, mesg: mesg});
+		}
+		
+		let mut bytes = ~[];
+		for uint::range(input.index, input.index + count) |i|
+		{
+			if input.text[i] as uint > 255u
+			{
+				return result::Err(Failed {old_state: input, err_state: input.advance(i - input.index), mesg: text_mesg});
+			}
+			vec::push(&mut bytes, input.text[i] as u8);
+		}
+		result::Ok(Succeeded {new_state: input.advance(count), value: @bytes})
+	}
+}
+
+/// Matches an unsigned LEB128 integer (as used by protobuf, DWARF, and WebAssembly):
+/// seven bits per byte, least significant group first, with the high bit set on all
+/// but the last byte. Values which don't fit in 64 bits are reported as errors.
+pub fn varint() -> Parser<u64>
+{
+	leb128(false)
+}
+
+/// base64 := [A-Za-z0-9+/]+ '='*
+/// 
+/// Matches base64 encoded text (RFC 4648) and returns the decoded bytes. Whitespace
+/// (including new lines) is allowed between the characters but isn't matched after the
+/// last one. Padding is optional but, if present, must complete the final group of four
+/// characters. Errors are reported at the character which is wrong, e.g. a '=' after a
+/// complete group.
+pub fn base64() -> Parser<@~[u8]>
+{
+	|input: State|
+	{
+		let text = input.text;
+		let error = |index: uint, lines: int, mesg: &str| result::Err(Failed {old_state: input, err_state: input.advance_lines(index - input.index, lines), mesg: @mesg.to_owned()});
+		
+		let mut bytes = ~[];
+		let mut bits = 0u;
+		let mut num_bits = 0u;
+		let mut count = 0u;
+		let mut i = input.index;
+		let mut lines = 0;
+		loop
+		{
+			let (j, n) = skip_blob_space(text, i);
+			match base64_value(text[j])
+			{
+				option::Some(value) =>
+				{
+					bits = (bits << 6u) | value;
+					num_bits += 6u;
+					if num_bits >= 8u
+					{
+						num_bits -= 8u;
+						vec::push(&mut bytes, (bits >> num_bits) as u8);
+						bits &= (1u << num_bits) - 1u;
+					}
+					count += 1u;
+					i = j + 1u;
+					lines += n;
+				}
+				option::None =>
+				{
+					break;
+				}
+			}
+		}
+		if count == 0u
+		{
+			return error(input.index, 0, "base64");
+		}
+		if count % 4u == 1u
+		{
+			// One character is only six bits so it can't be the end of a group.
+			return error(i, lines, "base64 character");
+		}
+		
+		let (j, n) = skip_blob_space(text, i);
+		if text[j] == '='
+		{
+			if count % 4u == 0u
+			{
+				return error(j, lines + n, "end of base64");
+			}
+			
+			let needed = 4u - count % 4u;
+			for uint::range(0u, needed) |_i|
+			{
+				let (k, m) = skip_blob_space(text, i);
+				if text[k] != '='
+				{
+					return error(k, lines + m, "'='");
+				}
+				i = k + 1u;
+				lines += m;
+			}
+		}
+		result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, lines), value: @bytes})
+	}
+}
+
+/// hex_blob := ([0-9a-fA-F][0-9a-fA-F])+
+/// 
+/// Matches pairs of hex digits (e.g. "DE AD be ef") and returns the bytes they encode.
+/// Whitespace (including new lines) is allowed between the pairs but isn't matched
+/// after the last one. An odd number of digits is an error reported at the character
+/// after the unpaired digit.
+pub fn hex_blob() -> Parser<@~[u8]>
+{
+	|input: State|
+	{
+		let text = input.text;
+		let mut bytes = ~[];
+		let mut i = input.index;
+		let mut lines = 0;
+		loop
+		{
+			let (j, n) = skip_blob_space(text, i);
+			match hex_value(text[j])
+			{
+				option::Some(high) =>
+				{
+					match hex_value(text[j + 1u])
+					{
+						option::Some(low) =>
+						{
+							vec::push(&mut bytes, (high*16u + low) as u8);
+							i = j + 2u;
+							lines += n;
+						}
+						option::None =>
+						{
+							return result::Err(Failed {old_state: input, err_state: input.advance_lines(j + 1u - input.index, lines + n), mesg: @~"hex digit"});
+						}
+					}
+				}
+				option::None =>
+				{
+					break;
+				}
+			}
+		}
+		
+		if vec::is_empty(bytes)
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: @~"hex digits"})
+		}
+		else
+		{
+			result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, lines), value: @bytes})
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Matches count bytes and returns them as an integer. Errors are reported at the
+// first byte.
+fn unsigned(count: uint, big_endian: bool) -> Parser<u64>
+{
+	let mesg = if count == 1u {@~"byte"} else {@fmt!("%u byte integer", count)};
+	let text_mesg = @~"binary input (see parse_binary)";
+	
+	|input: State|
+	{
+		if input.index + count >= vec::len(input.text)
+		{
+			return result::Err(Failed {old_state: input, err_state: input, mesg: mesg});
+		}
+		
+		let mut value = 0u64;
+		for uint::range(0u, count) |i|
+		{
+			let ch = input.text[input.index + if big_endian {i} else {count - i - 1u}];
+			if ch as uint > 255u
+			{
+				return result::Err(Failed {old_state: input, err_state: input, mesg: text_mesg});
+			}
+			value = (value << 8u64) | (ch as u64);
+		}
+		result::Ok(Succeeded {new_state: input.advance(count), value: value})
+	}
+}
+
+// Errors are reported at the first byte.
+fn leb128(signed: bool) -> Parser<u64>
+{
+	let mesg = @~"varint";
+	let overflow_mesg = @~"varint which fits in 64 bits";
+	
+	|input: State|
+	{
+		let mut value = 0u64;
+		let mut shift = 0u;
+		let mut i = input.index;
+		loop
+		{
+			if i + 1u >= vec::len(input.text) || input.text[i] as uint > 255u
+			{
+				return result::Err(Failed {old_state: input, err_state: input, mesg: mesg});
+			}
+			
+			// The tenth byte holds bit 63: the other bits must be zero (or, for negative
+			// signed numbers, copies of bit 63).
+			let b = input.text[i] as u8;
+			let bits = (b & 0x7Fu8) as u64;
+			if (shift == 63u && !(bits == 0u64 || (!signed && bits == 1u64) || (signed && bits == 0x7Fu64))) || shift > 63u
+			{
+				return result::Err(Failed {old_state: input, err_state: input, mesg: overflow_mesg});
+			}
+			value |= bits << shift as u64;
+			shift += 7u;
+			i += 1u;
+			
+			if b & 0x80u8 == 0u8
+			{
+				if signed && shift < 64u && b & 0x40u8 != 0u8
+				{
+					value |= -1i64 as u64 << shift as u64;		// sign extend
+				}
+				return result::Ok(Succeeded {new_state: input.advance(i - input.index), value: value});
+			}
+		}
+	}
+}
+
+fn base64_value(ch: char) -> Option<uint>
+{
+	if ch >= 'A' && ch <= 'Z' {option::Some((ch as uint) - ('A' as uint))}
+	else if ch >= 'a' && ch <= 'z' {option::Some((ch as uint) - ('a' as uint) + 26u)}
+	else if is_digit(ch) {option::Some((ch as uint) - ('0' as uint) + 52u)}
+	else if ch == '+' {option::Some(62u)}
+	else if ch == '/' {option::Some(63u)}
+	else {option::None}
+}
+
+fn hex_value(ch: char) -> Option<uint>
+{
+	if is_digit(ch) {option::Some((ch as uint) - ('0' as uint))}
+	else if ch >= 'a' && ch <= 'f' {option::Some((ch as uint) - ('a' as uint) + 10u)}
+	else if ch >= 'A' && ch <= 'F' {option::Some((ch as uint) - ('A' as uint) + 10u)}
+	else {option::None}
+}
+
+// Returns the index of the first non-whitespace character at or after i along with
+// the number of new lines skipped over.
+fn skip_blob_space(text: &[char], i: uint) -> (uint, int)
+{
+	let mut j = i;
+	let mut lines = 0;
+	while is_whitespace(text[j])
+	{
+		if text[j] == '\n'
+		{
+			lines += 1;
+		}
+		j += 1u;
+	}
+	(j, lines)
+}
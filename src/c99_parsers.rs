@@ -23,21 +23,43 @@ pub fn identifier() -> Parser<@~str>
 /// but we do so to make this parser more reusable.
 pub fn decimal_number() -> Parser<int>
 {
-	do match1(is_digit).thene
-		|text|
+	// Numbers are very common so rather than using match1 and int::from_str we
+	// accumulate the value as the digits are scanned.
+	let mesg = @~"";
+	
+	|input: State|
+	{
+		let mut i = input.index;
+		let mut value = 0;
+		let mut overflowed = false;
+		while is_digit(input.text[i])
 		{
-			match int::from_str(*text)
+			let digit = (input.text[i] as int) - ('0' as int);
+			if value > (int::max_value - digit)/10
 			{
-				option::Some(value) =>
-				{
-					ret(value)
-				}
-				_ =>
-				{
-					fails(fmt!("'%s' is out of range", *text))
-				}
+				overflowed = true;
+			}
+			else
+			{
+				value = 10*value + digit;
 			}
+			i += 1u;
+		}
+		
+		if i == input.index
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+		}
+		else if overflowed
+		{
+			let text = str::from_chars(vec::view(input.text, input.index, i));
+			result::Err(Failed {old_state: input, err_state: input.advance(i - input.index), mesg: @fmt!("'%s' is out of range", text)})
 		}
+		else
+		{
+			result::Ok(Succeeded {new_state: input.advance(i - input.index), value: value})
+		}
+	}
 }
 
 /// octal_number := 0 [0-7]*
@@ -0,0 +1,158 @@
+//! A calculator which evaluates arithmetic expressions as they are parsed.
+//!
+//! This is mostly an example of how to write an expression grammar: it uses chainl1
+//! and chainr1 for binary operators, prefix operators, and a forward reference for
+//! parenthesized expressions. Variables are kept in a map which is captured by the
+//! parser so values assigned by one statement can be used by later statements:
+//!
+//! ```
+//! let calc = calculator();
+//! calc.eval("r = 2");
+//! assert calc.eval("r^2 > 3") == result::Ok(1.0);
+//! ```
+use std::map::HashMap;
+
+use c99_parsers::*;
+use parsers::*;
+use types::*;
+
+/// Evaluates statements using variables assigned by earlier statements.
+pub struct Calculator {variables: HashMap<~str, float>, parser: Parser<(Option<@~str>, float)>}
+
+/// Returns a Calculator with no variables.
+pub fn calculator() -> Calculator
+{
+	let variables = HashMap();
+	Calculator {variables: variables, parser: calc_statement(variables).everything_ws()}
+}
+
+pub impl Calculator
+{
+	/// Evaluates text and returns its value. If text is an assignment the variable is
+	/// also set. Errors are formatted with format_failure.
+	fn eval(&self, text: &str) -> result::Result<float, ~str>
+	{
+		do result::chain(to_result(self.parser.parse(@~"calc", text), text))
+		|statement|
+		{
+			let (name, value) = statement;
+			match name
+			{
+				option::Some(n) => {self.variables.insert(copy *n, value);}
+				option::None => (),
+			}
+			result::Ok(value)
+		}
+	}
+	
+	/// Returns the value of the variable (if it has been set).
+	fn get(&self, name: &str) -> Option<float>
+	{
+		self.variables.find(name.to_owned())
+	}
+	
+	/// Sets the variable to value.
+	fn set(&self, name: &str, value: float)
+	{
+		self.variables.insert(name.to_owned(), value);
+	}
+}
+
+/// statement := (identifier '=')? expr
+/// 
+/// The value is the name of the assigned variable (if any) and the value of the
+/// expression. Variables are not assigned until the parse has succeeded so that
+/// backtracking cannot assign them.
+pub fn calc_statement(variables: HashMap<~str, float>) -> Parser<(Option<@~str>, float)>
+{
+	let expr = calc_expr(variables);
+	let assignment = do seq3(identifier().s0(), "=".s0(), expr) |name, _e, value| {result::Ok((option::Some(name), value))};
+	assignment.or(expr.map(|value| (option::None, value)))
+}
+
+/// expr := sum (('==' | '!=' | '<=' | '>=' | '<' | '>') sum)*
+/// 
+/// sum := product ([+-] product)*
+/// product := unary ([*/%] unary)*
+/// unary := '-' unary | '+' unary | power
+/// power := atom ('^' atom)*
+/// atom := number | variable | '(' expr ')'
+/// 
+/// Comparisons evaluate to 1.0 if true and 0.0 if false. The '^' operator is right
+/// associative and binds more tightly than unary minus, e.g. -2^2 is -4 (note that
+/// a negative exponent has to be parenthesized: 2^(-1)).
+pub fn calc_expr(variables: HashMap<~str, float>) -> Parser<float>
+{
+	let expr_ptr = @mut ret(0.0);
+	let expr_ref = forward_ref(expr_ptr);
+	let unary_ptr = @mut ret(0.0);
+	let unary_ref = forward_ref(unary_ptr);
+	
+	// Float_number requires a '.' or an exponent so integers are parsed separately.
+	let number = float_number().map(|v| v as float).or(decimal_number().map(|v| v as float)).s0();
+	let atom = or_v(@~[
+		number,
+		variable(variables).s0(),
+		seq3_ret1("(".s0(), expr_ref, ")".s0())]).err("number, variable, or '('");
+	
+	let power = do atom.chainr1("^".s0()) |lhs, _op, rhs| {f64::pow(lhs as f64, rhs as f64) as float};
+	
+	let unary = or_v(@~[
+		seq2_ret1("-".s0(), unary_ref).map(|v| -v),
+		seq2_ret1("+".s0(), unary_ref),
+		power]);
+	*unary_ptr = unary;
+	
+	let product = do unary.chainl1(or_v(@~["*".s0(), "/".s0(), "%".s0()]))
+	|lhs, op, rhs|
+	{
+		if op == @~"*" {lhs*rhs} else if op == @~"/" {lhs/rhs} else {lhs % rhs}
+	};
+	
+	let sum = do product.chainl1("+".s0().or("-".s0()))
+		|lhs, op, rhs| {if op == @~"+" {lhs + rhs} else {lhs - rhs}};
+	
+	let comparison = or_v(@~["==".s0(), "!=".s0(), "<=".s0(), ">=".s0(), "<".s0(), ">".s0()]);
+	let expr = do sum.chainl1(comparison)
+	|lhs, op, rhs|
+	{
+		let result =
+			if op == @~"==" {lhs == rhs}
+			else if op == @~"!=" {lhs != rhs}
+			else if op == @~"<=" {lhs <= rhs}
+			else if op == @~">=" {lhs >= rhs}
+			else if op == @~"<" {lhs < rhs}
+			else {lhs > rhs};
+		if result {1.0} else {0.0}
+	};
+	*expr_ptr = expr;
+	
+	expr
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Unknown variables are reported at the start of the name. The error is fatal so
+// that it isn't replaced by a less specific error from an alternative.
+fn variable(variables: HashMap<~str, float>) -> Parser<float>
+{
+	let name = identifier();
+	
+	|input: State|
+	{
+		do result::chain(name(input))
+		|pass|
+		{
+			match variables.find(copy *pass.value)
+			{
+				option::Some(value) =>
+				{
+					result::Ok(Succeeded {new_state: pass.new_state, value: value})
+				}
+				option::None =>
+				{
+					result::Err(set_fatal(Failed {old_state: input, err_state: input, mesg: @fmt!("unknown variable '%s'", *pass.value)}))
+				}
+			}
+		}
+	}
+}
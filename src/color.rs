@@ -0,0 +1,143 @@
+//! A parser for CSS style color literals, e.g. "#f80", "#ff8000", "#ff800080",
+//! "rgb(255, 128, 0)", or "rgba(100%, 50%, 0%, 0.5)".
+use c99_parsers::*;
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Alpha is the opacity: 0.0 is transparent and 1.0 is opaque.
+pub struct Color {r: u8, g: u8, b: u8, a: float}
+
+pub impl Color
+{
+	/// Returns the color as "#rrggbb" (or "#rrggbbaa" if it is not opaque).
+	fn to_hex(&self) -> ~str
+	{
+		let s = fmt!("#%02x%02x%02x", self.r as uint, self.g as uint, self.b as uint);
+		if self.a < 1.0 {s + fmt!("%02x", float::round(self.a*255.0) as uint)} else {s}
+	}
+}
+
+/// Parses text which consists of a single color. Errors are formatted with format_failure.
+pub fn parse_color(text: &str) -> result::Result<Color, ~str>
+{
+	to_result(color().everything_ws().parse(@~"color", text), text)
+}
+
+/// color := hex_color | rgb
+/// 
+/// hex_color := '#' (hex{3} | hex{6} | hex{8})
+/// rgb := ('rgba' | 'rgb') '(' channel ',' channel ',' channel (',' alpha)? ')'
+/// channel := [0-9]+ '%'?
+/// alpha := number '%'?
+/// 
+/// Channels are 0 to 255 (or 0% to 100%) and alpha is 0 to 1 (or 0% to 100%). Note
+/// that the three digit hex form duplicates each digit, e.g. "#f80" is "#ff8800".
+pub fn color() -> Parser<Color>
+{
+	let hex_color = seq2_ret1("#".lit(), hex_digits());
+	
+	let comma = ",".s0();
+	let opacity = seq2_ret1(comma, alpha().s0()).optional();
+	let channels = do seq6(channel().s0(), comma, channel().s0(), comma, channel().s0(), opacity)
+	|r, _c1, g, _c2, b, a|
+	{
+		result::Ok(Color {r: r, g: g, b: b, a: option::get_default(a, 1.0)})
+	};
+	let name = "rgba".lit().or("rgb".lit());
+	let rgb = seq4_ret2(name, "(".s0(), channels, ")".lit());
+	
+	or_v(@~[hex_color, rgb]).err("color")
+}
+
+// ---- Helpers ---------------------------------------------------------------
+pure fn is_hex_digit(ch: char) -> bool
+{
+	is_digit(ch) || (ch >= 'a' && ch <= 'f') || (ch >= 'A' && ch <= 'F')
+}
+
+fn hex_byte(s: &str, i: uint, width: uint) -> u8
+{
+	let digits = str::slice(s, i*width, (i + 1u)*width);
+	let value = uint::from_str_radix(digits, 16u).get();
+	if width == 1u {(17u*value) as u8} else {value as u8}
+}
+
+// Parses the digits after the '#'. The wrong number of digits is reported at the
+// first digit.
+fn hex_digits() -> Parser<Color>
+{
+	let digits = match1(is_hex_digit).err("hex digits");
+	let mesg = @~"3, 6, or 8 hex digits";
+	
+	|input: State|
+	{
+		do result::chain(digits(input))
+		|pass|
+		{
+			let s: &str = *pass.value;
+			let color = match str::len(s)
+			{
+				3u => option::Some(Color {r: hex_byte(s, 0u, 1u), g: hex_byte(s, 1u, 1u), b: hex_byte(s, 2u, 1u), a: 1.0}),
+				6u => option::Some(Color {r: hex_byte(s, 0u, 2u), g: hex_byte(s, 1u, 2u), b: hex_byte(s, 2u, 2u), a: 1.0}),
+				8u => option::Some(Color {r: hex_byte(s, 0u, 2u), g: hex_byte(s, 1u, 2u), b: hex_byte(s, 2u, 2u), a: (hex_byte(s, 3u, 2u) as float)/255.0}),
+				_ => option::None,
+			};
+			match color
+			{
+				option::Some(c) => result::Ok(Succeeded {new_state: pass.new_state, value: c}),
+				option::None => result::Err(Failed {old_state: input, err_state: input, mesg: mesg}),
+			}
+		}
+	}
+}
+
+// channel := [0-9]+ '%'?
+fn channel() -> Parser<u8>
+{
+	let number = seq2(decimal_number().err("number"), "%".lit().optional(), |n, p| result::Ok((n, p.is_some())));
+	let mesg = @~"number from 0 to 255 (or 0% to 100%)";
+	
+	|input: State|
+	{
+		do result::chain(number(input))
+		|pass|
+		{
+			let (n, percent) = pass.value;
+			let max = if percent {100} else {255};
+			if n <= max
+			{
+				let value = if percent {float::round((n as float)*255.0/100.0) as u8} else {n as u8};
+				result::Ok(Succeeded {new_state: pass.new_state, value: value})
+			}
+			else
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+			}
+		}
+	}
+}
+
+// alpha := number '%'?
+fn alpha() -> Parser<float>
+{
+	let value = float_number().map(|v| v as float).or(decimal_number().map(|v| v as float)).err("number");
+	let number = seq2(value, "%".lit().optional(), |n, p| result::Ok(if p.is_some() {n/100.0} else {n}));
+	let mesg = @~"alpha from 0 to 1 (or 0% to 100%)";
+	
+	|input: State|
+	{
+		do result::chain(number(input))
+		|pass|
+		{
+			if pass.value <= 1.0
+			{
+				result::Ok(pass)
+			}
+			else
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+			}
+		}
+	}
+}
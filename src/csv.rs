@@ -0,0 +1,167 @@
+//! A parser for comma separated values (RFC 4180).
+//!
+//! Fields may be quoted with '"' in which case they may contain the delimiter, line
+//! breaks, and quotes (written as two quotes). Records end with "\r\n" or "\n" (the
+//! last record need not have a line break). Whitespace is not skipped because it is
+//! part of the field. The delimiter is usually ',' but may be any other character
+//! (e.g. '\t' or ';').
+use parsers::*;
+use streaming::*;
+use types::*;
+
+/// Parses text which consists of zero or more records. Errors are formatted with
+/// format_failure.
+pub fn parse_csv(file: &str, text: &str, delimiter: char) -> result::Result<@~[@~[@~str]], ~str>
+{
+	to_result(csv_file(delimiter).parse(@file.to_owned(), text), text)
+}
+
+/// file := record*
+/// 
+/// Matches all of the text.
+pub fn csv_file(delimiter: char) -> Parser<@~[@~[@~str]]>
+{
+	let record = csv_record(delimiter);
+	
+	|input: State|
+	{
+		let mut output = input;
+		let mut records = ~[];
+		while !output.at_end()
+		{
+			match record(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					output = pass.new_state;
+					vec::push(&mut records, pass.value);
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+		}
+		result::Ok(Succeeded {new_state: output.advance(1u), value: @records})
+	}
+}
+
+/// record := field (delimiter field)* ('\r\n' | '\n' | &EOT)
+/// 
+/// Fails at the end of the text (so that there is always at least one character in
+/// a record). Note that a blank line is a record with one empty field.
+pub fn csv_record(delimiter: char) -> Parser<@~[@~str]>
+{
+	let field = csv_field(delimiter);
+	let line_end = "\r\n".lit().or("\n".lit()).err("end of line");
+	let mesg = @~"record";
+	
+	// This is like field.list(delimiter) except that errors within fields (e.g. a
+	// missing quote) are reported instead of ending the list.
+	|input: State|
+	{
+		if input.at_end()
+		{
+			return result::Err(Failed {old_state: input, err_state: input, mesg: mesg});
+		}
+		
+		let mut output = input;
+		let mut fields = ~[];
+		loop
+		{
+			match field(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					output = pass.new_state;
+					vec::push(&mut fields, pass.value);
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+			if output.peek() != delimiter
+			{
+				break;
+			}
+			output = output.advance(1u);
+		}
+		
+		if output.at_end()
+		{
+			result::Ok(Succeeded {new_state: output, value: @fields})
+		}
+		else
+		{
+			match line_end(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					result::Ok(Succeeded {new_state: State {line: pass.new_state.line + 1, ..pass.new_state}, value: @fields})
+				}
+				result::Err(ref failure) =>
+				{
+					result::Err(Failed {old_state: input, ..*failure})
+				}
+			}
+		}
+	}
+}
+
+/// field := '"' ([^"] | '""')* '"' | [^delimiter"\r\n]*
+pub fn csv_field(delimiter: char) -> Parser<@~str>
+{
+	let quoted = seq3_ret1("\"".lit(), quoted_text(), "\"".lit());
+	let unquoted = match0(|ch| ch != delimiter && ch != '"' && ch != '\r' && ch != '\n');
+	
+	// Or would accept an empty unquoted field when the closing quote is missing.
+	|input: State|
+	{
+		if input.peek() == '"' {quoted(input)} else {unquoted(input)}
+	}
+}
+
+/// Returns a Stream which parses one record at a time (e.g. from a file which is
+/// still being written).
+/// 
+/// Note that like all streams the next method returns NeedMore until text after the
+/// record arrives (because a record at the end of the buffer may be extended) so
+/// finish should be called to get the last record.
+pub fn csv_stream(file: @~str, delimiter: char) -> Stream<@~[@~str]>
+{
+	stream(csv_record(delimiter), file)
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// The text within a quoted field: stops at the first quote which isn't doubled.
+// Unlike match0 this counts the lines within the field.
+fn quoted_text() -> Parser<@~str>
+{
+	|input: State|
+	{
+		let mut text = ~"";
+		let mut i = input.index;
+		let mut lines = 0;
+		loop
+		{
+			let ch = input.text[i];
+			if ch == EOT || (ch == '"' && input.text[i + 1u] != '"')
+			{
+				break;
+			}
+			
+			str::push_char(&mut text, ch);
+			if ch == '"'
+			{
+				i += 1u;				// skip the second quote
+			}
+			else if ch == '\n'
+			{
+				lines += 1;
+			}
+			i += 1u;
+		}
+		result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, lines), value: @text})
+	}
+}
@@ -0,0 +1,151 @@
+//! A parser for human friendly durations, e.g. "1h30m", "2d 4h", or "250ms".
+//!
+//! The units are d (days), h (hours), m (minutes), s (seconds), and ms (milliseconds).
+//! Each unit may be used once and they must be written from largest to smallest.
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// A duration normalized so that each field is less than the next larger unit (e.g.
+/// "90m" has hours 1 and minutes 30).
+pub struct Duration {days: uint, hours: uint, minutes: uint, seconds: uint, milliseconds: uint}
+
+pub impl Duration
+{
+	/// Returns the entire duration in milliseconds.
+	fn total_milliseconds(&self) -> uint
+	{
+		let mut total = 0u;
+		for vec::eachi(fields(self)) |i, n|
+		{
+			total += *n*units()[i].second();
+		}
+		total
+	}
+	
+	/// Returns the duration using the same syntax as duration, e.g. "1h30m". Zero
+	/// fields are omitted (and a zero duration is "0ms").
+	fn to_str(&self) -> ~str
+	{
+		let mut s = ~"";
+		for vec::eachi(fields(self)) |i, n|
+		{
+			if *n > 0u
+			{
+				str::push_str(&mut s, fmt!("%u%s", *n, units()[i].first()));
+			}
+		}
+		if str::is_empty(s) {~"0ms"} else {s}
+	}
+}
+
+/// Returns the normalized Duration for a number of milliseconds.
+pub fn duration_from_milliseconds(milliseconds: uint) -> Duration
+{
+	Duration {
+		days: milliseconds/86400000u,
+		hours: milliseconds/3600000u % 24u,
+		minutes: milliseconds/60000u % 60u,
+		seconds: milliseconds/1000u % 60u,
+		milliseconds: milliseconds % 1000u}
+}
+
+/// Parses text which consists of a single duration. Errors are formatted with format_failure.
+pub fn parse_duration(text: &str) -> result::Result<Duration, ~str>
+{
+	to_result(duration().everything_ws().parse(@~"duration", text), text)
+}
+
+/// duration := (number unit [ \t\r\n]*)+
+/// 
+/// number := [0-9]+
+/// unit := 'd' | 'h' | 'm' | 's' | 'ms'
+/// 
+/// Unknown, repeated, and out of order units are reported at the start of the unit.
+/// Durations too long to be represented are reported at the start of the number
+/// which made them too long.
+pub fn duration() -> Parser<Duration>
+{
+	let number = match1(is_digit).err("number");
+	let unit = match1(is_alpha).err("unit");
+	let table = units();
+	let names = str::connect(vec::map(table, |u| fmt!("'%s'", u.first())), ", ");
+	
+	|input: State|
+	{
+		let mut output = input;
+		let mut total = 0u;
+		let mut next = 0u;		// index of the largest unit which may still be used
+		loop
+		{
+			let start = output;
+			let n = match number(output)
+			{
+				result::Ok(ref pass) => {output = pass.new_state; uint::from_str(*pass.value)}
+				result::Err(ref failure) if next == 0u => {return result::Err(Failed {old_state: input, ..*failure});}
+				result::Err(_) => {break;}
+			};
+			
+			let name = match unit(output)
+			{
+				result::Ok(ref pass) => pass.value,
+				result::Err(ref failure) => {return result::Err(Failed {old_state: input, ..*failure});}
+			};
+			let mesg = match vec::position(table, |u| *name == u.first())
+			{
+				option::Some(i) if i >= next =>
+				{
+					match n
+					{
+						option::Some(n) if n <= (uint::max_value - total)/table[i].second() =>
+						{
+							total += n*table[i].second();
+						}
+						_ =>
+						{
+							return result::Err(Failed {old_state: input, err_state: start, mesg: @~"duration is too large (it must fit in a uint of milliseconds)"});
+						}
+					}
+					next = i + 1u;
+					option::None
+				}
+				option::Some(_) =>
+				{
+					option::Some(@fmt!("unit '%s' is repeated or out of order (units go from 'd' to 'ms')", *name))
+				}
+				option::None =>
+				{
+					option::Some(@fmt!("unknown unit '%s' (expected %s)", *name, names))
+				}
+			};
+			match mesg
+			{
+				option::Some(mesg) => {return result::Err(Failed {old_state: input, err_state: output, mesg: mesg});}
+				option::None => (),
+			}
+			
+			output = output.advance(str::len(*name));
+			while is_whitespace(output.peek())
+			{
+				output = if output.peek() == '\n' {output.advance_lines(1u, 1)} else {output.advance(1u)};
+			}
+			if next == vec::len(table)
+			{
+				break;
+			}
+		}
+		result::Ok(Succeeded {new_state: output, value: duration_from_milliseconds(total)})
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Returns the unit names and the number of milliseconds in each (from largest to smallest).
+fn units() -> ~[(~str, uint)]
+{
+	~[(~"d", 86400000u), (~"h", 3600000u), (~"m", 60000u), (~"s", 1000u), (~"ms", 1u)]
+}
+
+fn fields(d: &Duration) -> ~[uint]
+{
+	~[d.days, d.hours, d.minutes, d.seconds, d.milliseconds]
+}
@@ -0,0 +1,358 @@
+//! Builds parsers from grammars written in EBNF at runtime.
+//!
+//! This is useful for tools which accept user supplied grammars (e.g. linters or
+//! syntax highlighters). The grammar is written using the same ISO 14977 subset that
+//! Grammar::to_ebnf produces (plus a few PEG conveniences):
+//!
+//! ```
+//! expr = term, {("+" | "-"), term} ;
+//! term = number | "(", expr, ")" ;
+//! number = ? digits ? ;
+//! ```
+//!
+//! Rules may be defined using `=`, `::=`, or `<-`. Commas between items are optional
+//! and items may be followed by `*` or `+`. Special sequences (e.g. `? digits ?`) are
+//! terminals which are mapped onto parsers by a function (see standard_terminals).
+//! Whitespace is skipped after each literal and terminal. The parsers produce Trees
+//! (or values computed by named actions, see interpret_with).
+//!
+//! Parsers are fn@ closures so they can't be sent to other tasks but grammars can be
+//! (as EBNF). A server can use to_sendable to build its grammar once and then have
+//! each worker task build its own parser from the SendableGrammar.
+use std::map::HashMap;
+
+use c99_parsers::*;
+use grammar::*;
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Generic parse tree. Nodes are created for each rule which matched and leaves
+/// for each literal and terminal.
+pub enum Tree
+{
+	Leaf(@~str),
+	Node(@~str, @~[Tree]),
+}
+
+/// A grammar which can be sent to (or shared by) other tasks. Ebnf is the grammar,
+/// start is the name of the rule to parse, and terminals is used to find the parsers
+/// for special sequences (see interpret).
+pub struct SendableGrammar {ebnf: ~str, start: ~str, terminals: extern fn (&str) -> Option<Parser<@~str>>}
+
+pub impl SendableGrammar
+{
+	/// Returns a parser for the grammar. This should be called once by each task which
+	/// uses the grammar (the parser may then be used for any number of parses).
+	fn parser(&self) -> Parser<Tree>
+	{
+		// to_sendable has already checked that this will work.
+		interpret(self.ebnf, self.start, self.terminals).get()
+	}
+}
+
+/// Parses an EBNF grammar. Errors are formatted with format_failure.
+pub fn parse_ebnf(text: &str) -> result::Result<Grammar, ~str>
+{
+	do result::chain(to_result(ebnf_parser().everything_ws().parse(@~"grammar", text), text))
+	|rules|
+	{
+		result::Ok(Grammar {rules: @mut copy *rules, syncs: @mut ~[]})
+	}
+}
+
+/// Returns a parser for the rule named start within the EBNF grammar in text. Terminals
+/// is used to find the parser for special sequences, e.g. `? digits ?`.
+pub fn interpret(text: &str, start: &str, terminals: fn@ (&str) -> Option<Parser<@~str>>) -> result::Result<Parser<Tree>, ~str>
+{
+	let node: fn@ (@~str) -> fn@ (@~[Tree]) -> @~[Tree] = |name| |children| @~[Node(name, children)];
+	do result::chain(build_rules(text, start, terminals, |s| Leaf(s), node))
+	|parser|
+	{
+		result::Ok(parser.map(|trees: @~[Tree]| trees[0]))
+	}
+}
+
+/// Like interpret except that the parser computes values instead of building a Tree
+/// (so EBNF can be used as a declarative front end for a grammar). Leaf is called with
+/// the text matched by each literal and terminal and actions is used to find the action
+/// for each rule: this is called with the values of the literals, terminals, and rules
+/// which the rule matched. Rules without an action pass their values on to the rule
+/// which referenced them (so the start rule must have an action).
+/// 
+/// ```
+/// let actions = |name: &str| if name == "sum" {option::Some(add)} else {option::None};
+/// let p = interpret_with("sum = num, {'+', num} ; num = ? digits ? ;", "sum", standard_terminals, to_value, actions);
+/// ```
+pub fn interpret_with<T: Copy Durable>(text: &str, start: &str, terminals: fn@ (&str) -> Option<Parser<@~str>>, leaf: fn@ (@~str) -> T, actions: fn@ (&str) -> Option<fn@ (@~[T]) -> T>) -> result::Result<Parser<T>, ~str>
+{
+	let node: fn@ (@~str) -> fn@ (@~[T]) -> @~[T] = |name|
+		match actions(*name)
+		{
+			option::Some(action) => |values| @~[action(values)],
+			option::None => |values| values,
+		};
+	do result::chain(build_rules(text, start, terminals, leaf, node))
+	|parser|
+	{
+		if actions(start).is_some()
+		{
+			result::Ok(parser.map(|values: @~[T]| values[0]))
+		}
+		else
+		{
+			result::Err(fmt!("there is no action for the start rule '%s'", start))
+		}
+	}
+}
+
+/// Returns a form of grammar which can be sent to other tasks (see SendableGrammar).
+/// Note that the parsers built from it return Trees (the values computed by grammar's
+/// own parsers can't be recovered) and that whitespace is skipped as in interpret. An
+/// error is returned if the grammar can't be interpreted, e.g. because terminals
+/// doesn't know one of its terminals.
+pub fn to_sendable(grammar: &Grammar, start: &str, terminals: extern fn (&str) -> Option<Parser<@~str>>) -> result::Result<SendableGrammar, ~str>
+{
+	let ebnf = grammar.to_ebnf();
+	do result::chain(interpret(ebnf, start, terminals))
+	|_parser|
+	{
+		result::Ok(SendableGrammar {ebnf: copy ebnf, start: start.to_owned(), terminals: terminals})
+	}
+}
+
+/// Terminals for the special sequences `? letters ?`, `? digits ?`, and `? identifier ?`.
+pub fn standard_terminals(name: &str) -> Option<Parser<@~str>>
+{
+	if name == "letters"
+	{
+		option::Some(match1(is_alpha))
+	}
+	else if name == "digits"
+	{
+		option::Some(match1(is_digit))
+	}
+	else if name == "identifier"
+	{
+		option::Some(identifier())
+	}
+	else
+	{
+		option::None
+	}
+}
+
+/// Returns a Lisp like representation of tree, e.g. `(expr (term "1") "+" (term "2"))`.
+pub fn tree_to_str(tree: &Tree) -> ~str
+{
+	match *tree
+	{
+		Leaf(ref text) => json_str(**text),
+		Node(ref name, ref children) =>
+		{
+			let mut s = ~"(" + **name;
+			for vec::each(**children) |child|
+			{
+				str::push_char(&mut s, ' ');
+				str::push_str(&mut s, tree_to_str(child));
+			}
+			str::push_char(&mut s, ')');
+			s
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn ebnf_parser() -> Parser<@~[Rule]>
+{
+	let choice_ptr = @mut fails("dummy");
+	let choice_ref: Parser<@Expr> = forward_ref(choice_ptr);
+	
+	// name := identifier
+	let name = identifier().s0();
+	
+	// string := '"' [^"]* '"' | "'" [^']* "'"
+	let string = seq3_ret1("\"".lit(), match0(|ch| ch != '"' && ch != EOT), "\"".s0()).or(
+		seq3_ret1("'".lit(), match0(|ch| ch != '\'' && ch != EOT), "'".s0()));
+	
+	// special := '?' [^?]* '?'
+	let special = seq3_ret1("?".lit(), match0(|ch| ch != '?' && ch != EOT), "?".s0());
+	
+	// primary := string | special | name | '[' choice ']' | '{' choice '}' | '(' choice ')'
+	let primary = or_v(@~[
+		string.map(|s: @~str| @Literal(s)),
+		special.map(|s: @~str| @Terminal(@str::trim(*s))),
+		name.map(|s: @~str| @Ref(s)),
+		seq3_ret1("[".s0(), choice_ref, "]".s0()).map(|e: @Expr| @Repeat(e, 0u, 1u)),
+		seq3_ret1("{".s0(), choice_ref, "}".s0()).map(|e: @Expr| @Repeat(e, 0u, 0u)),
+		seq3_ret1("(".s0(), choice_ref, ")".s0())]).err("expression");
+	
+	// item := primary [*+]?
+	let item = do seq2(primary, "*".s0().or("+".s0()).optional())
+		|e, suffix|
+		{
+			if suffix == option::Some(@~"*") {result::Ok(@Repeat(e, 0u, 0u))}
+			else if suffix == option::Some(@~"+") {result::Ok(@Repeat(e, 1u, 0u))}
+			else {result::Ok(e)}
+		};
+	
+	// sequence := item (','? item)*
+	let sequence = item.list(",".s0().optional()).map(|items: @~[@Expr]| if vec::len(*items) == 1u {items[0]} else {@Sequence(items)});
+	
+	// choice := sequence ('|' sequence)*
+	let choice = sequence.list("|".s0()).map(|items: @~[@Expr]| if vec::len(*items) == 1u {items[0]} else {@Choice(items)});
+	*choice_ptr = choice;
+	
+	// rule := name ('=' | '::=' | '<-') choice ';'
+	let defined = or_v(@~["=".s0(), "::=".s0(), "<-".s0()]);
+	let rule = do seq4(name, defined, choice, ";".s0())
+		|n, _d, e, _s| {result::Ok(Rule {name: n, expr: e})};
+	
+	// grammar := rule+
+	// Note that we don't use r1 so that errors in the first rule are reported where they happened.
+	do seq2(rule, rule.r0())
+		|first, rest| {result::Ok(@(~[first] + *rest))}
+}
+
+// Returns a parser for the rule named start within the EBNF grammar in text. Leaf
+// is used to make the values for literals and terminals and node to make the function
+// which converts the values a rule matched into the values it produces.
+fn build_rules<T: Copy Durable>(text: &str, start: &str, terminals: fn@ (&str) -> Option<Parser<@~str>>, leaf: fn@ (@~str) -> T, node: fn@ (@~str) -> fn@ (@~[T]) -> @~[T]) -> result::Result<Parser<@~[T]>, ~str>
+{
+	do result::chain(parse_ebnf(text))
+	|grammar|
+	{
+		// Rules may reference rules which are defined later so we use forward references.
+		let table: HashMap<~str, @mut Parser<@~[T]>> = HashMap();
+		for vec::each(*grammar.rules) |rule|
+		{
+			if table.contains_key(copy *rule.name)
+			{
+				return result::Err(fmt!("rule '%s' is defined more than once", *rule.name));
+			}
+			table.insert(copy *rule.name, @mut fails("dummy"));
+		}
+		
+		for vec::each(*grammar.rules) |rule|
+		{
+			match build(rule.expr, table, terminals, leaf)
+			{
+				result::Ok(body) =>
+				{
+					let name = rule.name;
+					let ptr = table.get(copy *name);
+					*ptr = body.map(node(name)).named(*name);
+				}
+				result::Err(ref mesg) =>
+				{
+					return result::Err(fmt!("%s: %s", *rule.name, *mesg));
+				}
+			}
+		}
+		
+		match table.find(start.to_owned())
+		{
+			option::Some(ptr) => result::Ok(*ptr),
+			option::None => result::Err(fmt!("there is no rule named '%s'", start)),
+		}
+	}
+}
+
+// Returns a parser for expr which produces the values for each rule, literal, and
+// terminal that it matched.
+fn build<T: Copy Durable>(expr: @Expr, rules: HashMap<~str, @mut Parser<@~[T]>>, terminals: fn@ (&str) -> Option<Parser<@~str>>, leaf: fn@ (@~str) -> T) -> result::Result<Parser<@~[T]>, ~str>
+{
+	match *expr
+	{
+		Literal(ref s) =>
+		{
+			let text: &str = **s;
+			result::Ok(text.lit().s0().map(|v: @~str| @~[leaf(v)]))
+		}
+		Terminal(ref name) =>
+		{
+			match terminals(**name)
+			{
+				option::Some(parser) => result::Ok(parser.s0().map(|v: @~str| @~[leaf(v)])),
+				option::None => result::Err(fmt!("unknown terminal '? %s ?'", **name)),
+			}
+		}
+		Ref(ref name) =>
+		{
+			match rules.find(copy **name)
+			{
+				option::Some(ptr) => result::Ok(forward_ref(ptr)),
+				option::None => result::Err(fmt!("undefined rule '%s'", **name)),
+			}
+		}
+		Sequence(ref items) =>
+		{
+			do result::chain(result::map_vec(**items, |e| build(*e, rules, terminals, leaf)))
+			|parsers|
+			{
+				result::Ok(concat(@parsers))
+			}
+		}
+		Choice(ref items) =>
+		{
+			do result::chain(result::map_vec(**items, |e| build(*e, rules, terminals, leaf)))
+			|parsers|
+			{
+				result::Ok(or_v(@parsers))
+			}
+		}
+		Repeat(e, 0u, 1u) =>
+		{
+			do result::chain(build(e, rules, terminals, leaf))
+			|parser|
+			{
+				result::Ok(parser.optional().map(|v: Option<@~[T]>| v.get_default(@~[])))
+			}
+		}
+		Repeat(e, n, m) =>
+		{
+			do result::chain(build(e, rules, terminals, leaf))
+			|parser|
+			{
+				result::Ok(parser.r(n, if m == 0u {uint::max_value} else {m}).map(flatten))
+			}
+		}
+	}
+}
+
+// Like a seqN function except that it works on any number of parsers and the
+// values from each parser are appended together.
+fn concat<T: Copy Durable>(parsers: @~[Parser<@~[T]>]) -> Parser<@~[T]>
+{
+	|input: State|
+	{
+		let mut output = input;
+		let mut values = ~[];
+		for vec::each(*parsers) |parser|
+		{
+			match (*parser)(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					output = pass.new_state;
+					vec::push_all(&mut values, *pass.value);
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+		}
+		result::Ok(Succeeded {new_state: output, value: @values})
+	}
+}
+
+fn flatten<T: Copy Durable>(values: @~[@~[T]]) -> @~[T]
+{
+	let mut result = ~[];
+	for vec::each(*values) |value|
+	{
+		vec::push_all(&mut result, **value);
+	}
+	@result
+}
@@ -0,0 +1,127 @@
+//! A parser for email addresses (the addr-spec production in RFC 5322).
+//!
+//! Obsolete syntax, comments, and folding whitespace are not supported. Domains are
+//! either host names (e.g. "mail.example.com") or address literals (e.g. "[127.0.0.1]").
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Local is the part before the '@'. If the local part was quoted then quoted is set and
+/// local has the quotes and escapes removed. Labels has each part of a host name (it is
+/// empty for address literals).
+pub struct Email {local: @~str, quoted: bool, domain: @~str, labels: @~[@~str]}
+
+pub impl Email
+{
+	/// Returns the address as text (re-quoting the local part if needed).
+	fn to_str(&self) -> ~str
+	{
+		if self.quoted
+		{
+			let mut local = ~"\"";
+			for str::each_char(*self.local) |ch|
+			{
+				if ch == '"' || ch == '\\'
+				{
+					str::push_char(&mut local, '\\');
+				}
+				str::push_char(&mut local, ch);
+			}
+			fmt!("%s\"@%s", local, *self.domain)
+		}
+		else
+		{
+			fmt!("%s@%s", *self.local, *self.domain)
+		}
+	}
+}
+
+/// Parses text which consists of a single address. Errors are formatted with format_failure.
+pub fn parse_email(text: &str) -> result::Result<Email, ~str>
+{
+	to_result(email().everything_ws().parse(@~"email", text), text)
+}
+
+/// addr_spec := local '@' domain
+/// 
+/// local := dot_atom | quoted_string
+/// dot_atom := atext+ ('.' atext+)*
+/// quoted_string := '"' ([^"\\] | '\\' [\x20-\x7e])* '"'
+/// domain := label ('.' label)* | '[' [^\[\]\\]* ']'
+/// label := [a-zA-Z0-9] ([a-zA-Z0-9-]* [a-zA-Z0-9])?
+pub fn email() -> Parser<Email>
+{
+	let dot_atom = match1(is_atext).list(".".lit()).map(|atoms| @at_connect(*atoms, "."));
+	let local = or_v(@~[
+		dot_atom.map(|s| (s, false)),
+		quoted_string().map(|s| (s, true))]).err("local part");
+	
+	let labels = label().list(".".lit());
+	let literal = seq3_ret_str("[".lit(), match0(|ch| ch != '[' && ch != ']' && ch != '\\' && is_print(ch)), "]".lit());
+	let domain = or_v(@~[
+		labels.map(|labels| (@at_connect(*labels, "."), labels)),
+		literal.map(|s| (s, @~[]))]).err("domain");
+	
+	do seq3(local, "@".lit(), domain)
+	|local, _at, domain|
+	{
+		let (local, quoted) = local;
+		let (domain, labels) = domain;
+		if str::len(*local) > 64u
+		{
+			result::Err(@~"local part is longer than 64 characters")
+		}
+		else if str::len(*domain) > 255u
+		{
+			result::Err(@~"domain is longer than 255 characters")
+		}
+		else
+		{
+			result::Ok(Email {local: local, quoted: quoted, domain: domain, labels: labels})
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+pure fn is_atext(ch: char) -> bool
+{
+	is_alphanum(ch) || option::is_some(&str::find_char("!#$%&'*+/=?^_`{|}~-", ch))
+}
+
+// quoted_string := '"' ([^"\\] | '\\' [\x20-\x7e])* '"'
+fn quoted_string() -> Parser<@~str>
+{
+	let qtext = "\"\\\r\n".noc();
+	let escaped = seq2_ret1("\\".lit(), anycp(|ch| ch >= ' ' && ch <= '~')).err("escaped character");
+	let body = qtext.or(escaped).r0().map(|chars| @str::from_chars(*chars));
+	seq3_ret1("\"".lit(), body, "\"".lit())
+}
+
+// Labels may not start or end with a hyphen and are at most 63 characters. Errors
+// are reported at the start of the label.
+fn label() -> Parser<@~str>
+{
+	let chars = match1(|ch| is_alphanum(ch) || ch == '-');
+	let hyphen_mesg = @~"label which doesn't start or end with '-'";
+	let length_mesg = @~"label with at most 63 characters";
+	
+	|input: State|
+	{
+		do result::chain(chars(input))
+		|pass|
+		{
+			if str::starts_with(*pass.value, "-") || str::ends_with(*pass.value, "-")
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: hyphen_mesg})
+			}
+			else if str::len(*pass.value) > 63u
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: length_mesg})
+			}
+			else
+			{
+				result::Ok(pass)
+			}
+		}
+	}
+}
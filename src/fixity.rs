@@ -0,0 +1,176 @@
+//! Operator precedence parsing for languages like Haskell and Prolog where operators
+//! and their precedence are declared within the text being parsed (e.g. "infixl 6 +").
+//!
+//! The operators live in an OperatorTable which is consulted as the text is parsed so
+//! expressions which follow a declaration are parsed using the updated table:
+//!
+//! ```
+//! let table = new_operator_table();
+//! let decl = declare_operators(table, seq3_ret1("infix".s1(), fixity, symbols));
+//! let expr = operator_expr(table, term, |op: @~str, lhs, rhs| apply(op, lhs, rhs));
+//! ```
+use misc::*;
+use parsers::*;
+use types::*;
+
+pub enum Assoc
+{
+	/// "a - b - c" is "(a - b) - c".
+	InfixLeft,
+	
+	/// "a ^ b ^ c" is "a ^ (b ^ c)".
+	InfixRight,
+	
+	/// "a == b == c" is an error.
+	InfixNon,
+}
+
+/// Operators with higher precedence bind more tightly.
+pub struct Fixity {assoc: Assoc, precedence: uint}
+
+pub struct Operator {symbol: @~str, fixity: Fixity}
+
+/// The declared operators, longest symbol first.
+pub struct OperatorTable {operators: @mut ~[Operator]}
+
+/// Returns a table with no operators.
+pub fn new_operator_table() -> OperatorTable
+{
+	OperatorTable {operators: @mut ~[]}
+}
+
+pub impl OperatorTable
+{
+	/// Adds symbol to the table. Returns false if symbol was already declared (in which
+	/// case its fixity is replaced).
+	fn declare(&self, symbol: &str, fixity: Fixity) -> bool
+	{
+		match vec::position(*self.operators, |op| *op.symbol == symbol.to_owned())
+		{
+			option::Some(i) =>
+			{
+				(*self.operators)[i] = Operator {symbol: self.operators[i].symbol, fixity: fixity};
+				false
+			}
+			option::None =>
+			{
+				let i = option::get_default(vec::position(*self.operators, |op| str::char_len(*op.symbol) < str::char_len(symbol)), vec::len(*self.operators));
+				vec::insert(&mut *self.operators, i, Operator {symbol: @symbol.to_owned(), fixity: fixity});
+				true
+			}
+		}
+	}
+	
+	fn lookup(&self, symbol: &str) -> Option<Fixity>
+	{
+		vec::find(*self.operators, |op| *op.symbol == symbol.to_owned()).map(|op| op.fixity)
+	}
+}
+
+/// expr := term (operator term)*
+/// 
+/// Operators are the symbols in table (the longest symbol which matches is used). Symbols
+/// which end with a letter or digit are not matched if they are followed by a letter
+/// or digit (so "div" doesn't match the start of "divisor"). Space after an operator is
+/// skipped so term should normally skip trailing space. Apply is called with the
+/// operator and its operands.
+/// 
+/// Operators with the same precedence must have the same associativity. It is an error
+/// for a non-associative operator to be an operand of an operator with the same
+/// precedence, e.g. "a == b == c" when == is InfixNon.
+pub fn operator_expr<T: Copy Durable>(table: OperatorTable, term: Parser<T>, apply: fn@ (@~str, T, T) -> T) -> Parser<T>
+{
+	|input: State|
+	{
+		climb(table, term, apply, input, 0u)
+	}
+}
+
+/// If parser succeeds then its operators are declared in table (replacing previous
+/// declarations of the same symbols). The declarations are not undone if parser is
+/// later backtracked over.
+pub fn declare_operators(table: OperatorTable, parser: Parser<@~[Operator]>) -> Parser<@~[Operator]>
+{
+	|input: State|
+	{
+		do result::chain(parser(input))
+		|pass|
+		{
+			for vec::each(*pass.value) |op|
+			{
+				table.declare(*op.symbol, op.fixity);
+			}
+			result::Ok(pass)
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Parses an expression whose operators all have at least min precedence.
+fn climb<T: Copy Durable>(table: OperatorTable, term: Parser<T>, apply: fn@ (@~str, T, T) -> T, input: State, min: uint) -> Status<T>
+{
+	do result::chain(term(input))
+	|pass|
+	{
+		let mut output = pass.new_state;
+		let mut value = pass.value;
+		let mut previous = option::None;
+		loop
+		{
+			match match_operator(table, output)
+			{
+				option::Some(op) if op.fixity.precedence >= min =>
+				{
+					let precedence = op.fixity.precedence;
+					let (next, non) = match op.fixity.assoc
+					{
+						InfixLeft => (precedence + 1u, false),
+						InfixRight => (precedence, false),
+						InfixNon => (precedence + 1u, true),
+					};
+					if non && previous == option::Some(precedence)
+					{
+						return result::Err(Failed {old_state: input, err_state: output, mesg: @fmt!("'%s' is non-associative", *op.symbol)});
+					}
+					
+					let after = skip_space(output.advance(str::char_len(*op.symbol)));
+					match climb(table, term, apply, after, next)
+					{
+						result::Ok(ref rhs) =>
+						{
+							value = apply(op.symbol, value, rhs.value);
+							output = rhs.new_state;
+							previous = if non {option::Some(precedence)} else {option::None};
+						}
+						result::Err(ref failure) =>
+						{
+							return result::Err(Failed {old_state: input, ..*failure});
+						}
+					}
+				}
+				_ =>
+				{
+					break;
+				}
+			}
+		}
+		result::Ok(Succeeded {new_state: output, value: value})
+	}
+}
+
+fn match_operator(table: OperatorTable, state: State) -> Option<Operator>
+{
+	// The longest symbol and the character after it may be looked at.
+	let text = state.text;
+	if vec::is_not_empty(*table.operators)
+	{
+		state.note_examined(state.index + str::char_len(*table.operators[0].symbol));
+	}
+	do vec::find(*table.operators)
+	|op|
+	{
+		let chars = str::chars(*op.symbol);
+		let end = state.index + vec::len(chars);
+		end < vec::len(text) && vec::alli(chars, |j, ch| text[state.index + j] == *ch) && !(is_alphanum(chars[vec::len(chars) - 1u]) && is_alphanum(text[end]))
+	}
+}
@@ -0,0 +1,297 @@
+//! Parsers for shell style glob patterns like "src/**/*.rs", "*.{c,h}", or "[!a-z]?.txt"
+//! (as used by .gitignore and editorconfig files).
+//!
+//! glob returns the structure of the pattern (which is handy for tools which want to
+//! inspect it, e.g. to find the literal directory prefix) and Glob's matches method can
+//! be used to test paths against it.
+use parsers::*;
+use types::*;
+
+pub enum GlobPart
+{
+	/// Text which must match exactly (escapes have been removed).
+	GlobText(@~str),
+	
+	/// "?": any character except '/'.
+	AnyChar,
+	
+	/// "*": any characters except '/'.
+	AnyChars,
+	
+	/// "**": any characters including '/'.
+	AnyPath,
+	
+	/// "**/": zero or more directories (so "a/**/b" matches "a/b" and "a/x/y/b").
+	AnyDirs,
+	
+	/// "[...]": any character (except '/') in the class. The bool is true if the class
+	/// starts with '!' or '^' (in which case it matches characters not in the class).
+	CharClass(bool, @~[ClassItem]),
+	
+	/// "{a,b}": any of the alternatives (which may be empty).
+	Alternatives(@~[@~[GlobPart]]),
+}
+
+pub enum ClassItem
+{
+	ClassChar(char),
+	
+	/// An inclusive range, e.g. "a-z".
+	ClassRange(char, char),
+}
+
+pub struct Glob {parts: @~[GlobPart]}
+
+pub impl Glob
+{
+	/// Returns true if the pattern matches all of path.
+	fn matches(&self, path: &str) -> bool
+	{
+		let mut steps = ~[];
+		compile_parts(*self.parts, &mut steps);
+		
+		let text = str::chars(path);
+		let mut failed = vec::from_elem((vec::len(steps) + 1u)*(vec::len(text) + 1u), false);
+		match_steps(steps, text, 0u, 0u, &mut failed)
+	}
+}
+
+/// Parses text which consists of a single glob. Errors are formatted with format_failure.
+pub fn parse_glob(text: &str) -> result::Result<Glob, ~str>
+{
+	to_result(glob().everything(ret(())).parse(@~"glob", text), text)
+}
+
+/// glob := part+
+/// 
+/// part := '**/' | '**' | '*' | '?' | class | alternatives | text
+/// class := '[' [!^]? ']'? item* ']'
+/// item := char ('-' char)?
+/// alternatives := '{' part* (',' part*)* '}'
+/// text := (escape | [^*?[{\\])+
+/// escape := '\\' any
+/// 
+/// Within alternatives text also ends at ',' and '}'. A ']' at the start of a class is
+/// part of the class (so "[]]" matches "]").
+pub fn glob() -> Parser<Glob>
+{
+	let nested_ptr = @mut ret(@~[]);
+	let nested_ref = forward_ref(nested_ptr);
+	
+	let escape = seq2_ret1("\\".lit(), "".noc()).err("escaped character");
+	
+	let class_char = escape.or("]".noc());
+	let item = do seq2(class_char, seq2_ret1("-".lit(), class_char).optional())
+	|low, high|
+	{
+		match high
+		{
+			option::Some(high) if high < low => result::Err(@fmt!("range ending at or after '%c'", low)),
+			option::Some(high) => result::Ok(ClassRange(low, high)),
+			option::None => result::Ok(ClassChar(low)),
+		}
+	};
+	let class = do seq5("[".lit(), "!^".anyc().optional(), "]".litv(ClassChar(']')).optional(), item.r0(), "]".lit())
+	|_o, negated, first, items, _c|
+	{
+		let mut all = match first {option::Some(item) => ~[item], option::None => ~[]};
+		vec::push_all(&mut all, *items);
+		result::Ok(CharClass(negated.is_some(), @all))
+	};
+	
+	let alternatives = seq3_ret1("{".lit(), nested_ref.list(",".lit()), "}".lit());
+	let alternatives = alternatives.map(|alts| Alternatives(alts));
+	
+	let wildcard = or_v(@~[
+		"**/".litv(AnyDirs),
+		"**".litv(AnyPath),
+		"*".litv(AnyChars),
+		"?".litv(AnyChar),
+		class,
+		alternatives]);
+	
+	*nested_ptr = wildcard.or(glob_text(escape, "*?[{\\,}")).r0();
+	wildcard.or(glob_text(escape, "*?[{\\")).r1().map(|parts| Glob {parts: parts})
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn glob_text(escape: Parser<char>, specials: &str) -> Parser<GlobPart>
+{
+	escape.or(specials.noc()).r1().map(|chars| GlobText(@str::from_chars(*chars)))
+}
+
+// A glob flattened so that each step has an index (which match_steps remembers
+// failures by). Alternatives become a fork to the start of each alternative and each
+// alternative ends with a jump past the others.
+enum GlobStep
+{
+	PartStep(GlobPart),
+	ForkStep(@~[uint]),
+	JumpStep(uint),
+}
+
+fn compile_parts(parts: &[GlobPart], steps: &mut ~[GlobStep])
+{
+	for vec::each(parts) |part|
+	{
+		match *part
+		{
+			Alternatives(alts) =>
+			{
+				let fork = vec::len(*steps);
+				vec::push(steps, ForkStep(@~[]));
+				
+				let mut starts = ~[];
+				let mut jumps = ~[];
+				for vec::each(*alts) |alt|
+				{
+					vec::push(&mut starts, vec::len(*steps));
+					compile_parts(**alt, steps);
+					vec::push(&mut jumps, vec::len(*steps));
+					vec::push(steps, JumpStep(0u));
+				}
+				
+				let end = vec::len(*steps);
+				for vec::each(jumps) |j|
+				{
+					(*steps)[*j] = JumpStep(end);
+				}
+				(*steps)[fork] = ForkStep(@starts);
+			}
+			_ =>
+			{
+				vec::push(steps, PartStep(*part));
+			}
+		}
+	}
+}
+
+// Returns true if the steps starting at step match all of the text starting at i.
+// Failed records the (step, i) pairs which cannot match so that each is only tried
+// once (otherwise patterns like "*a*a*a*b" or "{a,a}{a,a}{a,a}b" take exponential time).
+fn match_steps(steps: &[GlobStep], text: &[char], step: uint, i: uint, failed: &mut ~[bool]) -> bool
+{
+	let key = step*(vec::len(text) + 1u) + i;
+	if failed[key]
+	{
+		return false;
+	}
+	
+	let matched = match_step(steps, text, step, i, failed);
+	if !matched
+	{
+		failed[key] = true;
+	}
+	matched
+}
+
+fn match_step(steps: &[GlobStep], text: &[char], step: uint, i: uint, failed: &mut ~[bool]) -> bool
+{
+	if step == vec::len(steps)
+	{
+		return i == vec::len(text);
+	}
+	
+	let next = step + 1u;
+	match steps[step]
+	{
+		PartStep(GlobText(s)) =>
+		{
+			let chars = str::chars(*s);
+			i + vec::len(chars) <= vec::len(text) && vec::alli(chars, |j, ch| text[i + j] == *ch) && match_steps(steps, text, next, i + vec::len(chars), failed)
+		}
+		PartStep(AnyChar) =>
+		{
+			i < vec::len(text) && text[i] != '/' && match_steps(steps, text, next, i + 1u, failed)
+		}
+		PartStep(AnyChars) =>
+		{
+			let mut j = i;
+			loop
+			{
+				if match_steps(steps, text, next, j, failed)
+				{
+					return true;
+				}
+				if j == vec::len(text) || text[j] == '/'
+				{
+					return false;
+				}
+				j += 1u;
+			}
+		}
+		PartStep(AnyPath) =>
+		{
+			let mut j = i;
+			loop
+			{
+				if match_steps(steps, text, next, j, failed)
+				{
+					return true;
+				}
+				if j == vec::len(text)
+				{
+					return false;
+				}
+				j += 1u;
+			}
+		}
+		PartStep(AnyDirs) =>
+		{
+			// Try the rest of the pattern at the start of each directory.
+			let mut j = i;
+			loop
+			{
+				if match_steps(steps, text, next, j, failed)
+				{
+					return true;
+				}
+				while j < vec::len(text) && text[j] != '/'
+				{
+					j += 1u;
+				}
+				if j == vec::len(text)
+				{
+					return false;
+				}
+				j += 1u;
+			}
+		}
+		PartStep(CharClass(negated, items)) =>
+		{
+			i < vec::len(text) && text[i] != '/' && class_contains(*items, text[i]) != negated && match_steps(steps, text, next, i + 1u, failed)
+		}
+		PartStep(Alternatives(_)) =>
+		{
+			fail ~"alternatives should have been compiled into forks"
+		}
+		ForkStep(starts) =>
+		{
+			for vec::each(*starts) |start|
+			{
+				if match_steps(steps, text, *start, i, failed)
+				{
+					return true;
+				}
+			}
+			false
+		}
+		JumpStep(target) =>
+		{
+			match_steps(steps, text, target, i, failed)
+		}
+	}
+}
+
+fn class_contains(items: &[ClassItem], ch: char) -> bool
+{
+	do vec::any(items)
+	|item|
+	{
+		match *item
+		{
+			ClassChar(c) => ch == c,
+			ClassRange(low, high) => ch >= low && ch <= high,
+		}
+	}
+}
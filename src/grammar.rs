@@ -0,0 +1,977 @@
+//! Grammars which can describe themselves, e.g. as EBNF.
+//!
+//! Parsers are closures so they cannot be inspected. Instead productions are built
+//! from Syntax values which pair a parser with an Expr describing what it matches.
+//! Productions are added to a Grammar with the rule method and the grammar can then
+//! be exported (see to_ebnf). Because the description and the parser are built by the
+//! same code the documentation cannot get out of sync with the parser.
+//!
+//! The expressions are also used for error recovery: sync skips to a character which
+//! can follow a rule so that, e.g., `item.recover(&g, value)` resumes parsing after a
+//! bad list item without the grammar having to spell out where items end.
+use misc::{EOT, json_str};
+use parsers::*;
+use rand::RngUtil;
+use std::sort;
+use types::*;
+
+/// Describes what a parser matches.
+pub enum Expr
+{
+	/// Matches the string.
+	Literal(@~str),
+	
+	/// Matches something which can only be described in words, e.g. "decimal digit".
+	Terminal(@~str),
+	
+	/// Matches a rule within the grammar.
+	Ref(@~str),
+	
+	/// Matches each expression in turn.
+	Sequence(@~[@Expr]),
+	
+	/// Matches the first expression which matches.
+	Choice(@~[@Expr]),
+	
+	/// Matches the expression min to max times (max is zero if there is no upper bound).
+	Repeat(@Expr, uint, uint),
+}
+
+/// A parser along with a description of what it matches.
+pub struct Syntax<T: Copy Durable> {parser: Parser<T>, expr: @Expr}
+
+/// A named production within a grammar.
+pub struct Rule {name: @~str, expr: @Expr}
+
+/// The rules which have been added to the grammar (in the order they were added).
+/// Syncs are the follow sets used by the sync parsers (they are updated as rules are
+/// added).
+pub struct Grammar {rules: @mut ~[Rule], syncs: @mut ~[(@~str, @mut ~str)]}
+
+/// Returns syntax which matches the first alternative which matches. Unlike or this
+/// uses the expressions to figure out which characters can start each alternative so
+/// that alternatives which cannot match are skipped (see or_first).
+pub fn choice<T: Copy Durable>(alternatives: @~[Syntax<T>]) -> Syntax<T>
+{
+	let entries = do vec::map(*alternatives)
+		|a|
+		{
+			let first = match first_chars(a.expr) {option::Some(chars) => chars, option::None => ~""};
+			(@first, a.parser)
+		};
+	Syntax {parser: or_first(@entries), expr: @Choice(@vec::map(*alternatives, |a| a.expr))}
+}
+
+/// Returns a grammar with no rules.
+pub fn grammar() -> Grammar
+{
+	Grammar {rules: @mut ~[], syncs: @mut ~[]}
+}
+
+/// Returns syntax which matches s.
+pub fn literal(s: &str) -> Syntax<@~str>
+{
+	Syntax {parser: s.lit(), expr: @Literal(@s.to_owned())}
+}
+
+/// Returns syntax for an arbitrary parser. Description is used when exporting
+/// the grammar and as the parser's name and error label, e.g. "decimal digit".
+pub fn terminal<T: Copy Durable>(description: &str, parser: Parser<T>) -> Syntax<T>
+{
+	Syntax {parser: parser.named(description).err(description), expr: @Terminal(@description.to_owned())}
+}
+
+pub impl Grammar
+{
+	/// Returns warnings for alternatives which can never match because an earlier
+	/// alternative always matches first, e.g. `"<" | "<="` or `[a] | b`. These are easy
+	/// to write and otherwise silent: the parser simply fails later on.
+	fn check_alternatives(&self) -> ~[~str]
+	{
+		let mut warnings = ~[];
+		for vec::each(*self.rules) |rule|
+		{
+			check_expr(self, rule.name, rule.expr, &mut warnings);
+		}
+		return warnings;
+	}
+	
+	/// Returns errors for rules which are left recursive, e.g. `expr = expr, "+", term`
+	/// or `a = b, "x" ; b = [c], a`. Parsers for these rules recurse forever (or until
+	/// they exceed the limits) so this is worth checking once the grammar is built. Each
+	/// cycle is reported once with the path of rules which leads back to the first.
+	fn check_left_recursion(&self) -> ~[~str]
+	{
+		let mut errors = ~[];
+		let mut reported: ~[@~str] = ~[];
+		for vec::each(*self.rules) |rule|
+		{
+			if !vec::any(reported, |n| **n == *rule.name)
+			{
+				let mut visited = ~[rule.name];
+				match left_cycle(self, rule.name, rule.name, ~[rule.name], &mut visited)
+				{
+					option::Some(path) =>
+					{
+						vec::push(&mut errors, fmt!("%s is left recursive: %s", *rule.name, str::connect(vec::map(path, |n| copy **n), " -> ")));
+						vec::push_all(&mut reported, path);
+					}
+					option::None =>
+					{
+					}
+				}
+			}
+		}
+		return errors;
+	}
+	
+	/// Returns the characters which can follow the rule named name (in sorted order).
+	/// This is the rule's FOLLOW set except that terminals can't be described so they
+	/// are ignored (as is the end of the text).
+	fn follow_set(&self, name: &str) -> ~str
+	{
+		match vec::position(*self.rules, |r| str::eq_slice(*r.name, name))
+		{
+			option::Some(i) => copy follow_sets(self)[i],
+			option::None => fail fmt!("there is no rule named '%s'", name),
+		}
+	}
+	
+	/// Returns syntax which references a rule that has not been added yet. Parser
+	/// should be set to the rule's parser before parsing. This is used for recursive
+	/// productions, see forward_ref.
+	fn forward<T: Copy Durable>(&self, name: &str, parser: @mut Parser<T>) -> Syntax<T>
+	{
+		Syntax {parser: forward_ref(parser), expr: @Ref(@name.to_owned())}
+	}
+	
+	/// Returns random text which should be matched by the rule named start (e.g. for fuzz
+	/// testing, see check_fuzz). Terminals is called to generate text for terminals and is
+	/// passed their description, e.g. "decimal digits".
+	/// 
+	/// Note that s0 is not recorded in the grammar so no whitespace is generated (but
+	/// terminals may return text with trailing whitespace to keep tokens apart). Also
+	/// note that rules are expected to have an alternative which is not recursive.
+	fn generate(&self, start: &str, rng: rand::Rng, terminals: fn@ (&str) -> ~str) -> ~str
+	{
+		let mut text = ~"";
+		generate_expr(self, @Ref(@start.to_owned()), rng, terminals, 0u, &mut text);
+		return text;
+	}
+	
+	/// Adds a rule named name to the grammar and returns syntax which references it.
+	/// The parser is also named (see the named method).
+	fn rule<T: Copy Durable>(&self, name: &str, syntax: Syntax<T>) -> Syntax<T>
+	{
+		let name = @name.to_owned();
+		vec::push(&mut *self.rules, Rule {name: name, expr: syntax.expr});
+		if vec::is_not_empty(*self.syncs)
+		{
+			update_syncs(self);
+		}
+		Syntax {parser: syntax.parser.named(*name), expr: @Ref(name)}
+	}
+	
+	/// Returns a parser which skips up to (but not including) the next character which
+	/// can follow the rule named name (or the end of the text) and returns the skipped
+	/// text. This is the default skip parser for Syntax's recover method.
+	/// 
+	/// The rule must have already been added (fails if it hasn't). Rules may be added
+	/// afterwards and the follow set is updated as they are (e.g. a list rule which
+	/// references an item rule adds the list's separators to the item's follow set).
+	fn sync(&self, name: &str) -> Parser<@~str>
+	{
+		let follow = @mut self.follow_set(name);
+		vec::push(&mut *self.syncs, (@name.to_owned(), follow));
+		
+		|input: State|
+		{
+			let chars = copy *follow;
+			
+			let mut i = input.index;
+			let mut lines = 0;
+			while input.text[i] != EOT && !str::contains_char(chars, input.text[i])
+			{
+				if input.text[i] == '\n'
+				{
+					lines += 1;
+				}
+				i += 1u;
+			}
+			result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, lines), value: @str::from_chars(vec::slice(input.text, input.index, i))})
+		}
+	}
+	
+	/// Returns the grammar in ISO 14977 EBNF with one line per rule, e.g.
+	/// `expr = term, {("+" | "-"), term} ;`
+	/// 
+	/// Note that whitespace (e.g. from s0) is not included.
+	fn to_ebnf(&self) -> ~str
+	{
+		let mut s = ~"";
+		for vec::each(*self.rules) |rule|
+		{
+			str::push_str(&mut s, fmt!("%s = %s ;\n", *rule.name, expr_to_ebnf(rule.expr, CHOICE_PREC)));
+		}
+		return s;
+	}
+	
+	/// Returns railroad diagrams for the rules in the grammar as a Graphviz DOT graph
+	/// with one cluster per rule. Literals are drawn as rounded boxes, terminals as
+	/// dashed rounded boxes, and references to other rules as plain boxes. The output
+	/// can be rendered with something like `dot -Tsvg grammar.dot > grammar.svg`.
+	fn to_railroad(&self) -> ~str
+	{
+		let mut lines = ~[~"digraph grammar", ~"{", ~"\trankdir=LR;"];
+		for vec::eachi(*self.rules) |i, rule|
+		{
+			let prefix = fmt!("r%u", i);
+			let mut body = ~[];
+			let mut count = 0u;
+			
+			let start = add_node(&mut body, &mut count, prefix, "shape=circle, width=0.15, label=\"\"");
+			let exit = add_rail(&mut body, &mut count, prefix, rule.expr, start);
+			let end = add_node(&mut body, &mut count, prefix, "shape=doublecircle, width=0.1, label=\"\"");
+			vec::push(&mut body, fmt!("%s -> %s;", exit, end));
+			
+			vec::push(&mut lines, fmt!("\tsubgraph cluster_%s", prefix));
+			vec::push(&mut lines, ~"\t{");
+			vec::push(&mut lines, fmt!("\t\tlabel=%s;", json_str(*rule.name)));
+			for vec::each(body) |line| {vec::push(&mut lines, ~"\t\t" + *line);}
+			vec::push(&mut lines, ~"\t}");
+		}
+		vec::push(&mut lines, ~"}");
+		return str::connect(lines, "\n") + "\n";
+	}
+}
+
+pub impl<T: Copy Durable> Syntax<T>
+{
+	/// See the list method in Combinators.
+	fn list<U: Copy Durable>(&self, sep: Syntax<U>) -> Syntax<@~[T]>
+	{
+		let tail = @Repeat(sequence_expr(sep.expr, self.expr), 0u, 0u);
+		Syntax {parser: self.parser.list(sep.parser), expr: sequence_expr(self.expr, tail)}
+	}
+	
+	/// See the map method in Combinators.
+	fn map<U: Copy Durable>(&self, eval: fn@ (T) -> U) -> Syntax<U>
+	{
+		Syntax {parser: self.parser.map(eval), expr: self.expr}
+	}
+	
+	/// See the optional method in Combinators.
+	fn optional(&self) -> Syntax<Option<T>>
+	{
+		Syntax {parser: self.parser.optional(), expr: @Repeat(self.expr, 0u, 1u)}
+	}
+	
+	/// See the or method in Combinators.
+	fn or(&self, syntax2: Syntax<T>) -> Syntax<T>
+	{
+		Syntax {parser: self.parser.or(syntax2.parser), expr: choice_expr(self.expr, syntax2.expr)}
+	}
+	
+	/// See the r method in Combinators. M must be positive (use uint::max_value if there
+	/// is no upper bound).
+	fn r(&self, n: uint, m: uint) -> Syntax<@~[T]>
+	{
+		assert m > 0u;
+		Syntax {parser: self.parser.r(n, m), expr: @Repeat(self.expr, n, if m == uint::max_value {0u} else {m})}
+	}
+	
+	/// See the r0 method in Combinators.
+	fn r0(&self) -> Syntax<@~[T]>
+	{
+		Syntax {parser: self.parser.r0(), expr: @Repeat(self.expr, 0u, 0u)}
+	}
+	
+	/// See the r1 method in Combinators.
+	fn r1(&self) -> Syntax<@~[T]>
+	{
+		Syntax {parser: self.parser.r1(), expr: @Repeat(self.expr, 1u, 0u)}
+	}
+	
+	/// Like the recover method in Combinators except that the bad text is skipped using
+	/// the grammar's sync parser for the rule self references (so self should have been
+	/// returned by the rule method).
+	fn recover(&self, grammar: &Grammar, value: T) -> Syntax<T>
+	{
+		match *self.expr
+		{
+			Ref(name) => Syntax {parser: self.parser.recover(grammar.sync(*name), value), expr: self.expr},
+			_ => fail fmt!("recover needs a reference to a rule, not %s", expr_to_ebnf(self.expr, CHOICE_PREC)),
+		}
+	}
+	
+	/// See the s0 method in Combinators.
+	fn s0(&self) -> Syntax<T>
+	{
+		Syntax {parser: self.parser.s0(), expr: self.expr}
+	}
+	
+	/// See the then method in Combinators.
+	fn then<U: Copy Durable>(&self, syntax2: Syntax<U>) -> Syntax<U>
+	{
+		Syntax {parser: self.parser.then(syntax2.parser), expr: sequence_expr(self.expr, syntax2.expr)}
+	}
+}
+
+pub impl<T: Copy Durable> Syntax<T> : Parses<T>
+{
+	fn apply(&self, input: State) -> Status<T>
+	{
+		(self.parser)(input)
+	}
+	
+	/// Returns the EBNF for the syntax.
+	fn description(&self) -> Option<@~str>
+	{
+		option::Some(@expr_to_ebnf(self.expr, CHOICE_PREC))
+	}
+	
+	fn first_set(&self) -> Option<@~str>
+	{
+		match first_chars(self.expr)
+		{
+			option::Some(ref chars) => option::Some(@copy *chars),
+			option::None => option::None,
+		}
+	}
+	
+	/// Returns the rule name if the syntax is a reference to a rule.
+	fn name(&self) -> Option<@~str>
+	{
+		match *self.expr
+		{
+			Ref(name) => option::Some(name),
+			_ => option::None,
+		}
+	}
+	
+	fn to_parser(&self) -> Parser<T>
+	{
+		self.parser
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+const CHOICE_PREC: uint = 0;
+const SEQUENCE_PREC: uint = 1;
+const ATOM_PREC: uint = 2;
+
+// Generated text uses random choices until rules are nested this deeply.
+const GENERATE_DEPTH: uint = 8;
+const MAX_GENERATE_DEPTH: uint = 100;
+
+// Sequences and choices are flattened so that a.then(b).then(c) is exported
+// as a, b, c instead of (a, b), c.
+fn sequence_expr(lhs: @Expr, rhs: @Expr) -> @Expr
+{
+	match *lhs
+	{
+		Sequence(ref items) => @Sequence(@(**items + ~[rhs])),
+		_ => @Sequence(@~[lhs, rhs]),
+	}
+}
+
+fn choice_expr(lhs: @Expr, rhs: @Expr) -> @Expr
+{
+	match *lhs
+	{
+		Choice(ref items) => @Choice(@(**items + ~[rhs])),
+		_ => @Choice(@~[lhs, rhs]),
+	}
+}
+
+// Adds a node with the given attributes and returns its name.
+fn add_node(lines: &mut ~[~str], count: &mut uint, prefix: &str, attrs: &str) -> ~str
+{
+	let name = fmt!("%s_%u", prefix, *count);
+	*count += 1u;
+	vec::push(lines, fmt!("%s [%s];", name, attrs));
+	return name;
+}
+
+// Adds the nodes and edges for expr starting at node from. Returns the node
+// the rail leaves from.
+fn add_rail(lines: &mut ~[~str], count: &mut uint, prefix: &str, expr: @Expr, from: ~str) -> ~str
+{
+	match *expr
+	{
+		Literal(ref s) => add_station(lines, count, prefix, fmt!("shape=box, style=rounded, label=%s", json_str(**s)), from),
+		Terminal(ref s) => add_station(lines, count, prefix, fmt!("shape=box, style=\"rounded,dashed\", label=%s", json_str(**s)), from),
+		Ref(ref name) => add_station(lines, count, prefix, fmt!("shape=box, label=%s", json_str(**name)), from),
+		Sequence(ref items) =>
+		{
+			let mut node = from;
+			for vec::each(**items) |item|
+			{
+				node = add_rail(lines, count, prefix, *item, copy node);
+			}
+			node
+		}
+		Choice(ref items) =>
+		{
+			let join = add_node(lines, count, prefix, "shape=point");
+			for vec::each(**items) |item|
+			{
+				let exit = add_rail(lines, count, prefix, *item, copy from);
+				vec::push(lines, fmt!("%s -> %s;", exit, join));
+			}
+			join
+		}
+		Repeat(e, n, m) =>
+		{
+			let mut node = from;
+			for uint::range(0u, n) |_i|
+			{
+				node = add_rail(lines, count, prefix, e, copy node);
+			}
+			
+			if m == 0u
+			{
+				// Unbounded so loop back to a junction.
+				let junction = add_node(lines, count, prefix, "shape=point");
+				vec::push(lines, fmt!("%s -> %s;", node, junction));
+				let exit = add_rail(lines, count, prefix, e, copy junction);
+				vec::push(lines, fmt!("%s -> %s;", exit, junction));
+				junction
+			}
+			else if m > n
+			{
+				// Each optional copy may be bypassed.
+				let join = add_node(lines, count, prefix, "shape=point");
+				for uint::range(n, m) |_i|
+				{
+					vec::push(lines, fmt!("%s -> %s;", node, join));
+					node = add_rail(lines, count, prefix, e, copy node);
+				}
+				vec::push(lines, fmt!("%s -> %s;", node, join));
+				join
+			}
+			else
+			{
+				node
+			}
+		}
+	}
+}
+
+// Adds a node for a literal, terminal, or reference along with an edge to it.
+fn add_station(lines: &mut ~[~str], count: &mut uint, prefix: &str, attrs: ~str, from: ~str) -> ~str
+{
+	let node = add_node(lines, count, prefix, attrs);
+	vec::push(lines, fmt!("%s -> %s;", from, node));
+	return node;
+}
+
+// Returns the characters which can start expr or None if it can start with anything
+// (or match nothing). Note that references to other rules aren't followed.
+#[doc(hidden)]
+pub fn first_chars(expr: @Expr) -> Option<~str>
+{
+	match *expr
+	{
+		Literal(ref s) =>
+		{
+			if str::is_empty(**s) {option::None} else {option::Some(str::from_char(str::char_at(**s, 0u)))}
+		}
+		Terminal(_) | Ref(_) =>
+		{
+			option::None
+		}
+		Sequence(ref items) =>
+		{
+			first_chars(items[0])
+		}
+		Choice(ref items) =>
+		{
+			let mut chars = ~"";
+			for vec::each(**items) |item|
+			{
+				match first_chars(*item)
+				{
+					option::Some(ref first) => str::push_str(&mut chars, *first),
+					option::None => return option::None,
+				}
+			}
+			option::Some(chars)
+		}
+		Repeat(e, n, _) =>
+		{
+			if n > 0u {first_chars(e)} else {option::None}
+		}
+	}
+}
+
+// Returns the rules which can be referenced before expr has consumed anything.
+fn left_refs(grammar: &Grammar, expr: @Expr) -> ~[@~str]
+{
+	match *expr
+	{
+		Literal(_) | Terminal(_) => ~[],
+		Ref(name) => ~[name],
+		Sequence(ref items) =>
+		{
+			let mut names = ~[];
+			for vec::each(**items) |item|
+			{
+				vec::push_all(&mut names, left_refs(grammar, *item));
+				if !nullable(grammar, *item, ~[])
+				{
+					break;
+				}
+			}
+			names
+		}
+		Choice(ref items) => vec::concat(vec::map(**items, |item| left_refs(grammar, *item))),
+		Repeat(e, _, _) => left_refs(grammar, e),
+	}
+}
+
+// Returns the path from current back to target (using references which happen
+// before anything is consumed).
+fn left_cycle(grammar: &Grammar, target: @~str, current: @~str, path: ~[@~str], visited: &mut ~[@~str]) -> Option<~[@~str]>
+{
+	match vec::find(*grammar.rules, |r| *r.name == *current)
+	{
+		option::Some(ref rule) =>
+		{
+			for vec::each(left_refs(grammar, rule.expr)) |name|
+			{
+				if **name == *target
+				{
+					return option::Some(path + ~[*name]);
+				}
+				if !vec::any(*visited, |v| **v == **name)
+				{
+					vec::push(visited, *name);
+					match left_cycle(grammar, target, *name, path + ~[*name], visited)
+					{
+						option::Some(cycle) => return option::Some(cycle),
+						option::None => (),
+					}
+				}
+			}
+			option::None
+		}
+		option::None =>
+		{
+			option::None
+		}
+	}
+}
+
+// Like first_chars except that references are followed and the characters are only
+// those from literals (so the result may be incomplete).
+fn literal_firsts(grammar: &Grammar, expr: @Expr, visiting: ~[@~str]) -> ~[char]
+{
+	match *expr
+	{
+		Literal(ref s) =>
+		{
+			if str::is_empty(**s) {~[]} else {~[str::char_at(**s, 0u)]}
+		}
+		Terminal(_) =>
+		{
+			~[]
+		}
+		Ref(ref name) =>
+		{
+			if vec::any(visiting, |n| **n == **name)
+			{
+				~[]
+			}
+			else
+			{
+				match vec::find(*grammar.rules, |r| *r.name == **name)
+				{
+					option::Some(ref rule) => literal_firsts(grammar, rule.expr, visiting + ~[*name]),
+					option::None => ~[],
+				}
+			}
+		}
+		Sequence(ref items) =>
+		{
+			let mut chars = ~[];
+			for vec::each(**items) |item|
+			{
+				add_chars(&mut chars, literal_firsts(grammar, *item, copy visiting));
+				if !nullable(grammar, *item, ~[])
+				{
+					break;
+				}
+			}
+			chars
+		}
+		Choice(ref items) =>
+		{
+			let mut chars = ~[];
+			for vec::each(**items) |item|
+			{
+				add_chars(&mut chars, literal_firsts(grammar, *item, copy visiting));
+			}
+			chars
+		}
+		Repeat(e, _, _) =>
+		{
+			literal_firsts(grammar, e, visiting)
+		}
+	}
+}
+
+// Adds the characters which can follow the rules referenced within expr to follows
+// (which is indexed like grammar.rules) given that tail can follow expr. Returns true
+// if follows changed.
+fn add_follows(grammar: &Grammar, expr: @Expr, tail: ~[char], follows: &mut ~[~[char]]) -> bool
+{
+	match *expr
+	{
+		Literal(_) | Terminal(_) =>
+		{
+			false
+		}
+		Ref(ref name) =>
+		{
+			match vec::position(*grammar.rules, |r| *r.name == **name)
+			{
+				option::Some(i) => add_chars(&mut follows[i], tail),
+				option::None => false,
+			}
+		}
+		Sequence(ref items) =>
+		{
+			// Work backwards so that we know what can follow each item.
+			let mut changed = false;
+			let mut tail = tail;
+			let mut i = vec::len(**items);
+			while i > 0u
+			{
+				i -= 1u;
+				if add_follows(grammar, items[i], copy tail, follows)
+				{
+					changed = true;
+				}
+				
+				let firsts = literal_firsts(grammar, items[i], ~[]);
+				if nullable(grammar, items[i], ~[])
+				{
+					add_chars(&mut tail, firsts);
+				}
+				else
+				{
+					tail = firsts;
+				}
+			}
+			changed
+		}
+		Choice(ref items) =>
+		{
+			let mut changed = false;
+			for vec::each(**items) |item|
+			{
+				if add_follows(grammar, *item, copy tail, follows)
+				{
+					changed = true;
+				}
+			}
+			changed
+		}
+		Repeat(e, _, m) =>
+		{
+			// If e can repeat then it can be followed by itself.
+			let mut tail = tail;
+			if m != 1u
+			{
+				add_chars(&mut tail, literal_firsts(grammar, e, ~[]));
+			}
+			add_follows(grammar, e, tail, follows)
+		}
+	}
+}
+
+// Adds the chars which are not already in set. Returns true if any were added.
+// Returns the follow set of each rule (in the same order as the rules).
+fn follow_sets(grammar: &Grammar) -> ~[~str]
+{
+	let rules = copy *grammar.rules;
+	let mut follows = vec::from_elem(vec::len(rules), ~[]);
+	loop
+	{
+		let mut changed = false;
+		for vec::eachi(rules) |i, rule|
+		{
+			let tail = copy follows[i];
+			if add_follows(grammar, rule.expr, tail, &mut follows)
+			{
+				changed = true;
+			}
+		}
+		if !changed
+		{
+			break;
+		}
+	}
+	
+	vec::map(follows, |f| str::from_chars(sort::merge_sort(*f, |x, y| *x <= *y)))
+}
+
+// Recomputes the follow sets used by the sync parsers.
+fn update_syncs(grammar: &Grammar)
+{
+	let follows = follow_sets(grammar);
+	for vec::each(*grammar.syncs) |&(name, follow)|
+	{
+		// Sync checked that the rule exists.
+		let i = vec::position(*grammar.rules, |r| *r.name == *name).get();
+		*follow = copy follows[i];
+	}
+}
+
+fn add_chars(set: &mut ~[char], chars: &[char]) -> bool
+{
+	let mut changed = false;
+	for vec::each(chars) |ch|
+	{
+		if !vec::contains(*set, ch)
+		{
+			vec::push(set, *ch);
+			changed = true;
+		}
+	}
+	changed
+}
+
+fn check_expr(grammar: &Grammar, name: @~str, expr: @Expr, warnings: &mut ~[~str])
+{
+	match *expr
+	{
+		Literal(_) | Terminal(_) | Ref(_) =>
+		{
+		}
+		Sequence(ref items) =>
+		{
+			for vec::each(**items) |item| {check_expr(grammar, name, *item, warnings);}
+		}
+		Choice(ref items) =>
+		{
+			for vec::eachi(**items) |j, later|
+			{
+				for uint::range(0u, j) |i|
+				{
+					match shadows(grammar, items[i], *later)
+					{
+						option::Some(reason) =>
+						{
+							vec::push(warnings, fmt!("%s: alternative %u (%s) can never match because alternative %u (%s) %s", *name, j + 1u, expr_to_ebnf(*later, SEQUENCE_PREC), i + 1u, expr_to_ebnf(items[i], SEQUENCE_PREC), reason));
+							break;
+						}
+						option::None =>
+						{
+						}
+					}
+				}
+				check_expr(grammar, name, *later, warnings);
+			}
+		}
+		Repeat(e, _, _) =>
+		{
+			check_expr(grammar, name, e, warnings);
+		}
+	}
+}
+
+// Returns why earlier always matches whenever later could (or None).
+fn shadows(grammar: &Grammar, earlier: @Expr, later: @Expr) -> Option<~str>
+{
+	if nullable(grammar, earlier, ~[])
+	{
+		option::Some(~"can match nothing")
+	}
+	else if expr_to_ebnf(earlier, CHOICE_PREC) == expr_to_ebnf(later, CHOICE_PREC)
+	{
+		option::Some(~"is the same")
+	}
+	else
+	{
+		match *earlier
+		{
+			Literal(ref prefix) if str::starts_with(leading_text(later), **prefix) =>
+			{
+				option::Some(~"matches a prefix of it")
+			}
+			_ =>
+			{
+				option::None
+			}
+		}
+	}
+}
+
+// Appends random text matching expr to text. Once depth gets large the shortest
+// choices are used so that the text doesn't grow without bound.
+fn generate_expr(grammar: &Grammar, expr: @Expr, rng: rand::Rng, terminals: fn@ (&str) -> ~str, depth: uint, text: &mut ~str)
+{
+	match *expr
+	{
+		Literal(ref s) =>
+		{
+			str::push_str(text, **s);
+		}
+		Terminal(ref description) =>
+		{
+			str::push_str(text, terminals(**description));
+		}
+		Ref(ref name) =>
+		{
+			assert depth < MAX_GENERATE_DEPTH;			// every rule should have a non-recursive alternative
+			match vec::find(*grammar.rules, |r| *r.name == **name)
+			{
+				option::Some(ref rule) => generate_expr(grammar, rule.expr, rng, terminals, depth + 1u, text),
+				option::None => fail fmt!("there is no rule named '%s'", **name),
+			}
+		}
+		Sequence(ref items) =>
+		{
+			for vec::each(**items) |e|
+			{
+				generate_expr(grammar, *e, rng, terminals, depth, text);
+			}
+		}
+		Choice(ref items) =>
+		{
+			let i = if depth < GENERATE_DEPTH
+				{
+					rng.gen_uint_range(0u, vec::len(**items))
+				}
+				else
+				{
+					vec::position(**items, |e| !has_ref(*e)).get_default(0u)
+				};
+			generate_expr(grammar, items[i], rng, terminals, depth, text);
+		}
+		Repeat(e, n, m) =>
+		{
+			let max = if m == 0u {n + 3u} else {m};
+			let count = if depth < GENERATE_DEPTH {rng.gen_uint_range(n, max + 1u)} else {n};
+			for uint::range(0u, count) |_i|
+			{
+				generate_expr(grammar, e, rng, terminals, depth, text);
+			}
+		}
+	}
+}
+
+// Returns true if expr refers to a rule.
+fn has_ref(expr: @Expr) -> bool
+{
+	match *expr
+	{
+		Literal(_) | Terminal(_) => false,
+		Ref(_) => true,
+		Sequence(ref items) | Choice(ref items) => vec::any(**items, |e| has_ref(*e)),
+		Repeat(e, _, _) => has_ref(e),
+	}
+}
+
+// Returns true if expr can succeed without consuming anything. Visiting is used
+// to avoid infinite recursion with recursive rules.
+fn nullable(grammar: &Grammar, expr: @Expr, visiting: ~[@~str]) -> bool
+{
+	match *expr
+	{
+		Literal(ref s) => str::is_empty(**s),
+		Terminal(_) => false,
+		Ref(ref name) =>
+		{
+			if vec::any(visiting, |n| **n == **name)
+			{
+				false
+			}
+			else
+			{
+				match vec::find(*grammar.rules, |r| *r.name == **name)
+				{
+					option::Some(ref rule) => nullable(grammar, rule.expr, visiting + ~[*name]),
+					option::None => false,
+				}
+			}
+		}
+		Sequence(ref items) => vec::all(**items, |e| nullable(grammar, *e, copy visiting)),
+		Choice(ref items) => vec::any(**items, |e| nullable(grammar, *e, copy visiting)),
+		Repeat(e, n, _) => n == 0u || nullable(grammar, e, visiting),
+	}
+}
+
+// Returns the text which expr must start with (as far as literals go).
+fn leading_text(expr: @Expr) -> ~str
+{
+	match *expr
+	{
+		Literal(ref s) => copy **s,
+		Sequence(ref items) =>
+		{
+			let mut text = ~"";
+			for vec::each(**items) |item|
+			{
+				match **item
+				{
+					Literal(ref s) => str::push_str(&mut text, **s),
+					_ => break,
+				}
+			}
+			text
+		}
+		_ => ~"",
+	}
+}
+
+fn parenthesize(s: ~str, needed: bool) -> ~str
+{
+	if needed {~"(" + s + ~")"} else {s}
+}
+
+fn quote(s: &str) -> ~str
+{
+	if str::contains_char(s, '"') {~"'" + s + ~"'"} else {~"\"" + s + ~"\""}
+}
+
+#[doc(hidden)]
+pub fn expr_to_ebnf(expr: @Expr, prec: uint) -> ~str
+{
+	match *expr
+	{
+		Literal(ref s) => quote(**s),
+		Terminal(ref s) => fmt!("? %s ?", **s),
+		Ref(ref name) => copy **name,
+		Sequence(ref items) =>
+		{
+			parenthesize(str::connect(vec::map(**items, |e| expr_to_ebnf(*e, ATOM_PREC)), ", "), prec > SEQUENCE_PREC)
+		}
+		Choice(ref items) =>
+		{
+			parenthesize(str::connect(vec::map(**items, |e| expr_to_ebnf(*e, SEQUENCE_PREC)), " | "), prec > CHOICE_PREC)
+		}
+		Repeat(e, 0u, 0u) => fmt!("{%s}", expr_to_ebnf(e, CHOICE_PREC)),
+		Repeat(e, 0u, 1u) => fmt!("[%s]", expr_to_ebnf(e, CHOICE_PREC)),
+		Repeat(e, n, m) =>
+		{
+			// e.g. r(2, 3) is exported as e, e, [e]
+			let item = expr_to_ebnf(e, ATOM_PREC);
+			let mut parts = vec::from_elem(n, copy item);
+			if m == 0u
+			{
+				vec::push(&mut parts, fmt!("{%s}", expr_to_ebnf(e, CHOICE_PREC)));
+			}
+			else
+			{
+				for uint::range(n, m) |_i|
+				{
+					vec::push(&mut parts, fmt!("[%s]", expr_to_ebnf(e, CHOICE_PREC)));
+				}
+			}
+			parenthesize(str::connect(parts, ", "), prec > SEQUENCE_PREC && vec::len(parts) > 1u)
+		}
+	}
+}
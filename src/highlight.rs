@@ -0,0 +1,116 @@
+//! Support for syntax highlighters.
+//!
+//! highlight runs a list of lexical rules over text and returns the span and kind of
+//! each token. Unlike a full parse this doesn't build a value and never fails: text
+//! which no rule matches is classified as an error so that highlighting keeps working
+//! while the text is being edited.
+use misc::*;
+use types::*;
+
+/// The classifications used by highlighters.
+pub enum TokenKind
+{
+	KeywordToken,
+	IdentifierToken,
+	
+	/// Numbers, strings, characters, etc.
+	LiteralToken,
+	OperatorToken,
+	
+	/// Brackets, separators, etc.
+	PunctuationToken,
+	CommentToken,
+	
+	/// Text which none of the rules matched.
+	ErrorToken,
+}
+
+/// A classified piece of the text.
+pub struct Token {kind: TokenKind, span: Span}
+
+/// Returns the tokens in text. At each position the rule with the longest match is used
+/// (ties go to the earlier rule so keywords should be listed before identifiers).
+/// Whitespace between tokens is skipped and adjacent unmatched characters are merged
+/// into a single ErrorToken.
+/// 
+/// ```
+/// let rules = ~[
+///     (KeywordToken, or_v(@~["if".lit(), "else".lit()])),
+///     (IdentifierToken, identifier()),
+///     (LiteralToken, match1(is_digit)),
+///     (CommentToken, line_comment())];
+/// let tokens = highlight(rules, @~"test.c", text);
+/// ```
+pub fn highlight(rules: &[(TokenKind, Parser<@~str>)], file: @~str, text: &str) -> @~[Token]
+{
+	let mut tokens = ~[];
+	let mut state = State {file: file, text: chars_with_eot(text), index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	let mut error_start: Option<State> = option::None;
+	loop
+	{
+		// Skip whitespace (which also ends an error token).
+		while is_whitespace(state.peek())
+		{
+			error_start = flush_error(&mut tokens, error_start, state);
+			state = if state.peek() == '\n' {state.advance_lines(1u, 1)} else {state.advance(1u)};
+		}
+		if state.at_end()
+		{
+			break;
+		}
+		
+		match longest_match(rules, state)
+		{
+			option::Some((kind, end)) =>
+			{
+				error_start = flush_error(&mut tokens, error_start, state);
+				vec::push(&mut tokens, Token {kind: kind, span: state.span_to(end)});
+				state = end;
+			}
+			option::None =>
+			{
+				if error_start.is_none()
+				{
+					error_start = option::Some(state);
+				}
+				state = state.advance(1u);
+			}
+		}
+	}
+	flush_error(&mut tokens, error_start, state);
+	@tokens
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn flush_error(tokens: &mut ~[Token], error_start: Option<State>, end: State) -> Option<State>
+{
+	match error_start
+	{
+		option::Some(start) => vec::push(tokens, Token {kind: ErrorToken, span: start.span_to(end)}),
+		option::None => (),
+	}
+	option::None
+}
+
+// Returns the kind and end state of the longest rule which matched something.
+fn longest_match(rules: &[(TokenKind, Parser<@~str>)], state: State) -> Option<(TokenKind, State)>
+{
+	let mut best = option::None;
+	for vec::each(rules) |rule|
+	{
+		let (kind, parser) = *rule;
+		match parser(state)
+		{
+			result::Ok(ref pass) if pass.new_state.index > state.index =>
+			{
+				match best
+				{
+					option::Some((_, end)) if end.index >= pass.new_state.index => (),
+					_ => best = option::Some((kind, pass.new_state)),
+				}
+			}
+			_ => (),
+		}
+	}
+	best
+}
@@ -0,0 +1,237 @@
+//! Support for re-parsing text after small edits (e.g. from an editor).
+//!
+//! Parsers for productions which are expensive to re-parse (e.g. statements or
+//! function definitions) are wrapped with incremental. After the text has been
+//! edited edit_cache is called and the new text is parsed as usual: productions
+//! outside the edited region are then reused instead of being re-parsed.
+use std::map::HashMap;
+
+use parsers::*;
+use types::*;
+
+/// A cached successful parse of file. Length and lines are the number of characters
+/// and lines that were consumed. Examined is the number of characters the parser
+/// looked at (usually more than length, see State::note_examined). Start, start_offset, and start_line are where the
+/// parse began when it was cached (the spans in derived, captured, and comments are
+/// relative to that). Values, derived, captured, comments, and profile are the changes
+/// the parser made to the context (see memoize) and are made again when the entry is
+/// reused.
+pub struct Reused<T: Copy Durable> {file: @~str, length: uint, lines: int, examined: uint, value: T, start: uint, start_offset: uint, start_line: int, values: uint, derived: @~[Derivation], captured: @~[Capture], comments: @~[Spanned<@~str>], profile: @~[Profile]}
+
+/// Maps the index a parser was invoked at to the results of the parse (one for each
+/// file the parser was used with, e.g. the main text and included files).
+pub type ReparseCache<T: Copy Durable> = HashMap<uint, @~[Reused<T>]>;
+
+/// Returns an empty cache.
+pub fn reparse_cache<T: Copy Durable>() -> ReparseCache<T>
+{
+	HashMap()
+}
+
+/// Returns a parser which returns the cached result for the current file and index if
+/// there is one. Otherwise parser is called and successful results are added to cache.
+/// 
+/// Like memoize, reusing a result makes the same changes to the context that parser
+/// made (e.g. derivations and captures) and checks limits like the timeout.
+pub fn incremental<T: Copy Durable>(parser: Parser<T>, cache: ReparseCache<T>) -> Parser<T>
+{
+	|input: State|
+	{
+		let entries = match cache.find(input.index) {option::Some(entries) => entries, option::None => @~[]};
+		match vec::find(*entries, |e| *e.file == *input.file)
+		{
+			option::Some(ref entry) =>
+			{
+				reuse_entry(entry, input)
+			}
+			option::None =>
+			{
+				let (status, entry) = run_entry(parser, input);
+				if entry.is_some()
+				{
+					let others = vec::filter(*entries, |e| *e.file != *input.file);
+					cache.insert(input.index, @(others + ~[entry.get()]));
+				}
+				status
+			}
+		}
+	}
+}
+
+/// Updates cache after the removed characters starting at start were replaced with
+/// inserted characters.
+/// 
+/// Entries whose parse looked at any of the removed characters, or at the character
+/// the inserted characters were put in front of, are dropped: this includes more than
+/// the consumed text because parsers like match0 and or look at characters past where
+/// they stop (see State::note_examined). Entries after the edited region are shifted so that they line up with the new text.
+/// Note that this applies to the entries for every file so a cache shouldn't be used
+/// with more than one file which is being edited.
+pub fn edit_cache<T: Copy Durable>(cache: ReparseCache<T>, start: uint, removed: uint, inserted: uint)
+{
+	let mut kept = ~[];
+	for cache.each |index, entries|
+	{
+		if vec::all(*entries, |e| index + e.examined <= start)
+		{
+			vec::push(&mut kept, (index, entries));
+		}
+		else if index >= start + removed
+		{
+			vec::push(&mut kept, (index + inserted - removed, entries));
+		}
+		true
+	}
+	
+	cache.clear();
+	for vec::each(kept) |&(index, entries)|
+	{
+		cache.insert(index, entries);
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Runs parser and returns its result along with what to cache (nothing if the parse
+// failed or became fatal). This is like run_memo in parsers.rs.
+fn run_entry<T: Copy Durable>(parser: Parser<T>, input: State) -> (Status<T>, Option<Reused<T>>)
+{
+	let ctx = input.ctx;
+	let values = ctx.values;
+	let examined = ctx.examined;
+	let comments = vec::len(ctx.comments.kept);
+	let profile = if ctx.profiling.enabled {copy ctx.profiling.profile} else {~[]};
+	let mut derived = ~[];
+	derived <-> ctx.deriving.derived;
+	let mut captured = ~[];
+	captured <-> ctx.captured;
+	ctx.examined = input.index;
+	
+	let status = parser(input);
+	
+	derived <-> ctx.deriving.derived;
+	captured <-> ctx.captured;
+	let looked = ctx.examined;
+	ctx.examined = uint::max(examined, looked);
+	for vec::each(derived) |d|
+	{
+		add_derivation(ctx, *d);
+	}
+	for vec::each(captured) |c|
+	{
+		add_capture(ctx, *c, []);
+	}
+	
+	match status
+	{
+		result::Ok(ref pass) if ctx.fatal.is_none() =>
+		{
+			let entry = Reused {file: input.file, length: pass.new_state.index - input.index, lines: pass.new_state.line - input.line,
+					examined: uint::max(looked, pass.new_state.index) + 1u - input.index, value: pass.value,
+				start: input.index, start_offset: input.offset, start_line: input.line,
+				values: ctx.values - values, derived: @derived, captured: @captured,
+				comments: @vec::slice(ctx.comments.kept, comments, vec::len(ctx.comments.kept)),
+				profile: @if ctx.profiling.enabled {profile_delta(profile, ctx.profiling.profile)} else {~[]}};
+			(status, option::Some(entry))
+		}
+		_ =>
+		{
+			(status, option::None)
+		}
+	}
+}
+
+// Makes the cached changes to input's context and returns the cached result.
+fn reuse_entry<T: Copy Durable>(entry: &Reused<T>, input: State) -> Status<T>
+{
+	let ctx = input.ctx;
+	ctx.values += entry.values;
+	input.note_examined(input.index + entry.examined - 1u);
+	for vec::each(*entry.derived) |d|
+	{
+		add_derivation(ctx, shift_derivation(*d, entry, input));
+	}
+	for vec::each(*entry.captured) |c|
+	{
+		add_capture(ctx, Capture {span: shift_span(c.span, entry, input), ..*c}, []);
+	}
+	for vec::each(*entry.comments) |c|
+	{
+		vec::push(&mut ctx.comments.kept, Spanned {value: c.value, span: shift_span(c.span, entry, input)});
+	}
+	if ctx.profiling.enabled
+	{
+		add_profile_delta(ctx, *entry.profile);
+	}
+	
+	let output = input.advance_lines(entry.length, entry.lines);
+	match check_limits(input, output)
+	{
+		option::Some(ref failure) => result::Err(Failed {old_state: input, ..*failure}),
+		option::None => result::Ok(Succeeded {new_state: output, value: entry.value}),
+	}
+}
+
+// Moves span from where entry was parsed to input (spans within included files
+// are left alone).
+fn shift_span<T: Copy Durable>(span: Span, entry: &Reused<T>, input: State) -> Span
+{
+	if *span.file == *entry.file
+	{
+		Span {start: span.start - entry.start + input.index, end: span.end - entry.start + input.index,
+			start_offset: span.start_offset - entry.start_offset + input.offset, end_offset: span.end_offset - entry.start_offset + input.offset,
+			start_line: span.start_line - entry.start_line + input.line, end_line: span.end_line - entry.start_line + input.line, ..span}
+	}
+	else
+	{
+		span
+	}
+}
+
+fn shift_derivation<T: Copy Durable>(d: Derivation, entry: &Reused<T>, input: State) -> Derivation
+{
+	Derivation {start: d.start - entry.start + input.index, end: d.end - entry.start + input.index,
+		start_offset: d.start_offset - entry.start_offset + input.offset, end_offset: d.end_offset - entry.start_offset + input.offset,
+		children: @vec::map(*d.children, |c| shift_derivation(*c, entry, input)), ..d}
+}
+
+// Returns the counters which changed between before and after.
+fn profile_delta(before: &[Profile], after: &[Profile]) -> ~[Profile]
+{
+	do vec::filter_map(after) |p|
+	{
+		match vec::find(before, |b| *b.name == *p.name)
+		{
+			option::Some(ref b) if b.calls == p.calls =>
+			{
+				option::None
+			}
+			option::Some(ref b) =>
+			{
+				option::Some(Profile {name: p.name, calls: p.calls - b.calls, successes: p.successes - b.successes, failures: p.failures - b.failures, consumed: p.consumed - b.consumed})
+			}
+			option::None =>
+			{
+				option::Some(*p)
+			}
+		}
+	}
+}
+
+fn add_profile_delta(ctx: @mut Context, delta: &[Profile])
+{
+	for vec::each(delta) |d|
+	{
+		match vec::position(ctx.profiling.profile, |p| *p.name == *d.name)
+		{
+			option::Some(i) =>
+			{
+				let p = ctx.profiling.profile[i];
+				ctx.profiling.profile[i] = Profile {calls: p.calls + d.calls, successes: p.successes + d.successes, failures: p.failures + d.failures, consumed: p.consumed + d.consumed, ..p};
+			}
+			option::None =>
+			{
+				vec::push(&mut ctx.profiling.profile, *d);
+			}
+		}
+	}
+}
@@ -0,0 +1,249 @@
+//! A parser for INI style configuration files.
+//!
+//! ```
+//! ; comments start with ';' or '#'
+//! name = global value
+//!
+//! [server]
+//! host = example.com
+//! path = /usr/local/\
+//!        share
+//! ```
+//!
+//! Entries before the first section are placed in the "" section. Keys and values are
+//! trimmed and a value ending with a backslash is continued on the next line. Entries
+//! may use ':' instead of '='. Comments are skipped using skipping so they may be on
+//! their own line or follow a section header (values may contain ';' and '#'). Parse_ini
+//! recovers from bad lines so that all of the errors in a file are reported at once.
+use std::map::HashMap;
+
+use parsers::*;
+use types::*;
+
+/// Maps keys to values.
+pub type IniSection = HashMap<~str, @~str>;
+
+/// Maps section names to sections.
+pub type IniFile = HashMap<~str, IniSection>;
+
+/// Returned by ini_line.
+pub enum IniLine
+{
+	/// A section header, e.g. "[server]".
+	Section(@~str),
+	
+	/// A key and its value, e.g. "host = example.com".
+	Entry(@~str, @~str),
+	
+	/// A line with nothing but whitespace or a comment.
+	Blank,
+}
+
+/// Parses an entire file. If there are errors the remaining lines are still parsed and
+/// each error is returned (formatted with format_failure).
+pub fn parse_ini(file: &str, text: &str) -> result::Result<IniFile, ~[~str]>
+{
+	let parser = ini_line().skipping(ini_space());
+	let chars = chars_with_eot(text);
+	let mut state = State {file: @file.to_owned(), text: chars, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	
+	let sections: IniFile = HashMap();
+	let mut section = ~"";
+	sections.insert(~"", HashMap());
+	
+	let mut errors = ~[];
+	while !state.at_end()
+	{
+		match parser(state)
+		{
+			result::Ok(ref pass) =>
+			{
+				match pass.value
+				{
+					Section(name) =>
+					{
+						section = copy *name;
+						if !sections.contains_key(copy section)
+						{
+							sections.insert(copy section, HashMap());
+						}
+					}
+					Entry(key, value) =>
+					{
+						sections.get(copy section).insert(copy *key, value);
+					}
+					Blank =>
+					{
+					}
+				}
+				state = pass.new_state;
+			}
+			result::Err(ref failure) =>
+			{
+				// Recover by skipping to the start of the next line.
+				vec::push(&mut errors, format_failure(failure, text));
+				state = skip_line(state, uint::max(failure.err_state.index, state.index));
+			}
+		}
+	}
+	
+	if vec::is_empty(errors) {result::Ok(sections)} else {result::Err(errors)}
+}
+
+/// line := (section | entry)? end_of_line
+/// 
+/// section := '[' [^\]\n]+ ']'
+/// entry := key ('=' | ':') value
+/// 
+/// Leading space and comments are skipped with s0 so this should normally be used with
+/// `skipping(ini_space())`.
+pub fn ini_line() -> Parser<IniLine>
+{
+	let name = match1(|ch| ch != ']' && ch != '\n' && ch != '\r').map(|s| @str::trim(*s));
+	let section = seq3_ret1("[".s0(), name, "]".s0()).map(|name| Section(name));
+	
+	let key = match1_0(|ch| !is_key_end(ch) && ch != '[', |ch| !is_key_end(ch)).map(|s| @str::trim(*s));
+	let entry = do seq3(key, "=".lit().or(":".lit()), ini_value()) |k, _e, v| {result::Ok(Entry(k, v))};
+	
+	// Or_v reports the error which got the furthest so bad entries are reported as
+	// missing an '=' instead of as an unexpected character.
+	let content = or_v(@~[section, entry, blank()]);
+	seq3_ret1(ret(()).s0(), content, end_of_line())
+}
+
+/// space := [ \t]* comment?
+/// 
+/// comment := [;#] [^\n]*
+pub fn ini_space() -> Parser<()>
+{
+	|input: State|
+	{
+		let mut i = input.index;
+		while input.text[i] == ' ' || input.text[i] == '\t'
+		{
+			i += 1u;
+		}
+		if input.text[i] == ';' || input.text[i] == '#'
+		{
+			while input.text[i] != '\n' && input.text[i] != EOT
+			{
+				i += 1u;
+			}
+		}
+		result::Ok(Succeeded {new_state: input.advance(i - input.index), value: ()})
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+pure fn is_key_end(ch: char) -> bool
+{
+	ch == '=' || ch == ':' || ch == '\n' || ch == '\r' || ch == ';' || ch == '#'
+}
+
+// Matches the rest of the line (without the line break). Lines which end with a
+// backslash are continued on the next line.
+fn ini_value() -> Parser<@~str>
+{
+	|input: State|
+	{
+		let mut value = ~"";
+		let mut i = input.index;
+		let mut lines = 0;
+		loop
+		{
+			while input.text[i] == ' ' || input.text[i] == '\t'
+			{
+				i += 1u;
+			}
+			
+			let start = i;
+			while input.text[i] != '\n' && input.text[i] != EOT
+			{
+				i += 1u;
+			}
+			let line = str::trim(str::from_chars(vec::view(input.text, start, i)));
+			if str::ends_with(line, "\\") && input.text[i] == '\n'
+			{
+				str::push_str(&mut value, str::slice(line, 0u, str::len(line) - 1u));
+				i += 1u;
+				lines += 1;
+			}
+			else
+			{
+				// Leave the line break (if any) for end_of_line.
+				if input.text[i] == '\n' && i > start && input.text[i - 1u] == '\r'
+				{
+					i -= 1u;
+				}
+				str::push_str(&mut value, line);
+				break;
+			}
+		}
+		result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, lines), value: @value})
+	}
+}
+
+// Succeeds without consuming anything if at the end of a line.
+fn blank() -> Parser<IniLine>
+{
+	let end = end_of_line();
+	
+	|input: State|
+	{
+		do result::chain(end(input))
+		|_pass|
+		{
+			result::Ok(Succeeded {new_state: input, value: Blank})
+		}
+	}
+}
+
+// end_of_line := '\r'? '\n' | &EOT
+fn end_of_line() -> Parser<()>
+{
+	let mesg = @~"end of line";
+	
+	|input: State|
+	{
+		if input.at_end()
+		{
+			result::Ok(Succeeded {new_state: input, value: ()})
+		}
+		else if input.peek() == '\n'
+		{
+			result::Ok(Succeeded {new_state: input.advance_lines(1u, 1), value: ()})
+		}
+		else if input.peek() == '\r' && input.text[input.index + 1u] == '\n'
+		{
+			result::Ok(Succeeded {new_state: input.advance_lines(2u, 1), value: ()})
+		}
+		else
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+		}
+	}
+}
+
+// Returns the state at the start of the line after index.
+fn skip_line(state: State, index: uint) -> State
+{
+	let mut i = index;
+	while state.text[i] != '\n' && state.text[i] != EOT
+	{
+		i += 1u;
+	}
+	if state.text[i] == '\n'
+	{
+		i += 1u;
+	}
+	
+	let mut lines = 0;
+	for uint::range(state.index, i) |j|
+	{
+		if state.text[j] == '\n'
+		{
+			lines += 1;
+		}
+	}
+	state.advance_lines(i - state.index, lines)
+}
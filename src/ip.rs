@@ -0,0 +1,286 @@
+//! Parsers for IPv4 addresses (e.g. "192.168.0.1"), IPv6 addresses (e.g. "fe80::1" or
+//! "::ffff:10.0.0.1"), and networks written in CIDR notation (e.g. "10.0.0.0/8").
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// The four octets of an IPv4 address (most significant first).
+pub struct Ipv4 {octets: @~[u8]}
+
+/// The eight 16-bit segments of an IPv6 address (most significant first).
+pub struct Ipv6 {segments: @~[u16]}
+
+/// An address along with the number of bits in its network prefix.
+pub struct Network<T: Copy Durable> {address: T, prefix: uint}
+
+pub impl Ipv4
+{
+	/// Returns the address in dotted decimal form.
+	fn to_str(&self) -> ~str
+	{
+		str::connect(vec::map(*self.octets, |o| uint::str(*o as uint)), ".")
+	}
+}
+
+pub impl Ipv6
+{
+	/// Returns the address in the canonical form from RFC 5952: segments are lower
+	/// case hex without leading zeros and the longest run of two or more zero segments
+	/// is replaced with "::".
+	fn to_str(&self) -> ~str
+	{
+		// Find the first longest run of zeros.
+		let mut best = (0u, 0u);
+		let mut i = 0u;
+		while i < 8u
+		{
+			let mut j = i;
+			while j < 8u && self.segments[j] == 0u16
+			{
+				j += 1u;
+			}
+			if j - i > best.second() - best.first()
+			{
+				best = (i, j);
+			}
+			i = uint::max(j, i + 1u);
+		}
+		
+		let (start, end) = best;
+		if end - start >= 2u
+		{
+			fmt!("%s::%s", hex_segments(vec::view(*self.segments, 0u, start)), hex_segments(vec::view(*self.segments, end, 8u)))
+		}
+		else
+		{
+			hex_segments(*self.segments)
+		}
+	}
+}
+
+/// Parses text which consists of a single IPv4 address. Errors are formatted with format_failure.
+pub fn parse_ipv4(text: &str) -> result::Result<Ipv4, ~str>
+{
+	to_result(ipv4().everything_ws().parse(@~"address", text), text)
+}
+
+/// Parses text which consists of a single IPv6 address. Errors are formatted with format_failure.
+pub fn parse_ipv6(text: &str) -> result::Result<Ipv6, ~str>
+{
+	to_result(ipv6().everything_ws().parse(@~"address", text), text)
+}
+
+/// ipv4 := octet '.' octet '.' octet '.' octet
+/// 
+/// octet := [0-9]+
+/// 
+/// Octets must be from 0 to 255 and may not have leading zeros (these are ambiguous
+/// because some tools treat them as octal).
+pub fn ipv4() -> Parser<Ipv4>
+{
+	let dot = ".".lit();
+	do seq7(octet(), dot, octet(), dot, octet(), dot, octet())
+	|a, _d1, b, _d2, c, _d3, d|
+	{
+		result::Ok(Ipv4 {octets: @~[a, b, c, d]})
+	}
+}
+
+/// ipv6 := (segment ':')* segment | (segment ':')* ':' (':' segment)* | (segment ':')+ ipv4
+/// 
+/// segment := [0-9a-fA-F]{1,4}
+/// 
+/// The address must have eight segments unless '::' is used in which case the missing
+/// segments are zero. An embedded IPv4 address counts as two segments.
+pub fn ipv6() -> Parser<Ipv6>
+{
+	let embedded = ipv4();
+	let count_mesg = @~"IPv6 address with 8 segments (or fewer with '::')";
+	
+	|input: State|
+	{
+		let mut head = ~[];		// segments before the '::'
+		let mut tail = ~[];		// segments after the '::'
+		let mut compressed = false;
+		let mut i = input.index;
+		if input.text[i] == ':' && input.text[i + 1u] == ':'
+		{
+			compressed = true;
+			i += 2u;
+		}
+		
+		loop
+		{
+			let err_state = input.advance(i - input.index);
+			let mut j = i;
+			while is_hex_digit(input.text[j])
+			{
+				j += 1u;
+			}
+			
+			let segments = if input.text[j] == '.' && vec::all(vec::view(input.text, i, j), |c| is_digit(*c))
+			{
+				match embedded(err_state)
+				{
+					result::Ok(ref pass) =>
+					{
+						j = pass.new_state.index;
+						let o = pass.value.octets;
+						~[(o[0] as u16 << 8u16) | o[1] as u16, (o[2] as u16 << 8u16) | o[3] as u16]
+					}
+					result::Err(ref failure) =>
+					{
+						return result::Err(Failed {old_state: input, ..*failure});
+					}
+				}
+			}
+			else if j == i && compressed && vec::is_empty(tail)
+			{
+				break;		// nothing after the '::'
+			}
+			else if j == i
+			{
+				return result::Err(Failed {old_state: input, err_state: err_state, mesg: @~"hex digits"});
+			}
+			else if j - i > 4u
+			{
+				return result::Err(Failed {old_state: input, err_state: err_state, mesg: @~"at most 4 hex digits"});
+			}
+			else
+			{
+				~[uint::from_str_radix(str::from_chars(vec::view(input.text, i, j)), 16u).get() as u16]
+			};
+			if compressed {vec::push_all(&mut tail, segments)} else {vec::push_all(&mut head, segments)};
+			i = j;
+			
+			if vec::len(segments) == 2u || input.text[i] != ':'
+			{
+				break;
+			}
+			else if input.text[i + 1u] == ':'
+			{
+				if compressed
+				{
+					return result::Err(Failed {old_state: input, err_state: input.advance(i - input.index), mesg: @~"only one '::'"});
+				}
+				compressed = true;
+				i += 2u;
+			}
+			else
+			{
+				i += 1u;
+			}
+		}
+		
+		let count = vec::len(head) + vec::len(tail);
+		if (compressed && count < 8u) || (!compressed && count == 8u)
+		{
+			let segments = head + vec::from_elem(8u - count, 0u16) + tail;
+			result::Ok(Succeeded {new_state: input.advance(i - input.index), value: Ipv6 {segments: @segments}})
+		}
+		else
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: count_mesg})
+		}
+	}
+}
+
+/// ipv4_network := ipv4 ('/' [0-9]+)?
+/// 
+/// The prefix must be from 0 to 32. If it is omitted it is 32.
+pub fn ipv4_network() -> Parser<Network<Ipv4>>
+{
+	network(ipv4(), 32u)
+}
+
+/// ipv6_network := ipv6 ('/' [0-9]+)?
+/// 
+/// The prefix must be from 0 to 128. If it is omitted it is 128.
+pub fn ipv6_network() -> Parser<Network<Ipv6>>
+{
+	network(ipv6(), 128u)
+}
+
+// ---- Helpers ---------------------------------------------------------------
+pure fn is_hex_digit(ch: char) -> bool
+{
+	is_digit(ch) || (ch >= 'a' && ch <= 'f') || (ch >= 'A' && ch <= 'F')
+}
+
+// Bad octets are reported at the start of the octet.
+fn octet() -> Parser<u8>
+{
+	let digits = match1(is_digit).err("octet");
+	let mesg = @~"octet from 0 to 255 without leading zeros";
+	
+	|input: State|
+	{
+		do result::chain(digits(input))
+		|pass|
+		{
+			match uint::from_str(*pass.value)
+			{
+				option::Some(n) if n <= 255u && !(str::len(*pass.value) > 1u && str::starts_with(*pass.value, "0")) =>
+				{
+					result::Ok(Succeeded {new_state: pass.new_state, value: n as u8})
+				}
+				_ =>
+				{
+					result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+				}
+			}
+		}
+	}
+}
+
+fn hex_segments(segments: &[u16]) -> ~str
+{
+	str::connect(vec::map(segments, |s| fmt!("%x", *s as uint)), ":")
+}
+
+// Bad prefixes are reported at the start of the prefix.
+fn network<T: Copy Durable>(address: Parser<T>, max: uint) -> Parser<Network<T>>
+{
+	let slash = "/".lit();
+	let digits = match1(is_digit).err("prefix length");
+	let mesg = @fmt!("prefix length from 0 to %u", max);
+	
+	|input: State|
+	{
+		do result::chain(address(input))
+		|pass|
+		{
+			match slash(pass.new_state)
+			{
+				result::Ok(ref slashed) =>
+				{
+					match digits(slashed.new_state)
+					{
+						result::Ok(ref prefix) =>
+						{
+							match uint::from_str(*prefix.value)
+							{
+								option::Some(n) if n <= max =>
+								{
+									result::Ok(Succeeded {new_state: prefix.new_state, value: Network {address: pass.value, prefix: n}})
+								}
+								_ =>
+								{
+									result::Err(Failed {old_state: input, err_state: slashed.new_state, mesg: mesg})
+								}
+							}
+						}
+						result::Err(ref failure) =>
+						{
+							result::Err(Failed {old_state: input, ..*failure})
+						}
+					}
+				}
+				result::Err(_) =>
+				{
+					result::Ok(Succeeded {new_state: pass.new_state, value: Network {address: pass.value, prefix: max}})
+				}
+			}
+		}
+	}
+}
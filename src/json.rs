@@ -0,0 +1,155 @@
+//! A parser for JSON (RFC 4627).
+//!
+//! This is both a useful parser in its own right and a good example of a complete
+//! grammar written with the combinators. Objects are kept as vectors so that the
+//! original order of the members is preserved (and duplicate names are allowed).
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// A parsed JSON value.
+pub enum Json
+{
+	Null,
+	Bool(bool),
+	Number(float),
+	String(@~str),
+	Array(@~[Json]),
+	Object(@~[(@~str, Json)]),
+}
+
+/// Parses text which consists of a single JSON value (with optional whitespace around
+/// it). Errors are formatted with format_failure.
+pub fn parse_json(text: &str) -> result::Result<Json, ~str>
+{
+	to_result(json_value().everything_ws().parse(@~"json", text), text)
+}
+
+/// value := (object | array | string | number | 'true' | 'false' | 'null') ws
+/// 
+/// Whitespace after the value is skipped.
+pub fn json_value() -> Parser<Json>
+{
+	let value_ptr = @mut ret(Null);
+	let value_ref = forward_ref(value_ptr);
+	
+	// object := '{' (string ':' value (',' string ':' value)*)? '}'
+	let member = do seq3(json_string().s0(), ":".s0(), value_ref) |name, _c, value| {result::Ok((name, value))};
+	let object = seq3_ret1("{".s0(), member.list(",".s0()).optional(), "}".s0());
+	let object = object.map(|members| Object(option::get_default(members, @~[])));
+	
+	// array := '[' (value (',' value)*)? ']'
+	let array = seq3_ret1("[".s0(), value_ref.list(",".s0()).optional(), "]".s0());
+	let array = array.map(|values| Array(option::get_default(values, @~[])));
+	
+	let value = or_v(@~[
+		object,
+		array,
+		json_string().map(|s| String(s)).s0(),
+		json_number().map(|n| Number(n)).s0(),
+		"true".s0().map(|_v| Bool(true)),
+		"false".s0().map(|_v| Bool(false)),
+		"null".s0().map(|_v| Null)]).err("value");
+	*value_ptr = value;
+	
+	value
+}
+
+/// number := '-'? int frac? exp?
+/// 
+/// int := '0' | [1-9] [0-9]*
+/// frac := '.' [0-9]+
+/// exp := [eE] [+-]? [0-9]+
+pub fn json_number() -> Parser<float>
+{
+	let int_part = "0".lit().or(match1_0(|ch| ch >= '1' && ch <= '9', is_digit));
+	let frac = seq2_ret_str(".".lit(), match1(is_digit));
+	let exp = seq3_ret_str("eE".anyc(), "+-".anyc().optional(), match1(is_digit));
+	let number = seq4_ret_str("-".lit().optional(), int_part, frac.optional(), exp.optional());
+	
+	number.map(|text| float::from_str(*text).get()).err("number")
+}
+
+/// string := '"' (unescaped | escape)* '"'
+/// 
+/// unescaped := [^"\\\x00-\x1f]
+/// escape := '\\' ["\\/bfnrt] | '\\u' hex{4}
+/// 
+/// UTF-16 surrogate pairs (e.g. "\ud834\udd1e") are combined into one character.
+pub fn json_string() -> Parser<@~str>
+{
+	let unescaped = match1(|ch| ch != '"' && ch != '\\' && ch >= ' ' && ch != EOT);
+	let escape = seq2_ret1("\\".lit(), "\"\\/bfnrt".anyc()).map(|ch| @str::from_char(json_escape_to_char(ch)));
+	let part = or_v(@~[unescaped, escape, unicode_escape().map(|ch| @str::from_char(ch))]);
+	let body = part.r0().map(|parts| @str::concat(vec::map(*parts, |p| copy **p)));
+	
+	seq3_ret1("\"".lit(), body, "\"".lit()).err("string")
+}
+
+/// Returns value as compact JSON text.
+pub fn json_to_str(value: &Json) -> ~str
+{
+	match *value
+	{
+		Null => ~"null",
+		Bool(b) => if b {~"true"} else {~"false"},
+		Number(n) => float::to_str(n, 17u),
+		String(s) => json_str(*s),
+		Array(values) => ~"[" + str::connect(vec::map(*values, |v| json_to_str(v)), ",") + ~"]",
+		Object(members) =>
+		{
+			let members = do vec::map(*members) |m| {let (name, value) = copy *m; json_str(*name) + ":" + json_to_str(&value)};
+			~"{" + str::connect(members, ",") + ~"}"
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Backslash followed by one of ["\\/bfnrt].
+fn json_escape_to_char(ch: char) -> char
+{
+	match ch
+	{
+		'b' => '\x08',
+		'f' => '\x0c',
+		'n' => '\n',
+		'r' => '\r',
+		't' => '\t',
+		_ => ch,
+	}
+}
+
+// '\\u' hex{4} where high surrogates must be followed by a low surrogate.
+fn unicode_escape() -> Parser<char>
+{
+	let hex = "0123456789abcdefABCDEF".anyc();
+	let code = seq2_ret1("\\u".lit(), seq4_ret_str(hex, hex, hex, hex)).map(|s| uint::from_str_radix(*s, 16u).get());
+	
+	do code.thene()
+	|hi|
+	{
+		if hi >= 0xD800u && hi < 0xDC00u
+		{
+			do code.thene()
+			|lo|
+			{
+				if lo >= 0xDC00u && lo < 0xE000u
+				{
+					ret((0x10000u + ((hi - 0xD800u) << 10u) + (lo - 0xDC00u)) as char)
+				}
+				else
+				{
+					fails("low surrogate")
+				}
+			}
+		}
+		else if hi >= 0xDC00u && hi < 0xE000u
+		{
+			fails("high surrogate")
+		}
+		else
+		{
+			ret(hi as char)
+		}
+	}
+}
@@ -0,0 +1,222 @@
+//! Parsers for string literals whose end depends upon how they started, e.g. heredocs
+//! (which end with a line matching their opening tag) and raw strings (which end with
+//! as many '#'s as they started with).
+use misc::*;
+use types::*;
+
+/// The tag which opened a heredoc and the text of the lines before the closing tag
+/// (each line is terminated by a new line).
+pub struct Heredoc {tag: @~str, body: @~str}
+
+/// heredoc := '<<' ('-' | '~')? tag [ \t]* '\n' line* closing
+/// tag := name | "'" name "'" | '"' name '"'
+/// 
+/// The body ends with a line consisting of just the tag (name is [a-zA-Z0-9_]+). With
+/// `<<-` leading tabs are removed from the body and the closing line (as in POSIX
+/// shells). With `<<~` the closing line may be indented and the smallest indentation
+/// of the non-blank lines is removed from the body (as in Ruby). The new line after
+/// the closing tag is also matched.
+pub fn heredoc() -> Parser<Heredoc>
+{
+	|input: State|
+	{
+		let text = input.text;
+		let error = |index: uint, mesg: ~str| result::Err(Failed {old_state: input, err_state: input.advance(index - input.index), mesg: @mesg});
+		
+		let mut i = input.index;
+		if text[i] != '<' || text[i + 1u] != '<'
+		{
+			return error(i, ~"heredoc");
+		}
+		i += 2u;
+		
+		let mode = text[i];
+		if mode == '-' || mode == '~'
+		{
+			i += 1u;
+		}
+		let quote = text[i];
+		if quote == '\'' || quote == '"'
+		{
+			i += 1u;
+		}
+		
+		let start = i;
+		while is_alphanum(text[i]) || text[i] == '_'
+		{
+			i += 1u;
+		}
+		if i == start
+		{
+			return error(i, ~"heredoc tag");
+		}
+		let tag = str::from_chars(vec::slice(text, start, i));
+		if quote == '\'' || quote == '"'
+		{
+			if text[i] != quote
+			{
+				return error(i, fmt!("'%c'", quote));
+			}
+			i += 1u;
+		}
+		
+		while text[i] == ' ' || text[i] == '\t'
+		{
+			i += 1u;
+		}
+		if text[i] == '\r' && text[i + 1u] == '\n'
+		{
+			i += 1u;
+		}
+		if text[i] != '\n'
+		{
+			return error(i, ~"end of line");
+		}
+		i += 1u;
+		
+		// Collect lines until the closing line.
+		let mut lines = ~[];
+		let mut count = 1;
+		loop
+		{
+			if i + 1u >= vec::len(text)
+			{
+				return error(i, fmt!("closing %s line", tag));
+			}
+			
+			let mut end = i;
+			while text[end] != '\n' && end + 1u < vec::len(text)
+			{
+				end += 1u;
+			}
+			let line = str::from_chars(vec::slice(text, i, if end > i && text[end - 1u] == '\r' {end - 1u} else {end}));
+			if text[end] == '\n'
+			{
+				i = end + 1u;
+				count += 1;
+			}
+			else
+			{
+				i = end;
+			}
+			
+			let candidate = match mode
+			{
+				'-' => str::trim_left_chars(line, ~['\t']),
+				'~' => str::trim_left_chars(line, ~[' ', '\t']),
+				_ => copy line,
+			};
+			if candidate == tag
+			{
+				break;
+			}
+			vec::push(&mut lines, line);
+		}
+		
+		let body = @str::concat(vec::map(strip_indentation(lines, mode), |line| *line + "\n"));
+		let output = input.advance_lines(i - input.index, count);
+		result::Ok(Succeeded {new_state: output, value: Heredoc {tag: @tag, body: body}})
+	}
+}
+
+/// raw_string := prefix '#'* delim [^delim]* delim '#'*
+/// 
+/// Matches raw strings like Rust's `r"C:\dir"` and `r#"say "hi""#`. Delims are the
+/// characters which may quote the string (e.g. "\"'"). The string ends with the opening
+/// delimiter followed by as many '#'s as followed the prefix. Backslashes are not
+/// escapes. The value is the text between the delimiters.
+pub fn raw_string(prefix: &str, delims: &str) -> Parser<@~str>
+{
+	let prefix = str::chars(prefix);
+	let delims = delims.to_owned();
+	let quote_mesg = @str::connect(vec::map(str::chars(delims), |ch| fmt!("'%c'", *ch)), " or ");
+	
+	|input: State|
+	{
+		let text = input.text;
+		let error = |index: uint, mesg: @~str| result::Err(Failed {old_state: input, err_state: input.advance(index - input.index), mesg: mesg});
+		
+		let mut i = input.index;
+		for vec::each(prefix) |ch|
+		{
+			if text[i] != *ch
+			{
+				return error(input.index, @~"raw string");
+			}
+			i += 1u;
+		}
+		
+		let mut hashes = 0u;
+		while text[i] == '#'
+		{
+			hashes += 1u;
+			i += 1u;
+		}
+		let delim = text[i];
+		if delim == EOT || !str::contains_char(delims, delim)
+		{
+			return error(i, quote_mesg);
+		}
+		i += 1u;
+		
+		let start = i;
+		let mut lines = 0;
+		loop
+		{
+			if i + 1u >= vec::len(text)
+			{
+				return error(i, @fmt!("closing %c%s", delim, str::from_chars(vec::from_elem(hashes, '#'))));
+			}
+			if text[i] == delim
+			{
+				// EOT isn't a '#' so this stops at the end of the text.
+				let mut j = i + 1u;
+				while j < i + 1u + hashes && text[j] == '#'
+				{
+					j += 1u;
+				}
+				if j == i + 1u + hashes
+				{
+					break;
+				}
+			}
+			if text[i] == '\n'
+			{
+				lines += 1;
+			}
+			i += 1u;
+		}
+		
+		let value = @str::from_chars(vec::slice(text, start, i));
+		let end = i + 1u + hashes;
+		result::Ok(Succeeded {new_state: input.advance_lines(end - input.index, lines), value: value})
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn strip_indentation(lines: &[~str], mode: char) -> ~[~str]
+{
+	if mode == '-'
+	{
+		vec::map(lines, |line| str::trim_left_chars(*line, ~['\t']))
+	}
+	else if mode == '~'
+	{
+		let indents = do vec::filter_map(lines)
+			|line|
+			{
+				let trimmed = str::trim_left_chars(*line, ~[' ', '\t']);
+				if str::is_empty(trimmed) {option::None} else {option::Some(str::len(*line) - str::len(trimmed))}
+			};
+		let indent = vec::foldl(uint::max_value, indents, |a, b| uint::min(a, *b));
+		do vec::map(lines)
+		|line|
+		{
+			if str::len(*line) >= indent {str::slice(*line, indent, str::len(*line))} else {~""}
+		}
+	}
+	else
+	{
+		vec::from_slice(lines)
+	}
+}
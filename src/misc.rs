@@ -35,6 +35,62 @@ pub fn chars_with_eot(s: &str) -> @[char]
 	}
 }
 
+/// Decodes bytes into a string. If there is a UTF-8, UTF-16LE, or UTF-16BE byte
+/// order mark then it is used to select the encoding (and is stripped). Otherwise
+/// the bytes must be UTF-8.
+/// 
+/// On failure the text which was decoded before the error and an error message
+/// are returned.
+pub fn decode_bytes(bytes: &[u8]) -> result::Result<~str, (~str, ~str)>
+{
+	let len = vec::len(bytes);
+	if len >= 3u && bytes[0] == 0xEFu8 && bytes[1] == 0xBBu8 && bytes[2] == 0xBFu8
+	{
+		decode_utf8(vec::view(bytes, 3u, len))
+	}
+	else if len >= 2u && bytes[0] == 0xFFu8 && bytes[1] == 0xFEu8
+	{
+		decode_utf16(vec::view(bytes, 2u, len), false)
+	}
+	else if len >= 2u && bytes[0] == 0xFEu8 && bytes[1] == 0xFFu8
+	{
+		decode_utf16(vec::view(bytes, 2u, len), true)
+	}
+	else
+	{
+		decode_utf8(bytes)
+	}
+}
+
+/// Replaces Latin letters followed by a combining accent with the equivalent
+/// precomposed character (e.g. \"e\u0301\" becomes \"\u00E9\").
+/// 
+/// This is useful because editors differ in which form they emit and parsers like
+/// lit and identifier compare characters directly. Note that this is NFC for the
+/// common Latin-1 and Latin Extended-A letters but not a full NFC implementation
+/// (that requires the Unicode data tables). Also note that error positions will
+/// refer to the composed text.
+pub fn compose_accents(s: &str) -> ~str
+{
+	let mut chars: ~[char] = ~[];
+	for str::each_char(s) |ch|
+	{
+		let composed = if vec::is_not_empty(chars) {compose_accent(chars[vec::len(chars) - 1u], ch)} else {option::None};
+		match composed
+		{
+			option::Some(c) =>
+			{
+				chars[vec::len(chars) - 1u] = c;
+			}
+			option::None =>
+			{
+				vec::push(&mut chars, ch);
+			}
+		}
+	}
+	return str::from_chars(chars);
+}
+
 /// Returns true if ch is in [a-zA-Z].
 pub pure fn is_alpha(ch: char) -> bool
 {
@@ -87,6 +143,72 @@ pub fn repeat_char(ch: char, count: uint) -> ~str
 	return value;
 }
 
+/// Returns the number of bytes needed to encode the first index characters of
+/// text as UTF-8.
+pub fn byte_offset(text: &[char], index: uint) -> uint
+{
+	let mut offset = 0u;
+	for uint::range(0u, index) |i|
+	{
+		let code = text[i] as uint;
+		offset += if code < 0x80u {1u} else if code < 0x800u {2u} else if code < 0x10000u {3u} else {4u};
+	}
+	return offset;
+}
+
+/// Returns true if lhs and rhs are the same vector (not merely equal vectors).
+pub fn same_text(lhs: @[char], rhs: @[char]) -> bool
+{
+	unsafe {vec::raw::to_ptr(lhs) == vec::raw::to_ptr(rhs)}
+}
+
+/// Returns the index of the first character of each line within text.
+/// 
+/// Lines may be terminated by \r\n, \n, or \r.
+pub fn line_starts(text: &[char]) -> ~[uint]
+{
+	let mut starts = ~[0u];
+	let len = vec::len(text);
+	
+	let mut i = 0u;
+	while i < len
+	{
+		if text[i] == '\r' && i + 1u < len && text[i+1u] == '\n'
+		{
+			i += 1u;
+		}
+		if text[i] == '\r' || text[i] == '\n'
+		{
+			vec::push(&mut starts, i + 1u);
+		}
+		i += 1u;
+	}
+	
+	return starts;
+}
+
+/// Uses the result of line_starts to return the 1-based line and column of index.
+pub pure fn line_col(starts: &[uint], index: uint) -> (uint, uint)
+{
+	// Binary search for the last line which starts at or before index.
+	let mut lo = 0u;
+	let mut hi = vec::len(starts);
+	while hi - lo > 1u
+	{
+		let mid = (lo + hi)/2u;
+		if starts[mid] <= index
+		{
+			lo = mid;
+		}
+		else
+		{
+			hi = mid;
+		}
+	}
+	
+	return (lo + 1u, index - starts[lo] + 1u);
+}
+
 #[doc(hidden)]
 pub fn get_col(text: @[char], index: uint) -> uint
 {
@@ -100,6 +222,28 @@ pub fn get_col(text: @[char], index: uint) -> uint
 	return index - i + 1u;
 }
 
+/// Returns s as a quoted JSON string.
+pub fn json_str(s: &str) -> ~str
+{
+	let mut value = ~"\"";
+	str::reserve(&mut value, str::len(s) + 2u);
+	for str::each_char(s) |ch|
+	{
+		match ch
+		{
+			'"' => str::push_str(&mut value, "\\\""),
+			'\\' => str::push_str(&mut value, "\\\\"),
+			'\n' => str::push_str(&mut value, "\\n"),
+			'\r' => str::push_str(&mut value, "\\r"),
+			'\t' => str::push_str(&mut value, "\\t"),
+			_ if (ch as uint) < 0x20u => str::push_str(&mut value, fmt!("\\u%04x", ch as uint)),
+			_ => str::push_char(&mut value, ch),
+		}
+	}
+	str::push_char(&mut value, '"');
+	return value;
+}
+
 // Note that we don't want to escape control characters here because we need
 // one code point to map to one printed character (so our log_ok arrows point to
 // the right character).
@@ -117,3 +261,97 @@ pub fn munge_chars(chars: @[char]) -> ~str
 	for vec::each(chars) |ch| { str::push_char(&mut value, if is_print(*ch) {*ch} else {bullet});}
 	return value;
 }
+
+// ---- Helpers ---------------------------------------------------------------
+// Returns the precomposed character for base followed by the combining mark.
+fn compose_accent(base: char, mark: char) -> Option<char>
+{
+	// mark, base letters, and the corresponding precomposed letters
+	let table = [
+		('\u0300', "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),								// grave
+		('\u0301', "AEIOUYaeiouyCcNnSsZz", "ÁÉÍÓÚÝáéíóúýĆćŃńŚśŹź"),		// acute
+		('\u0302', "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),								// circumflex
+		('\u0303', "ANOano", "ÃÑÕãñõ"),										// tilde
+		('\u0308', "AEIOUaeiouyY", "ÄËÏÖÜäëïöüÿŸ"),							// diaeresis
+		('\u030A', "AaUu", "ÅåŮů"),											// ring above
+		('\u0327', "Cc", "Çç"),												// cedilla
+		('\u030C', "CcSsZzRrEeNn", "ČčŠšŽžŘřĚěŇň")];							// caron
+	
+	for vec::each(table) |&(m, bases, composed)|
+	{
+		if m == mark
+		{
+			match str::find_char(bases, base)
+			{
+				option::Some(i) => return option::Some(str::chars(composed)[i]),
+				option::None => return option::None,
+			}
+		}
+	}
+	return option::None;
+}
+
+fn decode_utf8(bytes: &[u8]) -> result::Result<~str, (~str, ~str)>
+{
+	if str::is_utf8(bytes)
+	{
+		return result::Ok(str::from_bytes(bytes));
+	}
+	
+	// Find the start of the first bad character so we can report where it is.
+	let len = vec::len(bytes);
+	let mut i = 0u;
+	loop
+	{
+		let width = str::utf8_char_width(bytes[i]);
+		if width == 0u || i + width > len || !str::is_utf8(vec::view(bytes, i, i + width))
+		{
+			return result::Err((str::from_bytes(vec::view(bytes, 0u, i)), ~"invalid UTF-8"));
+		}
+		i += width;
+	}
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> result::Result<~str, (~str, ~str)>
+{
+	let len = vec::len(bytes);
+	let unit = |i: uint|
+	{
+		if big_endian {(bytes[i] as uint << 8) | bytes[i+1u] as uint} else {(bytes[i+1u] as uint << 8) | bytes[i] as uint}
+	};
+	
+	let mut text = ~"";
+	str::reserve(&mut text, len/2u);
+	
+	let mut i = 0u;
+	while i < len
+	{
+		if i + 1u == len
+		{
+			return result::Err((text, ~"truncated UTF-16"));
+		}
+		
+		let u = unit(i);
+		if u >= 0xD800u && u <= 0xDBFFu
+		{
+			let l = if i + 3u < len {unit(i + 2u)} else {0u};
+			if l < 0xDC00u || l > 0xDFFFu
+			{
+				return result::Err((text, ~"unpaired UTF-16 surrogate"));
+			}
+			str::push_char(&mut text, (0x10000u + ((u - 0xD800u) << 10) + (l - 0xDC00u)) as char);
+			i += 4u;
+		}
+		else if u >= 0xDC00u && u <= 0xDFFFu
+		{
+			return result::Err((text, ~"unpaired UTF-16 surrogate"));
+		}
+		else
+		{
+			str::push_char(&mut text, u as char);
+			i += 2u;
+		}
+	}
+	
+	return result::Ok(text);
+}
@@ -0,0 +1,312 @@
+//! Parsers for numbers written for people rather than programs, e.g. "1.234,56" (as
+//! written in Germany), "1,234.56" (as written in the US), roman numerals, and
+//! ordinals like "2nd".
+use misc::*;
+use types::*;
+
+/// Decimal is the character which separates the fraction. Separators are the
+/// characters which may separate groups of three digits (only one of which may be
+/// used within a number).
+pub struct NumberFormat {decimal: char, separators: @~str}
+
+/// "1,234.56"
+pub fn english_format() -> NumberFormat
+{
+	NumberFormat {decimal: '.', separators: @~","}
+}
+
+/// "1.234,56" (also used by much of continental Europe and South America).
+pub fn german_format() -> NumberFormat
+{
+	NumberFormat {decimal: ',', separators: @~"."}
+}
+
+/// "1 234,56" (the separator may also be a no-break or narrow no-break space).
+pub fn french_format() -> NumberFormat
+{
+	NumberFormat {decimal: ',', separators: @~" \u00A0\u202F"}
+}
+
+/// "1'234.56"
+pub fn swiss_format() -> NumberFormat
+{
+	NumberFormat {decimal: '.', separators: @~"'"}
+}
+
+/// locale_integer := [0-9]+ | [0-9]{1,3} (sep [0-9]{3})*
+/// 
+/// Like decimal_number except that groups of digits may be separated with one of the
+/// format's separators. A separator which isn't followed by three digits is not
+/// matched (so "1,2,3" is three numbers).
+pub fn locale_integer(format: NumberFormat) -> Parser<int>
+{
+	|input: State|
+	{
+		match integer_digits(input, format)
+		{
+			option::Some((ref digits, end)) =>
+			{
+				match digits_value(*digits)
+				{
+					option::Some(value) => result::Ok(Succeeded {new_state: end, value: value}),
+					option::None => result::Err(Failed {old_state: input, err_state: end, mesg: @fmt!("'%s' is out of range", *digits)}),
+				}
+			}
+			option::None =>
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: @~"number"})
+			}
+		}
+	}
+}
+
+/// locale_float := locale_integer (decimal [0-9]+)?
+/// 
+/// Unlike float_number the fraction is optional and exponents are not allowed (they
+/// are rare outside of technical data).
+pub fn locale_float(format: NumberFormat) -> Parser<f64>
+{
+	|input: State|
+	{
+		match integer_digits(input, format)
+		{
+			option::Some((ref digits, end)) =>
+			{
+				let mut text = copy *digits;
+				let mut end = end;
+				if end.peek() == format.decimal && is_digit(end.text[end.index + 1u])
+				{
+					str::push_char(&mut text, '.');
+					end = end.advance(1u);
+					while is_digit(end.peek())
+					{
+						str::push_char(&mut text, end.peek());
+						end = end.advance(1u);
+					}
+				}
+				
+				let value = do str::as_c_str(text) |ptr|
+				{
+					unsafe { libc::strtod(ptr, ptr::null()) as f64 }
+				};
+				result::Ok(Succeeded {new_state: end, value: value})
+			}
+			option::None =>
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: @~"number"})
+			}
+		}
+	}
+}
+
+/// roman_numeral := 'M'{0,3} hundreds? tens? ones?
+/// 
+/// Matches numerals written in the standard subtractive form (e.g. "XIV" or "mcmxc")
+/// so the value is between 1 and 3999. The numeral must be all upper case or all lower
+/// case and may not be followed by a letter or digit (so the "civ" in "civil" is not
+/// matched).
+pub fn roman_numeral() -> Parser<uint>
+{
+	|input: State|
+	{
+		let text = input.text;
+		let lower = text[input.index] >= 'a' && text[input.index] <= 'z';
+		let places = [('M', '?', '?', 1000u), ('C', 'D', 'M', 100u), ('X', 'L', 'C', 10u), ('I', 'V', 'X', 1u)];
+		
+		let mut i = input.index;
+		let mut value = 0u;
+		for vec::each(places) |place|
+		{
+			let (one, five, ten, scale) = *place;
+			
+			// The longest pattern which matches is the only valid reading. There are no
+			// fives or tens of thousands.
+			let patterns = roman_patterns(one, five, ten);
+			let count = if scale == 1000u {3u} else {9u};
+			let mut digit = 0u;
+			let mut len = 0u;
+			for vec::eachi(vec::view(patterns, 0u, count)) |d, pattern|
+			{
+				let n = str::len(*pattern);
+				if n > len && vec::alli(str::chars(*pattern), |j, ch| roman_char(text[i + j], lower) == *ch)
+				{
+					digit = d + 1u;
+					len = n;
+				}
+			}
+			
+			value += digit*scale;
+			i += len;
+		}
+		
+		// The patterns are at most four characters long.
+		input.note_examined(i + 3u);
+		if value == 0u || is_alphanum(text[i])
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: @~"roman numeral"})
+		}
+		else
+		{
+			result::Ok(Succeeded {new_state: input.advance(i - input.index), value: value})
+		}
+	}
+}
+
+/// ordinal := [0-9]+ ('st' | 'nd' | 'rd' | 'th')
+/// 
+/// The suffix must agree with the number, e.g. "1st", "2nd", "3rd", "4th", "11th",
+/// "12th", and "21st". The suffix must be lower case and may not be followed by a
+/// letter or digit. The value is the number.
+pub fn ordinal() -> Parser<uint>
+{
+	|input: State|
+	{
+		let text = input.text;
+		let mut i = input.index;
+		let mut digits = ~"";
+		while is_digit(text[i])
+		{
+			str::push_char(&mut digits, text[i]);
+			i += 1u;
+		}
+		if str::is_empty(digits)
+		{
+			return result::Err(Failed {old_state: input, err_state: input, mesg: @~"ordinal"});
+		}
+		
+		let end = input.advance(i - input.index);
+		match digits_value(digits)
+		{
+			option::Some(value) =>
+			{
+				let value = value as uint;
+				let suffix = if value % 100u >= 11u && value % 100u <= 13u
+					{
+						"th"
+					}
+					else
+					{
+						match value % 10u
+						{
+							1u => "st",
+							2u => "nd",
+							3u => "rd",
+							_ => "th",
+						}
+					};
+				input.note_examined(i + 2u);
+				if text[i] == suffix[0] as char && text[i + 1u] == suffix[1] as char && !is_alphanum(text[i + 2u])
+				{
+					result::Ok(Succeeded {new_state: end.advance(2u), value: value})
+				}
+				else
+				{
+					result::Err(Failed {old_state: input, err_state: end, mesg: @fmt!("'%s'", suffix)})
+				}
+			}
+			option::None =>
+			{
+				result::Err(Failed {old_state: input, err_state: end, mesg: @fmt!("'%s' is out of range", digits)})
+			}
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn digits_value(digits: &str) -> Option<int>
+{
+	let mut value = 0;
+	for str::each_char(digits) |ch|
+	{
+		let digit = (ch as int) - ('0' as int);
+		if value > (int::max_value - digit)/10
+		{
+			return option::None;
+		}
+		value = 10*value + digit;
+	}
+	option::Some(value)
+}
+
+// Returns the numerals for one through nine, e.g. "I" through "IX".
+fn roman_patterns(one: char, five: char, ten: char) -> ~[~str]
+{
+	~[
+		repeat_char(one, 1u),
+		repeat_char(one, 2u),
+		repeat_char(one, 3u),
+		str::from_chars(~[one, five]),
+		str::from_char(five),
+		str::from_chars(~[five, one]),
+		str::from_chars(~[five, one, one]),
+		str::from_chars(~[five, one, one, one]),
+		str::from_chars(~[one, ten]),
+	]
+}
+
+// Lower case numerals are mapped to upper case and, when the numeral is lower
+// case, upper case letters are mapped to a character which doesn't match.
+fn roman_char(ch: char, lower: bool) -> char
+{
+	if !lower
+	{
+		ch
+	}
+	else if ch >= 'a' && ch <= 'z'
+	{
+		((ch as uint) - ('a' as uint) + ('A' as uint)) as char
+	}
+	else
+	{
+		'?'
+	}
+}
+
+// Returns the digits (without separators) and the state after them.
+fn integer_digits(input: State, format: NumberFormat) -> Option<(~str, State)>
+{
+	let text = input.text;
+	let mut i = input.index;
+	let mut digits = ~"";
+	while is_digit(text[i])
+	{
+		str::push_char(&mut digits, text[i]);
+		i += 1u;
+	}
+	if str::is_empty(digits)
+	{
+		return option::None;
+	}
+	
+	// Separators are only allowed if the first group is short enough.
+	let mut separator = option::None;
+	if i - input.index <= 3u
+	{
+		loop
+		{
+			if i + 4u >= vec::len(text)
+			{
+				break;
+			}
+			
+			let ch = text[i];
+			let ok = match separator
+			{
+				option::Some(sep) => ch == sep,
+				option::None => ch != EOT && str::contains_char(*format.separators, ch),
+			};
+			if !ok || !vec::all(vec::view(text, i + 1u, i + 4u), |c| is_digit(*c)) || is_digit(text[i + 4u])
+			{
+				break;
+			}
+			
+			separator = option::Some(ch);
+			for uint::range(i + 1u, i + 4u) |j|
+			{
+				str::push_char(&mut digits, text[j]);
+			}
+			i += 4u;
+		}
+	}
+	option::Some((digits, input.advance(i - input.index)))
+}
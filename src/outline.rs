@@ -0,0 +1,65 @@
+//! Support for editor outlines and code folding.
+//!
+//! parse_outline parses text and returns the spans of the productions which were
+//! marked as structural (e.g. functions, sections, and blocks). The productions are
+//! marked by giving parse_outline their names (see the named method).
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// A structural production which was parsed. The span's end_line is the line of the
+/// last character (which is what folding needs). Children are the structural
+/// productions within it.
+pub struct Fold {name: @~str, span: Span, children: @~[Fold]}
+
+/// Parses text and returns the value along with the outermost structural productions.
+/// Structural has the names of the productions to include. Note that a structural
+/// production is only reported if it was part of the successful parse (e.g. it
+/// wasn't within an alternative which later failed).
+pub fn parse_outline<T: Copy Durable>(parser: Parser<T>, file: @~str, text: &str, structural: &[~str]) -> result::Result<(T, @~[Fold]), ParseFailed>
+{
+	do result::chain(parser.parse_derivation(file, text))
+	|result|
+	{
+		let (value, derivations) = result;
+		let starts = line_starts(chars_with_eot(text));
+		result::Ok((value, @to_folds(*derivations, file, starts, structural)))
+	}
+}
+
+/// Returns the folds nested within folds as a flat list (parents before their
+/// children).
+pub fn flatten_folds(folds: &[Fold]) -> ~[Fold]
+{
+	let mut result = ~[];
+	for vec::each(folds) |fold|
+	{
+		vec::push(&mut result, *fold);
+		vec::push_all(&mut result, flatten_folds(*fold.children));
+	}
+	result
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Derivations which aren't structural are removed and their structural descendants
+// take their place.
+fn to_folds(derivations: &[Derivation], file: @~str, starts: &[uint], structural: &[~str]) -> ~[Fold]
+{
+	let mut folds = ~[];
+	for vec::each(derivations) |d|
+	{
+		let children = to_folds(*d.children, file, starts, structural);
+		if vec::contains(structural, &copy *d.name)
+		{
+			let (start_line, _) = line_col(starts, d.start);
+			let (end_line, _) = line_col(starts, if d.end > d.start {d.end - 1u} else {d.end});
+			let span = Span {file: file, start: d.start, end: d.end, start_offset: d.start_offset, end_offset: d.end_offset, start_line: start_line as int, end_line: end_line as int};
+			vec::push(&mut folds, Fold {name: d.name, span: span, children: @children});
+		}
+		else
+		{
+			vec::push_all(&mut folds, children);
+		}
+	}
+	folds
+}
@@ -0,0 +1,126 @@
+//! Support for parsing record per line formats (e.g. CSV or log files) using
+//! several tasks.
+//!
+//! Parsers are fn@ closures so they cannot be sent to other tasks. Instead each
+//! task calls a bare function to build its own parser and the values must be
+//! sendable (e.g. ~str instead of @~str).
+use std::future;
+
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Splits text into records ending with delimiter and parses them using count tasks.
+/// The results are returned in the same order as the records.
+/// 
+/// Make is called once by each task and should return a parser which matches an entire
+/// record (e.g. by using everything). Errors are formatted with format_failure and use
+/// the record's location within text. Note that when delimiter is '\n' a trailing '\r'
+/// is removed from each record and that empty records (e.g. blank lines) are parsed too.
+pub fn parse_records<T: Copy Durable Owned>(file: &str, text: &str, delimiter: char, count: uint, make: extern fn () -> Parser<T>) -> ~[result::Result<T, ~str>]
+{
+	assert count > 0u;
+	
+	let records = split_records(text, delimiter);
+	let size = (vec::len(records) + count - 1u)/count;
+	
+	let mut futures = ~[];
+	let mut i = 0u;
+	while i < vec::len(records)
+	{
+		let group = vec::slice(records, i, uint::min(i + size, vec::len(records)));
+		let file = file.to_owned();
+		vec::push(&mut futures, do future::spawn |move group, move file|
+		{
+			let parser = make();
+			let file = @copy file;
+			do vec::map(group) |record| {parse_record(parser, file, record)}
+		});
+		i += size;
+	}
+	
+	let mut results = ~[];
+	for vec::each(futures) |pending|
+	{
+		vec::push_all(&mut results, pending.get());
+	}
+	results
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// The text of a record along with the line, col and byte offset it starts at.
+struct Record {text: ~str, line: uint, col: uint, offset: uint}
+
+fn split_records(text: &str, delimiter: char) -> ~[Record]
+{
+	let mut records = ~[];
+	let mut record = ~"";
+	let mut line = 1u;
+	let mut col = 1u;
+	let mut offset = 0u;
+	let mut start = (1u, 1u, 0u);
+	for str::each_char(text) |ch|
+	{
+		if ch == delimiter
+		{
+			if delimiter == '\n' && str::ends_with(record, "\r")
+			{
+				str::pop_char(&mut record);
+			}
+			let (l, c, o) = start;
+			vec::push(&mut records, Record {text: move record, line: l, col: c, offset: o});
+			record = ~"";
+		}
+		else
+		{
+			str::push_char(&mut record, ch);
+		}
+		
+		if ch == '\n'
+		{
+			line += 1u;
+			col = 1u;
+		}
+		else
+		{
+			col += 1u;
+		}
+		offset += byte_offset([ch], 1u);
+		if ch == delimiter
+		{
+			start = (line, col, offset);
+		}
+	}
+	
+	// Text will normally end with a delimiter which doesn't start a new record.
+	if !str::is_empty(record)
+	{
+		let (l, c, o) = start;
+		vec::push(&mut records, Record {text: move record, line: l, col: c, offset: o});
+	}
+	records
+}
+
+fn parse_record<T: Copy Durable>(parser: Parser<T>, file: @~str, record: &Record) -> result::Result<T, ~str>
+{
+	do result::chain_err(parser.parse(file, record.text))
+	|failure|
+	{
+		// The failure is moved to where the record is within the text. Only the record's
+		// text is available so the part of its first line before the record is shown as
+		// spaces (which keeps the caret under the error).
+		let first = failure.line == 1u;
+		let failure = ParseFailed {line: failure.line + record.line - 1u, col: if first {failure.col + record.col - 1u} else {failure.col}, offset: failure.offset + record.offset, ..failure};
+		let lines = str::lines_any(record.text);
+		let line = if vec::is_empty(*failure.includes) && failure.line >= record.line && failure.line - record.line < vec::len(lines)
+		{
+			let line = copy lines[failure.line - record.line];
+			option::Some(if first {repeat_char(' ', record.col - 1u) + line} else {line})
+		}
+		else
+		{
+			option::None
+		};
+		result::Err(format_failure_line(&failure, line))
+	}
+}
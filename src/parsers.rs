@@ -3,7 +3,11 @@
 //! These can be divided into parsers that return chars, strings, and generic Ts.
 // TODO: probably should use individual modules for these, but the dependencies
 // are painful (see https://github.com/mozilla/rust/issues/3352).
-use core::str::CharRange;
+use io::ReaderUtil;
+use io::WriterUtil;
+use pipes::Peekable;
+use std::map::HashMap;
+use std::sort;
 
 use misc::*;
 use types::*;
@@ -11,8 +15,333 @@ use types::*;
 /// Return type of parse function.
 pub type ParseStatus<T: Copy Durable> = result::Result<T, ParseFailed>;
 
-/// Returned by parse function on error. Line and col are both 1-based.
-pub struct ParseFailed {file: @~str, line: uint, col: uint, mesg: @~str}
+/// Return type of parse_prefix.
+pub type PrefixStatus<T: Copy Durable> = result::Result<Prefix<T>, ParseFailed>;
+
+/// Returned by parse_prefix on success. rest_index is the index of the first
+/// unparsed character and rest_offset is its UTF-8 byte offset.
+pub struct Prefix<T: Copy Durable> {value: T, rest_index: uint, rest_offset: uint}
+
+/// Returned by parse_lenient. Value is set unless the parse failed (even after using
+/// the recover method). Errors has the errors which were recovered from along with
+/// the error which failed the parse (if any).
+pub struct Lenient<T: Copy Durable> {value: Option<T>, errors: @~[ParseFailed]}
+
+/// Returned by parse function on error. Line and col are both 1-based. Offset is
+/// the UTF-8 byte offset of the error within the text (which is what editors and
+/// language servers usually want). Includes is the chain of includes that led to
+/// file (outermost first). Incomplete is set if the parse failed because it ran
+/// into the end of the text (e.g. a REPL can use this to decide whether to prompt
+/// for another line instead of reporting an error).
+pub struct ParseFailed {file: @~str, line: uint, col: uint, offset: uint, mesg: @~str, includes: @~[Include], incomplete: bool}
+
+/// Returns a human readable description of failure, e.g.
+/// 
+/// ```
+/// calc:1:5: expression
+/// 2 * (
+///     ^
+/// ```
+/// 
+/// Text should be the text that was parsed. It is used to print the line with the
+/// error (the part of the message after the first line is omitted if the line is not
+/// within text, e.g. if the error was within an included file).
+pub fn format_failure(failure: &ParseFailed, text: &str) -> ~str
+{
+	let lines = str::lines_any(text);
+	if vec::is_empty(*failure.includes) && failure.line > 0u && failure.line <= vec::len(lines)
+	{
+		format_failure_line(failure, option::Some(copy lines[failure.line - 1u]))
+	}
+	else
+	{
+		format_failure_line(failure, option::None)
+	}
+}
+
+// Like format_failure except that line is the text of the line with the error (or
+// None if it isn't known).
+#[doc(hidden)]
+pub fn format_failure_line(failure: &ParseFailed, line: Option<~str>) -> ~str
+{
+	let mesg = if str::is_empty(*failure.mesg) {~"syntax error"} else {copy *failure.mesg};
+	let mut s = fmt!("%s:%u:%u: %s", *failure.file, failure.line, failure.col, mesg);
+	for vec::rev_each(*failure.includes) |include|
+	{
+		str::push_str(&mut s, fmt!("\n    included from %s:%d", *include.file, include.line));
+	}
+	
+	match line
+	{
+		option::Some(ref line) =>
+		{
+			// Tabs are copied so that the caret lines up however the tabs are displayed.
+			let mut caret = ~"";
+			for str::each_chari(*line) |i, ch|
+			{
+				if i + 1u >= failure.col {break;}
+				str::push_char(&mut caret, if ch == '\t' {'\t'} else {' '});
+			}
+			str::push_str(&mut s, fmt!("\n%s\n%s^", *line, caret));
+		}
+		option::None =>
+		{
+		}
+	}
+	return s;
+}
+
+/// Converts the result of a parse method into a result with a formatted error message
+/// (see format_failure).
+pub fn to_result<T: Copy Durable>(status: ParseStatus<T>, text: &str) -> result::Result<T, ~str>
+{
+	do result::chain_err(status)
+	|failure|
+	{
+		result::Err(format_failure(&failure, text))
+	}
+}
+
+// Converts the failure from a parser into the error returned by parse.
+// Note that the line and col are derived from the index (so they are correct even if a
+// custom parser failed to update line). Also note that we use err_state's text because
+// the error may be within an included file. The line starts are cached by the context
+// so converting many failures (e.g. for parse_lenient) only scans the text once.
+#[doc(hidden)]
+pub fn to_parse_failed(failure: &Failed) -> ParseFailed
+{
+	let state = failure.err_state;
+	let (line, col) = line_col(*state.line_starts(), state.index);
+	ParseFailed {file: state.file, line: line, col: col, offset: state.offset, mesg: failure.mesg, includes: state.includes, incomplete: state.at_end()}
+}
+
+// Runs parser over all of text. This is used to implement the parse methods.
+#[doc(hidden)]
+pub fn run_parse<T: Copy Durable>(parser: Parser<T>, file: @~str, text: &str, ctx: @mut Context) -> result::Result<Succeeded<T>, ParseFailed>
+{
+	// Text which came from a file may begin with a byte order mark which we don't want
+	// to parse. Offsets are still into text.
+	let bom = bom_len(text);
+	match run_parse_chars(parser, file, chars_with_eot(str::view(text, bom, str::len(text))), ctx)
+	{
+		result::Ok(ref pass) => result::Ok(*pass),
+		result::Err(ref failure) => result::Err(ParseFailed {offset: failure.offset + bom, ..*failure}),
+	}
+}
+
+// Returns the length in bytes of the byte order mark at the start of text (if any).
+fn bom_len(text: &str) -> uint
+{
+	if str::starts_with(text, "\uFEFF") {3u} else {0u}
+}
+
+// Like run_parse except that the text has already been converted (see chars_with_eot).
+#[doc(hidden)]
+pub fn run_parse_chars<T: Copy Durable>(parser: Parser<T>, file: @~str, chars: @[char], ctx: @mut Context) -> result::Result<Succeeded<T>, ParseFailed>
+{
+	assert vec::is_not_empty(chars) && chars[vec::len(chars) - 1u] == EOT;
+	
+	let limits = ctx.limits;
+	if limits.max_length > 0u && vec::len(chars) - 1u > limits.max_length
+	{
+		return result::Err(ParseFailed {file: file, line: 1u, col: 1u, offset: 0u, mesg: @fmt!("more than %u characters", limits.max_length), includes: @~[], incomplete: false});
+	}
+	
+	let input = State {file: file, text: chars, index: 0u, offset: 0u, line: 1, ctx: ctx, includes: @~[]};
+	let status = parser(input);
+	match input.ctx.fatal
+	{
+		option::Some(ref failure) =>
+		{
+			// Fatal errors are never incomplete (more text won't fix a timeout).
+			return result::Err(ParseFailed {incomplete: false, ..to_parse_failed(failure)});
+		}
+		option::None =>
+		{
+		}
+	}
+	
+	match status
+	{
+		result::Ok(ref pass) =>
+		{
+			result::Ok(*pass)
+		}
+		result::Err(ref failure) =>
+		{
+			result::Err(to_parse_failed(failure))
+		}
+	}
+}
+
+// Adds a derivation for a named production which just succeeded. Productions are
+// parsed left to right so earlier derivations which overlap node are from failed
+// alternatives and are discarded. These are always at the end (a success discards
+// those before it) so only the last derivations need to be checked.
+#[doc(hidden)]
+pub fn add_derivation(ctx: @mut Context, node: Derivation)
+{
+	while vec::is_not_empty(ctx.deriving.derived) && vec::last(ctx.deriving.derived).end > node.start
+	{
+		vec::pop(&mut ctx.deriving.derived);
+	}
+	vec::push(&mut ctx.deriving.derived, node);
+}
+
+// Like add_derivation except for captures. Captures is the captures which matched
+// within capture (see the capture method).
+#[doc(hidden)]
+pub fn add_capture(ctx: @mut Context, capture: Capture, captures: &[Capture])
+{
+	while vec::is_not_empty(ctx.captured) && vec::last(ctx.captured).span.end > capture.span.start
+	{
+		vec::pop(&mut ctx.captured);
+	}
+	for vec::each(captures) |c|
+	{
+		if c.span.end <= capture.span.end
+		{
+			vec::push(&mut ctx.captured, *c);
+		}
+	}
+	vec::push(&mut ctx.captured, capture);
+}
+
+// Updates the counters for the named production. Consumed is None if the
+// production failed to match.
+#[doc(hidden)]
+pub fn add_profile(ctx: @mut Context, name: @~str, consumed: Option<uint>)
+{
+	let (successes, failures, count) = match consumed
+		{
+			option::Some(count) => (1u, 0u, count),
+			option::None => (0u, 1u, 0u),
+		};
+	
+	match vec::position(ctx.profiling.profile, |p| *p.name == *name)
+	{
+		option::Some(i) =>
+		{
+			let p = ctx.profiling.profile[i];
+			ctx.profiling.profile[i] = Profile {calls: p.calls + 1u, successes: p.successes + successes, failures: p.failures + failures, consumed: p.consumed + count, ..p};
+		}
+		option::None =>
+		{
+			vec::push(&mut ctx.profiling.profile, Profile {name: name, calls: 1u, successes: successes, failures: failures, consumed: count});
+		}
+	}
+}
+
+/// Returns a JSON object describing a call to a traced parser (see the trace method)
+/// which started at input and took duration seconds. Indices are char indices, line
+/// and col are for the start of the call, and end is where the parser stopped (or
+/// failed). For example:
+/// 
+/// `{"name": "expr", "depth": 0, "line": 1, "col": 1, "start": 0, "end": 3, "passed": true, "duration": 0.000012}`
+pub fn trace_record<T: Copy Durable>(name: &str, input: State, status: &Status<T>, duration: float) -> ~str
+{
+	let prefix = fmt!("{\"name\": %s, \"depth\": %u, \"line\": %d, \"col\": %u, \"start\": %u", json_str(name), input.ctx.trace.depth, input.line, get_col(input.text, input.index), input.index);
+	match *status
+	{
+		result::Ok(ref pass) =>
+		{
+			fmt!("%s, \"end\": %u, \"passed\": true, \"duration\": %f}", prefix, pass.new_state.index, duration)
+		}
+		result::Err(ref failure) =>
+		{
+			fmt!("%s, \"end\": %u, \"passed\": false, \"mesg\": %s, \"duration\": %f}", prefix, failure.err_state.index, json_str(*failure.mesg), duration)
+		}
+	}
+}
+
+// Returns the lines the note method logs for a parser which started at input and
+// returned status (none if the trace level doesn't log status).
+#[doc(hidden)]
+pub fn note_lines<T: Copy Durable>(mesg: &str, input: State, status: &Status<T>) -> ~[~str]
+{
+	match *status
+	{
+		result::Ok(ref pass) =>
+		{
+			assert pass.new_state.index >= input.index;			// can't go backwards on success (but no progress is fine, eg e*)
+			if input.ctx.trace.level >= TRACE_ALL && pass.new_state.index > input.index
+			{
+				~[munge_chars(input.text), fmt!("%s^ %s parsed '%s'", repeat_char(' ', pass.new_state.index), mesg, str::slice(munge_chars(input.text), input.index, pass.new_state.index))]
+			}
+			else if input.ctx.trace.level >= TRACE_ALL
+			{
+				~[munge_chars(input.text), fmt!("%s^ %s passed", repeat_char(' ', pass.new_state.index), mesg)]
+			}
+			else
+			{
+				~[]
+			}
+		}
+		result::Err(ref failure) =>
+		{
+			assert failure.old_state.index == input.index;			// on errors the next parser must begin at the start
+			assert failure.err_state.index >= input.index || !same_text(failure.err_state.text, input.text);	// errors can't be before the input (unless they are in an included file)
+			
+			if input.ctx.trace.level < TRACE_FAILURES
+			{
+				~[]
+			}
+			else if failure.err_state.index > input.index
+			{
+				~[munge_chars(input.text), fmt!("%s^%s! %s failed", repeat_char('-', input.index), repeat_char(' ', failure.err_state.index - input.index), mesg)]
+			}
+			else
+			{
+				~[munge_chars(input.text), fmt!("%s^ %s failed", repeat_char('-', input.index), mesg)]
+			}
+		}
+	}
+}
+
+// Records failure as the fatal error for the parse (unless there already is one)
+// and returns failure.
+#[doc(hidden)]
+pub fn set_fatal(failure: Failed) -> Failed
+{
+	let ctx = failure.old_state.ctx;
+	if ctx.fatal.is_none()
+	{
+		ctx.fatal = option::Some(failure);
+	}
+	failure
+}
+
+// Returns the fatal error for the parse if there is one, e.g. because a limit
+// was exceeded. Note that once a limit has been exceeded the parse is doomed:
+// parse will report the failure even if a later parser (e.g. or) ignores it.
+// This is also where progress is reported.
+#[doc(hidden)]
+pub fn check_limits(input: State, output: State) -> Option<Failed>
+{
+	let ctx = input.ctx;
+	if ctx.fatal.is_none()
+	{
+		if ctx.progress.interval > 0u && output.index >= ctx.progress.next
+		{
+			ctx.progress.next = output.index - output.index % ctx.progress.interval + ctx.progress.interval;
+			ctx.progress.hook.get()(output.index);
+		}
+		
+		if ctx.limits.max_values > 0u && ctx.values > ctx.limits.max_values
+		{
+			set_fatal(Failed {old_state: input, err_state: output, mesg: @fmt!("more than %u values", ctx.limits.max_values)});
+		}
+		else if ctx.deadline > 0.0 && std::time::precise_time_s() > ctx.deadline
+		{
+			set_fatal(Failed {old_state: input, err_state: output, mesg: @~"timed out"});
+		}
+		else if ctx.cancel.is_some() && ctx.cancel.get().peek()
+		{
+			set_fatal(Failed {old_state: input, err_state: output, mesg: @~"cancelled"});
+		}
+	}
+	ctx.fatal
+}
 
 // ---- weird parsers -----------------------------------------------------------------------------
 // Returns a parser which matches the end of the input.
@@ -20,15 +349,17 @@ pub struct ParseFailed {file: @~str, line: uint, col: uint, mesg: @~str}
 #[doc(hidden)]
 pub fn eot() -> Parser<()>
 {
+	let mesg = @~"EOT";
+	
 	|input: State|
 	{
 		if input.text[input.index] == EOT
 		{
-			result::Ok(Succeeded {new_state: State {index: input.index + 1u, ..input}, value: ()})
+			result::Ok(Succeeded {new_state: input.advance(1u), value: ()})
 		}
 		else
 		{
-			result::Err(Failed {old_state: input, err_state: input, mesg: @~"EOT"})
+			result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
 		}
 	}
 }
@@ -38,6 +369,8 @@ pub fn eot() -> Parser<()>
 /// Returns the matched character.
 pub fn anycp(predicate: fn@ (char) -> bool) -> Parser<char>
 {
+	let mesg = @~"";
+	
 	|input: State| {
 		let mut i = input.index;
 		if input.text[i] != EOT && predicate(input.text[i])
@@ -47,11 +380,11 @@ pub fn anycp(predicate: fn@ (char) -> bool) -> Parser<char>
 		
 		if i > input.index
 		{
-			result::Ok(Succeeded {new_state: State {index: i, ..input}, value: input.text[input.index]})
+			result::Ok(Succeeded {new_state: input.advance(1u), value: input.peek()})
 		}
 		else
 		{
-			result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @~""})
+			result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
 		}
 	}
 }
@@ -73,6 +406,7 @@ pub impl &str : CharParsers
 		// Note that we're handing this string off to a closure so we can't get rid of this copy
 		// even if we make the impl on ~str.
 		let s = self.to_owned();
+		let mesg = @fmt!("[%s]", s);
 		
 		|input: State|
 		{
@@ -84,11 +418,11 @@ pub impl &str : CharParsers
 			
 			if i > input.index
 			{
-				result::Ok(Succeeded {new_state: State {index: i, ..input}, value: input.text[input.index]})
+				result::Ok(Succeeded {new_state: input.advance(1u), value: input.peek()})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @fmt!("[%s]", s)})
+				result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
 			}
 		}
 	}
@@ -96,6 +430,7 @@ pub impl &str : CharParsers
 	fn noc(&self) -> Parser<char>
 	{
 		let s = self.to_owned();
+		let mesg = @fmt!("[^%s]", s);
 		
 		|input: State|
 		{
@@ -107,11 +442,11 @@ pub impl &str : CharParsers
 			
 			if i > input.index
 			{
-				result::Ok(Succeeded {new_state: State {index: i, ..input}, value: input.text[input.index]})
+				result::Ok(Succeeded {new_state: input.advance(1u), value: input.peek()})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @fmt!("[^%s]", s)})
+				result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
 			}
 		}
 	}
@@ -146,8 +481,8 @@ pub fn match0(predicate: fn@ (char) -> bool) -> Parser<@~str>
 			i += 1u;
 		}
 		
-		let text = str::from_chars(vec::slice(input.text, input.index, i));
-		result::Ok(Succeeded {new_state: State {index: i, ..input}, value: @text})
+		let text = str::from_chars(vec::view(input.text, input.index, i));
+		result::Ok(Succeeded {new_state: input.advance(i - input.index), value: @text})
 	}
 }
 
@@ -157,6 +492,8 @@ pub fn match0(predicate: fn@ (char) -> bool) -> Parser<@~str>
 /// Note that this does not increment line.
 pub fn match1(predicate: fn@ (char) -> bool) -> Parser<@~str>
 {
+	let mesg = @~"";
+	
 	|input: State|
 	{
 		let mut i = input.index;
@@ -167,12 +504,12 @@ pub fn match1(predicate: fn@ (char) -> bool) -> Parser<@~str>
 		
 		if i > input.index
 		{
-			let text = str::from_chars(vec::slice(input.text, input.index, i));
-			result::Ok(Succeeded {new_state: State {index: i, ..input}, value: @text})
+			let text = str::from_chars(vec::view(input.text, input.index, i));
+			result::Ok(Succeeded {new_state: input.advance(i - input.index), value: @text})
 		}
 		else
 		{
-			result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @~""})
+			result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
 		}
 	}
 }
@@ -186,7 +523,7 @@ pub fn match1_0(prefix: fn@ (char) -> bool, suffix: fn@ (char) -> bool) -> Parse
 }
 
 /// optional_str := e?
-///
+/// 
 /// Returns an empty string on failure.
 pub fn optional_str(parser: Parser<@~str>) -> Parser<@~str>
 {
@@ -195,7 +532,7 @@ pub fn optional_str(parser: Parser<@~str>) -> Parser<@~str>
 		match parser(input)
 		{
 			result::Ok(ref pass)		=> result::Ok(Succeeded {new_state: pass.new_state, value: pass.value}),
-			result::Err(ref _failure)		=> result::Ok(Succeeded {new_state: input, value: @~""}),
+			result::Err(ref failure)		=> {note_discarded(failure); result::Ok(Succeeded {new_state: input, value: @~""})}
 		}
 	}
 }
@@ -203,9 +540,9 @@ pub fn optional_str(parser: Parser<@~str>) -> Parser<@~str>
 /// Calls fun once and matches the number of characters returned by fun. 
 /// 
 /// This does increment line.  Note that this succeeds even if zero characters are matched.
-///
+/// 
 /// # Fun's are typically written like this:
-///
+/// 
 /// ~~~
 /// fn to_new_line(chars: @[char], index: uint) -> uint
 /// {
@@ -252,17 +589,16 @@ pub fn scan(fun: fn@ (@[char], uint) -> uint) -> Parser<@~str>
 				}
 				i += 1u;
 			}
-			let text = str::from_chars(vec::slice(input.text, input.index, i));
-			result::Ok(Succeeded {new_state: State {index: i, line: line, ..input}, value: @text})
+			let text = str::from_chars(vec::view(input.text, input.index, i));
+			result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, line - input.line), value: @text})
 		}
 		else
 		{
-			result::Ok(Succeeded {new_state: State {index: i, line: line, ..input}, value: @~""})
+			result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, line - input.line), value: @~""})
 		}
 	}
 }
 
-
 /// If all the parsers are successful then the matched text is returned.
 pub fn seq2_ret_str<T0: Copy Durable, T1: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>) -> Parser<@~str>
 {
@@ -272,7 +608,7 @@ pub fn seq2_ret_str<T0: Copy Durable, T1: Copy Durable>(p0: Parser<T0>, p1: Pars
 		{
 			result::Ok(ref pass) =>
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
+				let text = str::from_chars(vec::view(input.text, input.index, pass.new_state.index));
 				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
 			}
 			result::Err(ref failure) =>
@@ -292,7 +628,7 @@ pub fn seq3_ret_str<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Pa
 		{
 			result::Ok(ref pass) =>
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
+				let text = str::from_chars(vec::view(input.text, input.index, pass.new_state.index));
 				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
 			}
 			result::Err(ref failure) =>
@@ -311,7 +647,7 @@ pub fn seq4_ret_str<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Co
 		{
 			result::Ok(ref pass) =>
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
+				let text = str::from_chars(vec::view(input.text, input.index, pass.new_state.index));
 				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
 			}
 			result::Err(ref failure) =>
@@ -330,7 +666,7 @@ pub fn seq5_ret_str<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Co
 		{
 			result::Ok(ref pass) =>
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
+				let text = str::from_chars(vec::view(input.text, input.index, pass.new_state.index));
 				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
 			}
 			result::Err(ref failure) =>
@@ -361,68 +697,54 @@ pub impl &str : StringParsers
 {
 	fn lit(&self) -> Parser<@~str>
 	{
-		let s = self.to_owned();
+		// Matching literals is where most grammars spend their time so we do as
+		// much as possible up front.
+		let value = @self.to_owned();
+		let chars = str::chars(*self);
+		let mesg = @fmt!("'%s'", *value);
 		
 		|input: State|
 		{
-			let mut i = 0u;
-			let mut j = input.index;
-			while i < str::len(s)
+			let len = vec::len(chars);
+			let mut j = 0u;
+			while j < len && chars[j] == input.text[input.index + j]
 			{
-				let CharRange {ch, next} = str::char_range_at(s, i);
-				if ch == input.text[j]
-				{
-					i = next;
-					j += 1u;
-				}
-				else
-				{
-					break;
-				}
+				j += 1u;
 			}
 			
-			if i == str::len(s)
+			if j == len
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, j));
-				result::Ok(Succeeded {new_state: State {index: j, ..input}, value: @text})
+				result::Ok(Succeeded {new_state: input.advance(len), value: value})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: j, ..input}, mesg: @fmt!("'%s'", s)})
+				result::Err(Failed {old_state: input, err_state: input.advance(j), mesg: mesg})
 			}
 		}
 	}
 	
 	fn liti(&self) -> Parser<@~str>
 	{
-		let s = str::to_lower(self);
+		let chars = str::chars(str::to_lower(*self));
+		let mesg = @fmt!("'%s'", str::to_lower(*self));
 		
 		|input: State|
 		{
-			let mut i = 0u;
-			let mut j = input.index;
-			while i < str::len(s)
+			let len = vec::len(chars);
+			let mut j = 0u;
+			while j < len && chars[j] == lower_char(input.text[input.index + j])
 			{
-				let CharRange {ch, next} = str::char_range_at(s, i);
-				if ch == lower_char(input.text[j])
-				{
-					i = next;
-					j += 1u;
-				}
-				else
-				{
-					break;
-				}
+				j += 1u;
 			}
 			
-			if i == str::len(s)
+			if j == len
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, j));
-				result::Ok(Succeeded {new_state: State {index: j, ..input}, value: @text})
+				let text = str::from_chars(vec::view(input.text, input.index, input.index + len));
+				result::Ok(Succeeded {new_state: input.advance(len), value: @text})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: j, ..input}, mesg: @fmt!("'%s'", s)})
+				result::Err(Failed {old_state: input, err_state: input.advance(j), mesg: mesg})
 			}
 		}
 	}
@@ -439,17 +761,89 @@ pub impl &str : StringParsers
 }
 
 // ---- generic parsers ---------------------------------------------------------------------------
+/// Returns the text of the first capture with the given name (see with_captures).
+pub fn capture_text(captures: &[Capture], name: &str) -> Option<@~str>
+{
+	match vec::find(captures, |c| str::eq_slice(*c.name, name))
+	{
+		option::Some(ref c) => option::Some(c.text),
+		option::None => option::None,
+	}
+}
+
+/// Returns a parser which uses the current character to pick the parser to call (in
+/// constant time). If there is no entry for the character then default is called
+/// (or the parse fails if there is no default).
+/// 
+/// This is useful for things like statements which start with a keyword. Note that the
+/// character is not consumed. Also see or_first.
+pub fn dispatch<T: Copy Durable>(entries: &[(char, Parser<T>)], default: Option<Parser<T>>) -> Parser<T>
+{
+	let table: HashMap<char, Parser<T>> = HashMap();
+	let mut chars = ~"";
+	for vec::each(entries) |entry|
+	{
+		let (ch, parser) = *entry;
+		if !table.contains_key(ch)
+		{
+			table.insert(ch, parser);
+			str::push_char(&mut chars, ch);
+		}
+	}
+	let mesg = @fmt!("[%s]", chars);
+	
+	|input: State|
+	{
+		match table.find(input.text[input.index])
+		{
+			option::Some(parser) =>
+			{
+				parser(input)
+			}
+			option::None =>
+			{
+				match default
+				{
+					option::Some(parser) => parser(input),
+					option::None => result::Err(Failed {old_state: input, err_state: input, mesg: mesg}),
+				}
+			}
+		}
+	}
+}
+
 /// Returns a parser which always fails.
 pub fn fails<T: Copy Durable>(mesg: &str) -> Parser<T>
 {
-	let mesg = mesg.to_owned();
-	|input: State| result::Err(Failed {old_state: input, err_state: input, mesg: @copy mesg})
+	let mesg = @mesg.to_owned();
+	|input: State| result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+}
+
+/// Returns a parser which succeeds without consuming anything if predicate returns true
+/// for user and otherwise fails with mesg. This is used for context sensitive grammars,
+/// e.g. `seq2_ret1(guard_state(flags, |f| f.async, "async support"), async_fn)`. Also
+/// see state_dependent.
+pub fn guard_state<S: Copy Durable>(user: S, predicate: fn@ (S) -> bool, mesg: &str) -> Parser<()>
+{
+	let mesg = @mesg.to_owned();
+	
+	|input: State|
+	{
+		if predicate(user)
+		{
+			result::Ok(Succeeded {new_state: input, value: ()})
+		}
+		else
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+		}
+	}
 }
 
 /// Parses with the aid of a pointer to a parser (useful for things like parenthesized expressions).
-///
+/// 
 /// # Usage is like this:
-///
+/// 
 /// ~~~
 /// // create a pointer that we can initialize later with the real expr parser
 /// let expr_ptr = @mut ret(0i);
@@ -462,254 +856,519 @@ pub fn fails<T: Copy Durable>(mesg: &str) -> Parser<T>
 /// ~~~
 pub fn forward_ref<T: Copy Durable>(parser: @mut Parser<T>) -> Parser<T>
 {
-	|input: State| (*parser)(input)
+	// Recursion is one of the two ways a parse can do unbounded work so we check the limits here.
+	|input: State|
+	{
+		match check_limits(input, input)
+		{
+			option::Some(ref failure) =>
+			{
+				result::Err(Failed {old_state: input, ..*failure})
+			}
+			option::None =>
+			{
+				(*parser)(input)
+			}
+		}
+	}
 }
 
-/// or_v := e0 | e1 | …
+/// Parses path (e.g. the argument of an #include directive), calls load to get the text
+/// of the file, and then uses body to parse all of that text.
 /// 
-/// This is a version of or that is nicer to use when there are more than two alternatives.
-pub fn or_v<T: Copy Durable>(parsers: @~[Parser<T>]) -> Parser<T>
+/// Errors within the included text name the included file and include the chain of
+/// includes. Note that once the path has been parsed errors are fatal (there's no
+/// good way to recover from a bad include).
+pub fn include<T: Copy Durable>(path: Parser<@~str>, load: fn@ (@~str) -> result::Result<~str, ~str>, body: Parser<T>) -> Parser<T>
 {
-	// A recursive algorithm would be a lot simpler, but it's not clear how that could
-	// produce good error messages.
-	assert !vec::is_empty(*parsers);
+	let body = seq2_ret0(body, eot());
 	
 	|input: State|
 	{
-		let mut result: Option<Status<T>> = None;
-		let mut errors = ~[];
-		let mut max_index = uint::max_value;
-		let mut i = 0u;
-		while i < vec::len(*parsers) && option::is_none(&result)
+		do result::chain(path(input))
+		|pass|
 		{
-			match parsers[i](input)
+			if input.file == pass.value || vec::any(*input.includes, |i| i.file == pass.value)
 			{
-				result::Ok(ref pass) =>
-				{
-					result = option::Some(result::Ok(*pass));
-				}
-				result::Err(ref failure) =>
+				result::Err(set_fatal(Failed {old_state: input, err_state: input, mesg: @fmt!("recursive include of '%s'", *pass.value)}))
+			}
+			else
+			{
+				match load(pass.value)
 				{
-					if failure.err_state.index > max_index || max_index == uint::max_value
+					result::Ok(ref text) =>
 					{
-						errors = ~[failure.mesg];
-						max_index = failure.err_state.index;
+						let includes = @vec::append(copy *input.includes, ~[Include {file: input.file, line: input.line}]);
+						let inner = State {file: pass.value, text: chars_with_eot(*text), index: 0u, offset: 0u, line: 1, ctx: input.ctx, includes: includes};
+						match body(inner)
+						{
+							result::Ok(ref pass2) =>
+							{
+								result::Ok(Succeeded {new_state: pass.new_state, value: pass2.value})
+							}
+							result::Err(ref failure) =>
+							{
+								result::Err(set_fatal(Failed {old_state: input, ..*failure}))
+							}
+						}
 					}
-					else if failure.err_state.index == max_index
+					result::Err(ref mesg) =>
 					{
-						vec::push(&mut errors, failure.mesg);
+						result::Err(set_fatal(Failed {old_state: input, err_state: input, mesg: @fmt!("couldn't include '%s': %s", *pass.value, *mesg)}))
 					}
 				}
 			}
-			i += 1u;
 		}
-		
-		if option::is_some(&result)
-		{
-			option::get(result)
-		}
-		else
-		{
-			let errs = do vec::filter(errors) |s| {!str::is_empty(**s)};
-			let mesg = at_connect(errs, ~" or ");
-			result::Err(Failed {old_state: input, err_state: State {index: max_index, ..input}, mesg: @mesg})
+	}
+}
+
+/// Uses len to parse a length and then uses the parser returned by body to parse exactly
+/// that many characters (or bytes, see parse_binary).
+/// 
+/// The body parser sees an EOT at the end of the field so, for example, r0 and list
+/// stop there. It is an error if the body doesn't consume the entire field. Note that
+/// the text up to the end of the field is copied (so that indexes within the field,
+/// e.g. in spans, are the same as in the text). This takes time proportional to the
+/// position of the field so a text with many fields takes quadratic time: this is
+/// best suited to smaller inputs like binary headers and records.
+pub fn length_prefixed<T: Copy Durable>(len: Parser<uint>, body: fn@ (uint) -> Parser<T>) -> Parser<T>
+{
+	|input: State|
+	{
+		do result::chain(len(input))
+		|pass|
+		{
+			let start = pass.new_state;
+			let count = pass.value;
+			
+			// The length may be hostile so it's checked before it is added to anything.
+			if count > vec::len(input.text) - 1u - start.index
+			{
+				result::Err(Failed {old_state: input, err_state: start, mesg: @fmt!("%u characters", count)})
+			}
+			else
+			{
+				let end = start.index + count;
+				let text = do at_vec::build_sized(end + 1u)
+				|push|
+				{
+					for uint::range(0u, end) |i| {push(input.text[i]);}
+					push(EOT);
+				};
+				match body(count)(State {text: text, ..start})
+				{
+					result::Ok(ref pass2) if pass2.new_state.index >= end =>
+					{
+						// Body may have consumed the EOT standing in for the end of the field
+						// (which is one byte).
+						let offset = if pass2.new_state.index > end {pass2.new_state.offset - 1u} else {pass2.new_state.offset};
+						result::Ok(Succeeded {new_state: State {text: input.text, index: end, offset: offset, ..pass2.new_state}, value: pass2.value})
+					}
+					result::Ok(ref pass2) =>
+					{
+						let err_state = State {text: input.text, ..pass2.new_state};
+						result::Err(Failed {old_state: input, err_state: err_state, mesg: @fmt!("end of the %u character field", count)})
+					}
+					result::Err(ref failure) =>
+					{
+						result::Err(Failed {old_state: input, err_state: State {text: input.text, ..failure.err_state}, mesg: failure.mesg})
+					}
+				}
+			}
 		}
 	}
 }
 
-/// Returns a parser which always succeeds, but does not consume any input.
-#[allow(deprecated_mode)]		// TODO: probably need to use &T instead
-pub fn ret<T: Copy Durable>(value: T) -> Parser<T>
+/// A load function for include which reads the file at path.
+pub fn load_file(path: @~str) -> result::Result<~str, ~str>
 {
-	|input: State| result::Ok(Succeeded {new_state: input, value: value})
+	io::read_whole_file_str(&Path(*path))
 }
 
-/// seq2 := e0 e1
-pub fn seq2<T0: Copy Durable, T1: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, eval: fn@ (T0, T1) -> result::Result<R, @~str>) -> Parser<R>
+/// Like the memoize method except that only the most recent results are remembered and
+/// the parser is named key.
+/// 
+/// This is intended for the few productions which are re-tried over and over, e.g. a
+/// type which starts several overlapping alternatives. These are normally re-tried at
+/// nearby positions so a small table is enough and, unlike memoize, memory use does
+/// not grow with the size of the text.
+pub fn memo<T: Copy Durable>(parser: Parser<T>, key: &str) -> Parser<T>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-		match eval(a0, a1)
+	let name = @key.to_owned();
+	let parser = parser.named(key);
+	let table: @mut ~[Option<(uint, Memo<T>)>] = @mut vec::from_elem(MEMO_SIZE, option::None);
+	let parse: @mut Option<(@[char], @mut Context)> = @mut option::None;
+	
+	|input: State|
+	{
+		if !same_parse(*parse, input)
 		{
-			result::Ok(ref value) =>
+			*table = vec::from_elem(MEMO_SIZE, option::None);
+			*parse = option::Some((input.text, input.ctx));
+		}
+		
+		let slot = input.index % MEMO_SIZE;
+		match copy (*table)[slot]
+		{
+			option::Some((index, memo)) if index == input.index =>
 			{
-				ret(*value)
+				// The named bookkeeping was skipped so the profile is updated here (the
+				// derivation is one of the remembered ones).
+				let status = reuse_memo(&memo, input);
+				if input.ctx.profiling.enabled
+				{
+					match status
+					{
+						result::Ok(ref pass) => add_profile(input.ctx, name, option::Some(pass.new_state.index - input.index)),
+						result::Err(_) => add_profile(input.ctx, name, option::None),
+					}
+				}
+				status
 			}
-			result::Err(mesg) =>
+			_ =>
 			{
-				fails(*mesg)
+				let (status, memo) = run_memo(parser, input);
+				if memo.is_some()
+				{
+					(*table)[slot] = option::Some((input.index, memo.get()));
+				}
+				status
 			}
 		}
-	}}
+	}
 }
 
-/// seq3 := e0 e1 e2
-pub fn seq3<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, eval: fn@ (T0, T1, T2) -> result::Result<R, @~str>) -> Parser<R>
+/// Like or_first except that the characters which can start each alternative come from
+/// its first_set (alternatives without a first_set are always tried).
+pub fn or_described<T: Copy Durable>(alternatives: &[@Parses<T>]) -> Parser<T>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-		match eval(a0, a1, a2)
+	let alternatives = do vec::map(alternatives)
+		|alternative|
 		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
+			let first = match alternative.first_set() {option::Some(first) => first, option::None => @~""};
+			(first, alternative.to_parser())
+		};
+	or_first(@alternatives)
+}
+
+/// Like or_v except that each alternative is paired with the characters which can
+/// start it (an empty string means that the alternative can start with anything,
+/// including nothing). Alternatives which cannot start with the current character
+/// are skipped.
+/// 
+/// This can make wide alternations, e.g. statements, much faster. Note that the
+/// error message is the same as or_v's (if no alternative matches the skipped
+/// alternatives are tried in order to find out what they expected).
+pub fn or_first<T: Copy Durable>(alternatives: @~[(@~str, Parser<T>)]) -> Parser<T>
+{
+	assert !vec::is_empty(*alternatives);
+	
+	|input: State|
+	{
+		let ch = input.text[input.index];
+		let count = vec::len(*alternatives);
+		let mut result: Option<Status<T>> = None;
+		let mut failures = vec::from_elem(count, option::None);
+		
+		// The first count steps try the alternatives which can start with ch and the
+		// rest try the alternatives which were skipped.
+		let mut i = 0u;
+		while i < 2u*count && option::is_none(&result)
+		{
+			let (first, parser) = alternatives[i % count];
+			let likely = str::is_empty(*first) || str::contains_char(*first, ch);
+			if likely == (i < count)
 			{
-				fails(*mesg)
+				match parser(input)
+				{
+					result::Ok(ref pass) =>
+					{
+						result = option::Some(result::Ok(*pass));
+					}
+					result::Err(ref failure) =>
+					{
+						failure.err_state.note_examined(failure.err_state.index);
+						failures[i % count] = option::Some(*failure);
+					}
+				}
 			}
+			i += 1u;
 		}
-	}}}
+		
+		match result
+		{
+			option::Some(status) => status,
+			option::None => result::Err(merge_failures(input, vec::filter_map(failures, |f| *f))),
+		}
+	}
 }
 
-/// seq4 := e0 e1 e2 e3
-pub fn seq4<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, eval: fn@ (T0, T1, T2, T3) -> result::Result<R, @~str>) -> Parser<R>
+// Returns a failure like or_v's for alternatives which all failed with failures.
+fn merge_failures(input: State, failures: &[Failed]) -> Failed
+{
+	let mut errors = ~[];
+	let mut err_state = input;
+	for vec::eachi(failures) |i, failure|
+	{
+		if i == 0u || failure.err_state.index > err_state.index
+		{
+			errors = ~[failure.mesg];
+			err_state = failure.err_state;
+		}
+		else if failure.err_state.index == err_state.index
+		{
+			vec::push(&mut errors, failure.mesg);
+		}
+	}
+	
+	let errs = do vec::filter(errors) |s| {!str::is_empty(**s)};
+	Failed {old_state: input, err_state: State {index: err_state.index, offset: err_state.offset, ..input}, mesg: @at_connect(errs, ~" or ")}
+}
+
+/// or_v := e0 | e1 | …
+/// 
+/// This is a version of or that is nicer to use when there are more than two alternatives.
+pub fn or_v<T: Copy Durable>(parsers: @~[Parser<T>]) -> Parser<T>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-		match eval(a0, a1, a2, a3)
+	// A recursive algorithm would be a lot simpler, but it's not clear how that could
+	// produce good error messages.
+	assert !vec::is_empty(*parsers);
+	
+	// Alternatives normally fail with the same messages each time so we save the
+	// combined message rather than rebuilding it for failures which are usually
+	// discarded by backtracking.
+	let last_errs = @mut ~[];
+	let last_mesg = @mut @~"";
+	
+	|input: State|
+	{
+		let mut result: Option<Status<T>> = None;
+		let mut errors = ~[];
+		let mut max_index = uint::max_value;
+		let mut max_offset = 0u;
+		let mut i = 0u;
+		while i < vec::len(*parsers) && option::is_none(&result)
 		{
-			result::Ok(ref value) =>
+			match parsers[i](input)
 			{
-				ret(*value)
+				result::Ok(ref pass) =>
+				{
+					result = option::Some(result::Ok(*pass));
+				}
+				result::Err(ref failure) =>
+				{
+					failure.err_state.note_examined(failure.err_state.index);
+					if failure.err_state.index > max_index || max_index == uint::max_value
+					{
+						errors = ~[failure.mesg];
+						max_index = failure.err_state.index;
+						max_offset = failure.err_state.offset;
+					}
+					else if failure.err_state.index == max_index
+					{
+						vec::push(&mut errors, failure.mesg);
+					}
+				}
 			}
-			result::Err(mesg) =>
+			i += 1u;
+		}
+		
+		if option::is_some(&result)
+		{
+			option::get(result)
+		}
+		else
+		{
+			let errs = do vec::filter(errors) |s| {!str::is_empty(**s)};
+			if !same_boxes(*last_errs, errs)
 			{
-				fails(*mesg)
+				*last_mesg = @at_connect(errs, ~" or ");
+				*last_errs = errs;
 			}
+			result::Err(Failed {old_state: input, err_state: State {index: max_index, offset: max_offset, ..input}, mesg: *last_mesg})
 		}
-	}}}}
+	}
+}
+
+/// Returns a parser which always succeeds, but does not consume any input.
+#[allow(deprecated_mode)]		// TODO: probably need to use &T instead
+pub fn ret<T: Copy Durable>(value: T) -> Parser<T>
+{
+	|input: State| result::Ok(Succeeded {new_state: input, value: value})
+}
+
+// Runs the next parser of a sequence which started at input. Its failures are
+// reported as failures of the sequence.
+#[doc(hidden)]
+pub fn seq_next<T: Copy Durable>(parser: Parser<T>, state: State, input: State) -> Status<T>
+{
+	do result::chain_err(parser(state)) |failure| {result::Err(Failed {old_state: input, ..failure})}
+}
+
+// Converts the result of the eval function of a sequence which started at input and
+// whose parsers succeeded at output.
+#[doc(hidden)]
+pub fn seq_result<R: Copy Durable>(value: result::Result<R, @~str>, input: State, output: State) -> Status<R>
+{
+	match value
+	{
+		result::Ok(ref value) => result::Ok(Succeeded {new_state: output, value: *value}),
+		result::Err(mesg) => result::Err(Failed {old_state: input, err_state: output, mesg: mesg}),
+	}
+}
+
+/// seq2 := e0 e1
+pub fn seq2<T0: Copy Durable, T1: Copy Durable, R: Copy Durable>
+	(parser0: Parser<T0>, parser1: Parser<T1>, eval: fn@ (T0, T1) -> result::Result<R, @~str>) -> Parser<R>
+{
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value), input, pass1.new_state)
+	}
+}
+
+/// seq3 := e0 e1 e2
+pub fn seq3<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, R: Copy Durable>
+	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, eval: fn@ (T0, T1, T2) -> result::Result<R, @~str>) -> Parser<R>
+{
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value), input, pass2.new_state)
+	}
+}
+
+/// seq4 := e0 e1 e2 e3
+pub fn seq4<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, R: Copy Durable>
+	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, eval: fn@ (T0, T1, T2, T3) -> result::Result<R, @~str>) -> Parser<R>
+{
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass3 = match seq_next(parser3, pass2.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value, pass3.value), input, pass3.new_state)
+	}
 }
 
 /// seq5 := e0 e1 e2 e3 e4
 pub fn seq5<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, R: Copy Durable>
 	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, eval: fn@ (T0, T1, T2, T3, T4) -> result::Result<R, @~str>) -> Parser<R>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-		match eval(a0, a1, a2, a3, a4)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass3 = match seq_next(parser3, pass2.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass4 = match seq_next(parser4, pass3.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value, pass3.value, pass4.value), input, pass4.new_state)
+	}
 }
 
 /// seq6 := e0 e1 e2 e3 e4 e5
 pub fn seq6<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, R: Copy Durable>
 	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, eval: fn@ (T0, T1, T2, T3, T4, T5) -> result::Result<R, @~str>) -> Parser<R>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-		match eval(a0, a1, a2, a3, a4, a5)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass3 = match seq_next(parser3, pass2.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass4 = match seq_next(parser4, pass3.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass5 = match seq_next(parser5, pass4.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value, pass3.value, pass4.value, pass5.value), input, pass5.new_state)
+	}
 }
 
 /// seq7 := e0 e1 e2 e3 e4 e5 e6
 pub fn seq7<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, T6: Copy Durable, R: Copy Durable>
 	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, parser6: Parser<T6>, eval: fn@ (T0, T1, T2, T3, T4, T5, T6) -> result::Result<R, @~str>) -> Parser<R>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-	do parser6.thene() |a6| {
-		match eval(a0, a1, a2, a3, a4, a5, a6)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}}
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass3 = match seq_next(parser3, pass2.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass4 = match seq_next(parser4, pass3.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass5 = match seq_next(parser5, pass4.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass6 = match seq_next(parser6, pass5.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value, pass3.value, pass4.value, pass5.value, pass6.value), input, pass6.new_state)
+	}
 }
 
 /// seq8 := e0 e1 e2 e3 e4 e5 e6 e7
 pub fn seq8<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, T6: Copy Durable, T7: Copy Durable, R: Copy Durable>
 	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, parser6: Parser<T6>, parser7: Parser<T7>, eval: fn@ (T0, T1, T2, T3, T4, T5, T6, T7) -> result::Result<R, @~str>) -> Parser<R>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-	do parser6.thene() |a6| {
-	do parser7.thene() |a7| {
-		match eval(a0, a1, a2, a3, a4, a5, a6, a7)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}}}
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass3 = match seq_next(parser3, pass2.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass4 = match seq_next(parser4, pass3.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass5 = match seq_next(parser5, pass4.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass6 = match seq_next(parser6, pass5.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass7 = match seq_next(parser7, pass6.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value, pass3.value, pass4.value, pass5.value, pass6.value, pass7.value), input, pass7.new_state)
+	}
 }
 
 /// seq9 := e0 e1 e2 e3 e4 e5 e6 e7 e8
 pub fn seq9<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, T6: Copy Durable, T7: Copy Durable, T8: Copy Durable, R: Copy Durable>
 	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, parser6: Parser<T6>, parser7: Parser<T7>, parser8: Parser<T8>, eval: fn@ (T0, T1, T2, T3, T4, T5, T6, T7, T8) -> result::Result<R, @~str>) -> Parser<R>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-	do parser6.thene() |a6| {
-	do parser7.thene() |a7| {
-	do parser8.thene() |a8| {
-		match eval(a0, a1, a2, a3, a4, a5, a6, a7, a8)
+	|input: State|
+	{
+		let pass0 = match seq_next(parser0, input, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass1 = match seq_next(parser1, pass0.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass2 = match seq_next(parser2, pass1.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass3 = match seq_next(parser3, pass2.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass4 = match seq_next(parser4, pass3.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass5 = match seq_next(parser5, pass4.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass6 = match seq_next(parser6, pass5.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass7 = match seq_next(parser7, pass6.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		let pass8 = match seq_next(parser8, pass7.new_state, input) {result::Ok(pass) => pass, result::Err(failure) => return result::Err(failure)};
+		seq_result(eval(pass0.value, pass1.value, pass2.value, pass3.value, pass4.value, pass5.value, pass6.value, pass7.value, pass8.value), input, pass8.new_state)
+	}
+}
+
+/// seq_v := e0 e1 ...
+/// 
+/// Like the seqN functions except that it works on any number of parsers of the same
+/// type. Like then chains, the parsers are run in a loop so the stack does not grow
+/// with the length of the sequence.
+pub fn seq_v<T: Copy Durable>(parsers: @~[Parser<T>]) -> Parser<@~[T]>
+{
+	|input: State|
+	{
+		let mut output = input;
+		let mut values = vec::with_capacity(vec::len(*parsers));
+		for vec::each(*parsers) |parser|
 		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
+			match (*parser)(output)
 			{
-				fails(*mesg)
+				result::Ok(ref pass) =>
+				{
+					output = pass.new_state;
+					vec::push(&mut values, pass.value);
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
 			}
 		}
-	}}}}}}}}}
+		result::Ok(Succeeded {new_state: output, value: @values})
+	}
 }
 
 /// seq2_ret0 := e0 e1
@@ -730,40 +1389,306 @@ pub fn seq3_ret0<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parse
 	seq3(p0, p1, p2, |a0, _a1, _a2| result::Ok(a0))
 }
 
-/// seq3_ret1 := e0 e1 e2
-pub fn seq3_ret1<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T1>
+/// seq3_ret1 := e0 e1 e2
+pub fn seq3_ret1<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T1>
+{
+	seq3(p0, p1, p2, |_a0, a1, _a2| result::Ok(a1))
+}
+
+/// seq3_ret2 := e0 e1 e2
+pub fn seq3_ret2<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T2>
+{
+	seq3(p0, p1, p2, |_a0, _a1, a2| result::Ok(a2))
+}
+
+/// seq4_ret0 := e0 e1 e2 e3
+pub fn seq4_ret0<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T0>
+{
+	seq4(p0, p1, p2, p3, |a0, _a1, _a2, _a3| result::Ok(a0))
+}
+
+/// seq4_ret1 := e0 e1 e2 e3
+pub fn seq4_ret1<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T1>
+{
+	seq4(p0, p1, p2, p3, |_a0, a1, _a2, _a3| result::Ok(a1))
+}
+
+/// seq4_ret2 := e0 e1 e2 e3
+pub fn seq4_ret2<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T2>
+{
+	seq4(p0, p1, p2, p3, |_a0, _a1, a2, _a3| result::Ok(a2))
+}
+
+/// seq4_ret3 := e0 e1 e2 e3
+pub fn seq4_ret3<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T3>
+{
+	seq4(p0, p1, p2, p3, |_a0, _a1, _a2, a3| result::Ok(a3))
+}
+
+/// Returns a parser which calls fun each time it is used and then uses the parser fun
+/// returned. This allows the grammar to depend upon things which change while parsing
+/// or between parses (e.g. C's typedef names or the syntax enabled by a version
+/// directive). User is typically a box (or Scope) which other parsers or the caller
+/// update. Note that changes made to it are not undone when a parser is backtracked over.
+pub fn state_dependent<S: Copy Durable, T: Copy Durable>(user: S, fun: fn@ (S) -> Parser<T>) -> Parser<T>
+{
+	|input: State|
+	{
+		let parser = fun(user);
+		parser(input)
+	}
+}
+
+// The number of results remembered by memo.
+const MEMO_SIZE: uint = 16u;
+
+// A result remembered by memo or memoize along with the changes the parser made to
+// the context (so that they can be made again when the result is reused). Examined is
+// how far the parser looked (see State::note_examined).
+struct Memo<T: Copy Durable> {status: Status<T>, values: uint, examined: uint, derived: @~[Derivation], captured: @~[Capture], comments: @~[Spanned<@~str>]}
+
+// Returns true if input is from the parse the remembered results came from. Results
+// are only reused within a parse: states refer to their parse's context and what the
+// parser does to the context depends upon how the parse was started (e.g. derivations
+// are only recorded by parse_derivation).
+fn same_parse(parse: Option<(@[char], @mut Context)>, input: State) -> bool
+{
+	match parse
+	{
+		option::Some((text, ctx)) =>
+		{
+			// The remembered states keep ctx alive so its address can't be reused.
+			same_text(text, input.text) && unsafe {cast::reinterpret_cast::<@mut Context, uint>(&ctx) == cast::reinterpret_cast::<@mut Context, uint>(&input.ctx)}
+		}
+		option::None =>
+		{
+			false
+		}
+	}
+}
+
+// Runs parser and returns its result along with what to remember (nothing if the
+// parse became fatal).
+fn run_memo<T: Copy Durable>(parser: Parser<T>, input: State) -> (Status<T>, Option<Memo<T>>)
+{
+	let ctx = input.ctx;
+	let values = ctx.values;
+	let examined = ctx.examined;
+	let comments = vec::len(ctx.comments.kept);
+	let mut derived = ~[];
+	derived <-> ctx.deriving.derived;
+	let mut captured = ~[];
+	captured <-> ctx.captured;
+	ctx.examined = input.index;
+	
+	let status = parser(input);
+	
+	derived <-> ctx.deriving.derived;
+	captured <-> ctx.captured;
+	let memo = Memo {status: status, values: ctx.values - values, examined: ctx.examined, derived: @derived, captured: @captured, comments: @vec::slice(ctx.comments.kept, comments, vec::len(ctx.comments.kept))};
+	add_memo_effects(ctx, &memo);
+	ctx.examined = uint::max(examined, ctx.examined);
+	(status, if ctx.fatal.is_none() {option::Some(memo)} else {option::None})
+}
+
+// Makes the remembered changes to input's context and returns the remembered result.
+fn reuse_memo<T: Copy Durable>(memo: &Memo<T>, input: State) -> Status<T>
+{
+	input.ctx.values += memo.values;
+	input.note_examined(memo.examined);
+	vec::push_all(&mut input.ctx.comments.kept, *memo.comments);
+	add_memo_effects(input.ctx, memo);
+	match check_limits(input, input)
+	{
+		option::Some(ref failure) => result::Err(Failed {old_state: input, ..*failure}),
+		option::None => memo.status,
+	}
+}
+
+// The derivations and captures are added as if the parser had added them itself (so
+// those from failed alternatives are discarded).
+fn add_memo_effects<T: Copy Durable>(ctx: @mut Context, memo: &Memo<T>)
+{
+	for vec::each(*memo.derived) |d|
+	{
+		add_derivation(ctx, *d);
+	}
+	for vec::each(*memo.captured) |c|
+	{
+		add_capture(ctx, *c, []);
+	}
+}
+
+// Then and thene chains nest (`a.then(b).then(c)` is `(a.then(b)).then(c)`) so running
+// each link by calling the previous one would use a stack frame per link. Instead the
+// most recently built chains of each type remember their steps and a link added to one
+// of them copies its steps so that one loop runs the whole chain.
+const CHAIN_SIZE: uint = 16u;
+
+// A step runs a parser and returns the state it stopped at. The value of the last step
+// is saved in value and taken as soon as the loop finishes. The values of the other
+// steps are saved by the step which produced them and taken by the next step before
+// it parses anything so chains can be re-entered (e.g. by recursive productions).
+struct Chain<T: Copy Durable> {parser: Parser<T>, steps: @~[fn@ (State) -> result::Result<State, Failed>], value: @mut Option<T>}
+
+struct Chains<T: Copy Durable> {recent: @mut ~[Chain<T>]}
+
+// Returns a parser which runs parser and then the parser eval returns for its value.
+fn chain<T: Copy Durable, U: Copy Durable>(parser: Parser<T>, eval: fn@ (T) -> Parser<U>) -> Parser<U>
+{
+	let (steps, previous) = match vec::find(*recent_chains::<T>().recent, |c| same_parser(c.parser, parser))
+	{
+		option::Some(ref c) =>
+		{
+			(copy *c.steps, c.value)
+		}
+		option::None =>
+		{
+			let value = @mut option::None;
+			let first: fn@ (State) -> result::Result<State, Failed> = |input: State| save_value(parser(input), value);
+			(~[first], value)
+		}
+	};
+	
+	let value = @mut option::None;
+	let last: fn@ (State) -> result::Result<State, Failed> = |input: State| save_value(eval(take_value(previous))(input), value);
+	let mut steps = steps;
+	vec::push(&mut steps, last);
+	let steps = @steps;
+	
+	let chained: Parser<U> = |input: State|
+	{
+		let mut output = input;
+		for vec::each(*steps) |step|
+		{
+			match (*step)(output)
+			{
+				result::Ok(state) =>
+				{
+					output = state;
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+		}
+		result::Ok(Succeeded {new_state: output, value: take_value(value)})
+	};
+	
+	let recent = recent_chains::<U>().recent;
+	if vec::len(*recent) == CHAIN_SIZE
+	{
+		vec::remove(&mut *recent, 0u);
+	}
+	vec::push(&mut *recent, Chain {parser: chained, steps: steps, value: value});
+	chained
+}
+
+fn save_value<T: Copy Durable>(status: Status<T>, value: @mut Option<T>) -> result::Result<State, Failed>
 {
-	seq3(p0, p1, p2, |_a0, a1, _a2| result::Ok(a1))
+	match status
+	{
+		result::Ok(ref pass) =>
+		{
+			*value = option::Some(pass.value);
+			result::Ok(pass.new_state)
+		}
+		result::Err(ref failure) =>
+		{
+			result::Err(*failure)
+		}
+	}
 }
 
-/// seq3_ret2 := e0 e1 e2
-pub fn seq3_ret2<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T2>
+fn take_value<T: Copy Durable>(value: @mut Option<T>) -> T
 {
-	seq3(p0, p1, p2, |_a0, _a1, a2| result::Ok(a2))
+	let result = option::get(*value);
+	*value = option::None;
+	result
 }
 
-/// seq4_ret0 := e0 e1 e2 e3
-pub fn seq4_ret0<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T0>
+fn recent_chains<T: Copy Durable>() -> @Chains<T>
 {
-	seq4(p0, p1, p2, p3, |a0, _a1, _a2, _a3| result::Ok(a0))
+	unsafe
+	{
+		match task::local_data::local_data_get(chains_key::<T>)
+		{
+			option::Some(chains) =>
+			{
+				chains
+			}
+			option::None =>
+			{
+				let chains = @Chains {recent: @mut ~[]};
+				task::local_data::local_data_set(chains_key::<T>, chains);
+				chains
+			}
+		}
+	}
 }
 
-/// seq4_ret1 := e0 e1 e2 e3
-pub fn seq4_ret1<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T1>
+fn chains_key<T: Copy Durable>(_c: @Chains<T>)
 {
-	seq4(p0, p1, p2, p3, |_a0, a1, _a2, _a3| result::Ok(a1))
 }
 
-/// seq4_ret2 := e0 e1 e2 e3
-pub fn seq4_ret2<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T2>
+// Returns true if lhs and rhs are the same closure (a closure is a code pointer and
+// an environment pointer). The recent chains keep their parsers alive so their
+// addresses can't be reused.
+fn same_parser<T: Copy Durable>(lhs: Parser<T>, rhs: Parser<T>) -> bool
 {
-	seq4(p0, p1, p2, p3, |_a0, _a1, a2, _a3| result::Ok(a2))
+	unsafe {cast::reinterpret_cast::<Parser<T>, (uint, uint)>(&lhs) == cast::reinterpret_cast::<Parser<T>, (uint, uint)>(&rhs)}
 }
 
-/// seq4_ret3 := e0 e1 e2 e3
-pub fn seq4_ret3<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T3>
+// Returns state advanced past any space. Normally space is [ \t\r\n]* but this
+// can be changed with the skipping method.
+#[doc(hidden)]
+pub fn skip_space(state: State) -> State
 {
-	seq4(p0, p1, p2, p3, |_a0, _a1, _a2, a3| result::Ok(a3))
+	match state.ctx.skipper
+	{
+		option::Some(skipper) =>
+		{
+			// The skipper itself may use s0 so it's run with the default definition of space.
+			state.ctx.skipper = option::None;
+			let status = skipper(state);
+			state.ctx.skipper = option::Some(skipper);
+			match status
+			{
+				result::Ok(ref pass) => pass.new_state,
+				result::Err(ref failure) => {failure.err_state.note_examined(failure.err_state.index); state},
+			}
+		}
+		option::None =>
+		{
+			// It would be simpler to write this with scan0, but scan0 is relatively inefficient
+			// and s0 is typically called a lot.
+			let mut i = state.index;
+			let mut line = state.line;
+			loop
+			{
+				if state.text[i] == '\r' && state.text[i+1u] == '\n'
+				{
+					line += 1;
+					i += 1u;
+				}
+				else if state.text[i] == '\n'
+				{
+					line += 1;
+				}
+				else if state.text[i] == '\r'
+				{
+					line += 1;
+				}
+				else if state.text[i] != ' ' && state.text[i] != '\t'
+				{
+					break;
+				}
+				i += 1u;
+			}
+			state.advance_lines(i - state.index, line - state.line)
+		}
+	}
 }
 
 // chain_suffix := (op e)*
@@ -805,6 +1730,39 @@ pub fn or_mesg(mesg1: @~str, mesg2: @~str) -> @~str
 	}
 }
 
+// Records failures which are discarded by combinators like r0 so that parse_expected
+// can report the farthest one.
+#[doc(hidden)]
+pub fn note_discarded(failure: &Failed)
+{
+	let ctx = failure.err_state.ctx;
+	failure.err_state.note_examined(failure.err_state.index);
+	if ctx.expecting.enabled
+	{
+		match ctx.expecting.farthest
+		{
+			option::Some(ref farthest) if farthest.err_state.index > failure.err_state.index =>
+			{
+			}
+			option::Some(ref farthest) if farthest.err_state.index == failure.err_state.index =>
+			{
+				ctx.expecting.farthest = option::Some(Failed {mesg: or_mesg(farthest.mesg, failure.mesg), ..*failure});
+			}
+			_ =>
+			{
+				ctx.expecting.farthest = option::Some(*failure);
+			}
+		}
+	}
+}
+
+// Returns true if the two vectors contain the same boxes.
+#[doc(hidden)]
+pub pure fn same_boxes(lhs: &[@~str], rhs: &[@~str]) -> bool
+{
+	vec::len(lhs) == vec::len(rhs) && vec::alli(lhs, |i, s| box::ptr_eq(*s, rhs[i]))
+}
+
 /// Parse methods which return a generic type.
 pub trait GenericParsers
 {
@@ -813,10 +1771,29 @@ pub trait GenericParsers
 }
 
 /// Parse methods used to compose parsers.
-///
+/// 
 /// Note that these don't actually consume input (although the parsers they are invoked with normally will).
 pub trait Combinators<T: Copy Durable>
 {
+	/// and := e1 e2
+	/// 
+	/// Returns both values. Together with or and map this allows grammars to be written
+	/// left to right, e.g. `p.and(q).and(r).map(|v| ...)` where v is ((p, q), r).
+	fn and<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<(T, U)>;
+	
+	/// Returns a parser whose value is a box containing the value of the previous parser.
+	/// 
+	/// The value is copied once (into the box) after which composing the parser only copies
+	/// the pointer. This is useful for productions which return large values, e.g. AST nodes.
+	/// Note that the combinators themselves still copy the values they are given so the
+	/// saving only applies to values which have been boxed.
+	fn boxed(&self) -> Parser<@T>;
+	
+	/// Records the text matched by the previous parser under name. The captures can be
+	/// retrieved with with_captures. This is useful for extraction grammars where threading
+	/// the pieces through sequence values would be tedious.
+	fn capture(&self, name: &str) -> Parser<T>;
+	
 	/// chainl1 := e (op e)*
 	/// 
 	/// Left associative binary operator. eval is called for each parsed op.
@@ -840,15 +1817,45 @@ pub trait Combinators<T: Copy Durable>
 	/// same type as parser which is backwards from how it is normally used.
 	fn everything<U: Copy Durable>(&self, space: Parser<U>) -> Parser<T>;
 	
+	/// Like everything except that leading [ \t\r\n]* is skipped (so no space parser has to be built).
+	fn everything_ws(&self) -> Parser<T>;
+	
+	/// Records the text matched by the previous parser when parse_with_comments is used.
+	/// This is intended for the comment part of a space parser used with skipping, e.g.
+	/// `match1(is_whitespace).or(line_comment().keep_comment()).r0()`.
+	fn keep_comment(&self) -> Parser<T>;
+	
 	/// list := e (sep e)*
 	/// 
 	/// Values for each parsed e are returned.
 	fn list<U: Copy Durable>(&self, sep: Parser<U>) -> Parser<@~[T]>;
 	
+	/// Returns a parser which matches the same text but whose value is eval applied to
+	/// the value of the previous parser.
+	fn map<U: Copy Durable>(&self, eval: fn@ (T) -> U) -> Parser<U>;
+	
+	/// Returns a parser which remembers the result of the previous parser at each index.
+	/// 
+	/// This is useful for productions which are re-tried at the same position, e.g. when
+	/// several alternatives start with the same production. Memoizing these (i.e. packrat
+	/// parsing) makes such grammars linear at the cost of memory. Note that the previous
+	/// parser must depend only upon the text. The results are discarded when a new parse
+	/// starts (even if it is of the same text) and reusing a result also reuses the
+	/// values, derivations, captures, and comments the previous parser produced.
+	fn memoize(&self) -> Parser<T>;
+	
+	/// Gives the previous parser a name which is used by things like trace and State::production.
+	/// 
+	/// Parsers are anonymous closures so this is the only way to tell which grammar production
+	/// is being parsed. Note that err does not name the parser so labeled productions
+	/// which should appear in things like derivations need to use named as well.
+	fn named(&self, name: &str) -> Parser<T>;
+	
 	/// Logs the result of the previous parser.
 	/// 
 	/// If it was successful then the log is at INFO level. Otherwise it is at DEBUG level.
-	/// Also see err method.
+	/// Note that nothing is logged unless tracing has been enabled at runtime with the
+	/// RPARSE_TRACE environment variable (see Context). Also see err method.
 	fn note(&self, mesg: &str) -> Parser<T>;
 	
 	/// optional := e?
@@ -860,7 +1867,85 @@ pub trait Combinators<T: Copy Durable>
 	/// Uses parser to parse text. Also see everything method.
 	fn parse(&self, file: @~str, text: &str) -> ParseStatus<T>;
 	
+	/// Like parse except that errors are reported as if text started at the given UTF-8 byte
+	/// offset, line, and col (all within some larger document).
+	/// 
+	/// This is useful when parsing snippets embedded within a larger document, e.g. doc
+	/// comments or template placeholders. Note that line and col are 1-based.
+	fn parse_at(&self, file: @~str, text: &str, offset: uint, line: uint, col: uint) -> ParseStatus<T>;
+	
+	/// Like parse except that the text is decoded from bytes (see decode_bytes).
+	/// Decoding errors are reported at the position of the bad bytes (but note that
+	/// the error offset is within the decoded text).
+	fn parse_bytes(&self, file: @~str, bytes: &[u8]) -> ParseStatus<T>;
+	
+	/// Like parse except that the parse fails with "cancelled" once something is sent
+	/// on cancel (or its channel is closed). This allows a UI or server to abort a
+	/// pathological parse from another task.
+	/// 
+	/// Like timeouts cancel is checked as the repetition parsers (e.g. r0 and list)
+	/// produce values and as forward_ref recurses. Cancellation is fatal: the parse
+	/// fails even if a later parser (e.g. or) would otherwise recover from it.
+	fn parse_cancellable(&self, file: @~str, text: &str, cancel: pipes::Port<()>) -> ParseStatus<T>;
+	
+	/// Like parse except that the text has already been converted with chars_with_eot.
+	/// 
+	/// This is useful when the same text is parsed many times (e.g. by different parsers)
+	/// because the text is not copied. Note that a leading byte order mark is not skipped.
+	fn parse_chars(&self, file: @~str, text: @[char]) -> ParseStatus<T>;
+	
+	/// Like parse except that the named productions which matched are also returned.
+	/// 
+	/// This is useful for debugging grammars and for things like syntax highlighters.
+	fn parse_derivation(&self, file: @~str, text: &str) -> result::Result<(T, @~[Derivation]), ParseFailed>;
+	
+	/// Parses text up to the UTF-8 byte offset and returns what could come next, e.g.
+	/// ["'else'", "statement"]. This is intended for things like editor completion.
+	/// An offset past the end of text is treated as the end and one within a character
+	/// as the start of that character.
+	/// 
+	/// The expectations are the alternatives from the errors at the offset (including
+	/// errors which were discarded, e.g. by r0) sorted and without duplicates. They are
+	/// empty if the parse failed before the offset (more text won't fix an error which
+	/// has already happened).
+	fn parse_expected(&self, file: @~str, text: &str, offset: uint) -> @~[@~str];
+	
+	/// Like parse except that the text is read from the file at path. If the file cannot be read
+	/// the error's file will be path and line and col will both be zero.
+	fn parse_file(&self, path: &str) -> ParseStatus<T>;
+	
+	/// Like parse except that the recover method is enabled so that as much of the value
+	/// as possible is returned along with all of the errors. This is intended for things
+	/// like editors which need a value even while the text is being edited.
+	fn parse_lenient(&self, file: @~str, text: &str) -> Lenient<T>;
+	
+	/// Like parse except that the parse fails if it exceeds limits.
+	fn parse_limited(&self, file: @~str, text: &str, limits: Limits) -> ParseStatus<T>;
+	
+	/// Like parse except that counters are also returned for each named production
+	/// which was invoked (in the order they first returned). This is useful when
+	/// optimizing grammars, e.g. a production with many failures may be worth memoizing
+	/// or reordering. Also see profile_to_str.
+	fn parse_profiled(&self, file: @~str, text: &str) -> (ParseStatus<T>, @~[Profile]);
+	
+	/// Like parse except that the parser need not match all of the text. Returns the
+	/// value along with the char index and UTF-8 byte offset of the unparsed text.
+	/// 
+	/// This is useful for things like parsing a directive at the start of a line.
+	fn parse_prefix(&self, file: @~str, text: &str) -> PrefixStatus<T>;
+	
+	/// Like parse_bytes except that the bytes are read from rdr.
+	fn parse_reader(&self, file: @~str, rdr: io::Reader) -> ParseStatus<T>;
+	
+	/// Like parse except that the comments matched by keep_comment parsers are also
+	/// returned (in the order they appear in the text). This allows tools like
+	/// formatters to use parsers which skip comments (see the skipping method).
+	fn parse_with_comments(&self, file: @~str, text: &str) -> result::Result<(T, @~[Spanned<@~str>]), ParseFailed>;
+	
 	/// Succeeds if parser matches input n to m times (inclusive).
+	/// 
+	/// Matching stops if parser succeeds without consuming anything (e.g. for e?) so
+	/// that the loop terminates. Note that list and chainl1 work the same way.
 	fn r(&self, n: uint, m: uint) -> Parser<@~[T]>;
 	
 	/// r0 := e*
@@ -873,38 +1958,95 @@ pub trait Combinators<T: Copy Durable>
 	/// Values for each parsed e are returned.
 	fn r1(&self) -> Parser<@~[T]>;
 	
+	/// If the previous parser fails and the parse is lenient (see parse_lenient) then the
+	/// error is recorded, skip is used to skip past the bad text, and value is returned.
+	/// Otherwise this is the same as the previous parser.
+	/// 
+	/// Skip should match up to a point where parsing can resume, e.g. the end of a
+	/// statement. If skip fails then the original error is returned. Note that errors
+	/// are recorded even if an enclosing alternative later fails so recover is best used
+	/// where there is no backtracking, e.g. on the elements of r0.
+	fn recover<U: Copy Durable>(&self, skip: Parser<U>, value: T) -> Parser<T>;
+	
+	/// Calls hook with the current char index each time the previous parser advances
+	/// past another interval characters. This allows tools which parse very large files
+	/// to display progress without wrapping every parser.
+	/// 
+	/// Progress is checked as the repetition parsers (e.g. r0 and list) produce values
+	/// and as forward_ref recurses so the hook may be called somewhat after each
+	/// multiple of interval. The index never decreases between calls (even when the
+	/// parse backtracks).
+	fn reporting_progress(&self, interval: uint, hook: fn@ (uint)) -> Parser<T>;
+	
 	/// s0 := e [ \t\r\n]*
+	/// 
+	/// Also see skipping.
 	fn s0(&self) -> Parser<T>;
 	
 	/// s1 := e [ \t\r\n]+
 	fn s1(&self) -> Parser<T>;
 	
+	/// Makes s0 and s1 use space, instead of [ \t\r\n], while the previous parser runs.
+	/// 
+	/// Space should skip all of the space at the current position (and succeed even if
+	/// there is none), e.g. whitespace and comments. Note that the previous parser will
+	/// normally be the start production, e.g. `expr.everything_ws().skipping(space)`.
+	fn skipping(&self, space: Parser<()>) -> Parser<T>;
+	
+	/// Returns a parser whose value includes the span of the text the previous parser
+	/// matched. Note that the span will include trailing space if the previous parser
+	/// used s0 so it's usually best to use spanned before s0, e.g. `p.spanned().s0()`.
+	fn spanned(&self) -> Parser<Spanned<T>>;
+	
 	/// If parser1 is successful is successful then parser2 is called (and the value from parser1
 	/// is ignored). If parser1 fails parser2 is not called.
+	/// 
+	/// Chains like `a.then(b).then(c)` are run in a loop so the stack does not grow with
+	/// the length of the chain (as long as each then is applied to the parser returned
+	/// by the previous then or thene).
 	fn then<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<U>;
 	
 	/// If parser is successful then the function returned by eval is called
 	/// with parser's result. If parser fails eval is not called.
 	/// 
-	/// Often used to translate parsed values: `p().thene({|pvalue| return(2*pvalue)})`
+	/// Often used to translate parsed values: `p().thene({|pvalue| return(2*pvalue)})`.
+	/// Like then, chains of thene are run in a loop.
 	fn thene<U: Copy Durable>(&self, eval: fn@ (T) -> Parser<U>) -> Parser<U>;
+	
+	/// Writes the position at which the previous parser was called and its result to stderr.
+	/// 
+	/// Output is indented by how many traces are nested so this is a convenient way to
+	/// debug a misbehaving production. Unlike note this always writes output. Note that
+	/// this also names the parser.
+	/// 
+	/// If the RPARSE_TRACE_FORMAT environment variable is "json" then a JSON record
+	/// is written for each call instead (see trace_record) so that external tools can
+	/// analyze large parses.
+	fn trace(&self, name: &str) -> Parser<T>;
+	
+	/// Returns a parser whose value is eval applied to the value of the previous parser
+	/// and the captures which were made while it ran (in the order they finished, see
+	/// capture_text). Captures made within the previous parser are not visible outside it.
+	/// 
+	/// Like derivations, captures made by alternatives which later failed are discarded
+	/// if a later capture overlaps them.
+	fn with_captures<U: Copy Durable>(&self, eval: fn@ (T, @~[Capture]) -> U) -> Parser<U>;
 }
 
 pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 {
-	fn chainl1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>
+	fn and<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<(T, U)>
 	{
 		|input: State|
 		{
 			do result::chain((*self)(input))
 			|pass|
 			{
-				match chain_suffix(self, op)(pass.new_state)
+				match parser2(pass.new_state)
 				{
 					result::Ok(ref pass2) =>
 					{
-						let value = vec::foldl(pass.value, *pass2.value, |lhs: T, rhs: &(U, T)| {eval(lhs, rhs.first(), rhs.second())});
-						result::Ok(Succeeded {new_state: pass2.new_state, value: value})
+						result::Ok(Succeeded {new_state: pass2.new_state, value: (pass.value, pass2.value)})
 					}
 					result::Err(ref failure) =>
 					{
@@ -915,6 +2057,100 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 		}
 	}
 	
+	fn boxed(&self) -> Parser<@T>
+	{
+		|input: State|
+		{
+			do result::chain((*self)(input))
+			|pass|
+			{
+				result::Ok(Succeeded {new_state: pass.new_state, value: @pass.value})
+			}
+		}
+	}
+	
+	fn capture(&self, name: &str) -> Parser<T>
+	{
+		let name = @name.to_owned();
+		
+		|input: State|
+		{
+			// Like named the captures made by the parser are collected separately so that
+			// those from failed alternatives can be dropped.
+			let mut siblings = ~[];
+			siblings <-> input.ctx.captured;
+			let status = (*self)(input);
+			
+			let mut inner = ~[];
+			inner <-> input.ctx.captured;
+			input.ctx.captured <-> siblings;
+			
+			match status
+			{
+				result::Ok(ref pass) =>
+				{
+					let text = @str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
+					add_capture(input.ctx, Capture {name: name, text: text, span: input.span_to(pass.new_state)}, inner);
+				}
+				result::Err(_) =>
+				{
+				}
+			}
+			status
+		}
+	}
+	
+	fn chainl1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>
+	{
+		// Unlike chainr1 we can evaluate as we go so there's no need to build a vector
+		// of (op, e) pairs.
+		let term = op.and(*self);
+		
+		|input: State|
+		{
+			do result::chain((*self)(input))
+			|pass|
+			{
+				let mut output = pass.new_state;
+				let mut value = pass.value;
+				loop
+				{
+					match term(output)
+					{
+						result::Ok(ref pass2) =>
+						{
+							if !pass2.consumed(output)
+							{
+								break;		// the loop would never terminate if we kept going
+							}
+							output = pass2.new_state;
+							let (operator, rhs) = pass2.value;
+							value = eval(value, operator, rhs);
+							
+							input.ctx.values += 1u;
+							match check_limits(input, output)
+							{
+								option::Some(ref failure) =>
+								{
+									return result::Err(Failed {old_state: input, ..*failure});
+								}
+								option::None =>
+								{
+								}
+							}
+						}
+						result::Err(ref failure) =>
+						{
+							note_discarded(failure);
+							break;
+						}
+					}
+				}
+				result::Ok(Succeeded {new_state: output, value: value})
+			}
+		}
+	}
+	
 	fn chainr1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>
 	{
 		|input: State|
@@ -962,19 +2198,21 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 	fn err(&self, label: &str) -> Parser<T>
 	{
 		let label = label.to_owned();
+		let parser = self.note(label);
+		let mesg = @copy label;
 		
 		|input: State|
 		{
-			do result::chain_err((self.note(label))(input))
+			do result::chain_err(parser(input))
 			|failure| 
 			{
 				if str::is_empty(label)
 				{
-					result::Err(Failed {mesg: @~"", ..failure})
+					result::Err(Failed {mesg: mesg, ..failure})
 				}
 				else if failure.err_state.index == input.index || str::is_empty(*failure.mesg)
 				{
-					result::Err(Failed {mesg: @copy label, ..failure})
+					result::Err(Failed {mesg: mesg, ..failure})
 				}
 				else
 				{
@@ -992,73 +2230,194 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 		seq3_ret1(space, self, eot())
 	}
 	
+	fn everything_ws(&self) -> Parser<T>
+	{
+		seq3_ret1(ret(()).s0(), self, eot())
+	}
+	
+	fn keep_comment(&self) -> Parser<T>
+	{
+		|input: State|
+		{
+			let status = (*self)(input);
+			if input.ctx.comments.enabled
+			{
+				match status
+				{
+					result::Ok(ref pass) if pass.consumed(input) =>
+					{
+						let text = @str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
+						vec::push(&mut input.ctx.comments.kept, Spanned {value: text, span: input.span_to(pass.new_state)});
+					}
+					_ =>
+					{
+					}
+				}
+			}
+			status
+		}
+	}
+	
 	fn list<U: Copy Durable>(&self, sep: Parser<U>) -> Parser<@~[T]>
 	{
-		let term = sep.then(self).r0();
+		let term = sep.then(self);
 		
+		// This is sep.then(self).r0() except that the values are pushed onto one vector
+		// instead of being concatenated onto the first value (which copies them all).
 		|input: State|
 		{
 			do result::chain((*self)(input))
 			|pass|
 			{
-				match term(pass.new_state)
+				let mut output = pass.new_state;
+				let mut values = ~[pass.value];
+				loop
 				{
-					result::Ok(ref pass2) =>
-					{
-						result::Ok(Succeeded {value: @(~[pass.value] + *pass2.value), ..*pass2})
-					}
-					result::Err(ref failure) =>
+					match term(output)
 					{
-						result::Err(Failed {old_state: input, ..*failure})
+						result::Ok(ref pass2) =>
+						{
+							if !pass2.consumed(output)
+							{
+								break;		// the loop would never terminate if we kept going
+							}
+							output = pass2.new_state;
+							vec::push(&mut values, pass2.value);
+							
+							input.ctx.values += 1u;
+							match check_limits(input, output)
+							{
+								option::Some(ref failure) =>
+								{
+									return result::Err(Failed {old_state: input, ..*failure});
+								}
+								option::None =>
+								{
+								}
+							}
+						}
+						result::Err(ref failure) =>
+						{
+							note_discarded(failure);
+							break;
+						}
 					}
 				}
+				result::Ok(Succeeded {new_state: output, value: @values})
 			}
 		}
 	}
 	
-	fn note(&self, mesg: &str) -> Parser<T>
+	fn map<U: Copy Durable>(&self, eval: fn@ (T) -> U) -> Parser<U>
 	{
-		let mesg = mesg.to_owned();
+		|input: State|
+		{
+			do result::chain((*self)(input))
+			|pass|
+			{
+				result::Ok(Succeeded {new_state: pass.new_state, value: eval(pass.value)})
+			}
+		}
+	}
+	
+	fn memoize(&self) -> Parser<T>
+	{
+		let table: HashMap<uint, Memo<T>> = HashMap();
+		let parse: @mut Option<(@[char], @mut Context)> = @mut option::None;
 		
 		|input: State|
 		{
-			match (*self)(input)
+			if !same_parse(*parse, input)
 			{
-				result::Ok(ref pass) =>
+				table.clear();
+				*parse = option::Some((input.text, input.ctx));
+			}
+			
+			match table.find(input.index)
+			{
+				option::Some(memo) =>
+				{
+					reuse_memo(&memo, input)
+				}
+				option::None =>
 				{
-					// Note that we make multiple calls to munge_chars which is fairly slow, but
-					// we only do that when actually logging: when info or debug logging is off
-					// the munge_chars calls aren't evaluated.
-					assert pass.new_state.index >= input.index;			// can't go backwards on success (but no progress is fine, eg e*)
-					if pass.new_state.index > input.index
+					let (status, memo) = run_memo(*self, input);
+					if memo.is_some()
 					{
-						info!("%s", munge_chars(input.text));
-						info!("%s^ %s parsed '%s'", repeat_char(' ', pass.new_state.index), mesg, str::slice(munge_chars(input.text), input.index, pass.new_state.index));
+						table.insert(input.index, memo.get());
 					}
-					else
+					status
+				}
+			}
+		}
+	}
+	
+	fn named(&self, name: &str) -> Parser<T>
+	{
+		let name = @name.to_owned();
+		
+		|input: State|
+		{
+			vec::push(&mut input.ctx.names, name);
+			let status = if input.ctx.deriving.enabled
+				{
+					let mut siblings = ~[];
+					siblings <-> input.ctx.deriving.derived;
+					let status = (*self)(input);
+					
+					let mut children = ~[];
+					children <-> input.ctx.deriving.derived;
+					input.ctx.deriving.derived <-> siblings;
+					
+					match status
 					{
-						info!("%s", munge_chars(input.text));
-						info!("%s^ %s passed", repeat_char(' ', pass.new_state.index), mesg);
+						result::Ok(ref pass) =>
+						{
+							let end = pass.new_state.index;
+							let children = vec::filter(children, |d| d.end <= end);
+							add_derivation(input.ctx, Derivation {name: name, start: input.index, end: end, start_offset: input.offset, end_offset: pass.new_state.offset, children: @children});
+						}
+						result::Err(_) =>
+						{
+						}
 					}
-					result::Ok(*pass)
+					status
 				}
-				result::Err(ref failure) =>
+				else
+				{
+					(*self)(input)
+				};
+			vec::pop(&mut input.ctx.names);
+			
+			if input.ctx.profiling.enabled
+			{
+				match status
+				{
+					result::Ok(ref pass) => add_profile(input.ctx, name, option::Some(pass.new_state.index - input.index)),
+					result::Err(_) => add_profile(input.ctx, name, option::None),
+				}
+			}
+			status
+		}
+	}
+	
+	fn note(&self, mesg: &str) -> Parser<T>
+	{
+		let mesg = mesg.to_owned();
+		
+		|input: State|
+		{
+			let status = (*self)(input);
+			
+			// Tracing is normally off so that production parses don't pay for it.
+			if input.ctx.trace.level != TRACE_OFF
+			{
+				for vec::each(note_lines(mesg, input, &status)) |line|
 				{
-					assert failure.old_state.index == input.index;			// on errors the next parser must begin at the start
-					assert failure.err_state.index >= input.index;			// errors can't be before the input
-					
-					debug!("%s", munge_chars(input.text));
-					if failure.err_state.index > input.index 
-					{
-						debug!("%s^%s! %s failed", repeat_char('-', input.index), repeat_char(' ', failure.err_state.index - input.index), mesg);
-					}
-					else
-					{
-						debug!("%s^ %s failed", repeat_char('-', input.index), mesg);
-					}
-					result::Err(*failure)
+					if status.is_ok() {info!("%s", *line)} else {debug!("%s", *line)}
 				}
 			}
+			status
 		}
 	}
 	
@@ -1072,8 +2431,9 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 				{
 					result::Ok(Succeeded {new_state: pass.new_state, value: option::Some(pass.value)})
 				}
-				result::Err(ref _failure) =>
+				result::Err(ref failure) =>
 				{
+					note_discarded(failure);
 					result::Ok(Succeeded {new_state: input, value: option::None})
 				}
 			}
@@ -1082,11 +2442,16 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 	
 	fn or(&self, parser2: Parser<T>) -> Parser<T>
 	{
+		// Like or_v we save the last combined message.
+		let empty = @~"";
+		let last = @mut (empty, empty, empty);
+		
 		|input: State|
 		{
 			do result::chain_err((*self)(input))
 			|failure1|
 			{
+				failure1.err_state.note_examined(failure1.err_state.index);
 				do result::chain_err(parser2(input))
 				|failure2|
 				{
@@ -1100,7 +2465,13 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 					}
 					else
 					{
-						result::Err(Failed {mesg: or_mesg(failure1.mesg, failure2.mesg), ..failure2})
+						let (mesg1, mesg2, _) = *last;
+						if !box::ptr_eq(mesg1, failure1.mesg) || !box::ptr_eq(mesg2, failure2.mesg)
+						{
+							*last = (failure1.mesg, failure2.mesg, or_mesg(failure1.mesg, failure2.mesg));
+						}
+						let (_, _, mesg) = *last;
+						result::Err(Failed {mesg: mesg, ..failure2})
 					}
 				}
 			}
@@ -1109,24 +2480,234 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 	
 	fn parse(&self, file: @~str, text: &str) -> ParseStatus<T>
 	{
-		let chars = chars_with_eot(text);
-		let input = State {file: file, text: chars, index: 0u, line: 1};
-		match (*self)(input)
+		self.parse_limited(file, text, no_limits())
+	}
+	
+	fn parse_at(&self, file: @~str, text: &str, offset: uint, line: uint, col: uint) -> ParseStatus<T>
+	{
+		do result::chain_err(self.parse(file, text))
+		|failure|
+		{
+			// Errors within included files are relative to their own file.
+			if vec::is_empty(*failure.includes)
+			{
+				let col = if failure.line == 1u {failure.col + col - 1u} else {failure.col};
+				result::Err(ParseFailed {line: failure.line + line - 1u, col: col, offset: failure.offset + offset, ..failure})
+			}
+			else
+			{
+				result::Err(failure)
+			}
+		}
+	}
+	
+	fn parse_bytes(&self, file: @~str, bytes: &[u8]) -> ParseStatus<T>
+	{
+		match decode_bytes(bytes)
+		{
+			result::Ok(ref text) =>
+			{
+				self.parse(file, *text)
+			}
+			result::Err((ref prefix, ref mesg)) =>
+			{
+				let chars = chars_with_eot(*prefix);
+				let (line, col) = line_col(line_starts(chars), vec::len(chars) - 1u);
+				result::Err(ParseFailed {file: file, line: line, col: col, offset: str::len(*prefix), mesg: @copy *mesg, includes: @~[], incomplete: false})
+			}
+		}
+	}
+	
+	fn parse_cancellable(&self, file: @~str, text: &str, cancel: pipes::Port<()>) -> ParseStatus<T>
+	{
+		let ctx = new_context(no_limits());
+		ctx.cancel = option::Some(@(move cancel));
+		do result::chain(run_parse(*self, file, text, ctx))
+		|pass|
+		{
+			result::Ok(pass.value)
+		}
+	}
+	
+	fn parse_chars(&self, file: @~str, text: @[char]) -> ParseStatus<T>
+	{
+		do result::chain(run_parse_chars(*self, file, text, new_context(no_limits())))
+		|pass|
+		{
+			result::Ok(pass.value)
+		}
+	}
+	
+	fn parse_derivation(&self, file: @~str, text: &str) -> result::Result<(T, @~[Derivation]), ParseFailed>
+	{
+		let ctx = new_context(no_limits());
+		ctx.deriving.enabled = true;
+		
+		do result::chain(run_parse(*self, file, text, ctx))
+		|pass|
+		{
+			let end = pass.new_state.index;
+			result::Ok((pass.value, @vec::filter(ctx.deriving.derived, |d| d.end <= end)))
+		}
+	}
+	
+	fn parse_expected(&self, file: @~str, text: &str, offset: uint) -> @~[@~str]
+	{
+		let ctx = new_context(no_limits());
+		ctx.expecting.enabled = true;
+		
+		// Offsets past the end or within a character are moved back to a character boundary.
+		let mut end = uint::min(offset, str::len(text));
+		while end > 0u && end < str::len(text) && (text[end] & 0xC0u8) == 0x80u8
+		{
+			end -= 1u;
+		}
+		
+		// Both the error which failed the parse and the errors which were discarded
+		// along the way (e.g. by r0) may have happened at the offset.
+		let mut mesgs = ~[];
+		match run_parse(*self, file, str::view(text, 0u, end), ctx)
+		{
+			result::Err(ref failure) if failure.incomplete =>
+			{
+				vec::push(&mut mesgs, failure.mesg);
+			}
+			_ =>
+			{
+			}
+		}
+		match ctx.expecting.farthest
+		{
+			option::Some(ref failure) if failure.err_state.at_end() =>
+			{
+				vec::push(&mut mesgs, failure.mesg);
+			}
+			_ =>
+			{
+			}
+		}
+		
+		let mut expected = ~[];
+		for vec::each(mesgs) |mesg|
+		{
+			if !str::is_empty(**mesg)
+			{
+				for vec::each(str::split_str(**mesg, " or ")) |e|
+				{
+					if !vec::contains(expected, e)
+					{
+						vec::push(&mut expected, copy *e);
+					}
+				}
+			}
+		}
+		@vec::map(sort::merge_sort(expected, |x, y| *x <= *y), |e| @copy *e)
+	}
+	
+	fn parse_file(&self, path: &str) -> ParseStatus<T>
+	{
+		match io::file_reader(&Path(path))
+		{
+			result::Ok(rdr) =>
+			{
+				self.parse_reader(@path.to_owned(), rdr)
+			}
+			result::Err(ref mesg) =>
+			{
+				result::Err(ParseFailed {file: @path.to_owned(), line: 0u, col: 0u, offset: 0u, mesg: @copy *mesg, includes: @~[], incomplete: false})
+			}
+		}
+	}
+	
+	fn parse_lenient(&self, file: @~str, text: &str) -> Lenient<T>
+	{
+		let ctx = new_context(no_limits());
+		ctx.lenient.enabled = true;
+		
+		let status = run_parse(*self, file, text, ctx);
+		let bom = bom_len(text);
+		let mut errors = vec::map(ctx.lenient.recovered, |f| {let failed = to_parse_failed(f); ParseFailed {offset: failed.offset + bom, ..failed}});
+		let value = match status
 		{
 			result::Ok(ref pass) =>
 			{
-				result::Ok(pass.value)
+				option::Some(pass.value)
 			}
 			result::Err(ref failure) =>
 			{
-				let col = get_col(chars, failure.err_state.index);
-				result::Err(ParseFailed {file: failure.old_state.file, line: failure.err_state.line as uint, col: col, mesg: failure.mesg})
+				vec::push(&mut errors, *failure);
+				option::None
 			}
+		};
+		Lenient {value: value, errors: @sort::merge_sort(errors, |x, y| x.offset <= y.offset)}
+	}
+	
+	fn parse_limited(&self, file: @~str, text: &str, limits: Limits) -> ParseStatus<T>
+	{
+		do result::chain(run_parse(*self, file, text, new_context(limits)))
+		|pass|
+		{
+			result::Ok(pass.value)
+		}
+	}
+	
+	fn parse_profiled(&self, file: @~str, text: &str) -> (ParseStatus<T>, @~[Profile])
+	{
+		let ctx = new_context(no_limits());
+		ctx.profiling.enabled = true;
+		
+		let status = do result::chain(run_parse(*self, file, text, ctx))
+			|pass|
+			{
+				result::Ok(pass.value)
+			};
+		(status, @copy ctx.profiling.profile)
+	}
+	
+	fn parse_prefix(&self, file: @~str, text: &str) -> PrefixStatus<T>
+	{
+		// The indexes are into text so they include the byte order mark (if any).
+		let bom = bom_len(text);
+		do result::chain(run_parse(*self, file, text, new_context(no_limits())))
+		|pass|
+		{
+			let state = pass.new_state;
+			result::Ok(Prefix {value: pass.value, rest_index: state.index + if bom > 0u {1u} else {0u}, rest_offset: state.offset + bom})
+		}
+	}
+	
+	fn parse_reader(&self, file: @~str, rdr: io::Reader) -> ParseStatus<T>
+	{
+		self.parse_bytes(file, rdr.read_whole_stream())
+	}
+	
+	fn parse_with_comments(&self, file: @~str, text: &str) -> result::Result<(T, @~[Spanned<@~str>]), ParseFailed>
+	{
+		let ctx = new_context(no_limits());
+		ctx.comments.enabled = true;
+		
+		do result::chain(run_parse(*self, file, text, ctx))
+		|pass|
+		{
+			// Space is often skipped more than once at the same position (e.g. when
+			// alternatives are retried) so the duplicates are removed.
+			let sorted = sort::merge_sort(ctx.comments.kept, |x, y| x.span.start <= y.span.start);
+			let mut comments = ~[];
+			for vec::each(sorted) |c|
+			{
+				if vec::is_empty(comments) || comments[vec::len(comments) - 1u].span.start != c.span.start
+				{
+					vec::push(&mut comments, *c);
+				}
+			}
+			result::Ok((pass.value, @comments))
 		}
 	}
 	
 	fn r(&self, n: uint, m: uint) -> Parser<@~[T]>
 	{
+		let mesg = @~"";
+		
 		|input: State|
 		{
 			let mut output = input;
@@ -1137,12 +2718,29 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 				{
 					result::Ok(ref pass) =>
 					{
-						assert pass.new_state.index > output.index;	// must make progress to ensure loop termination
+						if !pass.consumed(output)
+						{
+							break;		// the loop would never terminate if we kept going
+						}
 						output = pass.new_state;
 						vec::push(&mut values, pass.value);
+						
+						// Repetition is the other way a parse can do unbounded work.
+						input.ctx.values += 1u;
+						match check_limits(input, output)
+						{
+							option::Some(ref failure) =>
+							{
+								return result::Err(Failed {old_state: input, ..*failure});
+							}
+							option::None =>
+							{
+							}
+						}
 					}
-					result::Err(_) =>
+					result::Err(ref failure) =>
 					{
+						note_discarded(failure);
 						break;
 					}
 				}
@@ -1155,7 +2753,7 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: output, mesg: @~""})
+				result::Err(Failed {old_state: input, err_state: output, mesg: mesg})
 			}
 		}
 	}
@@ -1170,85 +2768,175 @@ pub impl<T: Copy Durable> Parser<T> : Combinators<T>
 		self.r(1u, uint::max_value)
 	}
 	
-	fn s0(&self) -> Parser<T>
+	fn recover<U: Copy Durable>(&self, skip: Parser<U>, value: T) -> Parser<T>
 	{
-		// It would be simpler to write this with scan0, but scan0 is relatively inefficient
-		// and s0 is typically called a lot.
 		|input: State|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			match (*self)(input)
 			{
-				let mut i = pass.new_state.index;
-				let mut line = pass.new_state.line;
-				loop
+				result::Err(ref failure) if input.ctx.lenient.enabled && input.ctx.fatal.is_none() =>
 				{
-					if input.text[i] == '\r' && input.text[i+1u] == '\n'
+					match skip(input)
 					{
-						line += 1;
-						i += 1u;
-					}
-					else if input.text[i] == '\n'
-					{
-						line += 1;
-					}
-					else if input.text[i] == '\r'
-					{
-						line += 1;
-					}
-					else if input.text[i] != ' ' && input.text[i] != '\t'
-					{
-						break;
+						result::Ok(ref pass) =>
+						{
+							vec::push(&mut input.ctx.lenient.recovered, *failure);
+							result::Ok(Succeeded {new_state: pass.new_state, value: value})
+						}
+						result::Err(_) =>
+						{
+							result::Err(*failure)
+						}
 					}
-					i += 1u;
 				}
-				
-				result::Ok(Succeeded {new_state: State {index: i, line: line, ..pass.new_state}, value: pass.value})
+				status =>
+				{
+					status
+				}
+			}
+		}
+	}
+	
+	fn reporting_progress(&self, interval: uint, hook: fn@ (uint)) -> Parser<T>
+	{
+		assert interval > 0u;
+		
+		|input: State|
+		{
+			let ctx = input.ctx;
+			let saved = ctx.progress;
+			ctx.progress = Progress {hook: option::Some(hook), interval: interval, next: input.index + interval};
+			let status = (*self)(input);
+			ctx.progress = saved;
+			status
+		}
+	}
+	
+	fn s0(&self) -> Parser<T>
+	{
+		|input: State|
+		{
+			do result::chain((*self)(input))
+			|pass|
+			{
+				result::Ok(Succeeded {new_state: skip_space(pass.new_state), value: pass.value})
 			}
 		}
 	}
 	
 	fn s1(&self) -> Parser<T>
 	{
+		let mesg = @~"whitespace";
+		
 		|input: State|
 		{
-			do result::chain(self.s0()(input))
+			do result::chain((*self)(input))
 			|pass|
 			{
-				if option::is_some(&str::find_char(" \t\r\n", input.text[pass.new_state.index - 1u]))	// little cheesy, but saves us from adding a helper fn
+				let state = skip_space(pass.new_state);
+				if state.index > pass.new_state.index || (state.index > 0u && option::is_some(&str::find_char(" \t\r\n", input.text[state.index - 1u])))
 				{
-					result::Ok(pass)
+					result::Ok(Succeeded {new_state: state, value: pass.value})
 				}
 				else
 				{
-					result::Err(Failed {old_state: input, err_state: pass.new_state, mesg: @~"whitespace"})
+					result::Err(Failed {old_state: input, err_state: state, mesg: mesg})
 				}
 			}
 		}
 	}
 	
-	fn then<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<U>
+	fn skipping(&self, space: Parser<()>) -> Parser<T>
+	{
+		|input: State|
+		{
+			let saved = input.ctx.skipper;
+			input.ctx.skipper = option::Some(space);
+			let status = (*self)(input);
+			input.ctx.skipper = saved;
+			status
+		}
+	}
+	
+	fn spanned(&self) -> Parser<Spanned<T>>
 	{
 		|input: State|
 		{
 			do result::chain((*self)(input))
 			|pass|
 			{
-				do result::chain_err(parser2(pass.new_state))
-					|failure| {result::Err(Failed {old_state: input, ..failure})}
+				result::Ok(Succeeded {new_state: pass.new_state, value: Spanned {value: pass.value, span: input.span_to(pass.new_state)}})
 			}
 		}
 	}
 	
+	fn then<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<U>
+	{
+		chain(*self, |_value| parser2)
+	}
+	
 	fn thene<U: Copy Durable>(&self, eval: fn@ (T) -> Parser<U>) -> Parser<U>
 	{
+		chain(*self, eval)
+	}
+	
+	fn trace(&self, name: &str) -> Parser<T>
+	{
+		let name = name.to_owned();
+		let parser = self.named(name);
+		
 		|input: State|
 		{
-			do result::chain((*self)(input))
+			let indent = repeat_char(' ', 2u*input.ctx.trace.depth);
+			if !input.ctx.trace.json
+			{
+				io::stderr().write_line(fmt!("%s-> %s @ %d:%u", indent, name, input.line, get_col(input.text, input.index)));
+			}
+			
+			let start_time = std::time::precise_time_s();
+			input.ctx.trace.depth += 1u;
+			let status = parser(input);
+			input.ctx.trace.depth -= 1u;
+			
+			if input.ctx.trace.json
+			{
+				io::stderr().write_line(trace_record(name, input, &status, std::time::precise_time_s() - start_time));
+				return status;
+			}
+			
+			match status
+			{
+				result::Ok(ref pass) =>
+				{
+					io::stderr().write_line(fmt!("%s<- %s passed %?", indent, name, pass.value));
+				}
+				result::Err(ref failure) =>
+				{
+					io::stderr().write_line(fmt!("%s<- %s failed '%s' @ %d:%u", indent, name, *failure.mesg, failure.err_state.line, get_col(failure.err_state.text, failure.err_state.index)));
+				}
+			}
+			status
+		}
+	}
+	
+	fn with_captures<U: Copy Durable>(&self, eval: fn@ (T, @~[Capture]) -> U) -> Parser<U>
+	{
+		|input: State|
+		{
+			let mut outer = ~[];
+			outer <-> input.ctx.captured;
+			let status = (*self)(input);
+			
+			let mut captured = ~[];
+			captured <-> input.ctx.captured;
+			input.ctx.captured <-> outer;
+			
+			do result::chain(status)
 			|pass|
 			{
-				do result::chain_err(eval(pass.value)(pass.new_state))
-					|failure| {result::Err(Failed {old_state: input, ..failure})}
+				let end = pass.new_state.index;
+				let captured = vec::filter(captured, |c| c.span.end <= end);
+				result::Ok(Succeeded {new_state: pass.new_state, value: eval(pass.value, @captured)})
 			}
 		}
 	}
@@ -1276,3 +2964,101 @@ impl &str : GenericParsers
 		}
 	}
 }
+
+// ---- parser interface --------------------------------------------------------------------------
+/// Something which can parse input, along with optional information about what it parses.
+/// 
+/// Parsers are closures so they cannot say anything about themselves. Types like Described
+/// and Syntax can and this allows functions like or_described to make use of that information.
+/// Parsers themselves implement this trait (returning None for everything) so that simple
+/// parsers can still be used.
+pub trait Parses<T: Copy Durable>
+{
+	/// Runs the parser.
+	fn apply(&self, input: State) -> Status<T>;
+	
+	/// Returns what is parsed, e.g. "expression". This is used in error messages.
+	fn description(&self) -> Option<@~str>;
+	
+	/// Returns the characters which can start a match or None if any character can
+	/// (or if they are not known).
+	fn first_set(&self) -> Option<@~str>;
+	
+	/// Returns the name of the production, e.g. for traces and profiles.
+	fn name(&self) -> Option<@~str>;
+	
+	/// Returns a parser which can be used with the combinators.
+	fn to_parser(&self) -> Parser<T>;
+}
+
+/// A parser along with information about what it parses.
+pub struct Described<T: Copy Durable> {parser: Parser<T>, name: Option<@~str>, description: Option<@~str>, first_set: Option<@~str>}
+
+pub impl<T: Copy Durable> Parser<T> : Parses<T>
+{
+	fn apply(&self, input: State) -> Status<T>
+	{
+		(*self)(input)
+	}
+	
+	fn description(&self) -> Option<@~str>
+	{
+		option::None
+	}
+	
+	fn first_set(&self) -> Option<@~str>
+	{
+		option::None
+	}
+	
+	fn name(&self) -> Option<@~str>
+	{
+		option::None
+	}
+	
+	fn to_parser(&self) -> Parser<T>
+	{
+		*self
+	}
+}
+
+pub impl<T: Copy Durable> Described<T> : Parses<T>
+{
+	fn apply(&self, input: State) -> Status<T>
+	{
+		(self.parser)(input)
+	}
+	
+	fn description(&self) -> Option<@~str>
+	{
+		self.description
+	}
+	
+	fn first_set(&self) -> Option<@~str>
+	{
+		self.first_set
+	}
+	
+	fn name(&self) -> Option<@~str>
+	{
+		self.name
+	}
+	
+	/// The parser uses err with the description and named with the name. Err only
+	/// relabels errors so the parser is named once (and not at all if name is None).
+	fn to_parser(&self) -> Parser<T>
+	{
+		let mut parser = self.parser;
+		match self.description
+		{
+			option::Some(description) => parser = parser.err(*description),
+			option::None => (),
+		}
+		match self.name
+		{
+			option::Some(name) => parser = parser.named(*name),
+			option::None => (),
+		}
+		parser
+	}
+}
@@ -0,0 +1,124 @@
+//! Shared instances of commonly used parsers.
+//!
+//! Grammars are normally built by functions like expr_parser which create new closures
+//! for every digit, space, and punctuation parser each time they are called. When a
+//! grammar is built per request this adds up so primitives returns parsers which are
+//! built once and then shared. Note that parsers are fn@ closures so they cannot be
+//! sent to other tasks: each task gets its own Primitives.
+//!
+//! Whole grammars can be shared the same way using the grammar method, e.g.
+//! `primitives().grammar(expr_key, expr_parser)` calls expr_parser the first time the
+//! task uses it and returns the same parser after that. Like the other primitives the
+//! cached grammars are per task, not per process.
+use std::map::HashMap;
+
+use c99_parsers::*;
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Pre-built parsers. Primitives which normally take a parameter, like lit, are methods
+/// which cache the parsers they return.
+pub struct Primitives {digits: Parser<@~str>, letters: Parser<@~str>, identifier: Parser<@~str>, decimal_number: Parser<int>, space: Parser<()>, literals: HashMap<~str, Parser<@~str>>, lexemes: HashMap<~str, Parser<@~str>>}
+
+/// Returns the Primitives for the current task (they are created the first time this is called).
+pub fn primitives() -> @Primitives
+{
+	unsafe
+	{
+		match task::local_data::local_data_get(primitives_key)
+		{
+			option::Some(p) =>
+			{
+				p
+			}
+			option::None =>
+			{
+				let p = @Primitives {
+					digits: match1(is_digit),
+					letters: match1(is_alpha),
+					identifier: identifier(),
+					decimal_number: decimal_number(),
+					space: ret(()).s0(),
+					literals: HashMap(),
+					lexemes: HashMap()};
+				task::local_data::local_data_set(primitives_key, p);
+				p
+			}
+		}
+	}
+}
+
+pub impl Primitives
+{
+	/// Returns the parser returned by make. Make is only called the first time the task
+	/// asks for key so functions like expr_parser can be used at many call sites without
+	/// rebuilding the grammar each time. Key is a task local data key for the grammar,
+	/// e.g. `fn expr_key(_p: @Parser<Expr>) {}`, and should be used with only one make
+	/// function. Note that make must not call grammar with its own key (recursive grammars
+	/// should use forward_ref instead).
+	/// 
+	/// The parser is cached for the current task only: other tasks call make again.
+	fn grammar<T: Copy Durable>(&self, key: task::local_data::LocalDataKey<Parser<T>>, make: fn () -> Parser<T>) -> Parser<T>
+	{
+		unsafe
+		{
+			match task::local_data::local_data_get(key)
+			{
+				option::Some(parser) =>
+				{
+					*parser
+				}
+				option::None =>
+				{
+					let parser = make();
+					task::local_data::local_data_set(key, @parser);
+					parser
+				}
+			}
+		}
+	}
+	
+	/// Like the lit method except that the parser is shared.
+	fn lit(&self, s: &str) -> Parser<@~str>
+	{
+		let s = s.to_owned();
+		match self.literals.find(copy s)
+		{
+			option::Some(parser) =>
+			{
+				parser
+			}
+			option::None =>
+			{
+				let parser = s.lit();
+				self.literals.insert(s, parser);
+				parser
+			}
+		}
+	}
+	
+	/// Like the s0 method for strings except that the parser is shared.
+	fn s0(&self, s: &str) -> Parser<@~str>
+	{
+		let s = s.to_owned();
+		match self.lexemes.find(copy s)
+		{
+			option::Some(parser) =>
+			{
+				parser
+			}
+			option::None =>
+			{
+				let parser = self.lit(s).s0();
+				self.lexemes.insert(s, parser);
+				parser
+			}
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn primitives_key(_p: @Primitives)
+{
+}
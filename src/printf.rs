@@ -0,0 +1,136 @@
+//! Parses printf style format strings, e.g. "%-10s %5.2f%%\n", for tools which check
+//! format strings against their arguments.
+//!
+//! The syntax is C's (including POSIX argument positions like "%2$s") but the
+//! conversions are not checked against a particular printf so, for example, "%qd"
+//! and "%lc" are accepted.
+use c99_parsers::*;
+use parsers::*;
+use types::*;
+
+/// How a width or precision was written.
+pub enum Count
+{
+	/// The count was omitted.
+	NoCount,
+	
+	/// A number, e.g. the 10 in "%10d".
+	CountOf(uint),
+	
+	/// "*" (the count is the next argument) or "*2$" (the count is the second argument).
+	CountArg(Option<uint>),
+}
+
+/// A conversion like "%-8.3lf". Position is the argument position (1-based) for things
+/// like "%2$d". Flags are the characters from "-+ #0'" in the order they were written.
+/// Length is the length modifier (e.g. "ll" or "z") and may be empty. Span covers the
+/// text from the '%' to the conversion character.
+pub struct PrintfSpec {position: Option<uint>, flags: @~str, width: Count, precision: Count, length: @~str, conversion: char, span: Span}
+
+/// The pieces of a format string.
+pub enum PrintfSegment
+{
+	/// Text between the conversions (with "%%" replaced by "%").
+	PrintfText(@~str),
+	
+	PrintfConversion(PrintfSpec),
+}
+
+/// Parses all of text as a format string. Errors are formatted with format_failure.
+pub fn parse_printf(text: &str) -> result::Result<@~[PrintfSegment], ~str>
+{
+	to_result(printf_format().everything(ret(())).parse(@~"format", text), text)
+}
+
+/// format := (text | '%%' | spec)*
+/// 
+/// spec := '%' position? flags width? ('.' precision?)? length? conversion
+/// position := [0-9]+ '$'
+/// flags := [-+ #0']*
+/// width := [0-9]+ | '*' position?
+/// precision := [0-9]+ | '*' position?
+/// length := 'hh' | 'h' | 'll' | 'l' | 'L' | 'j' | 'z' | 't' | 'q'
+/// conversion := [diouxXeEfFgGaAcspn]
+/// 
+/// A '.' without a precision means a precision of zero (as in C). Adjacent text is
+/// returned as a single PrintfText segment. Errors within a conversion are reported at
+/// the character which is wrong (e.g. the 'y' in "%5y").
+pub fn printf_format() -> Parser<@~[PrintfSegment]>
+{
+	let spec = spec();
+	
+	|input: State|
+	{
+		let mut output = input;
+		let mut segments = ~[];
+		let mut text = ~"";
+		while !output.at_end()
+		{
+			let ch = output.peek();
+			if ch == '%' && output.text[output.index + 1u] == '%'
+			{
+				str::push_char(&mut text, '%');
+				output = output.advance(2u);
+			}
+			else if ch == '%'
+			{
+				match spec(output.advance(1u))
+				{
+					result::Ok(ref pass) =>
+					{
+						let (position, flags, width, precision, length, conversion) = pass.value;
+						let span = output.span_to(pass.new_state);
+						flush_text(&mut segments, &mut text);
+						vec::push(&mut segments, PrintfConversion(PrintfSpec {position: position, flags: flags, width: width, precision: precision, length: length, conversion: conversion, span: span}));
+						output = pass.new_state;
+					}
+					result::Err(ref failure) =>
+					{
+						return result::Err(Failed {old_state: input, ..*failure});
+					}
+				}
+			}
+			else
+			{
+				str::push_char(&mut text, ch);
+				output = if ch == '\n' {output.advance_lines(1u, 1)} else {output.advance(1u)};
+			}
+		}
+		flush_text(&mut segments, &mut text);
+		result::Ok(Succeeded {new_state: output, value: @segments})
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn flush_text(segments: &mut ~[PrintfSegment], text: &mut ~str)
+{
+	if str::is_not_empty(*text)
+	{
+		let mut s = ~"";
+		s <-> *text;
+		vec::push(segments, PrintfText(@s));
+	}
+}
+
+// The part of a conversion after the '%'.
+fn spec() -> Parser<(Option<uint>, @~str, Count, Count, @~str, char)>
+{
+	let number = decimal_number().map(|n| n as uint);
+	let position = seq2_ret0(number, "$".lit());
+	let count = or_v(@~[
+		number.map(|n| CountOf(n)),
+		seq2_ret1("*".lit(), position.optional()).map(|p| CountArg(p))]);
+	let precision = seq2_ret1(".".lit(), count.optional()).map(|c| option::get_default(c, CountOf(0u)));
+	
+	let flags = "-+ #0'".anyc().r0().map(|chars| @str::from_chars(*chars));
+	let length = or_v(@~["hh".lit(), "h".lit(), "ll".lit(), "l".lit(), "L".lit(), "j".lit(), "z".lit(), "t".lit(), "q".lit()]);
+	let conversion = "diouxXeEfFgGaAcspn".anyc().err("conversion specifier");
+	
+	do seq6(position.optional(), flags, count.optional(), precision.optional(), length.optional(), conversion)
+	|position, flags, width, precision, length, conversion|
+	{
+		let width = option::get_default(width, NoCount);
+		let precision = option::get_default(precision, NoCount);
+		result::Ok((position, flags, width, precision, option::get_default(length, @~""), conversion))
+	}
+}
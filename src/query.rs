@@ -0,0 +1,173 @@
+//! A parser for URL query strings, e.g. "a=1&b=two+words&flag".
+//!
+//! Keys and values are percent-decoded (and '+' is decoded as a space). The decoded
+//! bytes must be UTF-8. Pairs without an '=' (flags) have no value.
+use std::map::HashMap;
+
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// A key and its value (None for flags).
+pub struct QueryPair {key: @~str, value: Option<@~str>}
+
+/// What to do if a key appears more than once.
+pub enum Duplicates
+{
+	/// Keep every pair.
+	KeepAll,
+	
+	/// Keep the first pair with the key.
+	KeepFirst,
+	
+	/// Keep the last pair with the key (in the position of the first pair).
+	KeepLast,
+	
+	/// Fail with an error at the second pair.
+	Reject,
+}
+
+/// Parses text which consists of a query string. Errors are formatted with format_failure.
+pub fn parse_query(text: &str, duplicates: Duplicates) -> result::Result<@~[QueryPair], ~str>
+{
+	to_result(query_string(duplicates).everything(ret(())).parse(@~"query", text), text)
+}
+
+/// Returns a map from each key to its value. Flags map to the empty string and if there
+/// are duplicate keys the last pair wins.
+pub fn query_map(pairs: &[QueryPair]) -> HashMap<~str, @~str>
+{
+	let map = HashMap();
+	for vec::each(pairs) |pair|
+	{
+		map.insert(copy *pair.key, pair.value.get_default(@~""));
+	}
+	map
+}
+
+/// query := (pair ('&' pair)*)?
+/// 
+/// pair := key ('=' value)?
+/// key := ([^&=#%+] | escape | '+')*
+/// value := ([^&#%+] | escape | '+')*
+/// escape := '%' hex hex
+/// 
+/// Empty pairs (e.g. from "a=1&&b=2") are skipped. Matching stops at a '#' (the start
+/// of a URL fragment).
+pub fn query_string(duplicates: Duplicates) -> Parser<@~[QueryPair]>
+{
+	let key = component(|ch| ch != '&' && ch != '=' && ch != '#');
+	let value = component(|ch| ch != '&' && ch != '#');
+	let pair = do seq2(key, seq2_ret1("=".lit(), value).optional()) |k, v| {result::Ok(QueryPair {key: k, value: v})};
+	
+	|input: State|
+	{
+		let mut output = input;
+		let mut pairs: ~[QueryPair] = ~[];
+		loop
+		{
+			let start = output;
+			match pair(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					output = pass.new_state;
+					if pass.consumed(start)
+					{
+						match vec::position(pairs, |p| p.key == pass.value.key)
+						{
+							option::Some(i) =>
+							{
+								match duplicates
+								{
+									KeepAll => vec::push(&mut pairs, pass.value),
+									KeepFirst => (),
+									KeepLast => pairs[i] = pass.value,
+									Reject =>
+									{
+										let mesg = @fmt!("key '%s' appears more than once", *pass.value.key);
+										return result::Err(Failed {old_state: input, err_state: start, mesg: mesg});
+									}
+								}
+							}
+							option::None =>
+							{
+								vec::push(&mut pairs, pass.value);
+							}
+						}
+					}
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+			
+			if output.peek() != '&'
+			{
+				break;
+			}
+			output = output.advance(1u);
+		}
+		result::Ok(Succeeded {new_state: output, value: @pairs})
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Matches characters satisfying predicate and returns them percent-decoded. Bad
+// escapes are reported at the '%' and invalid UTF-8 at the start of the component.
+fn component(predicate: fn@ (char) -> bool) -> Parser<@~str>
+{
+	let escape_mesg = @~"'%' followed by two hex digits";
+	let utf8_mesg = @~"percent escapes which are UTF-8";
+	
+	|input: State|
+	{
+		let mut bytes = ~[];
+		let mut i = input.index;
+		while input.text[i] != EOT && predicate(input.text[i])
+		{
+			let ch = input.text[i];
+			if ch == '%'
+			{
+				let hi = hex_value(input.text[i + 1u]);
+				let lo = if hi.is_some() {hex_value(input.text[i + 2u])} else {option::None};	// don't index past the EOT
+				match (hi, lo)
+				{
+					(option::Some(hi), option::Some(lo)) =>
+					{
+						vec::push(&mut bytes, (16u*hi + lo) as u8);
+						i += 3u;
+					}
+					_ =>
+					{
+						return result::Err(Failed {old_state: input, err_state: input.advance(i - input.index), mesg: escape_mesg});
+					}
+				}
+			}
+			else
+			{
+				let s = if ch == '+' {~" "} else {str::from_char(ch)};
+				vec::push_all(&mut bytes, str::to_bytes(s));
+				i += 1u;
+			}
+		}
+		
+		if str::is_utf8(bytes)
+		{
+			result::Ok(Succeeded {new_state: input.advance(i - input.index), value: @str::from_bytes(bytes)})
+		}
+		else
+		{
+			result::Err(Failed {old_state: input, err_state: input, mesg: utf8_mesg})
+		}
+	}
+}
+
+fn hex_value(ch: char) -> Option<uint>
+{
+	if is_digit(ch) {option::Some((ch as uint) - ('0' as uint))}
+	else if ch >= 'a' && ch <= 'f' {option::Some((ch as uint) - ('a' as uint) + 10u)}
+	else if ch >= 'A' && ch <= 'F' {option::Some((ch as uint) - ('A' as uint) + 10u)}
+	else {option::None}
+}
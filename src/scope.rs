@@ -0,0 +1,102 @@
+//! Symbol tables for grammars which need to resolve names while parsing.
+//!
+//! A Scope is a stack of maps from names to values (e.g. a declaration's type). The
+//! scope is captured by the parsers which use it:
+//!
+//! ```
+//! let scope = new_scope();
+//! let decl = define(scope, |name: @~str| name, seq2_ret1("let".s1(), identifier()));
+//! let block = scoped(scope, seq3_ret1("{".s0(), statement.r0(), "}".s0()));
+//! ```
+//!
+//! Note that definitions are not undone when the parser which made them is backtracked
+//! over (unless they were made within a scoped parser which failed) so define should
+//! be used in places where the grammar has committed to the declaration.
+use std::map::HashMap;
+
+use parsers::*;
+use types::*;
+
+/// Frames are the maps for each nested scope with the innermost scope last.
+pub struct Scope<T: Copy Durable> {frames: @mut ~[HashMap<~str, T>]}
+
+/// Returns a scope with one (global) frame.
+pub fn new_scope<T: Copy Durable>() -> Scope<T>
+{
+	Scope {frames: @mut ~[HashMap()]}
+}
+
+pub impl<T: Copy Durable> Scope<T>
+{
+	/// Pushes a new frame onto the scope.
+	fn enter(&self)
+	{
+		vec::push(&mut *self.frames, HashMap());
+	}
+	
+	/// Pops the innermost frame (along with the names which were defined within it).
+	fn exit(&self)
+	{
+		assert vec::len(*self.frames) > 1u;
+		vec::pop(&mut *self.frames);
+	}
+	
+	/// Adds name to the innermost frame. Returns false if name was already defined
+	/// within that frame (in which case the value is replaced).
+	fn define(&self, name: &str, value: T) -> bool
+	{
+		let frame = self.frames[vec::len(*self.frames) - 1u];
+		frame.insert(name.to_owned(), value)
+	}
+	
+	/// Returns the value of name from the innermost frame which defines it.
+	fn lookup(&self, name: &str) -> Option<T>
+	{
+		let mut i = vec::len(*self.frames);
+		while i > 0u
+		{
+			i -= 1u;
+			match self.frames[i].find(name.to_owned())
+			{
+				option::Some(value) => return option::Some(value),
+				option::None => (),
+			}
+		}
+		option::None
+	}
+	
+	/// Returns the number of frames (one if only the global frame is present).
+	fn depth(&self) -> uint
+	{
+		vec::len(*self.frames)
+	}
+}
+
+/// Runs parser within a new frame of scope. The frame is popped when parser returns
+/// (whether it succeeded or not).
+pub fn scoped<T: Copy Durable, U: Copy Durable>(scope: Scope<U>, parser: Parser<T>) -> Parser<T>
+{
+	|input: State|
+	{
+		scope.enter();
+		let status = parser(input);
+		scope.exit();
+		status
+	}
+}
+
+/// If parser succeeds then its value is defined in the innermost frame of scope using
+/// the name returned by name. Note that redefinitions within a frame are allowed (use
+/// the Scope methods to reject them).
+pub fn define<T: Copy Durable>(scope: Scope<T>, name: fn@ (T) -> @~str, parser: Parser<T>) -> Parser<T>
+{
+	|input: State|
+	{
+		do result::chain(parser(input))
+		|pass|
+		{
+			scope.define(*name(pass.value), pass.value);
+			result::Ok(pass)
+		}
+	}
+}
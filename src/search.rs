@@ -0,0 +1,99 @@
+//! Functions which use parsers to search text (like regular expressions do) instead of
+//! parsing all of it. This is useful for extraction tools, e.g. finding the links
+//! within a document.
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Tries parser at each position within text and returns the value and span of each
+/// match. After a match the search continues at the end of the match so matches don't
+/// overlap. Matches which consume nothing are ignored. Spans use "text" as the file.
+pub fn find_all<T: Copy Durable>(parser: Parser<T>, text: &str) -> @~[Spanned<T>]
+{
+	let mut matches = ~[];
+	let mut state = initial_state(text);
+	while !state.at_end()
+	{
+		match parser(state)
+		{
+			result::Ok(ref pass) if pass.new_state.index > state.index =>
+			{
+				vec::push(&mut matches, Spanned {value: pass.value, span: state.span_to(pass.new_state)});
+				state = pass.new_state;
+			}
+			_ =>
+			{
+				state = next_char(state);
+			}
+		}
+	}
+	@matches
+}
+
+/// Returns text with each match of parser (see find_all) replaced by the result of
+/// calling render with the match's value. This is handy for things like expanding
+/// templates and rewriting source code.
+pub fn replace_all<T: Copy Durable>(parser: Parser<T>, text: &str, render: fn@ (T) -> ~str) -> ~str
+{
+	let chars = chars_with_eot(text);
+	let mut result = ~"";
+	let mut last = 0u;
+	for vec::each(*find_all(parser, text)) |m|
+	{
+		str::push_str(&mut result, str::from_chars(vec::slice(chars, last, m.span.start)));
+		str::push_str(&mut result, render(m.value));
+		last = m.span.end;
+	}
+	str::push_str(&mut result, str::from_chars(vec::slice(chars, last, vec::len(chars) - 1u)));
+	result
+}
+
+/// Returns the text between the matches of delim (so there is always one more piece
+/// than there are delimiters). As with find_all delimiters which consume nothing are
+/// ignored. Also see split_skipping.
+pub fn split<U: Copy Durable>(delim: Parser<U>, text: &str) -> @~[@~str]
+{
+	split_skipping(delim, fails::<()>(""), text)
+}
+
+/// Like split except that delimiters within text matched by skip are ignored, e.g.
+/// skip might match quoted strings so that `a, "b, c"` is split into two pieces.
+pub fn split_skipping<U: Copy Durable, V: Copy Durable>(delim: Parser<U>, skip: Parser<V>, text: &str) -> @~[@~str]
+{
+	let mut pieces = ~[];
+	let mut state = initial_state(text);
+	let mut start = 0u;
+	while !state.at_end()
+	{
+		match delim(state)
+		{
+			result::Ok(ref pass) if pass.new_state.index > state.index =>
+			{
+				vec::push(&mut pieces, @str::from_chars(vec::slice(state.text, start, state.index)));
+				state = pass.new_state;
+				start = state.index;
+			}
+			_ =>
+			{
+				state = match skip(state)
+				{
+					result::Ok(ref pass) if pass.new_state.index > state.index => pass.new_state,
+					_ => next_char(state),
+				};
+			}
+		}
+	}
+	vec::push(&mut pieces, @str::from_chars(vec::slice(state.text, start, state.index)));
+	@pieces
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn initial_state(text: &str) -> State
+{
+	State {file: @~"text", text: chars_with_eot(text), index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]}
+}
+
+fn next_char(state: State) -> State
+{
+	if state.peek() == '\n' {state.advance_lines(1u, 1)} else {state.advance(1u)}
+}
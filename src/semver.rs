@@ -0,0 +1,270 @@
+//! Parsers for semantic versions (see semver.org) and version constraints.
+//!
+//! Versions look like "1.2.3", "1.0.0-alpha.1", or "1.0.0+build.5". Constraints are
+//! comparators separated by whitespace or commas (all of which must match) and
+//! alternatives separated by "||", e.g. "^1.2", ">=0.3 <0.5", or "~1.4 || 2.0.0".
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// A pre-release identifier. Numeric identifiers sort before alphanumeric ones.
+pub enum Identifier
+{
+	Numeric(uint),
+	Alphanumeric(@~str),
+}
+
+/// Pre is the pre-release identifiers (which sort before the release) and build is
+/// the build metadata (which is ignored when comparing versions).
+pub struct Version {major: uint, minor: uint, patch: uint, pre: @~[Identifier], build: @~[@~str]}
+
+/// The operators which may be used in a Comparator.
+pub enum Op
+{
+	/// "=" or no operator. If parts is less than three then the omitted parts may be anything.
+	Exact,
+	Greater,
+	GreaterEq,
+	Less,
+	LessEq,
+	
+	/// "^": changes which do not modify the left-most non-zero part.
+	Caret,
+	
+	/// "~": patch level changes (or minor level changes if only the major is given).
+	Tilde,
+}
+
+/// Parts is the number of parts in the version which were written (e.g. 2 for "^1.2").
+pub struct Comparator {op: Op, version: Version, parts: uint}
+
+/// A version matches if it matches all of the comparators in any of the alternatives.
+pub struct Constraint {alternatives: @~[@~[Comparator]]}
+
+pub impl Version
+{
+	/// Returns a negative number if self has a lower precedence than other, zero if they
+	/// have the same precedence, and a positive number otherwise.
+	fn compare(&self, other: &Version) -> int
+	{
+		if self.major != other.major {return if self.major < other.major {-1} else {1};}
+		if self.minor != other.minor {return if self.minor < other.minor {-1} else {1};}
+		if self.patch != other.patch {return if self.patch < other.patch {-1} else {1};}
+		
+		// A pre-release version has a lower precedence than the release.
+		match (vec::is_empty(*self.pre), vec::is_empty(*other.pre))
+		{
+			(true, true) => return 0,
+			(true, false) => return 1,
+			(false, true) => return -1,
+			(false, false) => (),
+		}
+		
+		let count = uint::min(vec::len(*self.pre), vec::len(*other.pre));
+		for uint::range(0u, count) |i|
+		{
+			let result = match (self.pre[i], other.pre[i])
+			{
+				(Numeric(x), Numeric(y)) => if x < y {-1} else if x > y {1} else {0},
+				(Numeric(_), Alphanumeric(_)) => -1,
+				(Alphanumeric(_), Numeric(_)) => 1,
+				(Alphanumeric(x), Alphanumeric(y)) => if *x < *y {-1} else if *x > *y {1} else {0},
+			};
+			if result != 0
+			{
+				return result;
+			}
+		}
+		(vec::len(*self.pre) as int) - (vec::len(*other.pre) as int)
+	}
+	
+	/// Returns the version formatted as semver text.
+	fn to_str(&self) -> ~str
+	{
+		let mut s = fmt!("%u.%u.%u", self.major, self.minor, self.patch);
+		if !vec::is_empty(*self.pre)
+		{
+			let pre = do vec::map(*self.pre) |p| {match *p {Numeric(n) => uint::str(n), Alphanumeric(a) => copy *a}};
+			s += ~"-" + str::connect(pre, ".");
+		}
+		if !vec::is_empty(*self.build)
+		{
+			s += ~"+" + str::connect(vec::map(*self.build, |b| copy **b), ".");
+		}
+		s
+	}
+}
+
+pub impl Comparator
+{
+	/// Returns true if version satisfies the comparator.
+	fn matches(&self, version: &Version) -> bool
+	{
+		let cmp = version.compare(&self.version);
+		match self.op
+		{
+			Exact if self.parts == 3u => cmp == 0,
+			Exact => cmp >= 0 && version.compare(&bump(&self.version, self.parts)) < 0,
+			Greater if self.parts == 3u => cmp > 0,
+			Greater => version.compare(&bump(&self.version, self.parts)) >= 0,
+			GreaterEq => cmp >= 0,
+			Less => cmp < 0,
+			LessEq if self.parts == 3u => cmp <= 0,
+			LessEq => version.compare(&bump(&self.version, self.parts)) < 0,
+			Caret => cmp >= 0 && version.compare(&bump(&self.version, caret_part(self))) < 0,
+			Tilde => cmp >= 0 && version.compare(&bump(&self.version, if self.parts == 1u {1u} else {2u})) < 0,
+		}
+	}
+}
+
+pub impl Constraint
+{
+	/// Returns true if version satisfies the constraint.
+	fn matches(&self, version: &Version) -> bool
+	{
+		do vec::any(*self.alternatives) |comparators| {vec::all(**comparators, |c| c.matches(version))}
+	}
+}
+
+/// Parses text which consists of a single version. Errors are formatted with format_failure.
+pub fn parse_version(text: &str) -> result::Result<Version, ~str>
+{
+	to_result(semver().everything_ws().parse(@~"version", text), text)
+}
+
+/// Parses text which consists of a single constraint. Errors are formatted with format_failure.
+pub fn parse_constraint(text: &str) -> result::Result<Constraint, ~str>
+{
+	to_result(version_constraint().everything_ws().parse(@~"constraint", text), text)
+}
+
+/// semver := number '.' number '.' number ('-' pre ('.' pre)*)? ('+' build ('.' build)*)?
+/// 
+/// number := '0' | [1-9] [0-9]*
+/// pre := number | [0-9a-zA-Z-]* [a-zA-Z-] [0-9a-zA-Z-]*
+/// build := [0-9a-zA-Z-]+
+pub fn semver() -> Parser<Version>
+{
+	do seq6(number().err("major version"), ".".lit(), number().err("minor version"), ".".lit(), number().err("patch version"), suffix())
+	|major, _d1, minor, _d2, patch, suffix|
+	{
+		let (pre, build) = suffix;
+		result::Ok(Version {major: major, minor: minor, patch: patch, pre: pre, build: build})
+	}
+}
+
+/// constraint := comparators ('||' comparators)*
+/// 
+/// comparators := comparator (','? comparator)*
+/// comparator := ('>=' | '<=' | '>' | '<' | '=' | '^' | '~')? partial
+/// partial := number ('.' number ('.' number suffix)?)?
+pub fn version_constraint() -> Parser<Constraint>
+{
+	let op = or_v(@~[
+		">=".s0().map(|_v| GreaterEq),
+		"<=".s0().map(|_v| LessEq),
+		">".s0().map(|_v| Greater),
+		"<".s0().map(|_v| Less),
+		"=".s0().map(|_v| Exact),
+		"^".s0().map(|_v| Caret),
+		"~".s0().map(|_v| Tilde)]).optional();
+	
+	let patch = seq3(".".lit(), number().err("patch version"), suffix(), |_d, patch, suffix| result::Ok((patch, suffix)));
+	let minor = seq3(".".lit(), number().err("minor version"), patch.optional(), |_d, minor, rest| result::Ok((minor, rest)));
+	let partial = do seq2(number().err("major version"), minor.optional())
+	|major, rest|
+	{
+		let (minor, patch, parts, pre, build) = match rest
+		{
+			option::None => (0u, 0u, 1u, @~[], @~[]),
+			option::Some((minor, option::None)) => (minor, 0u, 2u, @~[], @~[]),
+			option::Some((minor, option::Some((patch, (pre, build))))) => (minor, patch, 3u, pre, build),
+		};
+		result::Ok((Version {major: major, minor: minor, patch: patch, pre: pre, build: build}, parts))
+	};
+	
+	let comparator = do seq2(op, partial.s0())
+	|op, partial|
+	{
+		let (version, parts) = partial;
+		result::Ok(Comparator {op: option::get_default(op, Exact), version: version, parts: parts})
+	};
+	let comparators = seq2_ret0(comparator, ",".s0().optional()).r1();
+	
+	comparators.list("||".s0()).map(|alternatives| Constraint {alternatives: alternatives})
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// number := '0' | [1-9] [0-9]*
+fn number() -> Parser<uint>
+{
+	let digits = "0".lit().or(match1_0(|ch| ch >= '1' && ch <= '9', is_digit));
+	do digits.thene()
+	|s|
+	{
+		match uint::from_str(*s)
+		{
+			option::Some(n) => ret(n),
+			option::None => fails("number which fits in a uint"),
+		}
+	}
+}
+
+// The pre-release and build parts.
+fn suffix() -> Parser<(@~[Identifier], @~[@~str])>
+{
+	let ident = match1(|ch| is_alphanum(ch) || ch == '-');
+	let pre_ident = do ident.thene()
+	|s|
+	{
+		if str::all(*s, is_digit)
+		{
+			if str::len(*s) > 1u && str::char_at(*s, 0u) == '0'
+			{
+				fails("numeric identifier without leading zeros")
+			}
+			else
+			{
+				match uint::from_str(*s)
+				{
+					option::Some(n) => ret(Numeric(n)),
+					option::None => fails("number which fits in a uint"),
+				}
+			}
+		}
+		else
+		{
+			ret(Alphanumeric(s))
+		}
+	};
+	
+	let pre = seq2_ret1("-".lit(), pre_ident.list(".".lit())).err("pre-release identifier");
+	let build = seq2_ret1("+".lit(), ident.list(".".lit())).err("build identifier");
+	do seq2(pre.optional(), build.optional())
+	|pre, build|
+	{
+		result::Ok((option::get_default(pre, @~[]), option::get_default(build, @~[])))
+	}
+}
+
+// Returns the version with its nth part (1-based) incremented and the parts after it
+// (and the pre-release) zeroed.
+fn bump(version: &Version, n: uint) -> Version
+{
+	match n
+	{
+		1u => Version {major: version.major + 1u, minor: 0u, patch: 0u, pre: @~[], build: @~[]},
+		2u => Version {major: version.major, minor: version.minor + 1u, patch: 0u, pre: @~[], build: @~[]},
+		_ => Version {major: version.major, minor: version.minor, patch: version.patch + 1u, pre: @~[], build: @~[]},
+	}
+}
+
+// Caret allows changes to the parts after the left-most non-zero part (or after the
+// last part which was written if they are all zero).
+fn caret_part(comparator: &Comparator) -> uint
+{
+	let v = comparator.version;
+	if v.major > 0u || comparator.parts == 1u {1u}
+	else if v.minor > 0u || comparator.parts == 2u {2u}
+	else {3u}
+}
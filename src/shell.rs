@@ -0,0 +1,136 @@
+//! A parser which splits a command line into arguments using the POSIX shell quoting
+//! rules, e.g. `cc -o "my prog" 'a b.c'` has the arguments "cc", "-o", "my prog", and
+//! "a b.c".
+//!
+//! Variables, globs, and command substitutions are not expanded (so "$HOME" is just
+//! text). This is handy for tools which accept commands in configuration files.
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Parses text which consists of zero or more arguments. Errors are formatted with
+/// format_failure.
+pub fn parse_shell_words(text: &str) -> result::Result<@~[Spanned<@~str>], ~str>
+{
+	to_result(shell_words().everything(ret(())).parse(@~"command", text), text)
+}
+
+/// words := space* (word space*)*
+/// 
+/// word := (unquoted | '\\' char | "'" [^']* "'" | '"' (double | '\\' char)* '"')+
+/// unquoted := [^ \t\r\n'"\\]
+/// double := [^"\\]
+/// 
+/// Words are separated by unquoted whitespace. Each argument has the span of the word
+/// it was parsed from (including any quotes). A backslash followed by a new line is
+/// removed. Within double quotes a backslash only escapes '$', '`', '"', '\\', and new
+/// lines (otherwise it is retained).
+pub fn shell_words() -> Parser<@~[Spanned<@~str>]>
+{
+	|input: State|
+	{
+		let mut output = input;
+		let mut words = ~[];
+		loop
+		{
+			while is_whitespace(output.peek())
+			{
+				output = skip_char(output);
+			}
+			if output.at_end()
+			{
+				break;
+			}
+			
+			match word(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					vec::push(&mut words, Spanned {value: pass.value, span: output.span_to(pass.new_state)});
+					output = pass.new_state;
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+		}
+		result::Ok(Succeeded {new_state: output, value: @words})
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn skip_char(input: State) -> State
+{
+	if input.peek() == '\n' {input.advance_lines(1u, 1)} else {input.advance(1u)}
+}
+
+// Matches a single word and returns it with the quotes and escapes removed. Missing
+// closing quotes are reported at the end of the text.
+fn word(input: State) -> Status<@~str>
+{
+	let mut output = input;
+	let mut text = ~"";
+	while !output.at_end() && !is_whitespace(output.peek())
+	{
+		let ch = output.peek();
+		if ch == '\\'
+		{
+			output = output.advance(1u);
+			if output.at_end()
+			{
+				return result::Err(Failed {old_state: input, err_state: output, mesg: @~"character after '\\'"});
+			}
+			if output.peek() != '\n'
+			{
+				str::push_char(&mut text, output.peek());
+			}
+			output = skip_char(output);
+		}
+		else if ch == '\''
+		{
+			output = output.advance(1u);
+			while output.peek() != '\''
+			{
+				if output.at_end()
+				{
+					return result::Err(Failed {old_state: input, err_state: output, mesg: @~"'''"});
+				}
+				str::push_char(&mut text, output.peek());
+				output = skip_char(output);
+			}
+			output = output.advance(1u);
+		}
+		else if ch == '"'
+		{
+			output = output.advance(1u);
+			while output.peek() != '"'
+			{
+				if output.at_end()
+				{
+					return result::Err(Failed {old_state: input, err_state: output, mesg: @~"'\"'"});
+				}
+				if output.peek() == '\\' && option::is_some(&str::find_char("$`\"\\\n", output.text[output.index + 1u]))
+				{
+					output = output.advance(1u);
+					if output.peek() != '\n'
+					{
+						str::push_char(&mut text, output.peek());
+					}
+				}
+				else
+				{
+					str::push_char(&mut text, output.peek());
+				}
+				output = skip_char(output);
+			}
+			output = output.advance(1u);
+		}
+		else
+		{
+			str::push_char(&mut text, ch);
+			output = output.advance(1u);
+		}
+	}
+	result::Ok(Succeeded {new_state: output, value: @text})
+}
@@ -0,0 +1,281 @@
+//! Support for parsing text which arrives in chunks (e.g. from a socket or a
+//! log file which is still being written) and for lexing large inputs a token at a
+//! time.
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Returned by the Stream methods.
+pub enum StreamStatus<T>
+{
+	/// A value was parsed and the stream advanced past the text it consumed (the
+	/// consumed text is dropped the next time text is fed in).
+	Parsed(T),
+	
+	/// The parser ran into the end of the buffered text so more text needs to be fed in.
+	NeedMore,
+	
+	/// The parser failed before reaching the end of the buffered text.
+	Invalid(ParseFailed),
+}
+
+/// Buffers text and parses values out of it as the text arrives. Text is the buffered
+/// text followed by EOT and index is where the unparsed part of it starts. Line, col, and
+/// offset are the position of text[index] within all of the text which has been fed in.
+/// 
+/// Parsing a value only advances index so the buffered text is converted once per
+/// feed rather than once per value.
+pub struct Stream<T: Copy Durable> {parser: Parser<T>, file: @~str, text: @[char], index: uint, line: int, col: uint, offset: uint}
+
+/// Returns a Stream which uses parser to parse each value.
+/// 
+/// Typically parser will match a single record or message. Note that it makes little sense
+/// to use everything here: use the finish method instead.
+pub fn stream<T: Copy Durable>(parser: Parser<T>, file: @~str) -> Stream<T>
+{
+	Stream {parser: parser, file: file, text: @[EOT], index: 0u, line: 1, col: 1u, offset: 0u}
+}
+
+pub impl<T: Copy Durable> Stream<T>
+{
+	/// Appends text to the stream and then attempts to parse the next value.
+	fn feed(&mut self, text: &str) -> StreamStatus<T>
+	{
+		append(self, text);
+		self.next()
+	}
+	
+	/// Attempts to parse a value from the buffered text.
+	/// 
+	/// Failures and successes which reach the end of the buffered text return NeedMore
+	/// because more text may change the outcome. Successes which consume nothing return
+	/// Invalid (otherwise the same value would be returned forever). Note that this can
+	/// be called repeatedly to parse all of the values within the text from a single feed.
+	fn next(&mut self) -> StreamStatus<T>
+	{
+		step(self, false)
+	}
+	
+	/// Like next except that the end of the buffered text is treated as the end of the input.
+	fn finish(&mut self) -> StreamStatus<T>
+	{
+		step(self, true)
+	}
+}
+
+/// Returned by parse_partial and Continuation::resume.
+pub enum Partial<T: Copy Durable>
+{
+	/// The parser matched all of the text.
+	Complete(T),
+	
+	/// The parser ran into the end of the text: resume the continuation with more text.
+	Suspended(Continuation<T>),
+	
+	/// The parser failed before the end of the text.
+	Rejected(ParseFailed),
+}
+
+/// A parse which ran out of text. Text is the text parsed so far.
+pub struct Continuation<T: Copy Durable> {parser: Parser<T>, file: @~str, text: @~str}
+
+/// Like parse except that if parser runs into the end of the text a continuation
+/// is returned which can be resumed once more text is available.
+/// 
+/// This is intended for things like REPLs and interactive protocols. Note that
+/// resuming re-parses the text of the current message (parsers are closures so
+/// there is no way to capture their position within the parse).
+pub fn parse_partial<T: Copy Durable>(parser: Parser<T>, file: @~str, text: &str) -> Partial<T>
+{
+	match parser.parse(file, text)
+	{
+		result::Ok(value) =>
+		{
+			Complete(value)
+		}
+		result::Err(ref failure) =>
+		{
+			if failure.incomplete
+			{
+				Suspended(Continuation {parser: parser, file: file, text: @text.to_owned()})
+			}
+			else
+			{
+				Rejected(*failure)
+			}
+		}
+	}
+}
+
+pub impl<T: Copy Durable> Continuation<T>
+{
+	/// Resumes the parse with more text appended to the text parsed so far.
+	fn resume(&self, text: &str) -> Partial<T>
+	{
+		parse_partial(self.parser, self.file, *self.text + text)
+	}
+	
+	/// Finishes the parse without any more text (so it will fail if the text is incomplete).
+	fn finish(&self) -> ParseStatus<T>
+	{
+		self.parser.parse(self.file, *self.text)
+	}
+}
+
+/// Pulls tokens out of text which is read in chunks as a hand written token level
+/// parser asks for them (so memory use is proportional to the lookahead rather than
+/// the size of the input). Read returns the next chunk of text or None at the end of
+/// the input. Lookahead holds the tokens which have been peeked at but not taken.
+pub struct Lexer<T: Copy Durable> {stream: Stream<T>, read: fn@ () -> Option<~str>, lookahead: ~[T], done: bool}
+
+/// Returns a Lexer which uses token to parse each token.
+/// 
+/// Token should match one token along with the space after it (e.g. `identifier().s0()`).
+/// Note that space before the first token is not skipped.
+pub fn lexer<T: Copy Durable>(token: Parser<T>, file: @~str, read: fn@ () -> Option<~str>) -> Lexer<T>
+{
+	Lexer {stream: stream(token, file), read: read, lookahead: ~[], done: false}
+}
+
+pub impl<T: Copy Durable> Lexer<T>
+{
+	/// Returns the token n tokens ahead (zero is the next token) without taking it.
+	/// Returns None if the input ends first.
+	fn peek(&mut self, n: uint) -> result::Result<Option<T>, ParseFailed>
+	{
+		match fill(self, n + 1u)
+		{
+			result::Ok(count) => result::Ok(if n < count {option::Some(self.lookahead[n])} else {option::None}),
+			result::Err(ref failure) => result::Err(*failure),
+		}
+	}
+	
+	/// Takes the next token. Returns None at the end of the input.
+	fn next(&mut self) -> result::Result<Option<T>, ParseFailed>
+	{
+		match fill(self, 1u)
+		{
+			result::Ok(count) => result::Ok(if count > 0u {option::Some(vec::shift(&mut self.lookahead))} else {option::None}),
+			result::Err(ref failure) => result::Err(*failure),
+		}
+	}
+	
+	/// Returns true if all of the input has been lexed.
+	fn at_end(&mut self) -> result::Result<bool, ParseFailed>
+	{
+		match fill(self, 1u)
+		{
+			result::Ok(count) => result::Ok(count == 0u),
+			result::Err(ref failure) => result::Err(*failure),
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Drops the text which has already been parsed and appends text to what remains.
+fn append<T: Copy Durable>(stream: &mut Stream<T>, text: &str)
+{
+	let mut buffer = str::from_chars(vec::view(stream.text, stream.index, vec::len(stream.text) - 1u));
+	str::push_str(&mut buffer, text);
+	stream.text = chars_with_eot(buffer);
+	stream.index = 0u;
+}
+
+fn step<T: Copy Durable>(stream: &mut Stream<T>, final: bool) -> StreamStatus<T>
+{
+	let end = vec::len(stream.text) - 1u;			// index of the EOT
+	let input = State {file: stream.file, text: stream.text, index: stream.index, offset: stream.offset, line: stream.line, ctx: new_context(no_limits()), includes: @~[]};
+	match (stream.parser)(input)
+	{
+		result::Ok(ref pass) =>
+		{
+			// If the parser matched everything then more text might extend the match.
+			if (pass.new_state.index < end || final) && pass.new_state.index == stream.index
+			{
+				Invalid(ParseFailed {line: stream.line as uint, col: stream.col, offset: stream.offset, ..to_parse_failed(&Failed {old_state: input, err_state: input, mesg: @~"non-empty match"})})
+			}
+			else if pass.new_state.index < end || final
+			{
+				let (line, col, offset) = advance_position(stream, pass.new_state.index);
+				stream.index = pass.new_state.index;
+				stream.line = line;
+				stream.col = col;
+				stream.offset = offset;
+				Parsed(pass.value)
+			}
+			else
+			{
+				NeedMore
+			}
+		}
+		result::Err(ref failure) =>
+		{
+			if failure.err_state.at_end() && !final
+			{
+				NeedMore
+			}
+			else
+			{
+				// The unparsed text starts at stream.line and stream.col, not the start of the text.
+				let (line, col, offset) = advance_position(stream, failure.err_state.index);
+				Invalid(ParseFailed {line: line as uint, col: col, offset: offset, ..to_parse_failed(failure)})
+			}
+		}
+	}
+}
+
+// Returns the line, col, and byte offset of stream.text[index] (which must not be before
+// stream.index). Line breaks are counted the same way as line_starts.
+fn advance_position<T: Copy Durable>(stream: &Stream<T>, index: uint) -> (int, uint, uint)
+{
+	let mut line = stream.line;
+	let mut col = stream.col;
+	for uint::range(stream.index, index) |i|
+	{
+		let ch = stream.text[i];
+		if ch == '\n' || (ch == '\r' && stream.text[i + 1u] != '\n')
+		{
+			line += 1;
+			col = 1u;
+		}
+		else
+		{
+			col += 1u;
+		}
+	}
+	(line, col, stream.offset + byte_offset(vec::view(stream.text, stream.index, index), index - stream.index))
+}
+
+// Lexes tokens until n are in the lookahead (or the input ends). Returns the number
+// of tokens in the lookahead (which may be more than n).
+fn fill<T: Copy Durable>(lexer: &mut Lexer<T>, n: uint) -> result::Result<uint, ParseFailed>
+{
+	while vec::len(lexer.lookahead) < n
+	{
+		if lexer.done && lexer.stream.index + 1u == vec::len(lexer.stream.text)
+		{
+			break;
+		}
+		
+		match if lexer.done {lexer.stream.finish()} else {lexer.stream.next()}
+		{
+			Parsed(token) =>
+			{
+				vec::push(&mut lexer.lookahead, token);
+			}
+			NeedMore =>
+			{
+				match (lexer.read)()
+				{
+					option::Some(ref text) => append(&mut lexer.stream, *text),
+					option::None => lexer.done = true,
+				}
+			}
+			Invalid(ref failure) =>
+			{
+				return result::Err(*failure);
+			}
+		}
+	}
+	result::Ok(vec::len(lexer.lookahead))
+}
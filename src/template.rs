@@ -0,0 +1,118 @@
+//! Parses strings with `${expr}` placeholders, e.g. `"Hello ${user.name}!"`, as used by
+//! configuration files and templating tools. The placeholders are parsed by a parser
+//! supplied by the caller.
+//!
+//! `$$` stands for a single `$` (so `$${x}` is the text `${x}`). Other uses of `$` are
+//! just text.
+use parsers::*;
+use types::*;
+
+/// The pieces of a template.
+pub enum Segment<T: Copy Durable>
+{
+	/// Text outside placeholders (with `$$` replaced by `$`).
+	Verbatim(@~str),
+	
+	/// The value of a placeholder's expression and the span of the placeholder
+	/// (including the `${` and `}`).
+	Placeholder(T, Span),
+}
+
+/// Parses all of text as a template. Errors are formatted with format_failure.
+pub fn parse_template<T: Copy Durable>(expr: Parser<T>, text: &str) -> result::Result<@~[Segment<T>], ~str>
+{
+	to_result(template(expr).everything(ret(())).parse(@~"template", text), text)
+}
+
+/// template := (text | '$$' | '${' space* expr space* '}')*
+/// 
+/// Adjacent text is returned as a single Verbatim segment. Space (see the skipping
+/// method) is allowed around the expression.
+pub fn template<T: Copy Durable>(expr: Parser<T>) -> Parser<@~[Segment<T>]>
+{
+	|input: State|
+	{
+		let mut output = input;
+		let mut segments = ~[];
+		let mut text = ~"";
+		while !output.at_end()
+		{
+			let ch = output.peek();
+			let next = output.text[output.index + 1u];
+			if ch == '$' && next == '$'
+			{
+				str::push_char(&mut text, '$');
+				output = output.advance(2u);
+			}
+			else if ch == '$' && next == '{'
+			{
+				match placeholder(expr, output)
+				{
+					result::Ok(ref pass) =>
+					{
+						flush_text(&mut segments, &mut text);
+						vec::push(&mut segments, Placeholder(pass.value, output.span_to(pass.new_state)));
+						output = pass.new_state;
+					}
+					result::Err(ref failure) =>
+					{
+						return result::Err(Failed {old_state: input, ..*failure});
+					}
+				}
+			}
+			else
+			{
+				str::push_char(&mut text, ch);
+				output = if ch == '\n' {output.advance_lines(1u, 1)} else {output.advance(1u)};
+			}
+		}
+		flush_text(&mut segments, &mut text);
+		result::Ok(Succeeded {new_state: output, value: @segments})
+	}
+}
+
+/// Returns the text of the template with each placeholder replaced by the result of
+/// calling render with the placeholder's value.
+pub fn expand_template<T: Copy Durable>(segments: &[Segment<T>], render: fn (T) -> ~str) -> ~str
+{
+	let mut result = ~"";
+	for vec::each(segments) |segment|
+	{
+		match *segment
+		{
+			Verbatim(ref s) => str::push_str(&mut result, **s),
+			Placeholder(ref value, _) => str::push_str(&mut result, render(*value)),
+		}
+	}
+	result
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn flush_text<T: Copy Durable>(segments: &mut ~[Segment<T>], text: &mut ~str)
+{
+	if str::is_not_empty(*text)
+	{
+		let mut s = ~"";
+		s <-> *text;
+		vec::push(segments, Verbatim(@s));
+	}
+}
+
+// Parses '${' space* expr space* '}' (the input is at the '$').
+fn placeholder<T: Copy Durable>(expr: Parser<T>, input: State) -> Status<T>
+{
+	let body = skip_space(input.advance(2u));
+	do result::chain(expr(body))
+	|pass|
+	{
+		let end = skip_space(pass.new_state);
+		if end.peek() == '}'
+		{
+			result::Ok(Succeeded {new_state: end.advance(1u), value: pass.value})
+		}
+		else
+		{
+			result::Err(Failed {old_state: input, err_state: end, mesg: @~"'}'"})
+		}
+	}
+}
@@ -0,0 +1,393 @@
+//! Functions which make it easier to write unit tests for parsers.
+//!
+//! The parser is run on the text directly (so it does not have to match all of the
+//! text). If the check fails a description of the problem is printed to stderr and
+//! false is returned so these are normally used like `assert check_ok("2+3", expr, &5);`.
+use io::WriterUtil;
+use rand::RngUtil;
+use std::sort;
+
+use grammar::*;
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// Returns true if parser succeeds and its value is equal to expected.
+pub fn check_ok<T: Copy Durable cmp::Eq>(text: &str, parser: Parser<T>, expected: &T) -> bool
+{
+	check_ok_status(&run_check(text, parser), expected)
+}
+
+/// Returns true if parser fails with mesg on line.
+pub fn check_failed<T: Copy Durable>(text: &str, parser: Parser<T>, mesg: &str, line: int) -> bool
+{
+	check_failed_status(&run_check(text, parser), mesg, line)
+}
+
+/// Like check_failed except that the (1-based) column of the error is also checked.
+pub fn check_failed_at<T: Copy Durable>(text: &str, parser: Parser<T>, mesg: &str, line: int, col: uint) -> bool
+{
+	let result = run_check(text, parser);
+	if !check_failed_status(&result, mesg, line)
+	{
+		return false;
+	}
+	
+	let failure = result::get_err(&result);
+	let actual = get_col(failure.err_state.text, failure.err_state.index);
+	if actual != col
+	{
+		io::stderr().write_line(fmt!("Expected error '%s' at column %u but column is %u", mesg, col, actual));
+		return false;
+	}
+	return true;
+}
+
+/// Returns true if parser fails and the alternatives listed in the error message (e.g.
+/// "'if' or 'while'") are the same as expected (ignoring order).
+pub fn check_expected<T: Copy Durable>(text: &str, parser: Parser<T>, expected: &[~str]) -> bool
+{
+	match run_check(text, parser)
+	{
+		result::Ok(ref pass) =>
+		{
+			io::stderr().write_line(fmt!("Expected error %? but found %?", expected, pass.value));
+			return false;
+		}
+		result::Err(ref failure) =>
+		{
+			let actual = if str::is_empty(*failure.mesg) {~[]} else {str::split_str(*failure.mesg, " or ")};
+			if vec::len(actual) != vec::len(expected) || !vec::all(expected, |e| vec::contains(actual, e))
+			{
+				io::stderr().write_line(fmt!("Expected error %? but found error '%s'", expected, *failure.mesg));
+				return false;
+			}
+			return true;
+		}
+	}
+}
+
+/// Returns true if result succeeded and to_str of its value is expected. This is for
+/// functions like parse_json which return formatted errors.
+pub fn check_result<T>(result: &result::Result<T, ~str>, to_str: fn (&T) -> ~str, expected: &str) -> bool
+{
+	match *result
+	{
+		result::Ok(ref value) =>
+		{
+			let actual = to_str(value);
+			if actual != expected.to_owned()
+			{
+				io::stderr().write_line(fmt!("Expected %s but found %s", expected, actual));
+				return false;
+			}
+			return true;
+		}
+		result::Err(ref mesg) =>
+		{
+			io::stderr().write_line(fmt!("Expected %s but found error %s", expected, *mesg));
+			return false;
+		}
+	}
+}
+
+/// Returns true if result failed with an error which starts with expected (e.g. the
+/// location of the error). To_str is used to report unexpected successes.
+pub fn check_result_err<T>(result: &result::Result<T, ~str>, to_str: fn (&T) -> ~str, expected: &str) -> bool
+{
+	match *result
+	{
+		result::Ok(ref value) =>
+		{
+			io::stderr().write_line(fmt!("Expected error %s but found %s", expected, to_str(value)));
+			return false;
+		}
+		result::Err(ref mesg) =>
+		{
+			if !str::starts_with(*mesg, expected)
+			{
+				io::stderr().write_line(fmt!("Expected error %s but found error %s", expected, *mesg));
+				return false;
+			}
+			return true;
+		}
+	}
+}
+
+/// Parses each file in dir and compares the output with the file's .expected file. The
+/// output is the value (formatted with %?) or the error (formatted with format_failure).
+/// Returns a description of each file whose output did not match.
+/// 
+/// If update is true, or the RPARSE_UPDATE_GOLDEN environment variable is set, then the
+/// .expected files are written instead (they should then be reviewed and checked in).
+/// Note that errors use the file's name, not its path, so that the output does not
+/// depend upon where dir is.
+pub fn check_golden<T: Copy Durable>(dir: &str, parser: Parser<T>, update: bool) -> ~[~str]
+{
+	let update = update || os::getenv("RPARSE_UPDATE_GOLDEN").is_some();
+	let names = sort::merge_sort(os::list_dir(&Path(dir)), |a, b| *a <= *b);
+	
+	let mut problems = ~[];
+	for vec::each(names) |name|
+	{
+		if str::ends_with(*name, ".expected")
+		{
+			loop;
+		}
+		
+		let actual = match io::read_whole_file_str(&Path(dir).push(*name))
+		{
+			result::Ok(ref text) => golden_output(parser.parse(@copy *name, *text), *text),
+			result::Err(ref mesg) =>
+			{
+				vec::push(&mut problems, fmt!("%s: %s", *name, *mesg));
+				loop;
+			}
+		};
+		
+		let path = Path(dir).push(*name + ".expected");
+		if update
+		{
+			match io::file_writer(&path, ~[io::Create, io::Truncate])
+			{
+				result::Ok(writer) => writer.write_str(actual),
+				result::Err(ref mesg) => vec::push(&mut problems, fmt!("%s: %s", path.to_str(), *mesg)),
+			}
+		}
+		else
+		{
+			match io::read_whole_file_str(&path)
+			{
+				result::Ok(ref expected) =>
+				{
+					if *expected != actual
+					{
+						vec::push(&mut problems, fmt!("%s: expected\n%s\nbut found\n%s", *name, *expected, actual));
+					}
+				}
+				result::Err(ref mesg) =>
+				{
+					vec::push(&mut problems, fmt!("%s: %s", path.to_str(), *mesg));
+				}
+			}
+		}
+	}
+	problems
+}
+
+/// The result of check_corpus. Slowest has the names and times (in seconds) of the
+/// slowest files and errors has the first error for each file which failed.
+pub struct CorpusSummary {passed: uint, failed: uint, slowest: ~[(~str, float)], errors: ~[~str]}
+
+/// Parses every file in dir (and its sub-directories) and returns a summary of the
+/// results. Names are relative to dir. Normally used from a test like:
+/// 
+/// ```
+/// let summary = check_corpus("corpus/c", translation_unit(), 5u);
+/// if summary.failed > 0u {fail corpus_to_str(&summary);}
+/// ```
+pub fn check_corpus<T: Copy Durable>(dir: &str, parser: Parser<T>, max_slowest: uint) -> CorpusSummary
+{
+	let mut summary = CorpusSummary {passed: 0u, failed: 0u, slowest: ~[], errors: ~[]};
+	let mut times = ~[];
+	for vec::each(corpus_files(&Path(dir), "")) |name|
+	{
+		match io::read_whole_file_str(&Path(fmt!("%s/%s", dir, *name)))
+		{
+			result::Ok(ref text) =>
+			{
+				let start = std::time::precise_time_s();
+				let status = parser.parse(@copy *name, *text);
+				vec::push(&mut times, (copy *name, std::time::precise_time_s() - start));
+				match status
+				{
+					result::Ok(_) => summary.passed += 1u,
+					result::Err(ref failure) =>
+					{
+						summary.failed += 1u;
+						vec::push(&mut summary.errors, format_failure(failure, *text));
+					}
+				}
+			}
+			result::Err(ref mesg) =>
+			{
+				summary.failed += 1u;
+				vec::push(&mut summary.errors, fmt!("%s: %s", *name, *mesg));
+			}
+		}
+	}
+	
+	let times = sort::merge_sort(times, |a, b| {let (_, x) = *a; let (_, y) = *b; x >= y});
+	summary.slowest = vec::slice(times, 0u, uint::min(max_slowest, vec::len(times)));
+	summary
+}
+
+/// Returns the counts, the slowest files, and the errors from a check_corpus summary.
+pub fn corpus_to_str(summary: &CorpusSummary) -> ~str
+{
+	let mut s = fmt!("%u passed, %u failed\n", summary.passed, summary.failed);
+	if !vec::is_empty(summary.slowest)
+	{
+		str::push_str(&mut s, ~"slowest:\n");
+		for vec::each(summary.slowest) |entry|
+		{
+			let (name, seconds) = copy *entry;
+			str::push_str(&mut s, fmt!("   %.3f ms\t%s\n", 1000.0*seconds, name));
+		}
+	}
+	for vec::each(summary.errors) |err|
+	{
+		str::push_str(&mut s, *err + "\n");
+	}
+	s
+}
+
+/// Generates count random texts from the rule named start in grammar (see the generate
+/// method) and checks that parser matches all of them. Each text is also mutated (see
+/// near_miss) to check that near misses don't crash the parser and that reported errors
+/// are within the text. Returns a description of each problem which was found.
+/// 
+/// Parser will normally use everything so that the entire text has to match.
+pub fn check_fuzz<T: Copy Durable>(grammar: &Grammar, start: &str, parser: Parser<T>, terminals: fn@ (&str) -> ~str, count: uint, rng: rand::Rng) -> ~[~str]
+{
+	let mut problems = ~[];
+	for uint::range(0u, count) |_i|
+	{
+		let text = grammar.generate(start, rng, terminals);
+		match parser.parse(@~"fuzz", text)
+		{
+			result::Ok(_) => (),
+			result::Err(ref failure) => vec::push(&mut problems, fmt!("%s was rejected: %s", json_str(text), format_failure(failure, text))),
+		}
+		
+		let text = near_miss(text, rng);
+		match parser.parse(@~"fuzz", text)
+		{
+			result::Ok(_) => (),
+			result::Err(ref failure) =>
+			{
+				if failure.offset > str::len(text)
+				{
+					vec::push(&mut problems, fmt!("%s failed past the end of the text: %s", json_str(text), format_failure(failure, text)));
+				}
+			}
+		}
+	}
+	problems
+}
+
+/// Returns text with one random change: a character is deleted, duplicated, or replaced.
+/// The result will often not be valid (but it may be).
+pub fn near_miss(text: &str, rng: rand::Rng) -> ~str
+{
+	let chars = str::chars(text);
+	if vec::is_empty(chars)
+	{
+		return str::from_char(random_char(rng));
+	}
+	
+	let i = rng.gen_uint_range(0u, vec::len(chars));
+	let mut result = vec::slice(chars, 0u, i);
+	match rng.gen_uint_range(0u, 3u)
+	{
+		0u => (),
+		1u => {vec::push(&mut result, chars[i]); vec::push(&mut result, chars[i]);}
+		_ => vec::push(&mut result, random_char(rng)),
+	}
+	vec::push_all(&mut result, vec::view(chars, i + 1u, vec::len(chars)));
+	str::from_chars(result)
+}
+
+/// Like check_ok except that it works with the result of calling a parser.
+#[doc(hidden)]
+pub fn check_ok_status<T: Copy Durable cmp::Eq>(result: &Status<T>, expected: &T) -> bool
+{
+	match *result
+	{
+		result::Ok(ref pass) =>
+		{
+			if pass.value != *expected
+			{
+				io::stderr().write_line(fmt!("Expected %? but found %?", expected, pass.value));
+				return false;
+			}
+			return true;
+		}
+		result::Err(ref failure) =>
+		{
+			io::stderr().write_line(fmt!("Error: expected %? but found error %s", expected, *failure.mesg));
+			return false;
+		}
+	}
+}
+
+/// Like check_failed except that it works with the result of calling a parser.
+#[doc(hidden)]
+pub fn check_failed_status<T: Copy Durable>(result: &Status<T>, expected: &str, line: int) -> bool
+{
+	match *result
+	{
+		result::Ok(ref pass) =>
+		{
+			io::stderr().write_line(fmt!("Expected error '%s' but found %?", expected.to_owned(), pass.value));
+			return false;
+		}
+		result::Err(ref failure) =>
+		{
+			if !str::eq(failure.mesg, &expected.to_owned())
+			{
+				io::stderr().write_line(fmt!("Expected error '%s' but found error '%s'", expected.to_owned(), *failure.mesg));
+				return false;
+			}
+			if failure.err_state.line != line
+			{
+				io::stderr().write_line(fmt!("Expected error '%s' on line %d but line is %d", expected.to_owned(), line, failure.err_state.line));
+				return false;
+			}
+			return true;
+		}
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn run_check<T: Copy Durable>(text: &str, parser: Parser<T>) -> Status<T>
+{
+	info!("----------------------------------------------------");
+	let chars = chars_with_eot(text);
+	parser(State {file: @~"unit test", text: chars, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]})
+}
+
+fn golden_output<T: Copy Durable>(status: ParseStatus<T>, text: &str) -> ~str
+{
+	match status
+	{
+		result::Ok(ref value) => fmt!("%?\n", *value),
+		result::Err(ref failure) => format_failure(failure, text) + "\n",
+	}
+}
+
+// Returns the (sorted) names of the files in dir and its sub-directories relative
+// to the original dir.
+fn corpus_files(dir: &Path, prefix: &str) -> ~[~str]
+{
+	let mut files = ~[];
+	for vec::each(sort::merge_sort(os::list_dir(dir), |a, b| *a <= *b)) |name|
+	{
+		let rel = if str::is_empty(prefix) {copy *name} else {fmt!("%s/%s", prefix, *name)};
+		if os::path_is_dir(&dir.push(*name))
+		{
+			vec::push_all(&mut files, corpus_files(&dir.push(*name), rel));
+		}
+		else
+		{
+			vec::push(&mut files, move rel);
+		}
+	}
+	files
+}
+
+// Returns punctuation, a letter, a digit, or a space.
+fn random_char(rng: rand::Rng) -> char
+{
+	rng.gen_char_from("()[]{}<>+-*/,;.:'\"ax0 ")
+}
@@ -0,0 +1,48 @@
+use ast::*;
+use misc::*;
+use parsers::*;
+
+// expr := number ('+' number)*
+fn expr() -> Parser<@~[@~str]>
+{
+	let number = match1(is_digit).s0().named("number");
+	let negative = seq2_ret1("-".lit(), number).named("negative");
+	number.or(negative).list("+".s0()).named("expr")
+}
+
+fn parse_nodes<T: Copy Durable>(parser: Parser<T>, text: &str) -> ~[~str]
+{
+	match ast(parser).everything_ws().parse(@~"unit test", text)
+	{
+		result::Ok(nodes) => vec::map(*nodes, |n| node_to_str(n)),
+		result::Err(ref failure) => ~[copy *failure.mesg],
+	}
+}
+
+#[test]
+fn test_ast()
+{
+	assert parse_nodes(expr(), "1 + -2") == ~[~"(expr (number \"1 \") (negative (number \"2\")))"];
+	
+	// Only the outermost named productions are returned.
+	let p = seq2_ret1("let".s1(), expr());
+	assert parse_nodes(p, "let 1") == ~[~"(expr (number \"1\"))"];
+	assert parse_nodes(p.list(";".s0()), "let 1; let 2") == ~[~"(expr (number \"1\"))", ~"(expr (number \"2\"))"];
+}
+
+#[test]
+fn test_ast_spans()
+{
+	let result = ast(expr()).parse(@~"unit test", "12 +\n3");
+	let nodes = result::get(&result);
+	assert vec::len(*nodes) == 1u;
+	
+	let node = nodes[0];
+	assert *node.kind == ~"expr";
+	assert *node.text == ~"12 +\n3";
+	assert (node.span.start, node.span.end, node.span.start_line, node.span.end_line) == (0u, 6u, 1, 2);
+	
+	let child = node.children[1];
+	assert *child.text == ~"3";
+	assert (child.span.start, child.span.end, child.span.start_line) == (5u, 6u, 2);
+}
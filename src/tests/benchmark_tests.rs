@@ -0,0 +1,16 @@
+use io::WriterUtil;
+use benchmarks::*;
+
+#[test]
+fn test_benchmarks()
+{
+	// make bench sets RPARSE_BENCH so that larger texts are used and the timings are printed.
+	let timing = os::getenv("RPARSE_BENCH").is_some();
+	let benchmarks = run_benchmarks(if timing {100u} else {1u});
+	assert vec::len(benchmarks) == 3u;
+	
+	if timing
+	{
+		io::stderr().write_str(benchmarks_to_str(benchmarks));
+	}
+}
@@ -0,0 +1,153 @@
+use binary::*;
+use parsers::*;
+
+#[test]
+fn test_binary_unsigned()
+{
+	let bytes = [0x12u8, 0x34u8, 0x56u8, 0x78u8, 0x9Au8, 0xBCu8, 0xDEu8, 0xF0u8];
+	assert result::get(&parse_binary(byte(), @~"data", [0xFFu8])) == 0xFFu8;
+	assert result::get(&parse_binary(be_u16(), @~"data", vec::view(bytes, 0u, 2u))) == 0x1234u16;
+	assert result::get(&parse_binary(le_u16(), @~"data", vec::view(bytes, 0u, 2u))) == 0x3412u16;
+	assert result::get(&parse_binary(be_u32(), @~"data", vec::view(bytes, 0u, 4u))) == 0x12345678u32;
+	assert result::get(&parse_binary(le_u32(), @~"data", vec::view(bytes, 0u, 4u))) == 0x78563412u32;
+	assert result::get(&parse_binary(be_u64(), @~"data", bytes)) == 0x123456789ABCDEF0u64;
+	assert result::get(&parse_binary(le_u64(), @~"data", bytes)) == 0xF0DEBC9A78563412u64;
+}
+
+#[test]
+fn test_binary_signed()
+{
+	assert result::get(&parse_binary(signed_byte(), @~"data", [0xFFu8])) == -1i8;
+	assert result::get(&parse_binary(be_i16(), @~"data", [0xFFu8, 0xFEu8])) == -2i16;
+	assert result::get(&parse_binary(le_i16(), @~"data", [0xFEu8, 0xFFu8])) == -2i16;
+	assert result::get(&parse_binary(be_i32(), @~"data", [0x80u8, 0u8, 0u8, 0u8])) == i32::min_value;
+	assert result::get(&parse_binary(le_i32(), @~"data", [0x01u8, 0u8, 0u8, 0u8])) == 1i32;
+	assert result::get(&parse_binary(be_i64(), @~"data", [0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8])) == -1i64;
+	assert result::get(&parse_binary(le_i64(), @~"data", [0x7Fu8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8, 0u8])) == 127i64;
+}
+
+#[test]
+fn test_binary_mixed()
+{
+	// Text and binary parsers can be mixed and EOT bytes aren't the end of the input.
+	let bytes = str::to_bytes("GIF89a") + ~[0x03u8, 0u8, 0x20u8, 0x01u8];
+	assert result::get(&parse_binary(seq3_ret1("GIF89a".lit(), le_u16(), le_u16()), @~"data", bytes)) == 3u16;
+	assert result::get(&parse_binary(seq3_ret2("GIF89a".lit(), le_u16(), le_u16()), @~"data", bytes)) == 0x0120u16;
+	assert result::is_ok(&parse_binary(seq3_ret1("GIF89a".lit(), le_u16(), le_u16()).everything(ret(())), @~"data", bytes));
+	
+	match parse_binary(be_u32(), @~"data", [1u8, 2u8])
+	{
+		result::Err(ref failure) =>
+		{
+			assert *failure.mesg == ~"4 byte integer";
+			assert failure.col == 1u && failure.offset == 0u && failure.incomplete == false;
+		}
+		result::Ok(_) => fail,
+	}
+	match parse_binary(seq2_ret1(byte(), be_u16()), @~"data", [1u8, 2u8])
+	{
+		result::Err(ref failure) => assert failure.offset == 1u && *failure.mesg == ~"2 byte integer",
+		result::Ok(_) => fail,
+	}
+}
+
+#[test]
+fn test_binary_magic()
+{
+	let png = [0x89u8, 0x50u8, 0x4Eu8, 0x47u8];
+	let header = seq2_ret1(magic(png), take_bytes(2u));
+	assert *result::get(&parse_binary(header, @~"data", [0x89u8, 0x50u8, 0x4Eu8, 0x47u8, 1u8, 2u8])) == ~[1u8, 2u8];
+	assert vec::is_empty(*result::get(&parse_binary(take_bytes(0u), @~"data", [])));
+	
+	match parse_binary(header, @~"data", [0x89u8, 0x50u8, 0x4Fu8, 0x47u8, 1u8, 2u8])
+	{
+		result::Err(ref failure) => assert failure.offset == 2u && *failure.mesg == ~"magic number 89 50 4E 47",
+		result::Ok(_) => fail,
+	}
+	match parse_binary(header, @~"data", [0x89u8, 0x50u8])
+	{
+		result::Err(ref failure) => assert failure.offset == 2u && failure.incomplete,
+		result::Ok(_) => fail,
+	}
+	match parse_binary(header, @~"data", [0x89u8, 0x50u8, 0x4Eu8, 0x47u8, 1u8])
+	{
+		result::Err(ref failure) => assert failure.offset == 4u && *failure.mesg == ~"2 bytes",
+		result::Ok(_) => fail,
+	}
+}
+
+#[test]
+fn test_binary_varint()
+{
+	assert result::get(&parse_binary(varint(), @~"data", [0u8])) == 0u64;
+	assert result::get(&parse_binary(varint(), @~"data", [0x7Fu8])) == 127u64;
+	assert result::get(&parse_binary(varint(), @~"data", [0xE5u8, 0x8Eu8, 0x26u8])) == 624485u64;
+	assert result::get(&parse_binary(varint(), @~"data", [0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0xFFu8, 0x01u8])) == u64::max_value;
+	
+	assert result::get(&parse_binary(signed_varint(), @~"data", [0x02u8])) == 2i64;
+	assert result::get(&parse_binary(signed_varint(), @~"data", [0x7Eu8])) == -2i64;
+	assert result::get(&parse_binary(signed_varint(), @~"data", [0xC0u8, 0xBBu8, 0x78u8])) == -123456i64;
+	assert result::get(&parse_binary(signed_varint(), @~"data", [0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x7Fu8])) == i64::min_value;
+	
+	// Varints can be mixed with other parsers.
+	let p = seq2_ret1(varint(), byte());
+	assert result::get(&parse_binary(p, @~"data", [0xACu8, 0x02u8, 0x09u8])) == 9u8;
+}
+
+#[test]
+fn test_binary_varint_errors()
+{
+	match parse_binary(varint(), @~"data", [0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x02u8])
+	{
+		result::Err(ref failure) => assert failure.offset == 0u && *failure.mesg == ~"varint which fits in 64 bits",
+		result::Ok(_) => fail,
+	}
+	match parse_binary(signed_varint(), @~"data", [0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x80u8, 0x01u8])
+	{
+		result::Err(ref failure) => assert *failure.mesg == ~"varint which fits in 64 bits",
+		result::Ok(_) => fail,
+	}
+	match parse_binary(seq2_ret1(byte(), varint()), @~"data", [0x01u8, 0x80u8, 0x80u8])
+	{
+		result::Err(ref failure) => assert failure.offset == 1u && *failure.mesg == ~"varint" && failure.incomplete == false,
+		result::Ok(_) => fail,
+	}
+}
+
+fn decode(parser: Parser<@~[u8]>, text: &str) -> ~str
+{
+	match parser.parse_prefix(@~"unit test", text)
+	{
+		result::Ok(ref prefix) => fmt!("%s %u", str::connect(vec::map(*prefix.value, |b| fmt!("%02X", *b as uint)), " "), prefix.rest_index),
+		result::Err(ref failure) => fmt!("%s at %u:%u", *failure.mesg, failure.line, failure.col),
+	}
+}
+
+#[test]
+fn test_base64()
+{
+	assert decode(base64(), "TWFu") == ~"4D 61 6E 4";
+	assert decode(base64(), "TWE=") == ~"4D 61 4";
+	assert decode(base64(), "TQ==") == ~"4D 4";
+	assert decode(base64(), "TQ") == ~"4D 2";
+	assert decode(base64(), "TW Fu\nTWE=") == ~"4D 61 6E 4D 61 10";
+	assert decode(base64(), "TWFu \n") == ~"4D 61 6E 4";
+	
+	assert decode(base64(), "!") == ~"base64 at 1:1";
+	assert decode(base64(), "TWFuT") == ~"base64 character at 1:6";
+	assert decode(base64(), "TW\nFuT") == ~"base64 character at 2:4";
+	assert decode(base64(), "TWFu=") == ~"end of base64 at 1:5";
+	assert decode(base64(), "TQ=x") == ~"'=' at 1:4";
+}
+
+#[test]
+fn test_hex_blob()
+{
+	assert decode(hex_blob(), "DEADbeef") == ~"DE AD BE EF 8";
+	assert decode(hex_blob(), "de ad\nbe ef") == ~"DE AD BE EF 11";
+	assert decode(hex_blob(), "00 ") == ~"00 2";
+	
+	assert decode(hex_blob(), "xyz") == ~"hex digits at 1:1";
+	assert decode(hex_blob(), "abc") == ~"hex digit at 1:4";
+	assert decode(hex_blob(), "a bc") == ~"hex digit at 1:2";
+}
@@ -23,6 +23,8 @@ fn test_decimal_number()
 	assert check_int_failed("+78", p, "", 1);
 	assert check_int_failed("", p, "", 1);
 	assert check_int_failed("in", p, "", 1);
+	assert check_int_ok(int::str(int::max_value), p, int::max_value);
+	assert check_int_failed("99999999999999999999999", p, "'99999999999999999999999' is out of range", 1);
 }
 
 #[test]
@@ -0,0 +1,60 @@
+use calc::*;
+
+fn eval(text: &str) -> float
+{
+	result::get(&calculator().eval(text))
+}
+
+#[test]
+fn test_calc_arithmetic()
+{
+	assert eval("2 + 3*4") == 14.0;
+	assert eval("(2 + 3)*4") == 20.0;
+	assert eval("7 - 2 - 1") == 4.0;
+	assert eval("1.5 * 2") == 3.0;
+	assert eval("1e2 / 8") == 12.5;
+	assert eval("7 % 4") == 3.0;
+}
+
+#[test]
+fn test_calc_operators()
+{
+	assert eval("-3 + 1") == -2.0;
+	assert eval("--3") == 3.0;
+	assert eval("+(2)") == 2.0;
+	assert eval("2^3^2") == 512.0;
+	assert eval("-2^2") == -4.0;
+	assert eval("2^(-1)") == 0.5;
+	assert eval("1 < 2") == 1.0;
+	assert eval("2 <= 1") == 0.0;
+	assert eval("1 + 1 == 2") == 1.0;
+	assert eval("3 != 3") == 0.0;
+}
+
+#[test]
+fn test_calc_variables()
+{
+	let calc = calculator();
+	assert calc.eval("x = 3") == result::Ok(3.0);
+	assert calc.eval("y = x * 2") == result::Ok(6.0);
+	assert calc.eval("x + y") == result::Ok(9.0);
+	assert calc.eval("x == 3") == result::Ok(1.0);
+	assert calc.get("y") == option::Some(6.0);
+	
+	calc.set("z", 0.5);
+	assert calc.eval("z * 4") == result::Ok(2.0);
+	
+	// Failed statements don't assign anything.
+	assert result::is_err(&calc.eval("w = 1 +"));
+	assert calc.get("w") == option::None;
+}
+
+#[test]
+fn test_calc_errors()
+{
+	let calc = calculator();
+	assert calc.eval("1 + q") == result::Err(~"calc:1:5: unknown variable 'q'\n1 + q\n    ^");
+	assert calc.eval("q = q") == result::Err(~"calc:1:5: unknown variable 'q'\nq = q\n    ^");
+	assert calc.eval("(1") == result::Err(~"calc:1:3: ')'\n(1\n  ^");
+	assert str::starts_with(result::get_err(&calc.eval("")), "calc:1:1: ");
+}
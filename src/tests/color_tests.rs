@@ -0,0 +1,36 @@
+use color::*;
+
+fn rgba(text: &str) -> (u8, u8, u8, float)
+{
+	let c = result::get(&parse_color(text));
+	(c.r, c.g, c.b, c.a)
+}
+
+#[test]
+fn test_color_hex()
+{
+	assert rgba("#f80") == (255u8, 136u8, 0u8, 1.0);
+	assert rgba("#FF8000") == (255u8, 128u8, 0u8, 1.0);
+	assert rgba("#ff800000") == (255u8, 128u8, 0u8, 0.0);
+	assert result::get(&parse_color("#ff800080")).to_hex() == ~"#ff800080";
+	assert result::get(&parse_color("#0A0B0C")).to_hex() == ~"#0a0b0c";
+}
+
+#[test]
+fn test_color_rgb()
+{
+	assert rgba("rgb(255, 128, 0)") == (255u8, 128u8, 0u8, 1.0);
+	assert rgba("rgba( 1,2 ,3 , 0.25 )") == (1u8, 2u8, 3u8, 0.25);
+	assert rgba("rgba(100%, 50%, 0%, 50%)") == (255u8, 128u8, 0u8, 0.5);
+	assert rgba("rgb(0,0,0,1)") == (0u8, 0u8, 0u8, 1.0);
+}
+
+#[test]
+fn test_color_errors()
+{
+	assert result::get_err(&parse_color("#ff80")) == ~"color:1:2: 3, 6, or 8 hex digits\n#ff80\n ^";
+	assert result::get_err(&parse_color("rgb(256, 0, 0)")) == ~"color:1:5: number from 0 to 255 (or 0% to 100%)\nrgb(256, 0, 0)\n    ^";
+	assert result::get_err(&parse_color("rgb(0, 101%, 0)")) == ~"color:1:8: number from 0 to 255 (or 0% to 100%)\nrgb(0, 101%, 0)\n       ^";
+	assert result::get_err(&parse_color("rgba(0, 0, 0, 1.5)")) == ~"color:1:15: alpha from 0 to 1 (or 0% to 100%)\nrgba(0, 0, 0, 1.5)\n              ^";
+	assert result::get_err(&parse_color("red")) == ~"color:1:1: color\nred\n^";
+}
@@ -0,0 +1,87 @@
+use io::WriterUtil;
+use csv::*;
+use streaming::*;
+
+fn check_csv(text: &str, delimiter: char, expected: ~[~[~str]]) -> bool
+{
+	match parse_csv("test.csv", text, delimiter)
+	{
+		result::Ok(records) =>
+		{
+			let actual = vec::map(*records, |r| vec::map(**r, |f| copy **f));
+			if actual != expected
+			{
+				io::stderr().write_line(fmt!("Expected %? but found %?", expected, actual));
+				return false;
+			}
+			return true;
+		}
+		result::Err(ref mesg) =>
+		{
+			io::stderr().write_line(fmt!("Expected %? but found error %s", expected, *mesg));
+			return false;
+		}
+	}
+}
+
+#[test]
+fn test_csv_fields()
+{
+	assert check_csv("", ',', ~[]);
+	assert check_csv("a", ',', ~[~[~"a"]]);
+	assert check_csv("a,b,c\n", ',', ~[~[~"a", ~"b", ~"c"]]);
+	assert check_csv("a,,c\r\nd", ',', ~[~[~"a", ~"", ~"c"], ~[~"d"]]);
+	assert check_csv(" a , b ", ',', ~[~[~" a ", ~" b "]]);
+	assert check_csv("a\n\nb\n", ',', ~[~[~"a"], ~[~""], ~[~"b"]]);
+	assert check_csv(",", ',', ~[~[~"", ~""]]);
+}
+
+#[test]
+fn test_csv_quoting()
+{
+	assert check_csv("\"a,b\",c", ',', ~[~[~"a,b", ~"c"]]);
+	assert check_csv("\"say \"\"hi\"\"\"", ',', ~[~[~"say \"hi\""]]);
+	assert check_csv("\"\"", ',', ~[~[~""]]);
+	assert check_csv("\"line 1\nline 2\",x\ny", ',', ~[~[~"line 1\nline 2", ~"x"], ~[~"y"]]);
+}
+
+#[test]
+fn test_csv_delimiters()
+{
+	assert check_csv("a\tb,c\n1\t2", '\t', ~[~[~"a", ~"b,c"], ~[~"1", ~"2"]]);
+	assert check_csv("a;\"b;c\"", ';', ~[~[~"a", ~"b;c"]]);
+}
+
+#[test]
+fn test_csv_errors()
+{
+	assert parse_csv("test.csv", "a,\"b", ',') == result::Err(~"test.csv:1:5: '\"'\na,\"b\n    ^");
+	assert parse_csv("test.csv", "a\n\"b\"c", ',') == result::Err(~"test.csv:2:4: end of line\n\"b\"c\n   ^");
+}
+
+#[test]
+fn test_csv_stream()
+{
+	let mut s = csv_stream(@~"test.csv", ',');
+	match s.feed("a,b\n\"c\nd\",e")
+	{
+		Parsed(record) => assert record == @~[@~"a", @~"b"],
+		_ => fail ~"expected a record",
+	}
+	match s.next()
+	{
+		NeedMore => (),
+		_ => fail ~"expected NeedMore",
+	}
+	match s.feed("\nf")
+	{
+		Parsed(record) => assert record == @~[@~"c\nd", @~"e"],
+		_ => fail ~"expected a record",
+	}
+	match s.finish()
+	{
+		Parsed(record) => assert record == @~[@~"f"],
+		_ => fail ~"expected a record",
+	}
+	assert s.line == 4;
+}
@@ -0,0 +1,34 @@
+use duration::*;
+
+fn ms(text: &str) -> uint
+{
+	result::get(&parse_duration(text)).total_milliseconds()
+}
+
+#[test]
+fn test_duration()
+{
+	assert ms("250ms") == 250u;
+	assert ms("1h30m") == 5400000u;
+	assert ms("2d 4h") == 187200000u;
+	assert ms("1m 1s 1ms") == 61001u;
+	assert ms(" 0s ") == 0u;
+	
+	let d = result::get(&parse_duration("90m 3600s"));
+	assert d.hours == 2u && d.minutes == 30u && d.seconds == 0u;
+	assert d.to_str() == ~"2h30m";
+	assert duration_from_milliseconds(90061001u).to_str() == ~"1d1h1m1s1ms";
+	assert duration_from_milliseconds(0u).to_str() == ~"0ms";
+}
+
+#[test]
+fn test_duration_errors()
+{
+	assert result::get_err(&parse_duration("5y")) == ~"duration:1:2: unknown unit 'y' (expected 'd', 'h', 'm', 's', 'ms')\n5y\n ^";
+	assert result::get_err(&parse_duration("30m1h")) == ~"duration:1:5: unit 'h' is repeated or out of order (units go from 'd' to 'ms')\n30m1h\n    ^";
+	assert result::get_err(&parse_duration("1s 2s")) == ~"duration:1:5: unit 's' is repeated or out of order (units go from 'd' to 'ms')\n1s 2s\n    ^";
+	assert result::get_err(&parse_duration("1h30")) == ~"duration:1:5: unit\n1h30\n    ^";
+	assert str::starts_with(result::get_err(&parse_duration("")), "duration:1:1: number");
+	assert str::starts_with(result::get_err(&parse_duration("1d 99999999999999999999ms")), "duration:1:4: duration is too large");
+	assert str::starts_with(result::get_err(&parse_duration("300000000000d")), "duration:1:1: duration is too large");
+}
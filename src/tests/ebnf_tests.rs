@@ -0,0 +1,186 @@
+use io::WriterUtil;
+use std::future;
+
+use ebnf::*;
+use parsers::*;
+
+fn expr_grammar() -> ~str
+{
+	~"
+		expr = term, {(\"+\" | \"-\"), term} ;
+		term = number | \"(\", expr, \")\" ;
+		number = ? digits ? ;"
+}
+
+fn check_error(grammar: &str, start: &str, expected: &str) -> bool
+{
+	match interpret(grammar, start, standard_terminals)
+	{
+		result::Ok(_) =>
+		{
+			io::stderr().write_line(fmt!("Expected error %s", expected));
+			return false;
+		}
+		result::Err(ref mesg) =>
+		{
+			if *mesg != expected.to_owned()
+			{
+				io::stderr().write_line(fmt!("Expected error %s but found %s", expected, *mesg));
+				return false;
+			}
+			return true;
+		}
+	}
+}
+
+fn check_tree(parser: Parser<Tree>, text: &str, expected: &str) -> bool
+{
+	match parser.everything_ws().parse(@~"unit test", text)
+	{
+		result::Ok(ref tree) =>
+		{
+			let actual = tree_to_str(tree);
+			if actual != expected.to_owned()
+			{
+				io::stderr().write_line(fmt!("Expected %s but found %s", expected, actual));
+				return false;
+			}
+			return true;
+		}
+		result::Err(ref failure) =>
+		{
+			io::stderr().write_line(fmt!("Expected %s but found error %s", expected, *failure.mesg));
+			return false;
+		}
+	}
+}
+
+#[test]
+fn test_interpret()
+{
+	let p = interpret(expr_grammar(), "expr", standard_terminals).get();
+	
+	assert check_tree(p, "1", "(expr (term (number \"1\")))");
+	assert check_tree(p, " 1 + (2)", "(expr (term (number \"1\")) \"+\" (term \"(\" (expr (term (number \"2\"))) \")\"))");
+	assert p.everything_ws().parse(@~"unit test", "1 +").is_err();
+}
+
+enum Calc
+{
+	Number(int),
+	Symbol(@~str),
+}
+
+fn calc_leaf(text: @~str) -> Calc
+{
+	match int::from_str(*text)
+	{
+		option::Some(n) => Number(n),
+		option::None => Symbol(text),
+	}
+}
+
+fn calc_number(value: Calc) -> int
+{
+	match value
+	{
+		Number(n) => n,
+		Symbol(s) => fail fmt!("expected a number but found '%s'", *s),
+	}
+}
+
+fn calc_actions(name: &str) -> Option<fn@ (@~[Calc]) -> Calc>
+{
+	if name == "expr"
+	{
+		option::Some(|values: @~[Calc]|
+		{
+			let mut result = calc_number(values[0]);
+			for uint::range(0u, vec::len(*values)/2u) |i|
+			{
+				let rhs = calc_number(values[2u*i + 2u]);
+				result = match values[2u*i + 1u] {Symbol(op) if *op == ~"+" => result + rhs, _ => result - rhs};
+			}
+			Number(result)
+		})
+	}
+	else if name == "term"
+	{
+		// Numbers pass their value through so terms are either a number or "(", expr, ")".
+		option::Some(|values: @~[Calc]| if vec::len(*values) == 3u {values[1]} else {values[0]})
+	}
+	else
+	{
+		option::None
+	}
+}
+
+#[test]
+fn test_interpret_with()
+{
+	let p = interpret_with(expr_grammar(), "expr", standard_terminals, calc_leaf, calc_actions).get();
+	
+	assert calc_number(p.everything_ws().parse(@~"unit test", "1").get()) == 1;
+	assert calc_number(p.everything_ws().parse(@~"unit test", " 10 - (2 + 3) - 1").get()) == 4;
+	assert p.everything_ws().parse(@~"unit test", "1 +").is_err();
+	
+	match interpret_with(expr_grammar(), "number", standard_terminals, calc_leaf, calc_actions)
+	{
+		result::Ok(_) => fail ~"expected an error",
+		result::Err(ref mesg) => assert *mesg == ~"there is no action for the start rule 'number'",
+	}
+}
+
+#[test]
+fn test_peg_syntax()
+{
+	let p = interpret("list <- '[' item* ']' ; item ::= ? letters ? [','] ;", "list", standard_terminals).get();
+	
+	assert check_tree(p, "[]", "(list \"[\" \"]\")");
+	assert check_tree(p, "[a, bc]", "(list \"[\" (item \"a\" \",\") (item \"bc\") \"]\")");
+}
+
+#[test]
+fn test_sendable_grammar()
+{
+	let shared = to_sendable(&parse_ebnf(expr_grammar()).get(), "expr", standard_terminals).get();
+	
+	// Each task builds its own parser from the shared grammar.
+	let futures = do vec::from_fn(3u)
+	|i|
+	{
+		let shared = copy shared;
+		do future::spawn |move shared|
+		{
+			match shared.parser().everything_ws().parse(@~"unit test", fmt!("%u + 1", i))
+			{
+				result::Ok(ref tree) => tree_to_str(tree),
+				result::Err(ref failure) => copy *failure.mesg,
+			}
+		}
+	};
+	for vec::eachi(futures) |i, pending|
+	{
+		assert pending.get() == fmt!("(expr (term (number \"%u\")) \"+\" (term (number \"1\")))", i);
+	}
+	
+	let g = parse_ebnf("word = ? vowels ? ;").get();
+	assert result::get_err(&to_sendable(&g, "word", standard_terminals)) == ~"word: unknown terminal '? vowels ?'";
+	assert result::get_err(&to_sendable(&g, "missing", standard_terminals)) == ~"there is no rule named 'missing'";
+}
+
+#[test]
+fn test_round_trip()
+{
+	assert parse_ebnf(expr_grammar()).get().to_ebnf() == ~"expr = term, {(\"+\" | \"-\"), term} ;\nterm = number | \"(\", expr, \")\" ;\nnumber = ? digits ? ;\n";
+}
+
+#[test]
+fn test_grammar_errors()
+{
+	assert check_error("expr = ;", "expr", "grammar:1:8: expression\nexpr = ;\n       ^");
+	assert check_error("expr = foo ;", "expr", "expr: undefined rule 'foo'");
+	assert check_error("expr = ? widgets ? ;", "expr", "expr: unknown terminal '? widgets ?'");
+	assert check_error("a = 'x' ; a = 'y' ;", "a", "rule 'a' is defined more than once");
+	assert check_error("a = 'x' ;", "b", "there is no rule named 'b'");
+}
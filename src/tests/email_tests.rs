@@ -0,0 +1,37 @@
+use email::*;
+
+#[test]
+fn test_email()
+{
+	let e = result::get(&parse_email("john.q.public+tag@mail.example.com"));
+	assert *e.local == ~"john.q.public+tag";
+	assert !e.quoted;
+	assert *e.domain == ~"mail.example.com";
+	assert *e.labels == ~[@~"mail", @~"example", @~"com"];
+	assert e.to_str() == ~"john.q.public+tag@mail.example.com";
+	
+	let e = result::get(&parse_email("\"john \\\"q\\\" public\"@example.com"));
+	assert *e.local == ~"john \"q\" public";
+	assert e.quoted;
+	assert e.to_str() == ~"\"john \\\"q\\\" public\"@example.com";
+	
+	let e = result::get(&parse_email("root@[127.0.0.1]"));
+	assert *e.domain == ~"[127.0.0.1]";
+	assert vec::is_empty(*e.labels);
+	
+	assert result::is_ok(&parse_email("!#$%&'*+/=?^_`{|}~-@x-y.org"));
+}
+
+#[test]
+fn test_email_errors()
+{
+	assert result::get_err(&parse_email("@example.com")) == ~"email:1:1: local part\n@example.com\n^";
+	assert result::get_err(&parse_email("john")) == ~"email:1:5: '@'\njohn\n    ^";
+	assert result::get_err(&parse_email("john@")) == ~"email:1:6: domain\njohn@\n     ^";
+	assert result::get_err(&parse_email("john@-example.com")) == ~"email:1:6: domain\njohn@-example.com\n     ^";
+	assert result::get_err(&parse_email("\"john@example.com")) == ~"email:1:18: '\"'\n\"john@example.com\n                 ^";
+	assert result::is_err(&parse_email("john..doe@example.com"));
+	assert result::is_err(&parse_email("john@example..com"));
+	assert result::is_err(&parse_email("john@example.com."));
+	assert result::is_err(&parse_email(str::from_chars(vec::from_elem(65u, 'a')) + "@example.com"));
+}
@@ -0,0 +1,81 @@
+use c99_parsers::*;
+use fixity::*;
+use misc::*;
+use parsers::*;
+
+// program := (decl | expr ';')*
+// decl := ('infixl' | 'infixr' | 'infix') [0-9]+ op (',' op)* ';'
+// expr := name (op name)*
+fn program(text: &str) -> ~str
+{
+	let table = new_operator_table();
+	
+	let assoc = or_v(@~[
+		"infixl".s1().map(|_s| InfixLeft),
+		"infixr".s1().map(|_s| InfixRight),
+		"infix".s1().map(|_s| InfixNon)]);
+	let symbol = match1(|ch| !is_whitespace(ch) && ch != ',' && ch != ';' && ch != EOT).s0();
+	let decl = do seq4(assoc, decimal_number().s0(), symbol.list(",".s0()), ";".s0())
+		|assoc, precedence, symbols, _s|
+		{
+			result::Ok(@vec::map(*symbols, |symbol| Operator {symbol: *symbol, fixity: Fixity {assoc: assoc, precedence: precedence as uint}}))
+		};
+	let decl = declare_operators(table, decl).map(|_o| @~"");
+	
+	let name = match1(is_alpha).s0();
+	let expr = operator_expr(table, name, |op: @~str, lhs: @~str, rhs: @~str| @fmt!("(%s %s %s)", *lhs, *op, *rhs));
+	let statement = decl.or(seq2_ret0(expr, ";".s0()));
+	
+	match statement.r0().everything_ws().parse(@~"unit test", text)
+	{
+		result::Ok(values) => str::connect(vec::filter_map(*values, |v| if str::is_empty(**v) {option::None} else {option::Some(copy **v)}), " "),
+		result::Err(ref failure) => fmt!("error: %s", *failure.mesg),
+	}
+}
+
+#[test]
+fn test_operator_table()
+{
+	let table = new_operator_table();
+	assert table.declare("+", Fixity {assoc: InfixLeft, precedence: 6u});
+	assert table.declare("++", Fixity {assoc: InfixRight, precedence: 5u});
+	assert !table.declare("+", Fixity {assoc: InfixLeft, precedence: 7u});
+	
+	assert table.lookup("+").get().precedence == 7u;
+	assert table.lookup("++").get().precedence == 5u;
+	assert table.lookup("-").is_none();
+}
+
+#[test]
+fn test_precedence()
+{
+	assert program("infixl 6 +, -; infixl 7 *; a + b * c - d;") == ~"((a + (b * c)) - d)";
+	assert program("infixr 5 ++; infixl 6 +; a ++ b ++ c + d;") == ~"(a ++ (b ++ (c + d)))";
+	assert program("infixl 7 div; infixl 6 +; a div b + c;") == ~"((a div b) + c)";
+	assert str::starts_with(program("infixl 7 div; a divb;"), "error");
+}
+
+#[test]
+fn test_declarations_apply_to_later_expressions()
+{
+	// Until ^ is declared it isn't an operator.
+	assert str::starts_with(program("a ^ b;"), "error");
+	assert program("infixr 8 ^; a ^ b ^ c;") == ~"(a ^ (b ^ c))";
+	
+	// Redeclaring an operator changes how later expressions are parsed.
+	assert program("infixl 6 +; a + b + c; infixr 6 +; a + b + c;") == ~"((a + b) + c) (a + (b + c))";
+	assert program("infixl 6 +; infixl 7 *; a + b * c; infixl 5 *; a + b * c;") == ~"(a + (b * c)) ((a + b) * c)";
+}
+
+#[test]
+fn test_non_associative()
+{
+	assert program("infix 4 ==; infixl 6 +; a == b + c;") == ~"(a == (b + c))";
+	
+	let table = new_operator_table();
+	table.declare("==", Fixity {assoc: InfixNon, precedence: 4u});
+	table.declare("<", Fixity {assoc: InfixNon, precedence: 4u});
+	let p = operator_expr(table, match1(is_alpha).s0(), |_op: @~str, lhs: @~str, _rhs: @~str| lhs).everything_ws();
+	assert *result::get_err(&p.parse(@~"unit test", "a == b == c")).mesg == ~"'==' is non-associative";
+	assert result::get_err(&p.parse(@~"unit test", "a < b == c")).col == 7u;
+}
@@ -1,5 +1,6 @@
 use misc::*;
 use parsers::*;
+use testing::*;
 use tests::test_helpers::*;
 
 // Usually these would be written using then, but we are using this
@@ -12,7 +13,7 @@ fn parse_unary() -> Parser<char>
 		let ch = input.text[input.index];
 		if ch == '-' || ch == '+'
 		{
-			result::Ok(Succeeded {new_state: State {index: input.index + 1u ,.. input}, value: ch})
+			result::Ok(Succeeded {new_state: input.advance(1u), value: ch})
 		}
 		else
 		{
@@ -29,7 +30,7 @@ fn parse_digit() -> Parser<int>
 		if ch >= '0' && ch <= '9'
 		{
 			let value = option::get(char::to_digit(ch, 10u)) as int;
-			result::Ok(Succeeded {new_state: State {index: input.index + 1u ,.. input}, value: value})
+			result::Ok(Succeeded {new_state: input.advance(1u), value: value})
 		}
 		else
 		{
@@ -52,96 +53,1054 @@ fn parse_num(op: char) -> Parser<int>
 }
 
 
+#[test]
+fn test_and()
+{
+	let p = "a".lit().and("b".lit().s0()).and("c".lit()).map(|v: ((@~str, @~str), @~str)| {let ((a, b), c) = v; @(*a + *b + *c)});
+	
+	assert check_str_ok("abc", p, "abc");
+	assert check_str_ok("ab  c", p, "abc");
+	assert check_str_failed("abd", p, "'c'", 1);
+	assert check_str_failed("xbc", p, "'a'", 1);
+}
+
+// Stands in for a large AST node.
+struct Node {name: @~str, args: ~[@~str]}
+
+#[test]
+fn test_boxed()
+{
+	let p = do seq2(match1(is_alpha).s0(), match1(is_digit).s0().r0())
+		|name, args|
+		{
+			result::Ok(Node {name: name, args: copy *args})
+		};
+	let calls = p.boxed().r1();
+	
+	match calls.parse(@~"unit test", "f 1 2 g 3")
+	{
+		result::Ok(nodes) =>
+		{
+			assert vec::len(*nodes) == 2u;
+			assert nodes[0].name == @~"f" && nodes[0].args == ~[@~"1", @~"2"];
+			assert nodes[1].name == @~"g" && nodes[1].args == ~[@~"3"];
+		}
+		result::Err(ref failure) =>
+		{
+			io::stderr().write_line(fmt!("Error '%s'", *failure.mesg));
+			assert false;
+		}
+	}
+}
+
+#[test]
+fn test_capture()
+{
+	// version := 'v' major '.' minor ('-' tag)?
+	let number = match1(is_digit);
+	let tag = seq2_ret1("-".lit(), match1(is_alpha).capture("tag"));
+	let version = seq5_ret_str("v".lit(), number.capture("major"), ".".lit(), number.capture("minor"), tag.optional());
+	let p = do version.with_captures
+		|_v, captures|
+		{
+			(capture_text(*captures, "major"), capture_text(*captures, "minor"), capture_text(*captures, "tag"))
+		};
+	
+	assert p.parse(@~"unit test", "v1.22-beta").get() == (option::Some(@~"1"), option::Some(@~"22"), option::Some(@~"beta"));
+	assert p.parse(@~"unit test", "v3.4").get() == (option::Some(@~"3"), option::Some(@~"4"), option::None);
+	
+	// Captures from alternatives which failed are dropped.
+	let q = "a".lit().capture("x").then("b".lit()).or("a".lit().then("c".lit()).capture("y"));
+	let names = q.with_captures(|_v, captures| vec::map(*captures, |c| copy *c.name));
+	assert names.parse(@~"unit test", "ac").get() == ~[~"y"];
+	assert names.parse(@~"unit test", "ab").get() == ~[~"x"];
+}
+
 #[test]
 fn test_chainl1()
 {
-	let factor = parse_digit();
-	let op = "*".lit().or("/".lit());
-	let p = factor.chainl1(op, |lhs, op, rhs| if op == @~"*" {lhs * rhs} else {lhs / rhs} );
+	let factor = parse_digit();
+	let op = "*".lit().or("/".lit());
+	let p = factor.chainl1(op, |lhs, op, rhs| if op == @~"*" {lhs * rhs} else {lhs / rhs} );
+	
+	assert check_int_ok("2", p, 2);
+	assert check_int_ok("2*3", p, 6);
+	assert check_int_ok("2*3/4", p, 1);
+	assert check_int_ok("2*3/4/2", p, 0);
+	assert check_int_ok("2*3-4", p, 6);
+	assert check_int_ok("8/2/2", p, 2);			// left associative
+	
+	// Long chains are folded as they are parsed.
+	let sum = factor.chainl1("+".lit(), |lhs, _op, rhs| lhs + rhs);
+	let text = str::connect(vec::from_elem(5000u, ~"1"), "+");
+	assert sum.parse(@~"unit test", text).get() == 5000;
+}
+
+#[test]
+fn test_chainr1()
+{
+	let factor = parse_digit();
+	let op = "*".lit().or("/".lit());
+	let p = factor.chainr1(op, |lhs, op, rhs| if op == @~"*" {lhs * rhs} else {lhs / rhs} );
+	
+	assert check_int_ok("2", p, 2);
+	assert check_int_ok("2*3", p, 6);
+	assert check_int_ok("2*3/4", p, 0);
+	assert check_int_ok("2*3/4/2", p, 2);
+	assert check_int_ok("2*3-4", p, 6);
+}
+
+#[test]
+fn test_err()
+{
+	let p = "<".lit().then("foo".lit()).then(">".lit()).err("bracketed foo");
+	
+	assert check_str_ok("<foo>", p, ">");
+	assert check_str_failed("", p, "bracketed foo", 1);
+	assert check_str_failed("<", p, "'foo'", 1);
+	assert check_str_failed("<foo", p, "'>'", 1);
+}
+
+#[test]
+fn test_dispatch()
+{
+	let p = dispatch(~[('i', "if".lit()), ('w', "while".lit()), ('i', "import".lit())], option::None);
+	
+	assert check_str_ok("if", p, "if");
+	assert check_str_ok("while", p, "while");
+	assert check_str_failed("import", p, "'if'", 1);	// the first entry for a character wins
+	assert check_str_failed("x", p, "[iw]", 1);
+	assert check_str_failed("", p, "[iw]", 1);
+	
+	let q = dispatch(~[('i', "if".lit())], option::Some(match1(is_digit)));
+	assert check_str_ok("12", q, "12");
+	assert check_str_failed("x", q, "", 1);
+}
+
+#[test]
+fn test_everything()
+{
+	let s = ret(0).s0();
+	let p = parse_digit().everything(s);
+	
+	assert check_int_ok("2", p, 2);
+	assert check_int_ok("   \t3", p, 3);
+	assert check_int_failed("2 ", p, "EOT", 1);
+	assert check_int_failed("\t2\n", p, "EOT", 1);
+}
+
+#[test]
+fn test_everything_ws()
+{
+	let p = parse_digit().everything_ws();
+	
+	assert check_int_ok("2", p, 2);
+	assert check_int_ok(" \r\n\t3", p, 3);
+	assert check_int_failed("\n\n2 ", p, "EOT", 3);
+}
+
+#[test]
+fn test_fails()
+{
+	let p = fails::<char>("ack");
+	
+	assert check_char_failed("", p, "ack", 1);
+	assert check_char_failed("9", p, "ack", 1);
+}
+
+#[test]
+fn test_guard_state()
+{
+	let version = @mut 1;
+	let p = seq2_ret1(guard_state(version, |v| *v >= 2, "version 2"), "async".lit()).or("fn".lit());
+	
+	assert check_str_failed("async", p, "version 2 or 'fn'", 1);
+	*version = 2;
+	assert check_str_ok("async", p, "async");
+	assert check_str_ok("fn", p, "fn");
+}
+
+#[test]
+fn test_state_dependent()
+{
+	// Typedef names are identifiers which have been declared as types.
+	let typedefs = @mut ~[@~"size_t"];
+	let type_name = do state_dependent(typedefs)
+		|names|
+		{
+			do match1(|ch| is_alpha(ch) || ch == '_').thene
+			|name|
+			{
+				if vec::contains(*names, &name) {ret(name)} else {fails("type name")}
+			}
+		};
+	
+	assert check_str_ok("size_t", type_name, "size_t");
+	assert check_str_failed("foo", type_name, "type name", 1);
+	vec::push(&mut *typedefs, @~"foo");
+	assert check_str_ok("foo", type_name, "foo");
+}
+
+fn fake_load(path: @~str) -> result::Result<~str, ~str>
+{
+	if path == @~"a.h"
+	{
+		result::Ok(~"x\n#include b.h\n")
+	}
+	else if path == @~"b.h"
+	{
+		result::Ok(~"x x")
+	}
+	else if path == @~"c.h"
+	{
+		result::Ok(~"\n#include bad.h")
+	}
+	else if path == @~"bad.h"
+	{
+		result::Ok(~"x\nz")
+	}
+	else if path == @~"loop.h"
+	{
+		result::Ok(~"#include loop.h")
+	}
+	else
+	{
+		result::Err(~"no such file")
+	}
+}
+
+// Returns the number of x's.
+fn include_parser() -> Parser<int>
+{
+	let item_ptr = @mut ret(0);
+	let item_ref = forward_ref(item_ptr);
+	let items = item_ref.r0().thene(|counts| ret(vec::foldl(0, *counts, |sum, n| sum + *n)));
+	
+	let path = seq2_ret1("#include".s1(), match1(|c| !is_whitespace(c)).s0());
+	let x = "x".s0().thene(|_s| ret(1));
+	*item_ptr = x.or(include(path, fake_load, items));
+	
+	items.everything(ret(0))
+}
+
+#[test]
+fn test_include()
+{
+	let p = include_parser();
+	
+	match p.parse(@~"main.c", "x #include a.h x")
+	{
+		result::Ok(count) => assert count == 5,
+		result::Err(_) => assert false,
+	}
+	
+	match p.parse(@~"main.c", "x\n#include c.h")
+	{
+		result::Ok(_) =>
+		{
+			assert false;
+		}
+		result::Err(ref failure) =>
+		{
+			assert failure.file == @~"bad.h";
+			assert failure.line == 2u;
+			assert failure.col == 1u;
+			assert failure.mesg == @~"EOT";
+			assert vec::len(*failure.includes) == 2u;
+			assert failure.includes[0].file == @~"main.c";
+			assert failure.includes[0].line == 2;
+			assert failure.includes[1].file == @~"c.h";
+			assert failure.includes[1].line == 2;
+		}
+	}
+	
+	match p.parse(@~"main.c", "#include missing.h")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.mesg == @~"couldn't include 'missing.h': no such file",
+	}
+	
+	match p.parse(@~"main.c", "#include loop.h")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.mesg == @~"recursive include of 'loop.h'",
+	}
+}
+
+#[test]
+fn test_length_prefixed()
+{
+	// A decimal length, a colon, and then that many characters (like netstrings).
+	let len = seq2_ret0(match1(is_digit).map(|s| uint::from_str(*s).get()), ":".lit());
+	let p = length_prefixed(len, |_n| match0(|_ch| true));
+	assert check_str_ok("5:ab,cd", p, "ab,cd");
+	assert check_str_ok("0:", p, "");
+	
+	// The body is bounded so list stops at the end of the field.
+	let q = length_prefixed(len, |_n| "b".lit().list(",".lit())).r1();
+	match q.everything(ret(())).parse(@~"unit test", "3:b,b5:b,b,b")
+	{
+		result::Ok(lists) => assert vec::map(*lists, |l| vec::len(**l)) == ~[2u, 3u],
+		result::Err(_) => assert false,
+	}
+	
+	assert check_str_failed("9:abc", p, "9 characters", 1);
+	
+	// Huge lengths (e.g. from a hostile binary header) must not wrap around.
+	assert length_prefixed(ret(uint::max_value - 1u), |_n| match0(|_ch| true)).parse(@~"unit test", "ab").is_err();
+	match length_prefixed(len, |_n| "ab".lit()).parse(@~"unit test", "3:abc")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.col == 5u && failure.mesg == @~"end of the 3 character field",
+	}
+	match length_prefixed(len, |_n| "ab".lit()).parse(@~"unit test", "1:ab")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.col == 3u && failure.mesg == @~"'ab'",
+	}
+}
+
+#[test]
+fn test_list()
+{
+	let p = "b".lit().list(",".lit());
+	
+	assert check_str_array_ok("b", p, @~[@~"b"]);
+	assert check_str_array_ok("b,b", p, @~[@~"b", @~"b"]);
+	assert check_str_array_ok("b,b,b", p, @~[@~"b", @~"b", @~"b"]);
+	assert check_str_array_ok("b,b,c", p, @~[@~"b", @~"b"]);
+	
+	assert check_str_array_failed("", p, "'b'", 1);
+	assert check_str_array_failed("c", p, "'b'", 1);
+	
+	// Long lists should be fast (and still count towards limits).
+	let text = str::connect(vec::from_elem(10000u, ~"b"), ",");
+	assert vec::len(*p.parse(@~"unit test", text).get()) == 10000u;
+	assert p.parse_limited(@~"unit test", "b,b,b", Limits {max_length: 0u, max_values: 1u, timeout: 0.0}).is_err();
+}
+
+// Matches "ab" or "a". If "b" fails mark and restore are used to back up to the "a".
+fn parse_ab() -> Parser<@~str>
+{
+	|input: State|
+	{
+		do result::chain("a".lit()(input))
+		|pass|
+		{
+			let mark = pass.new_state.mark();
+			match "b".lit()(pass.new_state)
+			{
+				result::Ok(ref pass2) =>
+				{
+					result::Ok(Succeeded {new_state: pass2.new_state, value: @~"ab"})
+				}
+				result::Err(ref failure) =>
+				{
+					result::Ok(Succeeded {new_state: failure.err_state.restore(mark), value: @~"a"})
+				}
+			}
+		}
+	}
+}
+
+#[test]
+fn test_map()
+{
+	let p = match1(is_digit).map(|s: @~str| str::len(*s) as int);
+	
+	assert check_int_ok("123", p, 3);
+	assert check_int_failed("x", p, "", 1);
+}
+
+#[test]
+fn test_mark()
+{
+	let p = parse_ab().then("\n".lit().s0()).then("c".lit());
+	
+	assert check_str_ok("ab\nc", p, "c");
+	assert check_str_ok("a\nc", p, "c");
+	assert check_str_failed("a\nd", p, "'c'", 2);
+	
+	let text = chars_with_eot("a\nb");
+	let state = State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	let mark = state.mark();
+	let state2 = state.advance_lines(2u, 1);
+	let state3 = state2.restore(mark);
+	assert state2.offset == 2u;
+	assert state3.index == 0u && state3.offset == 0u;
+	assert state3.line == 1;
+}
+
+#[test]
+fn test_check_helpers()
+{
+	let p = seq2_ret1("a".s0(), or_v(@~["b".lit(), "c".lit(), "d".lit()]));
+	
+	assert check_ok("a b", p, &@~"b");
+	assert !check_ok("a b", p, &@~"c");
+	assert check_failed("a\n x", p, "'b' or 'c' or 'd'", 2);
+	assert check_failed_at("a\n x", p, "'b' or 'c' or 'd'", 2, 2u);
+	assert !check_failed_at("a\n x", p, "'b' or 'c' or 'd'", 2, 1u);
+	assert check_expected("a x", p, ~[~"'d'", ~"'b'", ~"'c'"]);
+	assert !check_expected("a x", p, ~[~"'b'", ~"'c'"]);
+	assert !check_expected("a b", p, ~[~"'b'"]);
+}
+
+#[test]
+fn test_check_golden()
+{
+	let dir = temp_dir("rparse-golden");
+	write_file(&dir.push("good.txt"), "ab");
+	write_file(&dir.push("bad.txt"), "ax");
+	
+	let p = "a".lit().then("b".lit()).everything_ws();
+	assert vec::is_empty(check_golden(dir.to_str(), p, true));
+	assert io::read_whole_file_str(&dir.push("good.txt.expected")).get() == ~"@~\"b\"\n";
+	assert io::read_whole_file_str(&dir.push("bad.txt.expected")).get() == ~"bad.txt:1:2: 'b'\nax\n ^\n";
+	assert vec::is_empty(check_golden(dir.to_str(), p, false));
+	
+	write_file(&dir.push("good.txt"), "abc");
+	assert check_golden(dir.to_str(), p, false) == ~[~"good.txt: expected\n@~\"b\"\n\nbut found\ngood.txt:1:3: EOT\nabc\n  ^\n"];
+	remove_temp_dir(&dir);
+}
+
+// Returns a new directory for a test's files (so tests running at the same time, or
+// in other checkouts, don't share files). Use remove_temp_dir to clean it up.
+fn temp_dir(name: &str) -> Path
+{
+	std::tempfile::mkdtemp(&os::tmpdir(), name).get()
+}
+
+fn remove_temp_dir(dir: &Path)
+{
+	for vec::each(os::list_dir(dir)) |name|
+	{
+		let path = dir.push(*name);
+		if os::path_is_dir(&path)
+		{
+			remove_temp_dir(&path);
+		}
+		else
+		{
+			os::remove_file(&path);
+		}
+	}
+	os::remove_dir(dir);
+}
+
+fn write_file(path: &Path, text: &str)
+{
+	match io::file_writer(path, ~[io::Create, io::Truncate])
+	{
+		result::Ok(writer) => writer.write_str(text),
+		result::Err(ref mesg) => fail copy *mesg,
+	}
+}
+
+#[test]
+fn test_consumed()
+{
+	let text = chars_with_eot("ab");
+	let state = State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	assert result::get(&"a".lit()(state)).consumed(state);
+	assert !result::get(&ret(0)(state)).consumed(state);
+	assert result::get_err(&"ax".lit()(state)).consumed();
+	assert !result::get_err(&"x".lit()(state)).consumed();
+}
+
+#[test]
+fn test_advance()
+{
+	let text = chars_with_eot("a\nb");
+	let state = State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	assert state.peek() == 'a';
+	assert state.advance(1u).peek() == '\n';
+	assert state.advance(1u).line == 1;
+	
+	let state2 = state.advance_lines(2u, 1);
+	assert state2.pos() == 2u;
+	assert state2.peek() == 'b';
+	assert state2.line == 2;
+	assert state2.advance(1u).peek() == EOT;
+}
+
+#[test]
+fn test_memo()
+{
+	let count = @mut 0u;
+	let digits = match1(is_digit);
+	let counted: Parser<@~str> = |input: State| {*count += 1u; digits(input)};
+	let number = memo(counted, "number");
+	let p = or_v(@~[seq2_ret0(number, "x".lit()), seq2_ret0(number, "y".lit()), number]);
+	
+	assert check_str_ok("12y", p, "12");
+	assert *count == 1u;
+	
+	assert check_str_ok("34", p, "34");
+	assert *count == 2u;			// new text so the old results are discarded
+	
+	// Only recent results are kept.
+	let text = chars_with_eot("12345678901234567890");
+	let state = State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	*count = 0u;
+	number(state);
+	number(state);
+	assert *count == 1u;
+	number(state.advance(16u));
+	number(state);
+	assert *count == 3u;
+	
+	assert check_str_failed("z", number.err("number"), "number", 1);
+}
+
+#[test]
+fn test_memoize()
+{
+	let count = @mut 0u;
+	let digits = match1(is_digit);
+	let counted: Parser<@~str> = |input: State| {*count += 1u; digits(input)};
+	let number = counted.memoize();
+	let p = or_v(@~[seq2_ret0(number, "x".lit()), seq2_ret0(number, "y".lit()), number]);
+	
+	assert check_str_ok("12y", p, "12");
+	assert *count == 1u;
+	
+	assert check_str_ok("34", p, "34");
+	assert *count == 2u;			// new text so the old results are discarded
+	
+	assert check_str_failed("z", p, "", 1);
+	assert *count == 3u;			// failures are memoized too
+}
+
+#[test]
+fn test_memoize_reparse()
+{
+	let count = @mut 0u;
+	let bs = "b".lit().named("b").r0();
+	let counted: Parser<@~[@~str]> = |input: State| {*count += 1u; bs(input)};
+	let p = counted.memoize().everything(ret(0));
+	let text = chars_with_eot("bbbb");
+	
+	assert p.parse_chars(@~"unit test", text).is_ok();
+	assert *count == 1u;
+	
+	// Parsing the same chars again is a new parse so the results aren't reused (this
+	// one records derivations and the first didn't).
+	let ctx = new_context(no_limits());
+	ctx.deriving.enabled = true;
+	assert run_parse_chars(p, @~"unit test", text, ctx).is_ok();
+	assert *count == 2u;
+	assert vec::len(ctx.deriving.derived) == 4u;
+}
+
+#[test]
+fn test_memo_profile()
+{
+	// Reusing a result still counts as a call of the named production.
+	let number = memo(match1(is_digit), "number");
+	let p = or_v(@~[seq2_ret0(number, "x".lit()), number]).everything(ret(0));
+	
+	let (status, profile) = p.parse_profiled(@~"unit test", "12");
+	assert status.is_ok();
+	let number = vec::find(*profile, |p| *p.name == ~"number").get();
+	assert number.calls == 2u && number.successes == 2u;
+}
+
+#[test]
+fn test_parse()
+{
+	let p = "<".lit().s0().then("foo".lit().s0()).then(">".lit()).err("bracketed foo");
+	
+	match p.parse(@~"unit test", ~"< foo\t>")
+	{
+		result::Ok(s) =>
+		{
+			if s != @~">"
+			{
+				io::stderr().write_line(fmt!("'>' but found '%s'.", *s));
+				assert false;
+			}
+		}
+		result::Err(ParseFailed {file, line, col, mesg, _}) =>
+		{
+			util::ignore(file);
+			io::stderr().write_line(fmt!("Error '%s' on line %u and col %u.", *mesg, line, col));
+			assert false;
+		}
+	}
+	
+	assert check_str_failed("<foo", p, "'>'", 1);
+	match p.parse(@~"unit test", ~"< \n\nfoo\tx")
+	{
+		result::Ok(s) =>
+		{
+			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
+			assert false;
+		}
+		result::Err(ParseFailed {file, line, col, mesg, _}) =>
+		{
+			assert file == @~"unit test";
+			assert line == 3u;
+			assert col == 5u;
+			assert mesg == @~"'>'";
+		}
+	}
+}
+
+#[test]
+fn test_to_result()
+{
+	let p = "<".lit().s0().then("foo".lit().s0()).then(">".lit()).err("bracketed foo");
+	
+	assert to_result(p.parse(@~"unit test", "<foo>"), "<foo>") == result::Ok(@~">");
+	
+	let text = "< \n\tfoo x";
+	assert to_result(p.parse(@~"unit test", text), text) == result::Err(~"unit test:2:6: '>'\n\tfoo x\n\t    ^");
+	
+	let failure = ParseFailed {file: @~"inner", line: 3u, col: 1u, offset: 0u, mesg: @~"", includes: @~[Include {file: @~"outer", line: 7}], incomplete: false};
+	assert format_failure(&failure, "") == ~"inner:3:1: syntax error\n    included from outer:7";
+}
+
+#[test]
+fn test_parse_lines()
+{
+	let p = "a".lit().s0().r0().then("b".lit()).everything(ret(0));
+	
+	match p.parse(@~"unit test", ~"a\r\na\ra\n\n a a c")
+	{
+		result::Ok(s) =>
+		{
+			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
+			assert false;
+		}
+		result::Err(ParseFailed {file, line, col, mesg, _}) =>
+		{
+			util::ignore(file);
+			util::ignore(mesg);
+			assert line == 5u;
+			assert col == 6u;
+		}
+	}
+	
+	let starts = line_starts(chars_with_eot("ab\r\nc\rd\ne"));
+	assert starts == ~[0u, 4u, 6u, 8u];
+	assert line_col(starts, 0u) == (1u, 1u);
+	assert line_col(starts, 2u) == (1u, 3u);
+	assert line_col(starts, 4u) == (2u, 1u);
+	assert line_col(starts, 7u) == (3u, 2u);
+	assert line_col(starts, 9u) == (4u, 2u);
+}
+
+#[test]
+fn test_cached_line_starts()
+{
+	let input = State {file: @~"unit test", text: chars_with_eot("a\nb"), index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	assert *input.line_starts() == ~[0u, 2u];
+	
+	// The starts are computed once for each text.
+	assert box::ptr_eq(input.line_starts(), input.advance(2u).line_starts());
+	let other = State {text: chars_with_eot("ab"), ..input};
+	assert *other.line_starts() == ~[0u];
+	assert vec::len(input.ctx.lines) == 2u;
+}
+
+#[test]
+fn test_incomplete()
+{
+	let p = seq3_ret1("(".lit().s0(), "x".lit().s0().r0(), ")".lit()).everything(ret(0));
+	
+	match p.parse(@~"unit test", "( x x")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.incomplete,
+	}
+	
+	match p.parse(@~"unit test", "( x y)")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert !failure.incomplete,
+	}
+	
+	match p.parse(@~"unit test", "(x) x")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert !failure.incomplete,
+	}
+}
+
+#[test]
+fn test_parse_at()
+{
+	let p = "a".lit().s0().r0().then("b".lit()).everything(ret(0));
+	
+	match p.parse_at(@~"doc.txt", "a a c", 100u, 10u, 7u)
+	{
+		result::Ok(_) =>
+		{
+			assert false;
+		}
+		result::Err(ref failure) =>
+		{
+			assert failure.file == @~"doc.txt";
+			assert failure.line == 10u;
+			assert failure.col == 11u;
+			assert failure.offset == 104u;
+		}
+	}
+	
+	match p.parse_at(@~"doc.txt", "a\n a c", 100u, 10u, 7u)
+	{
+		result::Ok(_) =>
+		{
+			assert false;
+		}
+		result::Err(ref failure) =>
+		{
+			assert failure.line == 11u;
+			assert failure.col == 4u;			// only the first line is offset by col
+			assert failure.offset == 105u;
+		}
+	}
+}
+
+fn parse_bytes_mesg(bytes: &[u8]) -> ~str
+{
+	let p = match1(|c| c != '\n').then("\n".lit()).then(match0(|_c| true)).everything(ret(0));
+	match p.parse_bytes(@~"unit test", bytes)
+	{
+		result::Ok(s) => copy *s,
+		result::Err(ref failure) => fmt!("%s at %u:%u", *failure.mesg, failure.line, failure.col),
+	}
+}
+
+#[test]
+fn test_parse_bytes()
+{
+	assert parse_bytes_mesg(~[0x61u8, 0x0Au8, 0x62u8]) == ~"b";
+	assert parse_bytes_mesg(~[0xEFu8, 0xBBu8, 0xBFu8, 0x61u8, 0x0Au8, 0xC3u8, 0xA9u8]) == ~"\u00E9";
+	assert parse_bytes_mesg(~[0xFFu8, 0xFEu8, 0x61u8, 0x00u8, 0x0Au8, 0x00u8, 0x3Du8, 0xD8u8, 0x00u8, 0xDEu8]) == ~"\U0001F600";
+	assert parse_bytes_mesg(~[0xFEu8, 0xFFu8, 0x00u8, 0x61u8, 0x00u8, 0x0Au8, 0x00u8, 0x62u8]) == ~"b";
 	
-	assert check_int_ok("2", p, 2);
-	assert check_int_ok("2*3", p, 6);
-	assert check_int_ok("2*3/4", p, 1);
-	assert check_int_ok("2*3/4/2", p, 0);
-	assert check_int_ok("2*3-4", p, 6);
+	assert parse_bytes_mesg(~[0x61u8, 0x0Au8, 0x62u8, 0xFFu8]) == ~"invalid UTF-8 at 2:2";
+	assert parse_bytes_mesg(~[0xFFu8, 0xFEu8, 0x61u8, 0x00u8, 0x0Au8]) == ~"truncated UTF-16 at 1:2";
+	assert parse_bytes_mesg(~[0xFEu8, 0xFFu8, 0x00u8, 0x61u8, 0xDCu8, 0x00u8]) == ~"unpaired UTF-16 surrogate at 1:2";
+	
+	match "x".lit().parse(@~"unit test", "\uFEFFx")
+	{
+		result::Ok(s) => assert s == @~"x",
+		result::Err(_) => assert false,
+	}
 }
 
 #[test]
-fn test_chainr1()
+fn test_offset()
 {
-	let factor = parse_digit();
-	let op = "*".lit().or("/".lit());
-	let p = factor.chainr1(op, |lhs, op, rhs| if op == @~"*" {lhs * rhs} else {lhs / rhs} );
+	let p = "\u00E9".lit().r0().then("x".lit());
 	
-	assert check_int_ok("2", p, 2);
-	assert check_int_ok("2*3", p, 6);
-	assert check_int_ok("2*3/4", p, 0);
-	assert check_int_ok("2*3/4/2", p, 2);
-	assert check_int_ok("2*3-4", p, 6);
+	match p.parse(@~"unit test", "\u00E9\u00E9y")
+	{
+		result::Ok(s) =>
+		{
+			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
+			assert false;
+		}
+		result::Err(ref failure) =>
+		{
+			assert failure.col == 3u;
+			assert failure.offset == 4u;
+		}
+	}
+	
+	let text = chars_with_eot("a\u00E9\u20AC\U0001F600b");
+	assert byte_offset(text, 0u) == 0u;
+	assert byte_offset(text, 2u) == 3u;
+	assert byte_offset(text, 4u) == 10u;
+	let state = State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	assert state.advance(3u).byte_index() == 6u;
+	assert state.advance(1u).advance(3u).offset == 10u;
+}
+
+fn derivation_to_str(d: &Derivation) -> ~str
+{
+	let children = vec::map(*d.children, |c| derivation_to_str(c));
+	if vec::is_empty(children)
+	{
+		fmt!("%s[%u,%u)", *d.name, d.start, d.end)
+	}
+	else
+	{
+		fmt!("%s[%u,%u)(%s)", *d.name, d.start, d.end, str::connect(children, " "))
+	}
 }
 
 #[test]
-fn test_err()
+fn test_parse_chars()
 {
-	let p = "<".lit().then("foo".lit()).then(">".lit()).err("bracketed foo");
+	// The same text can be parsed by different parsers without re-converting it.
+	let text = chars_with_eot("foo bar");
 	
-	assert check_str_ok("<foo>", p, ">");
-	assert check_str_failed("", p, "bracketed foo", 1);
-	assert check_str_failed("<", p, "'foo'", 1);
-	assert check_str_failed("<foo", p, "'>'", 1);
+	assert match1(is_alpha).s0().r1().parse_chars(@~"unit test", text).get() == @~[@~"foo", @~"bar"];
+	assert match1(is_alpha).parse_chars(@~"unit test", text).get() == @~"foo";
+	match "bar".lit().parse_chars(@~"unit test", text)
+	{
+		result::Ok(s) =>
+		{
+			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
+			assert false;
+		}
+		result::Err(ParseFailed {line, col, mesg, _}) =>
+		{
+			assert line == 1u;
+			assert col == 1u;
+			assert mesg == @~"'bar'";
+		}
+	}
 }
 
 #[test]
-fn test_everything()
+fn test_parse_derivation()
 {
-	let s = ret(0).s0();
-	let p = parse_digit().everything(s);
+	let digit = match1(is_digit).named("digit");
+	let sum = seq3_ret1(digit, "+".lit(), digit).named("sum");
+	let diff = seq3_ret1(digit, "-".lit(), digit).named("diff");
+	let p = sum.or(diff).s0().r0().named("exprs").everything(ret(0));
 	
-	assert check_int_ok("2", p, 2);
-	assert check_int_ok("   \t3", p, 3);
-	assert check_int_failed("2 ", p, "EOT", 1);
-	assert check_int_failed("\t2\n", p, "EOT", 1);
+	match p.parse_derivation(@~"unit test", "1-2 3+4")
+	{
+		result::Ok((_, ref derivations)) =>
+		{
+			let s = str::connect(vec::map(**derivations, |d| derivation_to_str(d)), " ");
+			assert s == ~"exprs[0,7)(diff[0,3)(digit[0,1) digit[2,3)) sum[4,7)(digit[4,5) digit[6,7)))";
+		}
+		result::Err(_) =>
+		{
+			assert false;
+		}
+	}
 }
 
 #[test]
-fn test_fails()
+fn test_parse_profiled()
 {
-	let p = fails::<char>("ack");
+	let digit = match1(is_digit).named("digit");
+	let sum = seq3_ret1(digit, "+".lit(), digit).named("sum");
+	let diff = seq3_ret1(digit, "-".lit(), digit).named("diff");
+	let p = sum.or(diff).s0().r0().named("exprs").everything(ret(0));
 	
-	assert check_char_failed("", p, "ack", 1);
-	assert check_char_failed("9", p, "ack", 1);
+	let (status, profile) = p.parse_profiled(@~"unit test", "1-2 3+4");
+	assert status.is_ok();
+	
+	let s = str::connect(vec::map(*profile, |p| fmt!("%s:%u/%u/%u/%u", *p.name, p.calls, p.successes, p.failures, p.consumed)), " ");
+	assert s == ~"digit:7/5/2/5 sum:3/1/2/3 diff:2/1/1/3 exprs:1/1/0/7";
+	assert str::contains(profile_to_str(*profile), "7\t5\t2\t5\tdigit");
 }
 
 #[test]
-fn test_list()
+fn test_parse_expected()
 {
-	let p = "b".lit().list(",".lit());
+	let value = or_v(@~["x".s0(), match1(is_digit).s0()]).err("value");
+	let statement = or_v(@~["if".s0().then(value), "let".s0().then("x".s0()).then("=".s0()).then(value)]).err("statement");
+	let p = statement.r0().everything_ws();
+	let text = "let x = 1 if ";
 	
-	assert check_str_array_ok("b", p, @~[@~"b"]);
-	assert check_str_array_ok("b,b", p, @~[@~"b", @~"b"]);
-	assert check_str_array_ok("b,b,b", p, @~[@~"b", @~"b", @~"b"]);
-	assert check_str_array_ok("b,b,c", p, @~[@~"b", @~"b"]);
+	assert vec::map(*p.parse_expected(@~"unit test", text, 0u), |e| copy **e) == ~[~"statement"];
+	assert vec::map(*p.parse_expected(@~"unit test", text, 6u), |e| copy **e) == ~[~"'='"];
+	assert vec::map(*p.parse_expected(@~"unit test", text, 8u), |e| copy **e) == ~[~"value"];
+	assert vec::map(*p.parse_expected(@~"unit test", text, 10u), |e| copy **e) == ~[~"statement"];
+	assert vec::map(*p.parse_expected(@~"unit test", text, 13u), |e| copy **e) == ~[~"value"];
 	
-	assert check_str_array_failed("", p, "'b'", 1);
-	assert check_str_array_failed("c", p, "'b'", 1);
+	// Offsets past the end or within a character are moved back.
+	assert vec::map(*p.parse_expected(@~"unit test", text, 100u), |e| copy **e) == ~[~"value"];
+	assert vec::map(*"x".lit().parse_expected(@~"unit test", "\u00E9", 1u), |e| copy **e) == ~[~"'x'"];
+	
+	// Nothing is expected if the text is complete or has an error before the offset.
+	assert vec::is_empty(*"x".lit().parse_expected(@~"unit test", "x", 1u));
+	assert vec::is_empty(*p.parse_expected(@~"unit test", "let y = ", 8u));
 }
 
 #[test]
-fn test_parse()
+fn test_parse_with_comments()
+{
+	// space := ([ \t\r\n] | '#' [^\n]*)*
+	let comment = seq2_ret_str("#".lit(), match0(|ch| ch != '\n' && ch != EOT)).keep_comment();
+	let space = match1(is_whitespace).or(comment).r0().map(|_v| ());
+	let item = "a".s0().then("b".s0()).or("a".s0().then("c".s0()));
+	let p = item.r0().everything_ws().skipping(space);
+	
+	let result = p.parse_with_comments(@~"unit test", "# one\na #two\nc a b");
+	let (_, comments) = result::get(&result);
+	assert vec::map(*comments, |c| copy *c.value) == ~[~"# one", ~"#two"];
+	assert vec::map(*comments, |c| (c.span.start, c.span.start_line)) == ~[(0u, 1), (8u, 2)];
+	
+	// Comments are only kept by parse_with_comments.
+	assert p.parse(@~"unit test", "a # x\nb").is_ok();
+	assert vec::is_empty(*result::get(&p.parse_with_comments(@~"unit test", "a b")).second());
+}
+
+#[test]
+fn test_parse_file()
+{
+	let p = "foo".lit();
+	
+	match p.parse_file("/no/such/rparse/file.txt")
+	{
+		result::Ok(s) =>
+		{
+			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
+			assert false;
+		}
+		result::Err(ParseFailed {file, line, col, mesg, _}) =>
+		{
+			assert file == @~"/no/such/rparse/file.txt";
+			assert line == 0u;
+			assert col == 0u;
+			assert !str::is_empty(*mesg);
+		}
+	}
+	
+	let dir = temp_dir("rparse-parse-file");
+	let path = dir.push("file.txt");
+	write_file(&path, "foo");
+	assert result::get(&p.parse_file(path.to_str())) == @~"foo";
+	
+	write_file(&path, "fob");
+	let failure = result::get_err(&p.parse_file(path.to_str()));
+	assert failure.file == @path.to_str();
+	assert failure.line == 1u && failure.col == 1u;
+	remove_temp_dir(&dir);
+}
+
+fn parse_limited_mesg(p: Parser<@~[@~str]>, text: &str, limits: Limits) -> ~str
+{
+	match p.parse_limited(@~"unit test", text, limits)
+	{
+		result::Ok(_) => ~"ok",
+		result::Err(ref failure) => copy *failure.mesg,
+	}
+}
+
+#[test]
+fn test_parse_lenient()
+{
+	// Statements are a name followed by a ';'. Bad statements are skipped up to the ';'.
+	let skip = match0(|ch| ch != ';').then(";".s0());
+	let statement = seq2_ret0(match1(is_alpha), ";".s0()).err("statement").recover(skip, @~"?");
+	let p = statement.r0().everything_ws();
+	
+	let result = p.parse_lenient(@~"unit test", "a; 1; b;\n c d; e;");
+	assert result.value.map(|v| vec::map(**v, |s| copy **s)) == option::Some(~[~"a", ~"?", ~"b", ~"?", ~"e"]);
+	assert vec::map(*result.errors, |e| (e.line, e.col)) == ~[(1u, 4u), (2u, 3u)];
+	assert *result.errors[0].mesg == ~"statement";
+	assert *result.errors[1].mesg == ~"';'";
+	
+	// If recovery fails the error which failed the parse is also returned.
+	let result = p.parse_lenient(@~"unit test", "1; b; c");
+	assert result.value.is_none();
+	assert vec::len(*result.errors) == 2u && result.errors[0].col == 1u;
+	
+	// Recovery is only enabled for lenient parses.
+	assert p.parse(@~"unit test", "a; 1;").is_err();
+	assert vec::is_empty(*p.parse_lenient(@~"unit test", "a; b;").errors);
+}
+
+#[test]
+fn test_parse_limited()
+{
+	let p = "b".lit().r0().everything(ret(0));
+	
+	assert parse_limited_mesg(p, "bbb", no_limits()) == ~"ok";
+	assert parse_limited_mesg(p, "bbb", Limits {max_length: 3u, ..no_limits()}) == ~"ok";
+	assert parse_limited_mesg(p, "bbbb", Limits {max_length: 3u, ..no_limits()}) == ~"more than 3 characters";
+	assert parse_limited_mesg(p, "bbb", Limits {max_values: 3u, ..no_limits()}) == ~"ok";
+	assert parse_limited_mesg(p, "bbbb", Limits {max_values: 3u, ..no_limits()}) == ~"more than 3 values";
+	assert parse_limited_mesg(p, repeat_char('b', 10000u), Limits {timeout: 0.000000001, ..no_limits()}) == ~"timed out";
+	
+	// The limit is enforced even though or recovers from the failure.
+	let q = "b".lit().r0().or(ret(@~[])).everything(ret(0));
+	assert parse_limited_mesg(q, "bbbb", Limits {max_values: 3u, ..no_limits()}) == ~"more than 3 values";
+}
+
+#[test]
+fn test_parse_cancellable()
+{
+	let p = "b".lit().r0().or(ret(@~[])).everything(ret(0));
+	
+	let (port, _chan) = pipes::stream();
+	assert p.parse_cancellable(@~"unit test", "bbbb", move port).is_ok();
+	
+	// The cancellation is fatal even though or recovers from the failure.
+	let (port, chan) = pipes::stream();
+	chan.send(());
+	assert *result::get_err(&p.parse_cancellable(@~"unit test", "bbbb", move port)).mesg == ~"cancelled";
+}
+
+#[test]
+fn test_reporting_progress()
+{
+	let indexes = @mut ~[];
+	let p = "b".lit().r0().reporting_progress(3u, |index| vec::push(&mut *indexes, index)).everything(ret(0));
+	
+	assert p.parse(@~"unit test", "bbbbbbbbbb").is_ok();
+	assert *indexes == ~[3u, 6u, 9u];
+	
+	// Progress is only reported while the previous parser runs.
+	let q = seq2_ret0("b".lit().r0().reporting_progress(3u, |index| vec::push(&mut *indexes, index)), "c".lit().r0());
+	*indexes = ~[];
+	assert q.parse(@~"unit test", "bbbbcccccccc").is_ok();
+	assert *indexes == ~[3u];
+}
+
+#[test]
+fn test_parse_prefix()
+{
+	let p = seq2_ret1("#".lit(), match1(|c| c != ' ')).s0();
+	
+	match p.parse_prefix(@~"unit test", "#d\u00E9fine x")
+	{
+		result::Ok(ref prefix) =>
+		{
+			assert prefix.value == @~"d\u00E9fine";
+			assert prefix.rest_index == 8u;
+			assert prefix.rest_offset == 9u;
+		}
+		result::Err(_) =>
+		{
+			assert false;
+		}
+	}
+	
+	match p.parse_prefix(@~"unit test", "#")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.col == 2u,
+	}
+	
+	// Positions are into the text even though its byte order mark isn't parsed.
+	match p.parse_prefix(@~"unit test", "\uFEFF#ab x")
+	{
+		result::Ok(ref prefix) => assert prefix.value == @~"ab" && prefix.rest_index == 5u && prefix.rest_offset == 7u,
+		result::Err(_) => assert false,
+	}
+	
+	match p.parse_prefix(@~"unit test", "\uFEFF#")
+	{
+		result::Ok(_) => assert false,
+		result::Err(ref failure) => assert failure.col == 2u && failure.offset == 4u,
+	}
+}
+
+#[test]
+fn test_parse_reader()
 {
 	let p = "<".lit().s0().then("foo".lit().s0()).then(">".lit()).err("bracketed foo");
 	
-	match p.parse(@~"unit test", ~"< foo\t>")
+	match io::with_str_reader("< foo\t>", |rdr| p.parse_reader(@~"unit test", rdr))
 	{
 		result::Ok(s) =>
 		{
-			if s != @~">"
-			{
-				io::stderr().write_line(fmt!("'>' but found '%s'.", *s));
-				assert false;
-			}
+			assert s == @~">";
 		}
-		result::Err(ParseFailed {file, line, col, mesg}) =>
+		result::Err(ParseFailed {file, line, col, mesg, _}) =>
 		{
 			util::ignore(file);
 			io::stderr().write_line(fmt!("Error '%s' on line %u and col %u.", *mesg, line, col));
@@ -149,18 +1108,17 @@ fn test_parse()
 		}
 	}
 	
-	assert check_str_failed("<foo", p, "'>'", 1);
-	match p.parse(@~"unit test", ~"< \n\nfoo\tx")
+	match io::with_str_reader("<\nfoo x", |rdr| p.parse_reader(@~"unit test", rdr))
 	{
 		result::Ok(s) =>
 		{
 			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
 			assert false;
 		}
-		result::Err(ParseFailed {file, line, col, mesg}) =>
+		result::Err(ParseFailed {file, line, col, mesg, _}) =>
 		{
 			assert file == @~"unit test";
-			assert line == 3u;
+			assert line == 2u;
 			assert col == 5u;
 			assert mesg == @~"'>'";
 		}
@@ -177,6 +1135,11 @@ fn test__r0()
 	assert check_str_array_ok("bb", p, @~[@~"b", @~"b"]);
 	assert check_str_array_ok("bbb", p, @~[@~"b", @~"b", @~"b"]);
 	assert check_str_array_ok("c", p, @~[]);
+	
+	// Repetition stops when nothing is consumed.
+	let q = optional_str("b".lit()).r0();
+	assert check_str_array_ok("bbc", q, @~[@~"b", @~"b"]);
+	assert check_str_array_ok("c", q, @~[]);
 }
 
 #[test]
@@ -214,6 +1177,91 @@ fn test_litv()
 	assert check_str_failed("pseudo foo", p, "'foo'", 1);
 }
 
+// Returns the name of the production it was called within.
+fn parse_production() -> Parser<@~str>
+{
+	|input: State|
+	{
+		result::Ok(Succeeded {new_state: input, value: input.production().get_default(@~"none")})
+	}
+}
+
+#[test]
+fn test_named()
+{
+	assert check_str_ok("", parse_production(), "none");
+	assert check_str_ok("", parse_production().named("inner"), "inner");
+	assert check_str_ok("", parse_production().named("inner").named("outer"), "inner");
+	assert check_str_ok("x", "x".lit().then(parse_production()).named("outer"), "outer");
+	assert check_str_ok("", parse_production().err("label"), "none");
+	assert check_str_ok("", parse_production().named("label").err("label"), "label");
+	
+	// the name is popped after the parser finishes
+	assert check_str_ok("", parse_production().named("inner").then(parse_production()), "none");
+	
+	// described parsers are named by their name, not their description
+	let described = Described {parser: parse_production(), name: option::Some(@~"num"), description: option::Some(@~"number"), first_set: option::None};
+	assert check_str_ok("", described.to_parser(), "num");
+	assert check_str_ok("", Described {name: option::None, ..described}.to_parser(), "none");
+}
+
+#[test]
+fn test_note()
+{
+	let p = "a".lit().note("a's").r0().note("list");
+	
+	for [TRACE_OFF, TRACE_FAILURES, TRACE_ALL].each |level|
+	{
+		let ctx = new_context(no_limits());
+		ctx.trace.level = *level;
+		
+		let result = p(State {file: @~"unit test", text: chars_with_eot("aab"), index: 0u, offset: 0u, line: 1, ctx: ctx, includes: @~[]});
+		assert result::get(&result).value == @~[@~"a", @~"a"];
+	}
+	
+	// Successes are only logged by TRACE_ALL and failures by TRACE_FAILURES and TRACE_ALL.
+	let ctx = new_context(no_limits());
+	let input = State {file: @~"unit test", text: chars_with_eot("ab"), index: 0u, offset: 0u, line: 1, ctx: ctx, includes: @~[]};
+	let passed = "a".lit()(input);
+	let failed = "b".lit()(input);
+	
+	ctx.trace.level = TRACE_OFF;
+	assert vec::is_empty(note_lines("a", input, &passed));
+	assert vec::is_empty(note_lines("b", input, &failed));
+	
+	ctx.trace.level = TRACE_FAILURES;
+	assert vec::is_empty(note_lines("a", input, &passed));
+	assert vec::last(note_lines("b", input, &failed)) == ~"^ b failed";
+	
+	ctx.trace.level = TRACE_ALL;
+	assert vec::last(note_lines("a", input, &passed)) == ~" ^ a parsed 'a'";
+	assert vec::last(note_lines("b", input, &failed)) == ~"^ b failed";
+}
+
+#[test]
+fn test_trace()
+{
+	let p = "a".lit().trace("a").r0().then("b".lit()).trace("ab");
+	
+	assert check_str_ok("aab", p, "b");
+	assert check_str_failed("aac", p, "'b'", 1);
+}
+
+#[test]
+fn test_trace_record()
+{
+	let text = chars_with_eot("ab\nc");
+	let input = State {file: @~"unit test", text: text, index: 3u, offset: 3u, line: 2, ctx: new_context(no_limits()), includes: @~[]};
+	
+	let passed: Status<@~str> = "c".lit()(input);
+	let s = trace_record("c\"", input, &passed, 0.5);
+	assert str::starts_with(s, "{\"name\": \"c\\\"\", \"depth\": 0, \"line\": 2, \"col\": 1, \"start\": 3, \"end\": 4, \"passed\": true, \"duration\": 0.5");
+	
+	let failed: Status<@~str> = "d".lit()(input);
+	let s = trace_record("d", input, &failed, 0.5);
+	assert str::contains(s, "\"end\": 3, \"passed\": false, \"mesg\": \"'d'\"");
+}
+
 #[test]
 fn test_optional()
 {
@@ -225,6 +1273,43 @@ fn test_optional()
 	assert check_str_failed("dbe", p, "'a'", 1);
 }
 
+#[test]
+fn test_or_first()
+{
+	let count = @mut 0u;
+	let while_p = "while".lit();
+	let counted: Parser<@~str> = |input: State| {*count += 1u; while_p(input)};
+	let p = or_first(@~[(@~"i", "if".lit()), (@~"w", counted), (@~"", match1(is_digit))]);
+	
+	assert check_str_ok("if", p, "if");
+	assert check_str_ok("while", p, "while");
+	assert check_str_ok("42", p, "42");
+	assert *count == 1u;						// while is only tried when the text starts with w
+	
+	assert check_str_failed("x", p, "'if' or 'while'", 1);
+	assert check_str_failed("wh", p, "'while'", 1);
+	assert *count == 3u;						// failed alternatives aren't retried to build the error
+}
+
+#[test]
+fn test_or_described()
+{
+	let count = @mut 0u;
+	let digits = match1(is_digit);
+	let counted: Parser<@~str> = |input: State| {*count += 1u; digits(input)};
+	let number = @Described {parser: counted, name: option::Some(@~"number"), description: option::Some(@~"number"), first_set: option::Some(@~"0123456789")};
+	let word = match1(is_alpha).err("word");
+	
+	assert number.name() == option::Some(@~"number");
+	assert word.first_set().is_none();
+	
+	let p = or_described(~[number as @Parses<@~str>, @word as @Parses<@~str>]);
+	assert check_str_ok("42", p, "42");
+	assert check_str_ok("abc", p, "abc");
+	assert *count == 1u;						// number is only tried when the text starts with a digit
+	assert check_str_failed("-", p, "number or word", 1);
+}
+
 #[test]
 fn test_or_v()
 {
@@ -237,8 +1322,16 @@ fn test_or_v()
 	assert check_str_failed("", p, "'a' or 'bb' or 'c'", 1);
 	
 	let text = chars_with_eot("bz");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = p(State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]});
 	assert result::get_err(&result).old_state.index == 0u;
+	
+	// Combined messages are only built once.
+	let q = "a".lit().or("b".lit());
+	let text = chars_with_eot("z");
+	let state = State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]};
+	assert box::ptr_eq(result::get_err(&p(state)).mesg, result::get_err(&p(state)).mesg);
+	assert box::ptr_eq(result::get_err(&q(state)).mesg, result::get_err(&q(state)).mesg);
+	assert result::get_err(&q(state)).mesg == @~"'a' or 'b'";
 }
 
 #[test]
@@ -269,6 +1362,23 @@ fn test_s1()
 	assert check_str_failed("x\n\r\nz", p, "'y'", 3);
 }
 
+#[test]
+fn test_skipping()
+{
+	// space := ([ \t\r\n] | '#' [^\n]*)*
+	let comment = seq2_ret_str("#".lit(), match0(|ch| ch != '\n' && ch != EOT));
+	let space = match1(is_whitespace).or(comment).r0().map(|_v| ());
+	let p = "a".s0().then("b".s1()).then("c".lit()).everything_ws().skipping(space);
+	
+	assert check_str_ok("a b c", p, "c");
+	assert check_str_ok("# hi\na # x\n b #y\nc", p, "c");
+	assert check_str_failed("a bc", p, "whitespace", 1);
+	assert check_str_failed("a b #x c d", p, "'c'", 1);
+	
+	// Outside of skipping s0 uses the normal definition of space.
+	assert check_str_failed("# hi\na b c", "a".s0().then("b".s1()).then("c".lit()).everything_ws(), "'a'", 1);
+}
+
 #[test]
 fn test_seq3()
 {
@@ -287,6 +1397,49 @@ fn test_seq3()
 	assert check_int_failed("2", p, "[+-]", 1);
 }
 
+#[test]
+fn test_seq_v()
+{
+	let p = seq_v(@~["a".lit(), "b".s0(), "c".lit()]);
+	
+	assert check_str_array_ok("abc", p, @~[@~"a", @~"b", @~"c"]);
+	assert check_str_array_ok("ab  c", p, @~[@~"a", @~"b", @~"c"]);
+	assert check_str_array_failed("ab d", p, "'c'", 1);
+	
+	let text = chars_with_eot("abd");
+	let result = p(State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]});
+	assert result::get_err(&result).old_state.index == 0u;
+	assert result::get_err(&result).err_state.index == 2u;
+	
+	// Long sequences don't use up the stack.
+	let parsers = vec::from_fn(5000u, |_i| "x".lit());
+	let text = str::repeat("x", 5000u);
+	assert vec::len(*seq_v(@parsers).parse(@~"unit test", text).get()) == 5000u;
+}
+
+#[test]
+fn test_spanned()
+{
+	let word = match1(is_alpha).spanned();
+	let p = do seq2(word.s0(), word) |a, b| {result::Ok((a, b))};
+	
+	let (a, b) = p.parse(@~"unit test", "ab\n  cde").get();
+	assert *a.value == ~"ab";
+	assert a.span.start == 0u && a.span.end == 2u;
+	assert a.span.start_line == 1 && a.span.end_line == 1;
+	
+	assert *b.value == ~"cde";
+	assert b.span.start == 5u && b.span.end == 8u;
+	assert b.span.start_line == 2 && b.span.end_line == 2;
+	assert b.span.file == @~"unit test";
+	
+	// Offsets count bytes, not characters.
+	let p = seq2_ret1("\u00E9".lit().s0(), match1(is_alpha).spanned());
+	let c = p.parse(@~"unit test", "\u00E9 cd").get();
+	assert c.span.start == 2u && c.span.end == 4u;
+	assert c.span.start_offset == 3u && c.span.end_offset == 5u;
+}
+
 #[test]
 fn test_then()
 {
@@ -299,8 +1452,17 @@ fn test_then()
 	assert check_str_failed("<foo-", p, "'>'", 1);
 	
 	let text = chars_with_eot("<foo-");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = p(State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]});
 	assert result::get_err(&result).old_state.index == 0u;	// if any of the then clauses fails we need to start over
+	
+	// Long chains don't use up the stack.
+	let mut p = "x".lit();
+	for uint::range(0u, 5000u) |_i|
+	{
+		p = p.then("x".lit());
+	}
+	assert check_str_ok(str::repeat("x", 5001u), p, "x");
+	assert check_str_failed(str::repeat("x", 5000u), p, "'x'", 1);
 }
 
 #[test]
@@ -315,10 +1477,37 @@ fn test_thene()
 	assert check_int_failed("--9", p, "digit", 1);
 	
 	let text = chars_with_eot("~9");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = p(State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]});
 	assert result::get_err(&result).old_state.index == 0u;	// simple case where parse_unary fails
 	
 	let text = chars_with_eot("--");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = p(State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]});
 	assert result::get_err(&result).old_state.index == 0u;	// if parse_num fails we need to start over
+	
+	// Chains may be re-entered by recursive productions.
+	let expr_ref = @mut ret(0);
+	let expr = forward_ref(expr_ref);
+	let nested = "(".lit().thene(|_s| expr).thene(|depth| ")".lit().then(ret(depth + 1)));
+	*expr_ref = nested.or("x".lit().then(ret(0)));
+	assert check_int_ok("((x))", *expr_ref, 2);
+	assert check_int_failed("((x)", *expr_ref, "')'", 1);
+}
+
+#[test]
+fn test_check_corpus()
+{
+	let dir = temp_dir("rparse-corpus");
+	os::make_dir(&dir.push("sub"), 0x1ff as libc::c_int);
+	write_file(&dir.push("a.txt"), "ab");
+	write_file(&dir.push("sub").push("b.txt"), "ax");
+	write_file(&dir.push("sub").push("c.txt"), "ab");
+	
+	let p = "a".lit().then("b".lit()).everything_ws();
+	let summary = check_corpus(dir.to_str(), p, 2u);
+	assert summary.passed == 2u;
+	assert summary.failed == 1u;
+	assert vec::len(summary.slowest) == 2u;
+	assert summary.errors == ~[~"sub/b.txt:1:2: 'b'\nax\n ^"];
+	assert str::starts_with(corpus_to_str(&summary), "2 passed, 1 failed\nslowest:\n");
+	remove_temp_dir(&dir);
 }
@@ -0,0 +1,94 @@
+use glob::*;
+
+fn show_parts(parts: &[GlobPart]) -> ~str
+{
+	do str::concat(vec::map(parts))
+	|part|
+	{
+		match *part
+		{
+			GlobText(s) => fmt!("'%s'", *s),
+			AnyChar => ~"?",
+			AnyChars => ~"*",
+			AnyPath => ~"**",
+			AnyDirs => ~"**/",
+			CharClass(negated, items) =>
+			{
+				let items = do vec::map(*items)
+				|item|
+				{
+					match *item
+					{
+						ClassChar(c) => str::from_char(c),
+						ClassRange(low, high) => fmt!("%c-%c", low, high),
+					}
+				};
+				fmt!("[%s%s]", if negated {"!"} else {""}, str::concat(items))
+			}
+			Alternatives(alts) => fmt!("{%s}", str::connect(vec::map(*alts, |alt| show_parts(**alt)), ",")),
+		}
+	}
+}
+
+fn show(pattern: &str) -> ~str
+{
+	show_parts(*result::get(&parse_glob(pattern)).parts)
+}
+
+fn check_matches(pattern: &str, path: &str) -> bool
+{
+	result::get(&parse_glob(pattern)).matches(path)
+}
+
+#[test]
+fn test_glob_parse()
+{
+	assert show("src/**/*.rs") == ~"'src/'**/*'.rs'";
+	assert show("*.{c,h}") == ~"*'.'{'c','h'}";
+	assert show("[!a-z_]?.txt") == ~"[!a-z_]?'.txt'";
+	assert show("[]-]") == ~"[]-]";
+	assert show("a\\*b") == ~"'a*b'";
+	assert show("x{,.bak}") == ~"'x'{,'.bak'}";
+	assert show("a,b}") == ~"'a,b}'";
+	
+	assert result::is_err(&parse_glob(""));
+	assert result::is_err(&parse_glob("[abc"));
+	assert result::is_err(&parse_glob("[z-a]"));
+	assert result::is_err(&parse_glob("{a,b"));
+	assert result::is_err(&parse_glob("a\\"));
+}
+
+#[test]
+fn test_glob_matches()
+{
+	assert check_matches("src/**/*.rs", "src/a.rs");
+	assert check_matches("src/**/*.rs", "src/x/y/a.rs");
+	assert !check_matches("src/**/*.rs", "src/a.c");
+	assert !check_matches("src/**/*.rs", "lib/a.rs");
+	
+	assert check_matches("*.{c,h}", "a.c");
+	assert check_matches("*.{c,h}", "b.h");
+	assert !check_matches("*.{c,h}", "a.cc");
+	assert !check_matches("*.{c,h}", "dir/a.c");
+	
+	assert check_matches("[!a-z]?.txt", "1x.txt");
+	assert !check_matches("[!a-z]?.txt", "ax.txt");
+	assert !check_matches("a?b", "a/b");
+	assert check_matches("[]]", "]");
+	assert check_matches("file\\*", "file*");
+	assert !check_matches("file\\*", "file1");
+	assert check_matches("x{,.bak}", "x");
+	assert check_matches("x{,.bak}", "x.bak");
+	assert check_matches("**", "a/b/c");
+}
+
+#[test]
+fn test_glob_backtracking()
+{
+	// These take exponential time unless failed matches are remembered.
+	let text = str::from_chars(vec::from_elem(40u, 'a'));
+	assert !check_matches("*a*a*a*a*a*a*a*a*a*a*b", text);
+	assert !check_matches(str::repeat("{a,a}", 30u) + "b", text);
+	assert check_matches(str::repeat("{a,a}", 30u) + "*", text);
+	assert !check_matches("**a**a**a**a**a**a**a**a**b", text);
+}
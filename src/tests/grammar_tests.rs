@@ -0,0 +1,234 @@
+use grammar::*;
+use misc::*;
+use parsers::*;
+use testing::*;
+use types::*;
+use tests::test_helpers::*;
+
+// expr := factor ('+' factor)*
+// factor := number | '(' expr ')'
+fn expr_grammar() -> (Grammar, Syntax<@~str>)
+{
+	let g = grammar();
+	let expr_ptr = @mut fails("dummy");
+	let expr = g.forward("expr", expr_ptr);
+	
+	let number = g.rule("number", terminal("decimal digits", match1(is_digit)).s0());
+	let group = literal("(").s0().then(expr).then(literal(")").s0()).map(|_s| @~"group");
+	let factor = g.rule("factor", number.or(group));
+	let sum = g.rule("expr", factor.list(literal("+").s0()).map(|v| @str::connect(vec::map(*v, |s| copy **s), "+")));
+	*expr_ptr = sum.parser;
+	
+	(g, sum)
+}
+
+// The parsers are never used so the references can be dummies.
+fn reference(g: &Grammar, name: &str) -> Syntax<@~str>
+{
+	g.forward(name, @mut fails("dummy"))
+}
+
+#[test]
+fn test_grammar_parse()
+{
+	let (_g, expr) = expr_grammar();
+	
+	assert check_str_ok("1 + 23", expr.parser, "1+23");
+	assert check_str_ok("1 + (2 + 3)", expr.parser, "1+group");
+	assert check_str_failed("(", expr.parser, "decimal digits or '('", 1);
+}
+
+#[test]
+fn test_to_ebnf()
+{
+	let (g, _expr) = expr_grammar();
+	
+	let ebnf = g.to_ebnf();
+	assert ebnf == ~"number = ? decimal digits ? ;\nfactor = number | \"(\", expr, \")\" ;\nexpr = factor, {\"+\", factor} ;\n";
+}
+
+#[test]
+fn test_follow_set()
+{
+	let (g, _expr) = expr_grammar();
+	
+	assert g.follow_set("expr") == ~")";
+	assert g.follow_set("factor") == ~")+";
+	assert g.follow_set("number") == ~")+";
+}
+
+#[test]
+fn test_grammar_recover()
+{
+	// list := '[' item (',' item)* ']'
+	let g = grammar();
+	let item = g.rule("item", terminal("decimal digits", match1(is_digit)).s0()).recover(&g, @~"?");
+	let list = g.rule("list", literal("[").s0().then(item.list(literal(",").s0())).then(literal("]").s0()));
+	assert g.follow_set("item") == ~",]";
+	
+	// Bad items are skipped up to the next ',' or ']'.
+	let p = list.parser.everything_ws();
+	let result = p.parse_lenient(@~"unit test", "[1, x y, 3]");
+	assert result.value.is_some();
+	assert vec::map(*result.errors, |e| (e.line, e.col)) == ~[(1u, 5u)];
+	assert *result.errors[0].mesg == ~"decimal digits";
+	
+	let result = p.parse_lenient(@~"unit test", "[1, 2, ?\n]");
+	assert result.value.is_some();
+	assert vec::map(*result.errors, |e| (e.line, e.col)) == ~[(1u, 8u)];
+	
+	let prefix = result::get(&g.sync("item").parse_prefix(@~"unit test", "x y, 2"));
+	assert *prefix.value == ~"x y" && prefix.rest_index == 3u;
+}
+
+#[test]
+#[should_fail]
+fn test_grammar_sync_unknown()
+{
+	// Misspelled rules are reported when the parser is built, not when it first recovers.
+	let g = grammar();
+	g.rule("item", literal("x"));
+	g.sync("itme");
+}
+
+#[test]
+fn test_repeat_ebnf()
+{
+	let a = @Literal(@~"a");
+	
+	assert expr_to_ebnf(@Repeat(a, 0u, 1u), 0u) == ~"[\"a\"]";
+	assert expr_to_ebnf(@Repeat(a, 1u, 0u), 0u) == ~"\"a\", {\"a\"}";
+	assert expr_to_ebnf(@Repeat(@Choice(@~[a, @Literal(@~"b\"")]), 2u, 3u), 2u) == ~"((\"a\" | 'b\"'), (\"a\" | 'b\"'), [\"a\" | 'b\"'])";
+	
+	// Combinators use uint::max_value for no upper bound.
+	let syntax = literal("a").r(1u, uint::max_value);
+	assert expr_to_ebnf(syntax.expr, 0u) == ~"\"a\", {\"a\"}";
+	assert syntax.parser.parse(@~"unit test", "aaa").is_ok();
+}
+
+#[test]
+fn test_to_railroad()
+{
+	let g = grammar();
+	g.rule("greeting", literal("hi").then(literal("!").optional()));
+	
+	let expected = ~[
+		~"digraph grammar",
+		~"{",
+		~"\trankdir=LR;",
+		~"\tsubgraph cluster_r0",
+		~"\t{",
+		~"\t\tlabel=\"greeting\";",
+		~"\t\tr0_0 [shape=circle, width=0.15, label=\"\"];",
+		~"\t\tr0_1 [shape=box, style=rounded, label=\"hi\"];",
+		~"\t\tr0_0 -> r0_1;",
+		~"\t\tr0_2 [shape=point];",
+		~"\t\tr0_1 -> r0_2;",
+		~"\t\tr0_3 [shape=box, style=rounded, label=\"!\"];",
+		~"\t\tr0_1 -> r0_3;",
+		~"\t\tr0_3 -> r0_2;",
+		~"\t\tr0_4 [shape=doublecircle, width=0.1, label=\"\"];",
+		~"\t\tr0_2 -> r0_4;",
+		~"\t}",
+		~"}"];
+	assert g.to_railroad() == str::connect(expected, "\n") + "\n";
+}
+
+#[test]
+fn test_railroad_loops()
+{
+	let (g, _expr) = expr_grammar();
+	let dot = g.to_railroad();
+	
+	// expr = factor, {"+", factor} loops back to the junction after the first factor.
+	assert str::contains(dot, "\t\tlabel=\"expr\";\n\t\tr2_0 [shape=circle, width=0.15, label=\"\"];\n\t\tr2_1 [shape=box, label=\"factor\"];\n\t\tr2_0 -> r2_1;\n\t\tr2_2 [shape=point];\n\t\tr2_1 -> r2_2;\n");
+	assert str::contains(dot, "\t\tr2_4 [shape=box, label=\"factor\"];\n\t\tr2_3 -> r2_4;\n\t\tr2_4 -> r2_2;\n");
+	assert str::contains(dot, "label=\"decimal digits\"");
+}
+
+#[test]
+fn test_check_alternatives()
+{
+	let g = grammar();
+	g.rule("op", literal("<").or(literal("<=")).or(literal(">")));
+	g.rule("ok", literal("<=").or(literal("<")));
+	g.rule("opt", literal("a").optional().map(|_v| @~"a").or(literal("b")));
+	g.rule("dup", literal("x").or(literal("y")).or(literal("x")));
+	g.rule("seq", literal("<").or(literal("<").then(literal("="))));
+	
+	let warnings = g.check_alternatives();
+	assert warnings == ~[
+		~"op: alternative 2 (\"<=\") can never match because alternative 1 (\"<\") matches a prefix of it",
+		~"opt: alternative 2 (\"b\") can never match because alternative 1 ([\"a\"]) can match nothing",
+		~"dup: alternative 3 (\"x\") can never match because alternative 1 (\"x\") is the same",
+		~"seq: alternative 2 (\"<\", \"=\") can never match because alternative 1 (\"<\") matches a prefix of it"];
+}
+
+#[test]
+fn test_check_left_recursion()
+{
+	let (g, _expr) = expr_grammar();
+	assert vec::is_empty(g.check_left_recursion());
+	
+	let g = grammar();
+	g.rule("direct", reference(&g, "direct").then(literal("x")));
+	g.rule("a", reference(&g, "b").then(literal("x")));
+	g.rule("b", literal("y").optional().then(reference(&g, "a")));
+	g.rule("nested", literal("(").then(reference(&g, "nested")).then(literal(")")));
+	
+	assert g.check_left_recursion() == ~[
+		~"direct is left recursive: direct -> direct",
+		~"a is left recursive: a -> b -> a"];
+}
+
+#[test]
+fn test_choice()
+{
+	let keyword = choice(@~[literal("if"), literal("while"), literal("return")]);
+	let p = choice(@~[keyword, terminal("digits", match1(is_digit))]);
+	
+	assert check_str_ok("while", p.parser, "while");
+	assert check_str_ok("12", p.parser, "12");
+	assert check_str_failed("x", p.parser, "'if' or 'while' or 'return' or digits", 1);
+	assert expr_to_ebnf(p.expr, 0u) == ~"(\"if\" | \"while\" | \"return\") | ? digits ?";
+	
+	assert first_chars(keyword.expr) == option::Some(~"iwr");
+	assert first_chars(p.expr) == option::None;
+	assert first_chars(literal("a").then(literal("b")).expr) == option::Some(~"a");
+	assert first_chars(literal("a").optional().expr) == option::None;
+	assert first_chars(literal("a").r1().expr) == option::Some(~"a");
+}
+
+#[test]
+fn test_parses()
+{
+	let (_g, expr) = expr_grammar();
+	assert expr.name() == option::Some(@~"expr");
+	assert check_str_ok("1+2", expr.to_parser(), "1+2");
+	
+	let group = literal("(").then(literal("x")).then(literal(")"));
+	assert group.name().is_none();
+	assert group.description() == option::Some(@~"\"(\", \"x\", \")\"");
+	assert group.first_set() == option::Some(@~"(");
+}
+
+#[test]
+fn test_generate()
+{
+	let (g, expr) = expr_grammar();
+	let rng = rand::seeded_rng(&~[1u8, 2u8, 3u8]);
+	let digits: fn@ (&str) -> ~str = |_description| ~"42";
+	
+	for uint::range(0u, 20u) |_i|
+	{
+		let text = g.generate("expr", rng, digits);
+		assert str::starts_with(text, "42") || str::starts_with(text, "(");
+		assert expr.parser.everything_ws().parse(@~"generated", text).is_ok();
+	}
+	
+	let problems = check_fuzz(&g, "expr", expr.parser.everything_ws(), digits, 50u, rng);
+	assert vec::is_empty(problems);
+	
+	let near = near_miss("1+2", rng);
+	assert str::len(near) >= 2u && str::len(near) <= 4u;
+}
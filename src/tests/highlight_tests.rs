@@ -0,0 +1,53 @@
+use c99_parsers::*;
+use highlight::*;
+use misc::*;
+use parsers::*;
+
+fn kind_name(kind: TokenKind) -> ~str
+{
+	match kind
+	{
+		KeywordToken => ~"keyword",
+		IdentifierToken => ~"identifier",
+		LiteralToken => ~"literal",
+		OperatorToken => ~"operator",
+		PunctuationToken => ~"punctuation",
+		CommentToken => ~"comment",
+		ErrorToken => ~"error",
+	}
+}
+
+// Returns "kind:text" for each token.
+fn tokens(text: &str) -> ~[~str]
+{
+	let rules = ~[
+		(KeywordToken, or_v(@~["if".lit(), "else".lit()])),
+		(IdentifierToken, identifier()),
+		(LiteralToken, match1(is_digit)),
+		(OperatorToken, or_v(@~["==".lit(), "=".lit()])),
+		(PunctuationToken, "(){};".anyc().map(|c| @str::from_char(c))),
+		(CommentToken, comment())];
+	let chars = str::chars(text);
+	do vec::map(*highlight(rules, @~"test", text)) |t|
+	{
+		fmt!("%s:%s", kind_name(t.kind), str::from_chars(vec::view(chars, t.span.start, t.span.end)))
+	}
+}
+
+#[test]
+fn test_highlight()
+{
+	assert tokens("if (x == 10) ifx = 1;") == ~[~"keyword:if", ~"punctuation:(", ~"identifier:x", ~"operator:==", ~"literal:10", ~"punctuation:)", ~"identifier:ifx", ~"operator:=", ~"literal:1", ~"punctuation:;"];
+	assert tokens("/* a\nb */ else") == ~[~"comment:/* a\nb */", ~"keyword:else"];
+	assert tokens("") == ~[];
+}
+
+#[test]
+fn test_highlight_errors()
+{
+	assert tokens("x @# y $") == ~[~"identifier:x", ~"error:@#", ~"identifier:y", ~"error:$"];
+	assert tokens("1@@2") == ~[~"literal:1", ~"error:@@", ~"literal:2"];
+	
+	let t = highlight(~[(IdentifierToken, identifier())], @~"test", "a\n  b");
+	assert t[1].span.start_line == 2 && t[1].span.start == 4u;
+}
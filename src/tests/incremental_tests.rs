@@ -0,0 +1,94 @@
+use c99_parsers::*;
+use incremental::*;
+use parsers::*;
+use types::*;
+use tests::test_helpers::*;
+
+fn counted(count: @mut uint) -> Parser<@~str>
+{
+	let p = identifier().s0();
+	
+	|input: State|
+	{
+		*count += 1u;
+		p(input)
+	}
+}
+
+#[test]
+fn test_reuse()
+{
+	let count = @mut 0u;
+	let cache = reparse_cache();
+	let p = incremental(counted(count), cache).r0();
+	
+	assert check_str_array_ok("ab cd ef", p, @~[@~"ab", @~"cd", @~"ef"]);
+	assert *count == 4u;			// three identifiers and a failure at the end
+	
+	assert check_str_array_ok("ab cd ef", p, @~[@~"ab", @~"cd", @~"ef"]);
+	assert *count == 5u;			// failures aren't cached
+	
+	// "ab cd ef" => "ab xyz ef"
+	edit_cache(cache, 3u, 2u, 3u);
+	assert check_str_array_ok("ab xyz ef", p, @~[@~"ab", @~"xyz", @~"ef"]);
+	assert *count == 8u;			// "ab " touches the edit so only "ef" is reused
+}
+
+#[test]
+fn test_edit_cache()
+{
+	let cache = reparse_cache();
+	let p = incremental(identifier().s0(), cache).r0();
+	
+	assert check_str_array_ok("abc def ghi", p, @~[@~"abc", @~"def", @~"ghi"]);
+	assert cache.size() == 3u;
+	
+	// "abc def ghi" => "abc ghi"
+	edit_cache(cache, 4u, 4u, 0u);
+	assert cache.size() == 1u;			// "abc " touches the edit and "def " was removed
+	assert cache.contains_key(4u);		// "ghi" was shifted
+	assert check_str_array_ok("abc ghi", p, @~[@~"abc", @~"ghi"]);
+}
+
+#[test]
+fn test_reuse_effects()
+{
+	let count = @mut 0u;
+	let cache = reparse_cache();
+	let word = incremental(counted(count).capture("word"), cache);
+	let p = word.r0().with_captures(|_words, captures| @vec::map(*captures, |c| @fmt!("%s@%u", *c.text, c.span.start)));
+	
+	assert check_str_array_ok("ab cd", p, @~[@~"ab @0", @~"cd@3"]);
+	assert *count == 3u;
+	
+	// "ab cd" => "ab   cd"
+	edit_cache(cache, 3u, 0u, 2u);
+	assert check_str_array_ok("ab   cd", p, @~[@~"ab   @0", @~"cd@5"]);
+	assert *count == 5u;			// "cd" was reused and its capture moved
+}
+
+#[test]
+fn test_reuse_per_file()
+{
+	let cache = reparse_cache();
+	let p = incremental(identifier(), cache);
+	
+	assert *p.parse(@~"a.txt", "ab").get() == ~"ab";
+	assert *p.parse(@~"b.txt", "cd").get() == ~"cd";
+	assert *p.parse(@~"a.txt", "ab").get() == ~"ab";
+	assert cache.size() == 1u && vec::len(*cache.get(0u)) == 2u;
+}
+
+#[test]
+fn test_edit_examined()
+{
+	let cache = reparse_cache();
+	let p = incremental("abcd".lit().or("ab".lit()), cache);
+	
+	assert *p.parse_prefix(@~"t.txt", "abcx").get().value == ~"ab";
+	
+	// "abcx" => "abcd"
+	edit_cache(cache, 3u, 1u, 1u);
+	assert cache.size() == 0u;			// "ab" is shorter than the edit but "abcd" looked at the x
+	assert *p.parse_prefix(@~"t.txt", "abcd").get().value == ~"abcd";
+}
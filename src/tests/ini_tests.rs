@@ -0,0 +1,55 @@
+use ini::*;
+
+fn lookup(file: &IniFile, section: &str, key: &str) -> ~str
+{
+	match file.find(section.to_owned())
+	{
+		option::Some(entries) =>
+		{
+			match entries.find(key.to_owned())
+			{
+				option::Some(value) => copy *value,
+				option::None => ~"<missing key>",
+			}
+		}
+		option::None => ~"<missing section>",
+	}
+}
+
+#[test]
+fn test_ini()
+{
+	let text = ~"; global settings\nname = top level\n\n[server] ; the server\n  host=example.com\nport : 80\n# done\n[empty]\n";
+	let file = result::get(&parse_ini("test.ini", text));
+	assert lookup(&file, "", "name") == ~"top level";
+	assert lookup(&file, "server", "host") == ~"example.com";
+	assert lookup(&file, "server", "port") == ~"80";
+	assert lookup(&file, "server", "name") == ~"<missing key>";
+	assert file.get(~"empty").size() == 0u;
+	assert file.size() == 3u;
+}
+
+#[test]
+fn test_ini_values()
+{
+	let text = ~"a = x ; not a comment\nb =\nc = one \\\n    two\r\nd = last";
+	let file = result::get(&parse_ini("test.ini", text));
+	assert lookup(&file, "", "a") == ~"x ; not a comment";
+	assert lookup(&file, "", "b") == ~"";
+	assert lookup(&file, "", "c") == ~"one two";
+	assert lookup(&file, "", "d") == ~"last";
+	
+	// Later entries replace earlier ones.
+	let file = result::get(&parse_ini("test.ini", "[s]\nk = 1\n[t]\n[s]\nk = 2\n"));
+	assert lookup(&file, "s", "k") == ~"2";
+}
+
+#[test]
+fn test_ini_errors()
+{
+	// Each bad line is reported.
+	let errors = result::get_err(&parse_ini("test.ini", "[ok]\ngarbage\nk = v\n[bad\nx = y\n"));
+	assert vec::len(errors) == 2u;
+	assert str::starts_with(errors[0], "test.ini:2:8: ");
+	assert str::starts_with(errors[1], "test.ini:4:5: ']'");
+}
@@ -0,0 +1,63 @@
+use ip::*;
+use parsers::*;
+
+fn v6(text: &str) -> ~[u16]
+{
+	copy *result::get(&parse_ipv6(text)).segments
+}
+
+#[test]
+fn test_ipv4()
+{
+	let a = result::get(&parse_ipv4("192.168.0.1"));
+	assert *a.octets == ~[192u8, 168u8, 0u8, 1u8];
+	assert a.to_str() == ~"192.168.0.1";
+	assert result::is_ok(&parse_ipv4("0.0.0.0"));
+	assert result::is_ok(&parse_ipv4("255.255.255.255"));
+	
+	assert result::get_err(&parse_ipv4("1.2.256.4")) == ~"address:1:5: octet from 0 to 255 without leading zeros\n1.2.256.4\n    ^";
+	assert result::get_err(&parse_ipv4("1.02.3.4")) == ~"address:1:3: octet from 0 to 255 without leading zeros\n1.02.3.4\n  ^";
+	assert result::get_err(&parse_ipv4("1.2.3")) == ~"address:1:6: '.'\n1.2.3\n     ^";
+	assert result::is_err(&parse_ipv4("1.2.3.4.5"));
+}
+
+#[test]
+fn test_ipv6()
+{
+	assert v6("2001:db8:0:0:0:0:2:1") == ~[0x2001u16, 0xdb8u16, 0u16, 0u16, 0u16, 0u16, 2u16, 1u16];
+	assert v6("2001:DB8::2:1") == ~[0x2001u16, 0xdb8u16, 0u16, 0u16, 0u16, 0u16, 2u16, 1u16];
+	assert v6("::") == ~[0u16, 0u16, 0u16, 0u16, 0u16, 0u16, 0u16, 0u16];
+	assert v6("::1") == ~[0u16, 0u16, 0u16, 0u16, 0u16, 0u16, 0u16, 1u16];
+	assert v6("fe80::") == ~[0xfe80u16, 0u16, 0u16, 0u16, 0u16, 0u16, 0u16, 0u16];
+	assert v6("::ffff:10.0.0.1") == ~[0u16, 0u16, 0u16, 0u16, 0u16, 0xffffu16, 0x0a00u16, 1u16];
+	assert v6("1:2:3:4:5:6:1.2.3.4") == ~[1u16, 2u16, 3u16, 4u16, 5u16, 6u16, 0x0102u16, 0x0304u16];
+	
+	assert result::get(&parse_ipv6("2001:0db8:0:0:1:0:0:1")).to_str() == ~"2001:db8::1:0:0:1";
+	assert result::get(&parse_ipv6("0:0:0:0:0:0:0:1")).to_str() == ~"::1";
+	assert result::get(&parse_ipv6("1:0:2:0:3:0:4:0")).to_str() == ~"1:0:2:0:3:0:4:0";
+	assert result::get(&parse_ipv6("::")).to_str() == ~"::";
+}
+
+#[test]
+fn test_ipv6_errors()
+{
+	assert result::get_err(&parse_ipv6("1:2:3")) == ~"address:1:1: IPv6 address with 8 segments (or fewer with '::')\n1:2:3\n^";
+	assert result::get_err(&parse_ipv6("1::2::3")) == ~"address:1:5: only one '::'\n1::2::3\n    ^";
+	assert result::get_err(&parse_ipv6("12345::")) == ~"address:1:1: at most 4 hex digits\n12345::\n^";
+	assert result::get_err(&parse_ipv6("1:2:")) == ~"address:1:5: hex digits\n1:2:\n    ^";
+	assert result::is_err(&parse_ipv6("1:2:3:4:5:6:7:8:9"));
+	assert result::is_err(&parse_ipv6("1::2:3:4:5:6:7:8"));
+}
+
+#[test]
+fn test_networks()
+{
+	let n = result::get(&ipv4_network().everything_ws().parse(@~"net", "10.0.0.0/8"));
+	assert n.address.to_str() == ~"10.0.0.0" && n.prefix == 8u;
+	assert result::get(&ipv4_network().everything_ws().parse(@~"net", "10.0.0.1")).prefix == 32u;
+	assert result::get(&ipv6_network().everything_ws().parse(@~"net", "fe80::/10")).prefix == 10u;
+	assert result::get(&ipv6_network().everything_ws().parse(@~"net", "::1")).prefix == 128u;
+	
+	let text = "10.0.0.0/33";
+	assert result::get_err(&to_result(ipv4_network().everything_ws().parse(@~"net", text), text)) == ~"net:1:10: prefix length from 0 to 32\n10.0.0.0/33\n         ^";
+}
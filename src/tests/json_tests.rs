@@ -0,0 +1,66 @@
+use json::*;
+use testing::*;
+
+fn check_json(text: &str, expected: &str) -> bool
+{
+	check_result(&parse_json(text), json_to_str, expected)
+}
+
+// Errors are only checked up to the location because the messages depend upon how
+// the alternatives in the grammar are merged.
+fn check_json_err(text: &str, location: &str) -> bool
+{
+	check_result_err(&parse_json(text), json_to_str, location)
+}
+
+#[test]
+fn test_json_literals()
+{
+	assert check_json("null", "null");
+	assert check_json(" true ", "true");
+	assert check_json("false\n", "false");
+	assert check_json("0", "0");
+	assert check_json("-12", "-12");
+	assert check_json("0.5", "0.5");
+	assert check_json("1e3", "1000");
+	assert check_json("25E-1", "2.5");
+}
+
+#[test]
+fn test_json_strings()
+{
+	assert check_json("\"\"", "\"\"");
+	assert check_json("\"hello world\"", "\"hello world\"");
+	assert check_json("\"a\\\"b\\\\c\\/d\"", "\"a\\\"b\\\\c/d\"");
+	assert check_json("\"\\n\\t\\r\"", "\"\\n\\t\\r\"");
+	assert check_json("\"\\u0041\\u00e9\"", "\"A\u00e9\"");
+	assert check_json("\"\\ud834\\udd1e\"", "\"\U0001d11e\"");
+	
+	match parse_json("\"\\b\\f\"")
+	{
+		result::Ok(String(s)) => assert *s == ~"\x08\x0c",
+		_ => fail ~"expected a string",
+	}
+}
+
+#[test]
+fn test_json_containers()
+{
+	assert check_json("[]", "[]");
+	assert check_json("{}", "{}");
+	assert check_json("[1, [2, []], {\"a\": null}]", "[1,[2,[]],{\"a\":null}]");
+	assert check_json("{\"b\" : 1, \"a\" : [true, false], \"b\": \"x\"}", "{\"b\":1,\"a\":[true,false],\"b\":\"x\"}");
+	assert check_json("\n{\n\t\"name\": \"widget\",\n\t\"tags\": [\"a\", \"b\"]\n}\n", "{\"name\":\"widget\",\"tags\":[\"a\",\"b\"]}");
+}
+
+#[test]
+fn test_json_errors()
+{
+	assert check_json_err("", "json:1:1: value");
+	assert check_json_err("[1,]", "json:1:4:");
+	assert check_json_err("[1 2]", "json:1:4:");
+	assert check_json_err("{\"a\" 1}", "json:1:6:");
+	assert check_json_err("012", "json:1:2:");
+	assert check_json_err("\"\\x\"", "json:1:2:");
+	assert check_json_err("[1,\n 2,\n3 4]", "json:3:3:");
+}
@@ -0,0 +1,69 @@
+use literals::*;
+use parsers::*;
+
+fn body(text: &str) -> ~str
+{
+	match heredoc().parse(@~"unit test", text)
+	{
+		result::Ok(ref doc) => copy *doc.body,
+		result::Err(ref failure) => fmt!("%u:%u %s", failure.line, failure.col, *failure.mesg),
+	}
+}
+
+#[test]
+fn test_heredoc()
+{
+	assert body("<<END\nhello\n  world\nEND\n") == ~"hello\n  world\n";
+	assert body("<<'END'  \nx\nEND") == ~"x\n";
+	assert body("<<END\nEND") == ~"";
+	assert body("<<END\n END\nEND\n") == ~" END\n";
+	
+	// Indentation stripping.
+	assert body("<<-END\n\t\tx\n\ty\n\tEND\n") == ~"x\ny\n";
+	assert body("<<~END\n    x\n\n      y\n  END\n") == ~"x\n\n  y\n";
+	
+	assert body("<<END\nx\nEN") == ~"3:3 closing END line";
+	assert body("<<\nx") == ~"1:3 heredoc tag";
+	assert body("<<\"END\nx") == ~"1:7 '\"'";
+	assert body("<<END x\nEND") == ~"1:7 end of line";
+}
+
+#[test]
+fn test_heredoc_lines()
+{
+	// The parse continues on the line after the closing tag.
+	let p = seq2_ret1(heredoc(), "x".lit());
+	match p.parse(@~"unit test", "<<END\na\nb\nEND\ny")
+	{
+		result::Ok(_) => fail ~"expected an error",
+		result::Err(ref failure) => assert (failure.line, failure.col) == (5u, 1u),
+	}
+}
+
+fn raw(text: &str) -> ~str
+{
+	match raw_string("r", "\"").parse(@~"unit test", text)
+	{
+		result::Ok(s) => copy *s,
+		result::Err(ref failure) => fmt!("%u:%u %s", failure.line, failure.col, *failure.mesg),
+	}
+}
+
+#[test]
+fn test_raw_string()
+{
+	assert raw("r\"C:\\dir\"") == ~"C:\\dir";
+	assert raw("r#\"say \"hi\"\"#") == ~"say \"hi\"";
+	assert raw("r##\"a\"#b\"##") == ~"a\"#b";
+	assert raw("r\"\"") == ~"";
+	assert raw("r\"a\nb\"") == ~"a\nb";
+	
+	assert raw("\"x\"") == ~"1:1 raw string";
+	assert raw("r#x") == ~"1:3 '\"'";
+	assert raw("r#\"abc\"") == ~"1:8 closing \"#";
+	
+	// Either delimiter may be used.
+	let p = raw_string("R", "'\"");
+	assert *p.parse(@~"unit test", "R#'a\"'#").get() == ~"a\"";
+	assert *p.parse(@~"unit test", "R\"b'\"").get() == ~"b'";
+}
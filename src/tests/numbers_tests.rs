@@ -0,0 +1,92 @@
+use numbers::*;
+use parsers::*;
+
+fn integer(format: NumberFormat, text: &str) -> ~str
+{
+	match locale_integer(format).parse_prefix(@~"unit test", text)
+	{
+		result::Ok(ref prefix) => fmt!("%d %u", prefix.value, prefix.rest_index),
+		result::Err(ref failure) => copy *failure.mesg,
+	}
+}
+
+#[test]
+fn test_locale_integer()
+{
+	assert integer(english_format(), "1,234,567") == ~"1234567 9";
+	assert integer(english_format(), "1234567") == ~"1234567 7";
+	assert integer(german_format(), "12.345") == ~"12345 6";
+	assert integer(french_format(), "12 345") == ~"12345 6";
+	assert integer(swiss_format(), "1'000") == ~"1000 5";
+	
+	// Separators which aren't followed by a group of three digits aren't matched.
+	assert integer(english_format(), "1,2,3") == ~"1 1";
+	assert integer(english_format(), "1,2345") == ~"1 1";
+	assert integer(english_format(), "1234,567") == ~"1234 4";
+	assert integer(english_format(), "1,234.5") == ~"1234 5";
+	
+	// Only one separator may be used within a number.
+	let mixed = NumberFormat {decimal: '.', separators: @~", "};
+	assert integer(mixed, "1,234 567") == ~"1234 5";
+	
+	assert integer(english_format(), "x") == ~"number";
+	assert integer(english_format(), "99,999,999,999,999,999,999") == ~"'99999999999999999999' is out of range";
+}
+
+#[test]
+fn test_locale_float()
+{
+	let p = locale_float(german_format()).everything_ws();
+	assert p.parse(@~"unit test", "1.234,56").get() == 1234.56f64;
+	assert p.parse(@~"unit test", "0,5").get() == 0.5f64;
+	assert p.parse(@~"unit test", "7").get() == 7.0f64;
+	assert p.parse(@~"unit test", "1,").is_err();
+	
+	let p = locale_float(english_format()).everything_ws();
+	assert p.parse(@~"unit test", "1,234.56").get() == 1234.56f64;
+	assert p.parse(@~"unit test", "1.234,56").is_err();
+}
+
+fn check_uint(parser: Parser<uint>, text: &str) -> ~str
+{
+	match parser.parse_prefix(@~"unit test", text)
+	{
+		result::Ok(ref prefix) => fmt!("%u %u", prefix.value, prefix.rest_index),
+		result::Err(ref failure) => fmt!("%s at %u", *failure.mesg, failure.col),
+	}
+}
+
+#[test]
+fn test_roman_numeral()
+{
+	assert check_uint(roman_numeral(), "XIV") == ~"14 3";
+	assert check_uint(roman_numeral(), "mcmxc") == ~"1990 5";
+	assert check_uint(roman_numeral(), "MMMCMXCIX") == ~"3999 9";
+	assert check_uint(roman_numeral(), "iv. Results") == ~"4 2";
+	assert check_uint(roman_numeral(), "VIII") == ~"8 4";
+	
+	assert check_uint(roman_numeral(), "IIII") == ~"roman numeral at 1";
+	assert check_uint(roman_numeral(), "MMMM") == ~"roman numeral at 1";
+	assert check_uint(roman_numeral(), "civil") == ~"roman numeral at 1";
+	assert check_uint(roman_numeral(), "Xiv") == ~"roman numeral at 1";
+	assert check_uint(roman_numeral(), "") == ~"roman numeral at 1";
+}
+
+#[test]
+fn test_ordinal()
+{
+	assert check_uint(ordinal(), "1st") == ~"1 3";
+	assert check_uint(ordinal(), "2nd") == ~"2 3";
+	assert check_uint(ordinal(), "3rd") == ~"3 3";
+	assert check_uint(ordinal(), "4th") == ~"4 3";
+	assert check_uint(ordinal(), "11th") == ~"11 4";
+	assert check_uint(ordinal(), "12th") == ~"12 4";
+	assert check_uint(ordinal(), "113th") == ~"113 5";
+	assert check_uint(ordinal(), "21st chapter") == ~"21 4";
+	assert check_uint(ordinal(), "102nd") == ~"102 5";
+	
+	assert check_uint(ordinal(), "1th") == ~"'st' at 2";
+	assert check_uint(ordinal(), "11st") == ~"'th' at 3";
+	assert check_uint(ordinal(), "2ndary") == ~"'nd' at 2";
+	assert check_uint(ordinal(), "first") == ~"ordinal at 1";
+}
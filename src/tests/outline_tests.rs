@@ -0,0 +1,53 @@
+use misc::*;
+use outline::*;
+use parsers::*;
+
+// block := name '{' (block | item)* '}'
+// item := name ';'
+fn blocks() -> Parser<()>
+{
+	let block_ptr = @mut ret(());
+	let block_ref = forward_ref(block_ptr);
+	
+	let name = match1(is_alpha).s0().named("name");
+	let item = seq2_ret0(name, ";".s0()).map(|_v| ()).named("item");
+	let block = seq4_ret3(name, "{".s0(), block_ref.or(item).r0(), "}".s0()).map(|_v| ()).named("block");
+	*block_ptr = block;
+	
+	block.r0().map(|_v| ()).everything_ws()
+}
+
+fn fold_to_str(fold: &Fold) -> ~str
+{
+	let children = str::connect(vec::map(*fold.children, |f| fold_to_str(f)), " ");
+	fmt!("%s:%d-%d(%s)", *fold.name, fold.span.start_line, fold.span.end_line, children)
+}
+
+#[test]
+fn test_parse_outline()
+{
+	let text = "a {\n  x;\n  b {\n    y;\n  }\n}\nc { z; }\n";
+	match parse_outline(blocks(), @~"test", text, [~"block"])
+	{
+		result::Ok((_, ref folds)) =>
+		{
+			let s = str::connect(vec::map(**folds, |f| fold_to_str(f)), " ");
+			assert s == ~"block:1-6(block:3-5()) block:7-7()";
+			assert vec::len(flatten_folds(**folds)) == 3u;
+			assert folds[1].span.start == 28u;
+		}
+		result::Err(_) =>
+		{
+			assert false;
+		}
+	}
+	
+	// Items are folded too if they are structural.
+	match parse_outline(blocks(), @~"test", "a { x; }", [~"block", ~"item"])
+	{
+		result::Ok((_, ref folds)) => assert str::connect(vec::map(**folds, |f| fold_to_str(f)), " ") == ~"block:1-1(item:1-1())",
+		result::Err(_) => assert false,
+	}
+	
+	assert parse_outline(blocks(), @~"test", "a {", [~"block"]).is_err();
+}
@@ -0,0 +1,37 @@
+use misc::*;
+use parallel::*;
+use parsers::*;
+use types::*;
+
+fn number_parser() -> Parser<int>
+{
+	match1(is_digit).map(|s: @~str| int::from_str(*s).get()).err("number").everything(ret(()))
+}
+
+fn multi_line_parser() -> Parser<int>
+{
+	match1(is_digit).map(|s: @~str| int::from_str(*s).get()).err("expected\na number").everything(ret(()))
+}
+
+#[test]
+fn test_parse_records()
+{
+	let results = parse_records("numbers", "1\n2\r\nx\n4\n", '\n', 2u, number_parser);
+	assert vec::len(results) == 4u;
+	assert results[0] == result::Ok(1);
+	assert results[1] == result::Ok(2);
+	assert results[2] == result::Err(~"numbers:3:1: number\nx\n^");
+	assert results[3] == result::Ok(4);
+	
+	// More tasks than records.
+	let results = parse_records("numbers", "5;6", ';', 8u, number_parser);
+	assert results == ~[result::Ok(5), result::Ok(6)];
+	
+	// Errors use the location of the record.
+	let results = parse_records("numbers", "5;6x", ';', 1u, number_parser);
+	assert results[1] == result::Err(~"numbers:1:4: EOT\n  6x\n   ^");
+	
+	// Messages may span lines.
+	let results = parse_records("numbers", "1\nx\n", '\n', 1u, multi_line_parser);
+	assert results[1] == result::Err(~"numbers:2:1: expected\na number\nx\n^");
+}
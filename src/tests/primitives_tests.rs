@@ -0,0 +1,54 @@
+use parsers::*;
+use primitives::*;
+use tests::test_helpers::*;
+
+#[test]
+fn test_primitives()
+{
+	let p = primitives();
+	
+	assert box::ptr_eq(p, primitives());
+	assert check_str_ok("123x", p.digits, "123");
+	assert check_int_ok("42", p.decimal_number, 42);
+	assert check_str_ok("foo_1 bar", p.identifier, "foo_1");
+}
+
+#[test]
+fn test_shared_literals()
+{
+	let p = primitives();
+	let plus = p.s0("+");
+	
+	assert check_str_ok("+  1", plus.then(p.digits), "1");
+	assert check_str_ok("+1", p.lit("+"), "+");
+	
+	let count = p.literals.size();
+	p.lit("+");
+	p.s0("+");
+	assert p.literals.size() == count;
+	assert p.lexemes.contains_key(~"+");
+}
+
+fn number_list() -> Parser<@~[int]>
+{
+	primitives().decimal_number.list(primitives().s0(","))
+}
+
+fn number_list_key(_p: @Parser<@~[int]>)
+{
+}
+
+#[test]
+fn test_shared_grammar()
+{
+	let p = primitives();
+	let calls = @mut 0u;
+	
+	let list = p.grammar(number_list_key, || {*calls += 1u; number_list()});
+	assert list.parse(@~"unit test", "1,2, 3").get() == @~[1, 2, 3];
+	assert *calls == 1u;
+	
+	// The second call returns the cached parser.
+	assert p.grammar(number_list_key, || {*calls += 1u; number_list()}).parse(@~"unit test", "4").get() == @~[4];
+	assert *calls == 1u;
+}
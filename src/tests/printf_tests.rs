@@ -0,0 +1,76 @@
+use printf::*;
+
+fn show_count(count: Count) -> ~str
+{
+	match count
+	{
+		NoCount => ~"-",
+		CountOf(n) => uint::str(n),
+		CountArg(option::None) => ~"*",
+		CountArg(option::Some(n)) => fmt!("*%u$", n),
+	}
+}
+
+// Conversions are shown as <position flags width precision length conversion>.
+fn show(text: &str) -> ~str
+{
+	match parse_printf(text)
+	{
+		result::Ok(segments) =>
+		{
+			let parts = do vec::map(*segments)
+			|segment|
+			{
+				match *segment
+				{
+					PrintfText(s) => fmt!("'%s'", *s),
+					PrintfConversion(ref spec) =>
+					{
+						let position = match spec.position {option::Some(n) => uint::str(n), option::None => ~"-"};
+						fmt!("<%s '%s' %s %s '%s' %c>", position, *spec.flags, show_count(spec.width), show_count(spec.precision), *spec.length, spec.conversion)
+					}
+				}
+			};
+			str::connect(parts, " ")
+		}
+		result::Err(ref err) => copy *err,
+	}
+}
+
+#[test]
+fn test_printf()
+{
+	assert show("hello") == ~"'hello'";
+	assert show("%d") == ~"<- '' - - '' d>";
+	assert show("x=%-10s|%5.2f%%\n") == ~"'x=' <- '-' 10 - '' s> '|' <- '' 5 2 '' f> '%\n'";
+	assert show("%08.3lf") == ~"<- '0' 8 3 'l' f>";
+	assert show("%llu %hhx %zd") == ~"<- '' - - 'll' u> ' ' <- '' - - 'hh' x> ' ' <- '' - - 'z' d>";
+	assert show("%*.*s") == ~"<- '' * * '' s>";
+	assert show("%2$*1$d") == ~"<2 '' *1$ - '' d>";
+	assert show("%.d") == ~"<- '' - 0 '' d>";
+	assert show("%+ #x") == ~"<- '+ #' - - '' x>";
+	assert show("100%%") == ~"'100%'";
+}
+
+#[test]
+fn test_printf_spans()
+{
+	let segments = result::get(&parse_printf("a %5d\nb %s"));
+	match segments[1]
+	{
+		PrintfConversion(ref spec) => assert spec.span.start == 2u && spec.span.end == 5u,
+		_ => fail,
+	}
+	match segments[3]
+	{
+		PrintfConversion(ref spec) => assert spec.span.start == 8u && spec.span.end == 10u && spec.span.start_line == 2,
+		_ => fail,
+	}
+}
+
+#[test]
+fn test_printf_errors()
+{
+	assert show("%5y") == ~"format:1:3: conversion specifier\n%5y\n  ^";
+	assert show("trailing %") == ~"format:1:11: conversion specifier\ntrailing %\n          ^";
+}
@@ -0,0 +1,51 @@
+use query::*;
+
+fn pairs_to_str(pairs: &[QueryPair]) -> ~str
+{
+	let parts = do vec::map(pairs)
+	|p|
+	{
+		match p.value
+		{
+			option::Some(v) => fmt!("%s=%s", *p.key, *v),
+			option::None => copy *p.key,
+		}
+	};
+	str::connect(parts, ", ")
+}
+
+#[test]
+fn test_query()
+{
+	let pairs = result::get(&parse_query("a=1&b=two+words&flag", KeepAll));
+	assert pairs_to_str(*pairs) == ~"a=1, b=two words, flag";
+	assert pairs[2].value.is_none();
+	
+	let pairs = result::get(&parse_query("name=J%C3%BCrgen&path=%2Fusr%2fbin&empty=&&x%3Dy=%26", KeepAll));
+	assert pairs_to_str(*pairs) == ~"name=Jürgen, path=/usr/bin, empty=, x=y=&";
+	
+	assert vec::is_empty(*result::get(&parse_query("", KeepAll)));
+}
+
+#[test]
+fn test_query_duplicates()
+{
+	let text = "a=1&b=2&a=3";
+	assert pairs_to_str(*result::get(&parse_query(text, KeepAll))) == ~"a=1, b=2, a=3";
+	assert pairs_to_str(*result::get(&parse_query(text, KeepFirst))) == ~"a=1, b=2";
+	assert pairs_to_str(*result::get(&parse_query(text, KeepLast))) == ~"a=3, b=2";
+	assert result::get_err(&parse_query(text, Reject)) == ~"query:1:9: key 'a' appears more than once\na=1&b=2&a=3\n        ^";
+	
+	let map = query_map(*result::get(&parse_query("a=1&flag&a=2", KeepAll)));
+	assert map.size() == 2u;
+	assert map.get(~"a") == @~"2";
+	assert map.get(~"flag") == @~"";
+}
+
+#[test]
+fn test_query_errors()
+{
+	assert result::get_err(&parse_query("a=%2", KeepAll)) == ~"query:1:3: '%' followed by two hex digits\na=%2\n  ^";
+	assert result::get_err(&parse_query("a=1&b%zz=2", KeepAll)) == ~"query:1:6: '%' followed by two hex digits\na=1&b%zz=2\n     ^";
+	assert result::get_err(&parse_query("a=1&b=%FF", KeepAll)) == ~"query:1:7: percent escapes which are UTF-8\na=1&b=%FF\n      ^";
+}
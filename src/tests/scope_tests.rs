@@ -0,0 +1,61 @@
+use misc::*;
+use parsers::*;
+use scope::*;
+
+// block := '{' (decl | reference | block)* '}'
+// decl := 'let' name ';'
+// reference := name ';'
+fn blocks(scope: Scope<@~str>) -> Parser<()>
+{
+	let block_ptr = @mut ret(());
+	let block_ref = forward_ref(block_ptr);
+	
+	let name = match1(is_alpha).s0();
+	let decl = define(scope, |name: @~str| name, seq3_ret1("let".s1(), name, ";".s0()));
+	let reference = do seq2_ret0(name, ";".s0()).thene
+		|name|
+		{
+			if scope.lookup(*name).is_some() {ret(name)} else {fails("defined name")}
+		};
+	let statement = or_v(@~[decl, reference, block_ref.map(|_v| @~"")]);
+	let block = seq3_ret1("{".s0(), statement.r0(), "}".s0()).map(|_v| ());
+	*block_ptr = scoped(scope, block);
+	
+	block_ref.everything_ws()
+}
+
+#[test]
+fn test_scope_methods()
+{
+	let scope: Scope<int> = new_scope();
+	assert scope.define("a", 1);
+	assert !scope.define("a", 2);
+	
+	scope.enter();
+	assert scope.define("a", 3);
+	assert scope.define("b", 4);
+	assert scope.depth() == 2u;
+	assert scope.lookup("a") == option::Some(3);
+	assert scope.lookup("b") == option::Some(4);
+	
+	scope.exit();
+	assert scope.depth() == 1u;
+	assert scope.lookup("a") == option::Some(2);
+	assert scope.lookup("b").is_none();
+}
+
+#[test]
+fn test_scoped()
+{
+	let scope = new_scope();
+	let p = blocks(scope);
+	
+	assert p.parse(@~"unit test", "{let a; a; {let b; a; b;} a;}").is_ok();
+	assert p.parse(@~"unit test", "{let a; {let b;} a;}").is_ok();
+	assert p.parse(@~"unit test", "{let a; {let b;} b;}").is_err();
+	assert p.parse(@~"unit test", "{a; let a;}").is_err();
+	
+	// Frames are popped even if the parse failed.
+	assert scope.depth() == 1u;
+	assert scope.lookup("a").is_none();
+}
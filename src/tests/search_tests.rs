@@ -0,0 +1,56 @@
+use misc::*;
+use parsers::*;
+use search::*;
+
+fn values<T: Copy Durable>(matches: &[Spanned<T>]) -> ~[T]
+{
+	vec::map(matches, |m| m.value)
+}
+
+#[test]
+fn test_find_all()
+{
+	let number = match1(is_digit).map(|s| int::from_str(*s).get());
+	let matches = find_all(number, "a1 b22\nc333d");
+	
+	assert values(*matches) == ~[1, 22, 333];
+	assert vec::map(*matches, |m| (m.span.start, m.span.end, m.span.start_line)) == ~[(1u, 2u, 1), (4u, 6u, 1), (8u, 11u, 2)];
+	
+	// Matches don't overlap and empty matches are ignored.
+	assert values(*find_all("aa".lit(), "aaaaa")) == ~[@~"aa", @~"aa"];
+	assert vec::is_empty(*find_all(match0(is_digit), "abc"));
+	assert vec::is_empty(*find_all(number, ""));
+}
+
+#[test]
+fn test_replace_all()
+{
+	// Expands $name using a table of variables.
+	let variable = seq2_ret1("$".lit(), match1(is_alpha));
+	let lookup: fn@ (@~str) -> ~str = |name| if *name == ~"who" {~"world"} else {~"?"};
+	
+	assert replace_all(variable, "hello $who, $x!", lookup) == ~"hello world, ?!";
+	assert replace_all(variable, "no variables", lookup) == ~"no variables";
+	assert replace_all(variable, "$who$who", lookup) == ~"worldworld";
+	assert replace_all(variable, "", lookup) == ~"";
+}
+
+fn pieces(values: @~[@~str]) -> ~[~str]
+{
+	vec::map(*values, |v| copy **v)
+}
+
+#[test]
+fn test_split()
+{
+	let comma = seq2_ret0(",".lit(), match0(is_whitespace));
+	
+	assert pieces(split(comma, "a, b,c,, d")) == ~[~"a", ~"b", ~"c", ~"", ~"d"];
+	assert pieces(split(comma, "abc")) == ~[~"abc"];
+	assert pieces(split(comma, "")) == ~[~""];
+	assert pieces(split(comma, ",")) == ~[~"", ~""];
+	
+	// Delimiters within quoted strings are skipped.
+	let quoted = seq3_ret_str("\"".lit(), match0(|ch| ch != '"'), "\"".lit());
+	assert pieces(split_skipping(comma, quoted, "a, \"b, c\", d")) == ~[~"a", ~"\"b, c\"", ~"d"];
+}
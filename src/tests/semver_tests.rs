@@ -0,0 +1,79 @@
+use semver::*;
+
+fn version(text: &str) -> Version
+{
+	result::get(&parse_version(text))
+}
+
+fn check_matches(constraint: &str, text: &str) -> bool
+{
+	result::get(&parse_constraint(constraint)).matches(&version(text))
+}
+
+#[test]
+fn test_semver()
+{
+	let v = version("1.22.333");
+	assert v.major == 1u && v.minor == 22u && v.patch == 333u;
+	assert vec::is_empty(*v.pre) && vec::is_empty(*v.build);
+	
+	let v = version("1.0.0-alpha.10+build.5-x");
+	assert vec::len(*v.pre) == 2u;
+	assert *v.build == ~[@~"build", @~"5-x"];
+	assert v.to_str() == ~"1.0.0-alpha.10+build.5-x";
+	
+	assert result::get_err(&parse_version("1.2")) == ~"version:1:4: '.'\n1.2\n   ^";
+	assert result::get_err(&parse_version("01.2.3")) == ~"version:1:2: '.'\n01.2.3\n ^";
+	assert result::is_err(&parse_version("1.2.3-01"));
+	assert result::is_err(&parse_version("1.2.3-"));
+	
+	assert str::contains(result::get_err(&parse_version("99999999999999999999.0.0")), "number which fits in a uint");
+	assert str::contains(result::get_err(&parse_version("1.0.0-99999999999999999999")), "number which fits in a uint");
+}
+
+#[test]
+fn test_semver_compare()
+{
+	// The ordering from semver.org.
+	let ordered = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0", "1.0.1", "1.1.0", "2.0.0"];
+	for uint::range(0u, vec::len(ordered) - 1u) |i|
+	{
+		assert version(ordered[i]).compare(&version(ordered[i + 1u])) < 0;
+		assert version(ordered[i + 1u]).compare(&version(ordered[i])) > 0;
+	}
+	assert version("1.0.0+a").compare(&version("1.0.0+b")) == 0;
+}
+
+#[test]
+fn test_constraint()
+{
+	assert check_matches("1.2.3", "1.2.3");
+	assert !check_matches("=1.2.3", "1.2.4");
+	assert check_matches("1.2", "1.2.9");
+	assert !check_matches("1.2", "1.3.0");
+	
+	assert check_matches("^1.2", "1.9.0");
+	assert !check_matches("^1.2", "2.0.0");
+	assert !check_matches("^1.2", "1.1.9");
+	assert check_matches("^0.2.3", "0.2.9");
+	assert !check_matches("^0.2.3", "0.3.0");
+	assert !check_matches("^0.0.3", "0.0.4");
+	
+	assert check_matches("~1.2.3", "1.2.9");
+	assert !check_matches("~1.2.3", "1.3.0");
+	assert check_matches("~1", "1.9.0");
+	
+	assert check_matches(">=0.3 <0.5", "0.4.2");
+	assert !check_matches(">=0.3 <0.5", "0.5.0");
+	assert !check_matches(">= 0.3, < 0.5", "0.2.9");
+	assert check_matches(">1.2", "1.3.0");
+	assert !check_matches(">1.2", "1.2.5");
+	assert check_matches("<=1.2", "1.2.5");
+	
+	assert check_matches("~1.4 || 2.0.0", "2.0.0");
+	assert check_matches("~1.4 || 2.0.0", "1.4.7");
+	assert !check_matches("~1.4 || 2.0.0", "1.5.0");
+	
+	assert result::is_err(&parse_constraint(">=0.3 <"));
+	assert result::is_err(&parse_constraint("||"));
+}
@@ -0,0 +1,31 @@
+use shell::*;
+
+fn words(text: &str) -> ~[~str]
+{
+	vec::map(*result::get(&parse_shell_words(text)), |w| copy *w.value)
+}
+
+#[test]
+fn test_shell_words()
+{
+	assert words("cc -o \"my prog\" 'a b.c'") == ~[~"cc", ~"-o", ~"my prog", ~"a b.c"];
+	assert words("  a\tb\n c  ") == ~[~"a", ~"b", ~"c"];
+	assert words("") == ~[];
+	assert words("'' \"\"") == ~[~"", ~""];
+	assert words("a\\ b c\\\\d") == ~[~"a b", ~"c\\d"];
+	assert words("pre'quoted'\"and\"post") == ~[~"prequotedandpost"];
+	assert words("'it''s' \"\\$HOME \\n \\\"x\\\"\"") == ~[~"its", ~"$HOME \\n \"x\""];
+	assert words("one \\\ntwo") == ~[~"one", ~"two"];
+	
+	let w = result::get(&parse_shell_words("ls  'my dir'\nwc"));
+	assert w[1].span.start == 4u && w[1].span.end == 12u;
+	assert w[2].span.start == 13u && w[2].span.start_line == 2;
+}
+
+#[test]
+fn test_shell_words_errors()
+{
+	assert result::get_err(&parse_shell_words("echo 'hi")) == ~"command:1:9: '''\necho 'hi\n        ^";
+	assert result::get_err(&parse_shell_words("echo \"hi")) == ~"command:1:9: '\"'\necho \"hi\n        ^";
+	assert result::get_err(&parse_shell_words("echo \\")) == ~"command:1:7: character after '\\'\necho \\\n      ^";
+}
@@ -0,0 +1,228 @@
+use io::WriterUtil;
+use c99_parsers::*;
+use misc::*;
+use parsers::*;
+use streaming::*;
+
+fn check_parsed(status: StreamStatus<@~str>, expected: &str) -> bool
+{
+	match status
+	{
+		Parsed(value) =>
+		{
+			if *value != expected.to_owned()
+			{
+				io::stderr().write_line(fmt!("Expected %? but found %?", expected, value));
+				return false;
+			}
+			return true;
+		}
+		NeedMore =>
+		{
+			io::stderr().write_line(fmt!("Expected %? but found NeedMore", expected));
+			return false;
+		}
+		Invalid(ref failure) =>
+		{
+			io::stderr().write_line(fmt!("Expected %? but found error %s", expected, *failure.mesg));
+			return false;
+		}
+	}
+}
+
+fn check_need_more(status: StreamStatus<@~str>) -> bool
+{
+	match status
+	{
+		NeedMore =>
+		{
+			return true;
+		}
+		_ =>
+		{
+			io::stderr().write_line(fmt!("Expected NeedMore but found %?", status));
+			return false;
+		}
+	}
+}
+
+fn check_invalid(status: StreamStatus<@~str>, expected: &str, line: uint) -> bool
+{
+	match status
+	{
+		Invalid(ref failure) =>
+		{
+			if *failure.mesg != expected.to_owned()
+			{
+				io::stderr().write_line(fmt!("Expected error '%s' but found error '%s'", expected, *failure.mesg));
+				return false;
+			}
+			if failure.line != line
+			{
+				io::stderr().write_line(fmt!("Expected error '%s' on line %u but line is %u", expected, line, failure.line));
+				return false;
+			}
+			return true;
+		}
+		_ =>
+		{
+			io::stderr().write_line(fmt!("Expected error '%s' but found %?", expected, status));
+			return false;
+		}
+	}
+}
+
+#[test]
+fn test_feed()
+{
+	let mut s = stream(seq2_ret0(identifier(), ";".s0()), @~"unit test");
+	
+	assert check_need_more(s.feed(""));
+	assert check_need_more(s.feed("ab"));
+	assert check_parsed(s.feed("c;\nde"), "abc");
+	assert check_need_more(s.next());
+	assert check_parsed(s.feed(";x;y"), "de");
+	assert check_parsed(s.next(), "x");
+	assert check_need_more(s.next());
+	assert check_need_more(s.feed("; "));		// more whitespace may follow
+	assert check_parsed(s.feed("z"), "y");
+}
+
+#[test]
+fn test_invalid()
+{
+	let mut s = stream(seq2_ret0(identifier(), ";".s0()), @~"unit test");
+	
+	assert check_parsed(s.feed("a;\nb;\nc"), "a");
+	assert check_parsed(s.next(), "b");
+	assert check_invalid(s.feed(":"), "';'", 3u);
+	
+	// Positions are within all of the text fed in, not the text which remains.
+	let mut s = stream(seq2_ret0(identifier(), ";".s0()), @~"unit test");
+	assert check_parsed(s.feed("a;\nbc;d:"), "a");
+	assert check_parsed(s.next(), "bc");
+	match s.next()
+	{
+		Invalid(ref failure) => assert failure.line == 2u && failure.col == 5u && failure.offset == 7u,
+		_ => fail ~"expected Invalid",
+	}
+	
+	let mut s = stream(seq2_ret0(identifier(), ";".s0()), @~"unit test");
+	assert check_parsed(s.feed("a;b:"), "a");
+	match s.next()
+	{
+		Invalid(ref failure) => assert failure.line == 1u && failure.col == 4u && failure.offset == 3u,
+		_ => fail ~"expected Invalid",
+	}
+}
+
+#[test]
+fn test_finish()
+{
+	let mut s = stream(identifier(), @~"unit test");
+	
+	assert check_need_more(s.feed("abc"));
+	assert check_parsed(s.finish(), "abc");
+	
+	let mut s = stream(seq2_ret0(identifier(), ";".lit()), @~"unit test");
+	assert check_need_more(s.feed("abc"));
+	assert check_invalid(s.finish(), "';'", 1u);
+}
+
+#[test]
+fn test_empty_match()
+{
+	// Successes which consume nothing are errors so next doesn't return them forever.
+	let mut s = stream(match0(is_digit).s0(), @~"unit test");
+	assert check_parsed(s.feed("12 x"), "12");
+	assert check_invalid(s.next(), "non-empty match", 1u);
+	assert check_invalid(s.finish(), "non-empty match", 1u);
+	
+	let (_pending, read) = chunks(~[~"1\n", ~"x"]);
+	let mut lexer = lexer(match0(is_digit).s0(), @~"unit test", read);
+	assert lexer.next().get() == option::Some(@~"1");
+	assert result::get_err(&lexer.next()).line == 2u;
+}
+
+#[test]
+fn test_parse_partial()
+{
+	let p = seq3_ret1("(".lit().s0(), identifier().s0().r0(), ")".lit()).everything(ret(0));
+	
+	let k = match parse_partial(p, @~"unit test", "(a b")
+	{
+		Suspended(k) => k,
+		_ => fail ~"expected Suspended",
+	};
+	
+	let k = match k.resume("\n c")
+	{
+		Suspended(k) => k,
+		_ => fail ~"expected Suspended",
+	};
+	assert result::is_err(&k.finish());
+	
+	match k.resume(")")
+	{
+		Complete(names) => assert names == @~[@~"a", @~"b", @~"c"],
+		_ => fail ~"expected Complete",
+	}
+	
+	match parse_partial(p, @~"unit test", "(a 9")
+	{
+		Rejected(ref failure) => assert failure.col == 4u,
+		_ => fail ~"expected Rejected",
+	}
+}
+
+// Returns a function which returns the chunks one at a time.
+fn chunks(chunks: &[~str]) -> (@mut ~[~str], fn@ () -> Option<~str>)
+{
+	let pending = @mut vec::from_slice(chunks);
+	let read: fn@ () -> Option<~str> = || if vec::is_empty(*pending) {option::None} else {option::Some(vec::shift(&mut *pending))};
+	(pending, read)
+}
+
+#[test]
+fn test_lexer()
+{
+	let token = or_v(@~[match1(is_digit), "+".lit(), "-".lit()]).s0();
+	let (pending, read) = chunks(~[~"1", ~"2 +", ~" 3\n-", ~" 4 + 1", ~"00"]);
+	let mut lexer = lexer(token, @~"unit test", read);
+	
+	// Text is only read as tokens are needed.
+	assert lexer.next().get() == option::Some(@~"12");
+	assert vec::len(*pending) == 3u;
+	
+	assert lexer.peek(2u).get() == option::Some(@~"-");
+	assert vec::len(lexer.lookahead) == 3u;
+	assert lexer.next().get() == option::Some(@~"+");
+	
+	let mut total = 12;
+	let mut sign = 1;
+	while !lexer.at_end().get()
+	{
+		let token = lexer.next().get().get();
+		match *token
+		{
+			~"+" => sign = 1,
+			~"-" => sign = -1,
+			_ => total += sign*int::from_str(*token).get(),
+		}
+	}
+	assert total == 111;
+	assert lexer.next().get().is_none();
+	assert vec::is_empty(*pending);
+}
+
+#[test]
+fn test_lexer_error()
+{
+	let token = or_v(@~[match1(is_digit), "+".lit()]).s0();
+	let (_pending, read) = chunks(~[~"1 +", ~"\n", ~"x"]);
+	let mut lexer = lexer(token, @~"unit test", read);
+	
+	assert lexer.next().get() == option::Some(@~"1");
+	assert lexer.next().get() == option::Some(@~"+");
+	assert result::get_err(&lexer.next()).line == 2u;
+}
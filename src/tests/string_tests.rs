@@ -25,6 +25,10 @@ fn test_lit()
 	assert check_str_failed("pseudo foo", p, "'foo'", 1);
 	assert check_str_failed("FoO", p, "'foo'", 1);
 	assert check_str_failed("FOO", p, "'foo'", 1);
+	assert check_str_failed("fo", p, "'foo'", 1);
+	
+	assert check_str_ok("caf\u00E9!", "caf\u00E9".lit(), "caf\u00E9");
+	assert check_str_ok("bar", "".lit(), "");
 }
 
 #[test]
@@ -99,3 +103,22 @@ fn test_seq3_ret_str()
 	assert check_str_failed("", p, "'\"'", 1);
 	assert check_str_failed("\"hmm", p, "'\"'", 1);
 }
+
+#[test]
+fn test_compose_accents()
+{
+	let p = "caf\u00E9".lit();
+	
+	assert compose_accents("cafe\u0301") == ~"caf\u00E9";
+	assert compose_accents("\u0301e\u0308x\u0327") == ~"\u0301\u00EBx\u0327";
+	assert compose_accents("plain") == ~"plain";
+	assert check_str_ok(compose_accents("cafe\u0301"), p, "caf\u00E9");
+	assert check_str_failed("cafe\u0301", p, "'caf\u00E9'", 1);
+}
+
+#[test]
+fn test_json_str()
+{
+	assert json_str("plain") == ~"\"plain\"";
+	assert json_str("a\"b\\c\n\u0001") == ~"\"a\\\"b\\\\c\\n\\u0001\"";
+}
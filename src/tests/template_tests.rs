@@ -0,0 +1,44 @@
+use c99_parsers::*;
+use parsers::*;
+use template::*;
+
+fn expand(text: &str) -> ~str
+{
+	match parse_template(identifier().err("name"), text)
+	{
+		result::Ok(segments) => expand_template(*segments, |name| ~"<" + *name + ~">"),
+		result::Err(mesg) => mesg,
+	}
+}
+
+#[test]
+fn test_template()
+{
+	assert expand("Hello ${user}!") == ~"Hello <user>!";
+	assert expand("${ a }${b}") == ~"<a><b>";
+	assert expand("no placeholders") == ~"no placeholders";
+	assert expand("") == ~"";
+	assert expand("$$5 $x $${y}") == ~"$5 $x ${y}";
+	
+	assert str::starts_with(expand("x\n${1}"), "template:2:3: name");
+	assert str::starts_with(expand("${a b}"), "template:1:5: '}'");
+}
+
+#[test]
+fn test_template_segments()
+{
+	let segments = result::get(&parse_template(identifier(), "a ${b} c"));
+	assert vec::len(*segments) == 3u;
+	match segments[1]
+	{
+		Placeholder(ref name, ref span) =>
+		{
+			assert **name == ~"b";
+			assert (span.start, span.end) == (2u, 6u);
+		}
+		Verbatim(_) =>
+		{
+			fail ~"expected a placeholder";
+		}
+	}
+}
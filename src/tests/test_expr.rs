@@ -34,8 +34,7 @@ fn expr_parser() -> Parser<int>
 	*expr_ptr = expr;
 	
 	// start := s0 expr EOT
-	let s = ret(0).s0();
-	expr.everything(s)
+	expr.everything_ws()
 }
 
 #[test]
@@ -5,6 +5,7 @@
 use c99_parsers::*;
 use parsers::*;
 use result::*;
+use testing::*;
 use tests::test_helpers::*;
 
 struct Attribute {name: @~str, value: @~str}
@@ -50,11 +51,11 @@ fn check_xml_ok(inText: &str, expected: &str, parser: Parser<Xml>) -> bool
 {
 	info!("----------------------------------------------------");
 	let text = chars_with_eot(inText);
-	match parser(State {file: @~"unit test", text: text, index: 0u, line: 1,})
+	match parser(State {file: @~"unit test", text: text, index: 0u, offset: 0u, line: 1, ctx: new_context(no_limits()), includes: @~[]})
 	{
 		result::Ok(ref pass) =>
 		{
-			check_ok(&result::Ok(Succeeded {new_state: pass.new_state, value: @pass.value.to_str()}), &@expected.to_owned())
+			check_ok_status(&result::Ok(Succeeded {new_state: pass.new_state, value: @pass.value.to_str()}), &@expected.to_owned())
 		}
 		result::Err(ref failure) =>
 		{
@@ -65,10 +66,7 @@ fn check_xml_ok(inText: &str, expected: &str, parser: Parser<Xml>) -> bool
 
 fn check_xml_failed(inText: &str, parser: Parser<Xml>, expected: &str, line: int) -> bool
 {
-	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
-	return check_failed(&result, expected, line);
+	check_failed(inText, parser, expected, line)
 }
 
 // string_body := [^"]*
@@ -0,0 +1,63 @@
+use c99_parsers::*;
+use parsers::*;
+use types::*;
+use unparse::*;
+
+fn number() -> Dual<int>
+{
+	dual(decimal_number(), |v| int::str(v))
+}
+
+#[test]
+fn test_print()
+{
+	let pair = number().s0().and(dual_lit(",").s0()).map(|v: (int, @~str)| {let (n, _s) = v; n}, |n| (n, @~","));
+	assert pair.print(5) == ~"5 , ";
+	
+	let list = number().s0().list(";");
+	assert list.print(@~[1, 2, 3]) == ~"1 ;2 ;3 ";
+	assert list.print(@~[]) == ~"";
+	
+	let optional = number().optional();
+	assert optional.print(option::Some(7)) == ~"7";
+	assert optional.print(option::None) == ~"";
+}
+
+#[test]
+fn test_check_round_trip()
+{
+	let list = number().s0().list(",");
+	assert list.check_round_trip(~[@~[1], @~[10, 20, 30]]).is_empty();
+	
+	// The printer and the parser disagree about the separator.
+	let broken = dual(list.parser, |v: @~[int]| str::connect(vec::map(*v, |n| int::str(*n)), ";"));
+	let problems = broken.check_round_trip(~[@~[1], @~[1, 2]]);
+	assert vec::len(problems) == 1u;
+	assert str::starts_with(problems[0], "@~[1, 2] was printed as '1;2' which failed to parse");
+	
+	// The printer drops the sign.
+	let broken = dual(decimal_number(), |v: int| int::str(int::abs(v)));
+	let problems = broken.check_round_trip(~[-3]);
+	assert vec::len(problems) == 1u;
+}
+
+#[test]
+fn test_render()
+{
+	let inner = indent_block("{", ~[Str(@~"b;")], "}", 2u);
+	assert render(&indent_block("{", ~[Str(@~"a;"), inner], "}", 4u)) == ~"{\n    a;\n    {\n      b;\n    }\n}";
+	assert render(&indent_block("{", ~[], "}", 4u)) == ~"{}";
+	
+	assert render(&sep_list(~[Str(@~"1"), Str(@~"2"), Str(@~"3")], ", ")) == ~"1, 2, 3";
+	assert render(&sep_list(~[], ", ")) == ~"";
+}
+
+#[test]
+fn test_dual_doc()
+{
+	let parser = seq3_ret1("{".s0(), seq2_ret0(decimal_number().s0(), ";".s0()).r0(), "}".s0());
+	let block = dual_doc(parser, |v: @~[int]| indent_block("{", vec::map(*v, |n| Str(@(int::str(*n) + ";"))), "}", 4u));
+	
+	assert block.print(@~[1, 2]) == ~"{\n    1;\n    2;\n}";
+	assert block.check_round_trip(~[@~[], @~[1], @~[10, 20, 30]]).is_empty();
+}
@@ -0,0 +1,48 @@
+use ast::*;
+use ebnf::*;
+use misc::*;
+use parsers::*;
+use walk::*;
+
+// block := name '{' (block | item)* '}'
+// item := name ';'
+fn blocks() -> Parser<@~[AstNode]>
+{
+	let block_ptr = @mut ret(());
+	let block_ref = forward_ref(block_ptr);
+	
+	let name = match1(is_alpha).s0();
+	let item = seq2_ret0(name, ";".s0()).map(|_v| ()).named("item");
+	let block = seq4_ret3(name, "{".s0(), block_ref.or(item).r0(), "}".s0()).map(|_v| ()).named("block");
+	*block_ptr = block;
+	
+	ast(block.r0()).everything_ws()
+}
+
+fn texts(nodes: &[AstNode]) -> ~[~str]
+{
+	vec::map(nodes, |n| copy *n.text)
+}
+
+#[test]
+fn test_walk_nodes()
+{
+	let nodes = result::get(&blocks().parse(@~"unit test", "a {b; c {d;} e;}"));
+	
+	assert texts(preorder(*nodes, node_children)) == ~[~"a {b; c {d;} e;}", ~"b; ", ~"c {d;} ", ~"d;", ~"e;"];
+	assert texts(postorder(*nodes, node_children)) == ~[~"b; ", ~"d;", ~"c {d;} ", ~"e;", ~"a {b; c {d;} e;}"];
+	assert texts(find_kind(*nodes, node_children, node_kind, "item")) == ~[~"b; ", ~"d;", ~"e;"];
+	assert find_first_kind(*nodes, node_children, node_kind, "block").map(|n| copy *n.text) == option::Some(~"a {b; c {d;} e;}");
+	assert find_first_kind(*nodes, node_children, node_kind, "missing").is_none();
+}
+
+#[test]
+fn test_walk_trees()
+{
+	let grammar = "list = item, {',', item} ; item = ? identifier ? ;";
+	let parser = interpret(grammar, "list", standard_terminals).get();
+	let tree = parser.everything_ws().parse(@~"unit test", "x, y").get();
+	
+	assert vec::map(preorder(~[tree], tree_children), |t| tree_to_str(t)) == ~[~"(list (item \"x\") \",\" (item \"y\"))", ~"(item \"x\")", ~"\"x\"", ~"\",\"", ~"(item \"y\")", ~"\"y\""];
+	assert vec::len(find_kind(~[tree], tree_children, tree_kind, "item")) == 2u;
+}
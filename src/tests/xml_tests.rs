@@ -0,0 +1,82 @@
+use xml::*;
+use testing::*;
+
+fn root_to_str(root: &XmlElement) -> ~str
+{
+	xml_to_str(&Element(*root))
+}
+
+fn check_xml(text: &str, expected: &str) -> bool
+{
+	check_result(&parse_xml("test.xml", text), root_to_str, expected)
+}
+
+fn check_xml_err(text: &str, expected: &str) -> bool
+{
+	check_result_err(&parse_xml("test.xml", text), root_to_str, expected)
+}
+
+#[test]
+fn test_xml_elements()
+{
+	assert check_xml("<a/>", "<a/>");
+	assert check_xml("<a></a>", "<a/>");
+	assert check_xml("<a><b/><c>text</c></a>", "<a><b/><c>text</c></a>");
+	assert check_xml("<ns:a x.y-z='1' b = \"two\" />", "<ns:a x.y-z=\"1\" b=\"two\"/>");
+	assert check_xml("<?xml version=\"1.0\"?>\n<!-- top -->\n<a>\n  <b/>\n</a>\n", "<a>\n  <b/>\n</a>");
+}
+
+#[test]
+fn test_xml_content()
+{
+	assert check_xml("<a>x &lt; y &amp;&amp; &#65;&#x42;</a>", "<a>x &lt; y &amp;&amp; AB</a>");
+	assert check_xml("<a v=\"&quot;q&apos;\"/>", "<a v=\"&quot;q'\"/>");
+	assert check_xml("<a><![CDATA[<not> & markup]]></a>", "<a><![CDATA[<not> & markup]]></a>");
+	assert check_xml("<a><!-- c --></a>", "<a><!-- c --></a>");
+	
+	let root = result::get(&parse_xml("test.xml", "<a x=\"1\"><b/>text</a>"));
+	assert root.attribute("x") == option::Some(@~"1");
+	assert root.attribute("y") == option::None;
+	assert vec::len(*root.children) == 2u;
+	match root.children[1]
+	{
+		Text(s, _) => assert *s == ~"text",
+		_ => fail ~"expected text",
+	}
+}
+
+#[test]
+fn test_xml_spans()
+{
+	let root = result::get(&parse_xml("test.xml", "<a>\n <b\n  x='1'/>\n</a>"));
+	assert root.span.start == 0u;
+	assert root.span.end == 22u;
+	match root.children[1]
+	{
+		Element(ref b) =>
+		{
+			assert b.span.start == 5u;
+			assert b.span.end == 17u;
+			assert b.span.start_line == 2;
+			assert b.span.end_line == 3;
+			assert b.attributes[0].span.start == 10u;
+			assert b.attributes[0].span.end == 15u;
+		}
+		_ => fail ~"expected an element",
+	}
+}
+
+#[test]
+fn test_xml_errors()
+{
+	assert check_xml_err("<a></b>", "test.xml:1:8: end tag 'a' but found 'b'");
+	assert check_xml_err("<a><b></a>", "test.xml:1:11: end tag 'b' but found 'a'");
+	assert check_xml_err("<a>&nbsp;</a>", "test.xml:1:4: ");
+	assert check_xml_err("<a>&#xD800;</a>", "test.xml:1:4: ");
+	assert check_xml_err("<a>&#x110000;</a>", "test.xml:1:4: ");
+	assert check_xml_err("<a>&#0;</a>", "test.xml:1:4: ");
+	assert check_xml_err("<a>&#99999999999999999999;</a>", "test.xml:1:4: ");
+	assert check_xml_err("<a><!-- oops</a>", "test.xml:1:17: '-->'");
+	assert check_xml_err("<a x=1/>", "test.xml:1:6: attribute value");
+	assert check_xml_err("<a/><b/>", "test.xml:1:5: ");
+}
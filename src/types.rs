@@ -1,14 +1,105 @@
 //! Types used by all parse functions.
+use misc::byte_offset;
+use misc::line_starts;
+use misc::same_text;
 
 // TODO: should be able to get rid of all the Durable bounds once
 // https://github.com/mozilla/rust/issues/2992 is fixed
 
 /// Type for parse functions.
+/// 
+/// Values are copied as parsers are composed (e.g. by then and the seqN functions) so
+/// large values, like AST nodes, should be boxed (see the boxed method). Copying a box
+/// only copies the pointer. Note that the Copy bound itself is needed because closures
+/// and results can only pass values around by copying them.
 pub type Parser<T: Copy Durable> = fn@ (State) -> Status<T>;
 
 /// Input argument for parse functions. File is not interpreted and need 
-/// not be a path. Text is assumed to end with EOT. Lines are 1-based.
-pub struct State {file: @~str, text: @[char], index: uint, line: int}
+/// not be a path. Text is assumed to end with EOT. Offset is the UTF-8 byte offset
+/// of index. Lines are 1-based. Ctx is shared by all the states used within a parse.
+/// Includes is the chain of includes that led to file (outermost first, see the
+/// include parser).
+/// 
+/// States are passed by value and only index, offset, and line change as a parse advances
+/// so creating a new state copies a few pointers but does not allocate. Primitives
+/// scan using a local index and create one state for everything they match.
+pub struct State {file: @~str, text: @[char], index: uint, offset: uint, line: int, ctx: @mut Context, includes: @~[Include]}
+
+/// The file and line of an include directive.
+pub struct Include {file: @~str, line: int}
+
+/// Limits on the resources a parse may use (useful when parsing untrusted input).
+/// max_length is the maximum number of characters, max_values is the maximum number
+/// of values that the repetition parsers (e.g. r0 and list) may produce, and timeout
+/// is in seconds. Zero means no limit.
+pub struct Limits {max_length: uint, max_values: uint, timeout: float}
+
+/// Data shared by all the states used within a parse. Deadline is from
+/// std::time::precise_time_s (or zero). Values is the number of values the repetition
+/// parsers have produced. Fatal is set for errors which should fail the parse even if
+/// a later parser (e.g. or) recovers from them, e.g. when a limit is exceeded. Names
+/// is the stack of named productions being parsed (see the named method). If skipper
+/// is set then it is used by s0 and s1 to skip space (see the skipping method).
+/// Captured records the captures which matched (see the capture method). If cancel is
+/// set then the parse fails once a message is sent on the port (see parse_cancellable).
+/// Lines caches the line starts of the texts used within the parse (see State::line_starts).
+/// Examined is the furthest index parsers are known to have looked at (see
+/// State::note_examined).
+pub struct Context {limits: Limits, deadline: float, values: uint, fatal: Option<Failed>, names: ~[@~str], skipper: Option<Parser<()>>, captured: ~[Capture], cancel: Option<@pipes::Port<()>>, lines: ~[(@[char], @~[uint])], examined: uint, trace: Tracing, deriving: Deriving, profiling: Profiling, lenient: Leniency, expecting: Expecting, comments: Comments, progress: Progress}
+
+/// Level is one of the TRACE constants and controls what the note and err methods
+/// log. It defaults to the value of the RPARSE_TRACE environment variable. Depth is
+/// the nesting level of the trace method. If json is set then the trace method writes
+/// JSON records instead of indented lines. It defaults to true if the
+/// RPARSE_TRACE_FORMAT environment variable is "json". The environment variables are
+/// read once per task.
+pub struct Tracing {level: uint, depth: uint, json: bool}
+
+/// If enabled then derived records the named productions which were parsed (see
+/// parse_derivation).
+pub struct Deriving {enabled: bool, derived: ~[Derivation]}
+
+/// If enabled then profile records how often each named production was used (see
+/// parse_profiled).
+pub struct Profiling {enabled: bool, profile: ~[Profile]}
+
+/// If enabled then recovered records the errors which the recover method recovered
+/// from (see parse_lenient).
+pub struct Leniency {enabled: bool, recovered: ~[Failed]}
+
+/// If enabled then farthest records the farthest error which was discarded by methods
+/// like r0 and optional (see parse_expected).
+pub struct Expecting {enabled: bool, farthest: Option<Failed>}
+
+/// If enabled then kept records the text matched by keep_comment parsers (see
+/// parse_with_comments).
+pub struct Comments {enabled: bool, kept: ~[Spanned<@~str>]}
+
+/// If hook is set then it is called with the current index each time the parse
+/// reaches next (which then advances by interval). See the reporting_progress method.
+pub struct Progress {hook: Option<fn@ (uint)>, interval: uint, next: uint}
+
+/// A named production which matched the text from start to end (char indices).
+/// The offsets are the UTF-8 byte offsets of start and end. Children are the named
+/// productions which matched within it.
+pub struct Derivation {name: @~str, start: uint, end: uint, start_offset: uint, end_offset: uint, children: @~[Derivation]}
+
+/// Text which was matched by a parser marked with the capture method.
+pub struct Capture {name: @~str, text: @~str, span: Span}
+
+/// Counters for a named production. Calls is the number of times the production
+/// was invoked, successes and failures are the number of times it matched or
+/// didn't, and consumed is the total number of characters it matched.
+pub struct Profile {name: @~str, calls: uint, successes: uint, failures: uint, consumed: uint}
+
+/// Nothing is logged.
+pub const TRACE_OFF: uint = 0;
+
+/// Failures are logged (at DEBUG level).
+pub const TRACE_FAILURES: uint = 1;
+
+/// Failures and successes are logged (at DEBUG and INFO levels).
+pub const TRACE_ALL: uint = 2;
 
 /// Return type of parse functions.
 pub type Status<T: Copy Durable> = Result<Succeeded<T>, Failed>;
@@ -20,3 +111,195 @@ pub struct Succeeded<T: Copy Durable> {new_state: State, value: T}
 /// old_state should be identical to the input state. err_state is where 
 /// the error happened.
 pub struct Failed {old_state: State, err_state: State, mesg: @~str}
+
+pub impl<T: Copy Durable> Succeeded<T>
+{
+	/// Returns true if the parser which was given input consumed some of it.
+	fn consumed(&self, input: State) -> bool
+	{
+		self.new_state.index > input.index || !same_text(self.new_state.text, input.text)
+	}
+}
+
+pub impl Failed
+{
+	/// Returns true if the parser consumed some input before it failed. Most
+	/// combinators keep going after an alternative fails without consuming
+	/// anything, but they may want to give up if it did consume input.
+	fn consumed(&self) -> bool
+	{
+		self.err_state.index > self.old_state.index || !same_text(self.err_state.text, self.old_state.text)
+	}
+}
+
+/// The extent of the text matched by a parser. Start and end are indexes into the
+/// text (end is just past the last character matched) and the offsets are their UTF-8
+/// byte offsets. Lines are 1-based.
+pub struct Span {file: @~str, start: uint, end: uint, start_offset: uint, end_offset: uint, start_line: int, end_line: int}
+
+/// A value along with the span of the text it was parsed from (see the spanned method).
+pub struct Spanned<T: Copy Durable> {value: T, span: Span}
+
+/// Returns Limits which do not limit anything.
+pub fn no_limits() -> Limits
+{
+	Limits {max_length: 0u, max_values: 0u, timeout: 0.0}
+}
+
+/// Returns a Context to be used with a new parse.
+pub fn new_context(limits: Limits) -> @mut Context
+{
+	let deadline = if limits.timeout > 0.0 {std::time::precise_time_s() + limits.timeout} else {0.0};
+	let (trace, json) = trace_settings();
+	@mut Context {limits: limits, deadline: deadline, values: 0u, fatal: option::None, names: ~[], skipper: option::None, captured: ~[], cancel: option::None, lines: ~[], examined: 0u,
+		trace: Tracing {level: trace, depth: 0u, json: json},
+		deriving: Deriving {enabled: false, derived: ~[]},
+		profiling: Profiling {enabled: false, profile: ~[]},
+		lenient: Leniency {enabled: false, recovered: ~[]},
+		expecting: Expecting {enabled: false, farthest: option::None},
+		comments: Comments {enabled: false, kept: ~[]},
+		progress: Progress {hook: option::None, interval: 0u, next: 0u}}
+}
+
+/// A saved position within the input. See the mark and restore methods.
+pub struct Mark {index: uint, offset: uint, line: int}
+
+pub impl State
+{
+	/// Returns the parts of the state which change as input is consumed so
+	/// that they can be restored later.
+	fn mark(&self) -> Mark
+	{
+		Mark {index: self.index, offset: self.offset, line: self.line}
+	}
+	
+	/// Returns true if index is at (or past) the EOT which ends the text.
+	fn at_end(&self) -> bool
+	{
+		self.index + 1u >= vec::len(self.text)
+	}
+	
+	/// Returns the name of the innermost named production being parsed.
+	fn production(&self) -> Option<@~str>
+	{
+		if vec::is_empty(self.ctx.names) {option::None} else {option::Some(vec::last(self.ctx.names))}
+	}
+	
+	/// Returns the start of each line in text (see line_starts). These are only computed
+	/// once per parse for each text.
+	fn line_starts(&self) -> @~[uint]
+	{
+		for vec::each(self.ctx.lines) |entry|
+		{
+			let (text, starts) = *entry;
+			if same_text(text, self.text)
+			{
+				return starts;
+			}
+		}
+		
+		let starts = @line_starts(self.text);
+		vec::push(&mut self.ctx.lines, (self.text, starts));
+		starts
+	}
+	
+	/// Records that a parser looked at the text up to index. Failures are assumed to
+	/// have looked at the text up to their err_state so this is only needed by parsers
+	/// which look past where they stop or fail (e.g. to check that a keyword isn't
+	/// followed by a letter). The incremental parser uses this to decide which cached
+	/// results an edit invalidates.
+	fn note_examined(&self, index: uint)
+	{
+		if index > self.ctx.examined
+		{
+			self.ctx.examined = index;
+		}
+	}
+	
+	/// Returns the UTF-8 byte offset of index (i.e. offset).
+	fn byte_index(&self) -> uint
+	{
+		self.offset
+	}
+	
+	/// Returns a state like self except that it is at the position saved by mark.
+	fn restore(&self, mark: Mark) -> State
+	{
+		State {index: mark.index, offset: mark.offset, line: mark.line, ..*self}
+	}
+	
+	/// Returns the character at index (this will be EOT at the end of the text).
+	fn peek(&self) -> char
+	{
+		self.text[self.index]
+	}
+	
+	/// Returns the index of the current character within text.
+	fn pos(&self) -> uint
+	{
+		self.index
+	}
+	
+	/// Returns a state n characters further along. Note that line is not changed so
+	/// this should only be used to skip characters which are not new lines (also see
+	/// advance_lines).
+	fn advance(&self, n: uint) -> State
+	{
+		State {index: self.index + n, offset: self.offset + byte_offset(vec::view(self.text, self.index, self.index + n), n), ..*self}
+	}
+	
+	/// Returns the span from self to end (which should be a later state for the same text).
+	fn span_to(&self, end: State) -> Span
+	{
+		Span {file: self.file, start: self.index, end: end.index, start_offset: self.offset, end_offset: end.offset, start_line: self.line, end_line: end.line}
+	}
+	
+	/// Like advance except that line is incremented by lines.
+	fn advance_lines(&self, n: uint, lines: int) -> State
+	{
+		State {index: self.index + n, offset: self.offset + byte_offset(vec::view(self.text, self.index, self.index + n), n), line: self.line + lines, ..*self}
+	}
+}
+
+/// Returns a table with one line for each profile (e.g. from parse_profiled).
+pub fn profile_to_str(profile: &[Profile]) -> ~str
+{
+	let mut s = ~"calls\tsuccesses\tfailures\tconsumed\tname\n";
+	for vec::each(profile) |p|
+	{
+		str::push_str(&mut s, fmt!("%u\t%u\t%u\t%u\t%s\n", p.calls, p.successes, p.failures, p.consumed, *p.name));
+	}
+	return s;
+}
+
+// ---- Helpers ---------------------------------------------------------------
+// Returns the trace level and whether to use JSON from the environment. They are
+// read once per task because new_context is called for every parse.
+fn trace_settings() -> (uint, bool)
+{
+	unsafe
+	{
+		match task::local_data::local_data_get(trace_settings_key)
+		{
+			option::Some(settings) =>
+			{
+				*settings
+			}
+			option::None =>
+			{
+				let level = match os::getenv("RPARSE_TRACE")
+				{
+					option::Some(ref level) => uint::from_str(*level).get_default(TRACE_OFF),
+					option::None => TRACE_OFF,
+				};
+				let json = os::getenv("RPARSE_TRACE_FORMAT") == option::Some(~"json");
+				task::local_data::local_data_set(trace_settings_key, @(level, json));
+				(level, json)
+			}
+		}
+	}
+}
+
+fn trace_settings_key(_s: @(uint, bool))
+{
+}
@@ -0,0 +1,198 @@
+//! Parsers paired with printers.
+//!
+//! Dual values pair a parser with a printer which does the reverse. Combining duals
+//! combines both halves so printers for simple grammars come for free. The printers
+//! can be used to write values back out and check_round_trip can be used to find
+//! places where the parser and the printer disagree.
+//!
+//! Printers which need indentation (e.g. formatters) can build a Doc instead of a
+//! string and use dual_doc:
+//!
+//! ```
+//! let block = dual_doc(block_parser, |v: @~[Statement]| indent_block("{", vec::map(*v, to_doc), "}", 4u));
+//! ```
+use parsers::*;
+use types::*;
+
+/// A parser along with a printer which produces text that the parser will accept.
+pub struct Dual<T: Copy Durable> {parser: Parser<T>, printer: fn@ (T) -> ~str}
+
+/// Text to be printed along with the new lines and indentation within it.
+pub enum Doc
+{
+	/// Text which doesn't contain new lines.
+	Str(@~str),
+	
+	/// A new line followed by the current indentation.
+	Newline,
+	
+	/// Indents the new lines within the doc by the given number of spaces.
+	Indented(uint, @Doc),
+	Concat(@~[Doc]),
+}
+
+/// Returns a Dual with the given parser and printer.
+pub fn dual<T: Copy Durable>(parser: Parser<T>, printer: fn@ (T) -> ~str) -> Dual<T>
+{
+	Dual {parser: parser, printer: printer}
+}
+
+/// Like dual except that the printer returns a Doc (which is rendered with render).
+pub fn dual_doc<T: Copy Durable>(parser: Parser<T>, printer: fn@ (T) -> Doc) -> Dual<T>
+{
+	Dual {parser: parser, printer: |v: T| render(&printer(v))}
+}
+
+/// Returns a Dual which parses and prints s.
+pub fn dual_lit(s: &str) -> Dual<@~str>
+{
+	let text = s.to_owned();
+	Dual {parser: s.lit(), printer: |_v| copy text}
+}
+
+/// Returns the docs with sep between each of them, e.g. `sep_list(args, ", ")`.
+pub fn sep_list(docs: &[Doc], sep: &str) -> Doc
+{
+	let mut result = ~[];
+	for vec::eachi(docs) |i, doc|
+	{
+		if i > 0u
+		{
+			vec::push(&mut result, Str(@sep.to_owned()));
+		}
+		vec::push(&mut result, *doc);
+	}
+	Concat(@result)
+}
+
+/// Returns open followed by each doc on its own line (indented by indent spaces)
+/// followed by close on its own line. If docs is empty then close immediately
+/// follows open.
+pub fn indent_block(open: &str, docs: &[Doc], close: &str, indent: uint) -> Doc
+{
+	if vec::is_empty(docs)
+	{
+		return Str(@(open + close));
+	}
+	
+	let mut body = ~[];
+	for vec::each(docs) |doc|
+	{
+		vec::push(&mut body, Newline);
+		vec::push(&mut body, *doc);
+	}
+	Concat(@~[Str(@open.to_owned()), Indented(indent, @Concat(@body)), Newline, Str(@close.to_owned())])
+}
+
+/// Returns the text for doc.
+pub fn render(doc: &Doc) -> ~str
+{
+	let mut result = ~"";
+	render_into(&mut result, doc, 0u);
+	result
+}
+
+pub impl<T: Copy Durable> Dual<T>
+{
+	/// Like the and method in Combinators. The printer prints both values.
+	fn and<U: Copy Durable>(&self, dual2: Dual<U>) -> Dual<(T, U)>
+	{
+		let printer1 = self.printer;
+		let printer2 = dual2.printer;
+		Dual {parser: self.parser.and(dual2.parser), printer: |v: (T, U)| {let (a, b) = v; printer1(a) + printer2(b)}}
+	}
+	
+	/// Like the list method in Combinators except that sep is a literal (which is
+	/// printed between each value).
+	fn list(&self, sep: &str) -> Dual<@~[T]>
+	{
+		let text = sep.to_owned();
+		let printer = self.printer;
+		Dual {parser: self.parser.list(sep.s0()), printer: |v: @~[T]| str::connect(vec::map(*v, |e| printer(*e)), text)}
+	}
+	
+	/// Like the map method in Combinators. Unmap should do the reverse of map.
+	fn map<U: Copy Durable>(&self, map: fn@ (T) -> U, unmap: fn@ (U) -> T) -> Dual<U>
+	{
+		let printer = self.printer;
+		Dual {parser: self.parser.map(map), printer: |v: U| printer(unmap(v))}
+	}
+	
+	/// Like the optional method in Combinators. Nothing is printed for None.
+	fn optional(&self) -> Dual<Option<T>>
+	{
+		let printer = self.printer;
+		Dual {parser: self.parser.optional(), printer: |v: Option<T>| match v {option::Some(x) => printer(x), option::None => ~""}}
+	}
+	
+	/// Returns the text for value.
+	fn print(&self, value: T) -> ~str
+	{
+		(self.printer)(value)
+	}
+	
+	/// Like the s0 method in Combinators. The printer appends a space.
+	fn s0(&self) -> Dual<T>
+	{
+		let printer = self.printer;
+		Dual {parser: self.parser.s0(), printer: |v: T| printer(v) + " "}
+	}
+}
+
+pub impl<T: Copy Durable cmp::Eq> Dual<T>
+{
+	/// Prints each value, parses the result, and returns a description of each value
+	/// that didn't parse back into itself (i.e. parse(print(x)) != x).
+	fn check_round_trip(&self, values: &[T]) -> ~[~str]
+	{
+		let parser = self.parser.everything_ws();
+		let mut problems = ~[];
+		for vec::each(values) |value|
+		{
+			let text = (self.printer)(*value);
+			match parser.parse(@~"round trip", text)
+			{
+				result::Ok(ref parsed) =>
+				{
+					if *parsed != *value
+					{
+						vec::push(&mut problems, fmt!("%? was printed as '%s' which parsed as %?", *value, text, *parsed));
+					}
+				}
+				result::Err(ref failure) =>
+				{
+					vec::push(&mut problems, fmt!("%? was printed as '%s' which failed to parse: %s", *value, text, format_failure(failure, text)));
+				}
+			}
+		}
+		problems
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+fn render_into(result: &mut ~str, doc: &Doc, indent: uint)
+{
+	match *doc
+	{
+		Str(ref s) =>
+		{
+			str::push_str(result, **s);
+		}
+		Newline =>
+		{
+			str::push_char(result, '\n');
+			str::push_str(result, str::from_chars(vec::from_elem(indent, ' ')));
+		}
+		Indented(n, ref inner) =>
+		{
+			render_into(result, &**inner, indent + n);
+		}
+		Concat(ref docs) =>
+		{
+			for vec::each(**docs) |d|
+			{
+				render_into(result, d, indent);
+			}
+		}
+	}
+}
@@ -0,0 +1,105 @@
+//! Traversals for the generic trees produced by parse_derivation, ast, and interpret
+//! (Derivations, AstNodes, and Trees).
+//!
+//! The traversals work with any tree type given a function which returns a node's
+//! children, e.g.
+//!
+//! ```
+//! let calls = find_kind(*nodes, node_children, node_kind, "call");
+//! for vec::each(postorder(*derivations, derivation_children)) |d| {...}
+//! ```
+use ast::*;
+use ebnf::*;
+use types::*;
+
+/// Returns the nodes in roots and their descendants with parents before their
+/// children (i.e. in the order in which they appear in the text).
+pub fn preorder<T: Copy Durable>(roots: &[T], children: fn (&T) -> @~[T]) -> ~[T]
+{
+	let mut result = ~[];
+	let mut stack = vec::reversed(roots);
+	while vec::is_not_empty(stack)
+	{
+		let node = vec::pop(&mut stack);
+		vec::push_all(&mut stack, vec::reversed(*children(&node)));
+		vec::push(&mut result, node);
+	}
+	result
+}
+
+/// Returns the nodes in roots and their descendants with children before their parents.
+/// This is the order in which things like expression trees are evaluated.
+pub fn postorder<T: Copy Durable>(roots: &[T], children: fn (&T) -> @~[T]) -> ~[T]
+{
+	// This is preorder with the children visited right to left, reversed.
+	let mut result = ~[];
+	let mut stack = vec::from_slice(roots);
+	while vec::is_not_empty(stack)
+	{
+		let node = vec::pop(&mut stack);
+		vec::push_all(&mut stack, *children(&node));
+		vec::push(&mut result, node);
+	}
+	vec::reversed(result)
+}
+
+/// Returns the nodes in preorder whose kind is name.
+pub fn find_kind<T: Copy Durable>(roots: &[T], children: fn (&T) -> @~[T], kind: fn (&T) -> Option<@~str>, name: &str) -> ~[T]
+{
+	do vec::filter(preorder(roots, children))
+	|node|
+	{
+		match kind(node)
+		{
+			option::Some(k) => str::eq_slice(*k, name),
+			option::None => false,
+		}
+	}
+}
+
+/// Returns the first node in preorder whose kind is name.
+pub fn find_first_kind<T: Copy Durable>(roots: &[T], children: fn (&T) -> @~[T], kind: fn (&T) -> Option<@~str>, name: &str) -> Option<T>
+{
+	let nodes = find_kind(roots, children, kind, name);
+	if vec::is_not_empty(nodes) {option::Some(nodes[0])} else {option::None}
+}
+
+/// The children functions are for use with the traversal functions.
+pub fn derivation_children(d: &Derivation) -> @~[Derivation]
+{
+	d.children
+}
+
+pub fn derivation_kind(d: &Derivation) -> Option<@~str>
+{
+	option::Some(d.name)
+}
+
+pub fn node_children(node: &AstNode) -> @~[AstNode]
+{
+	node.children
+}
+
+pub fn node_kind(node: &AstNode) -> Option<@~str>
+{
+	option::Some(node.kind)
+}
+
+pub fn tree_children(tree: &Tree) -> @~[Tree]
+{
+	match *tree
+	{
+		Leaf(_) => @~[],
+		Node(_, ref children) => *children,
+	}
+}
+
+/// Leaves have no kind.
+pub fn tree_kind(tree: &Tree) -> Option<@~str>
+{
+	match *tree
+	{
+		Leaf(_) => option::None,
+		Node(ref name, _) => option::Some(*name),
+	}
+}
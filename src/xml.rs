@@ -0,0 +1,366 @@
+//! A parser for a well-formed subset of XML 1.0.
+//!
+//! Elements, attributes, text, CDATA sections, comments, and the predefined and
+//! numeric entity references are supported. Processing instructions (including the
+//! XML declaration) and comments outside of the root element are skipped. DTDs are
+//! not supported. Each node records the span of the text it was parsed from so that
+//! tools can report problems in the original document.
+use misc::*;
+use parsers::*;
+use types::*;
+
+/// An attribute's value has its entity references replaced.
+pub struct XmlAttribute {name: @~str, value: @~str, span: Span}
+
+/// The span covers the element from the '<' in the start tag to the '>' in the end tag.
+pub struct XmlElement {name: @~str, attributes: @~[XmlAttribute], children: @~[XmlNode], span: Span}
+
+/// The content of an element.
+pub enum XmlNode
+{
+	Element(XmlElement),
+	
+	/// Entity references are replaced. Note that whitespace between elements is
+	/// preserved as text.
+	Text(@~str, Span),
+	
+	/// The text between '<![CDATA[' and ']]>'.
+	CData(@~str, Span),
+	
+	/// The text between '<!--' and '-->'.
+	Comment(@~str, Span),
+}
+
+pub impl XmlElement
+{
+	/// Returns the value of the named attribute.
+	fn attribute(&self, name: &str) -> Option<@~str>
+	{
+		do vec::find(*self.attributes) |a| {*a.name == name.to_owned()}.map(|a| a.value)
+	}
+}
+
+/// Parses text which consists of a single root element. Errors are formatted with
+/// format_failure.
+pub fn parse_xml(file: &str, text: &str) -> result::Result<XmlElement, ~str>
+{
+	to_result(xml_document().parse(@file.to_owned(), text), text)
+}
+
+/// document := misc* element misc* EOT
+/// 
+/// misc := comment | pi | [ \t\r\n]+
+/// pi := '<?' (anything but '?>')* '?>'
+pub fn xml_document() -> Parser<XmlElement>
+{
+	let pi = scan_between("<?", "?>");
+	let misc = comment().map(|_v| ()).or(pi.map(|_v| ())).s0().r0();
+	seq4_ret2(ret(()).s0(), misc, xml_element().s0(), misc.then(eot()))
+}
+
+/// element := '<' name attribute* ('/>' | '>' content* '</' name '>')
+/// 
+/// content := element | comment | cdata | text
+/// attribute := name '=' ('"' [^<&"]* '"' | "'" [^<&']* "'")
+pub fn xml_element() -> Parser<XmlElement>
+{
+	let element_ptr = @mut fails("element");
+	let element_ref = forward_ref(element_ptr);
+	
+	let name = match1_0(is_name_start, is_name_char).err("name");
+	
+	// Attributes are separated by whitespace.
+	let value = attribute_value('"').or(attribute_value('\'')).err("attribute value");
+	let attribute = do seq3(name.s0(), "=".s0(), value) |n, _e, v| {result::Ok((n, v))};
+	let attribute = do attribute.spanned().s0().map() |s| {let (n, v) = s.value; XmlAttribute {name: n, value: v, span: s.span}};
+	
+	// Content stops at the end tag.
+	let node = or_v(@~[comment(), cdata(), element_ref.map(|e| Element(e)), text()]);
+	let content = do repeat_while(node) |s| {!s.at_end() && !(s.peek() == '<' && s.text[s.index + 1u] == '/')};
+	let end_tag = seq3_ret1("</".lit(), name.s0(), ">".lit());
+	let full = do seq3(">".lit(), content, end_tag) |_gt, children, close| {result::Ok(option::Some((children, close)))};
+	let tail = "/>".lit().map(|_v| option::None).or(full);
+	
+	let attributes = do repeat_while(attribute) |s| {is_name_start(s.peek())};
+	let element = do seq4("<".lit(), name.s0(), attributes, tail)
+	|_lt, name, attributes, tail|
+	{
+		match tail
+		{
+			option::None => result::Ok((name, attributes, @~[])),
+			option::Some((children, close)) =>
+			{
+				if close == name
+				{
+					result::Ok((name, attributes, children))
+				}
+				else
+				{
+					result::Err(@fmt!("end tag '%s' but found '%s'", *name, *close))
+				}
+			}
+		}
+	};
+	let element = do element.spanned().map()
+	|s|
+	{
+		let (name, attributes, children) = s.value;
+		XmlElement {name: name, attributes: attributes, children: children, span: s.span}
+	};
+	*element_ptr = element;
+	
+	element
+}
+
+/// Returns the node as XML text. Text and attribute values are escaped so that
+/// parsing the result will produce the same node.
+pub fn xml_to_str(node: &XmlNode) -> ~str
+{
+	match *node
+	{
+		Element(ref e) =>
+		{
+			let mut s = ~"<" + *e.name;
+			for vec::each(*e.attributes) |a|
+			{
+				str::push_str(&mut s, fmt!(" %s=\"%s\"", *a.name, escape(*a.value)));
+			}
+			if vec::is_empty(*e.children)
+			{
+				str::push_str(&mut s, "/>");
+			}
+			else
+			{
+				str::push_str(&mut s, ">");
+				for vec::each(*e.children) |child|
+				{
+					str::push_str(&mut s, xml_to_str(child));
+				}
+				str::push_str(&mut s, fmt!("</%s>", *e.name));
+			}
+			s
+		}
+		Text(s, _) => escape(*s),
+		CData(s, _) => fmt!("<![CDATA[%s]]>", *s),
+		Comment(s, _) => fmt!("<!--%s-->", *s),
+	}
+}
+
+// ---- Helpers ---------------------------------------------------------------
+pure fn is_name_start(ch: char) -> bool
+{
+	is_alpha(ch) || ch == '_' || ch == ':'
+}
+
+pure fn is_name_char(ch: char) -> bool
+{
+	is_alphanum(ch) || ch == '_' || ch == ':' || ch == '.' || ch == '-'
+}
+
+fn escape(s: &str) -> ~str
+{
+	let mut result = ~"";
+	for str::each_char(s) |ch|
+	{
+		match ch
+		{
+			'<' => str::push_str(&mut result, "&lt;"),
+			'>' => str::push_str(&mut result, "&gt;"),
+			'&' => str::push_str(&mut result, "&amp;"),
+			'"' => str::push_str(&mut result, "&quot;"),
+			_ => str::push_char(&mut result, ch),
+		}
+	}
+	result
+}
+
+// value := quote [^<&quote]* quote
+fn attribute_value(quote: char) -> Parser<@~str>
+{
+	let q = str::from_char(quote);
+	let q: &str = q;
+	seq3_ret1(q.lit(), text_with_refs(|ch| ch != quote && ch != '<'), q.lit())
+}
+
+// comment := '<!--' (anything but '-->')* '-->'
+fn comment() -> Parser<XmlNode>
+{
+	do scan_between("<!--", "-->").spanned().map() |s| {Comment(s.value, s.span)}
+}
+
+// cdata := '<![CDATA[' (anything but ']]>')* ']]>'
+fn cdata() -> Parser<XmlNode>
+{
+	do scan_between("<![CDATA[", "]]>").spanned().map() |s| {CData(s.value, s.span)}
+}
+
+// text := ([^<&] | reference)+
+fn text() -> Parser<XmlNode>
+{
+	let text = text_with_refs(|ch| ch != '<');
+	let mesg = @~"";
+	
+	|input: State|
+	{
+		match text(input)
+		{
+			result::Ok(ref pass) if pass.consumed(input) =>
+			{
+				result::Ok(Succeeded {new_state: pass.new_state, value: Text(pass.value, input.span_to(pass.new_state))})
+			}
+			result::Ok(_) =>
+			{
+				result::Err(Failed {old_state: input, err_state: input, mesg: mesg})
+			}
+			result::Err(ref failure) =>
+			{
+				result::Err(*failure)
+			}
+		}
+	}
+}
+
+// Like parser.r0() except that parser is called while more returns true and errors
+// are reported (instead of ending the repetition). This gives much better error
+// messages for errors inside of nested elements.
+fn repeat_while<T: Copy Durable>(parser: Parser<T>, more: fn@ (State) -> bool) -> Parser<@~[T]>
+{
+	|input: State|
+	{
+		let mut output = input;
+		let mut values = ~[];
+		while more(output)
+		{
+			match parser(output)
+			{
+				result::Ok(ref pass) =>
+				{
+					output = pass.new_state;
+					vec::push(&mut values, pass.value);
+				}
+				result::Err(ref failure) =>
+				{
+					return result::Err(Failed {old_state: input, ..*failure});
+				}
+			}
+		}
+		result::Ok(Succeeded {new_state: output, value: @values})
+	}
+}
+
+// Matches zero or more characters satisfying predicate (other than '&') along with
+// entity references. The references are replaced with the characters they refer to.
+fn text_with_refs(predicate: fn@ (char) -> bool) -> Parser<@~str>
+{
+	|input: State|
+	{
+		let mut text = ~"";
+		let mut i = input.index;
+		let mut lines = 0;
+		loop
+		{
+			let ch = input.text[i];
+			if ch == '&'
+			{
+				let mut j = i + 1u;
+				while input.text[j] != ';' && input.text[j] != EOT && input.text[j] != '<' && j < i + 12u
+				{
+					j += 1u;
+				}
+				let name = str::from_chars(vec::view(input.text, i + 1u, j));
+				match entity_char(name)
+				{
+					option::Some(value) if input.text[j] == ';' =>
+					{
+						str::push_char(&mut text, value);
+						i = j + 1u;
+					}
+					_ =>
+					{
+						let err_state = input.advance_lines(i - input.index, lines);
+						return result::Err(Failed {old_state: input, err_state: err_state, mesg: @~"entity reference"});
+					}
+				}
+			}
+			else if ch != EOT && predicate(ch)
+			{
+				str::push_char(&mut text, ch);
+				if ch == '\n'
+				{
+					lines += 1;
+				}
+				i += 1u;
+			}
+			else
+			{
+				break;
+			}
+		}
+		result::Ok(Succeeded {new_state: input.advance_lines(i - input.index, lines), value: @text})
+	}
+}
+
+// Returns the character for the name of an entity, e.g. "lt" or "#x3C".
+fn entity_char(name: &str) -> Option<char>
+{
+	if name == "lt" {option::Some('<')}
+	else if name == "gt" {option::Some('>')}
+	else if name == "amp" {option::Some('&')}
+	else if name == "quot" {option::Some('"')}
+	else if name == "apos" {option::Some('\'')}
+	else if str::starts_with(name, "#x") {option::chain(uint::from_str_radix(str::slice(name, 2u, str::len(name)), 16u), code_point)}
+	else if str::starts_with(name, "#") {option::chain(uint::from_str(str::slice(name, 1u, str::len(name))), code_point)}
+	else {option::None}
+}
+
+// Character references must be to a non-zero Unicode scalar value (i.e. not a surrogate).
+fn code_point(n: uint) -> Option<char>
+{
+	if n == 0u || (n >= 0xD800u && n <= 0xDFFFu) || n > 0x10FFFFu {option::None} else {option::Some(n as char)}
+}
+
+// Matches start, the text up to end, and end. Returns the text between them.
+fn scan_between(start: &str, end: &str) -> Parser<@~str>
+{
+	let start_chars = str::chars(start);
+	let end_chars = str::chars(end);
+	let start_mesg = @fmt!("'%s'", start);
+	let end_mesg = @fmt!("'%s'", end);
+	
+	|input: State|
+	{
+		if !matches_at(input, input.index, start_chars)
+		{
+			return result::Err(Failed {old_state: input, err_state: input, mesg: start_mesg});
+		}
+		
+		let first = input.index + vec::len(start_chars);
+		let mut i = first;
+		let mut lines = 0;
+		while !matches_at(input, i, end_chars)
+		{
+			if input.text[i] == EOT
+			{
+				return result::Err(Failed {old_state: input, err_state: input.advance_lines(i - input.index, lines), mesg: end_mesg});
+			}
+			if input.text[i] == '\n'
+			{
+				lines += 1;
+			}
+			i += 1u;
+		}
+		
+		let text = str::from_chars(vec::view(input.text, first, i));
+		result::Ok(Succeeded {new_state: input.advance_lines(i + vec::len(end_chars) - input.index, lines), value: @text})
+	}
+}
+
+fn matches_at(input: State, index: uint, chars: &[char]) -> bool
+{
+	let mut j = 0u;
+	while j < vec::len(chars) && input.text[index + j] == chars[j]
+	{
+		j += 1u;
+	}
+	j == vec::len(chars)
+}